@@ -0,0 +1,98 @@
+// accessibility options, from the [accessibility] section of settings.toml:
+//
+//   [accessibility]
+//   palette = "colorblind"    # or "rainbow" (the default)
+//   high_contrast = true      # dark boxes behind the on-screen text
+//   particle_width = 1.5      # how thick the particles' trails are drawn
+//   reduce_flashing = true    # no sparks, no storm darkening
+//
+// the colorblind palette runs dark blue to yellow (like viridis), so fast and
+// slow can be told apart by lightness alone, and the divergence and vorticity
+// views go blue and orange instead of blue and red.
+
+use macroquad::color;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::Component;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Palette {
+    Rainbow,
+    Colorblind,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct Accessibility {
+    pub palette: Palette,
+    pub high_contrast: bool,
+    pub particle_width: f32,
+    pub reduce_flashing: bool,
+}
+
+impl Default for Accessibility {
+    fn default() -> Accessibility {
+        Accessibility { palette: Palette::Rainbow, high_contrast: false, particle_width: 0.5, reduce_flashing: false }
+    }
+}
+
+// a few stops along viridis, dark to light
+const VIRIDIS: [(f32, f32, f32); 5] = [
+    (0.27, 0.00, 0.33),
+    (0.23, 0.32, 0.55),
+    (0.13, 0.57, 0.55),
+    (0.37, 0.79, 0.38),
+    (0.99, 0.91, 0.14),
+];
+
+fn viridis(t: f32) -> Color {
+    along_stops(&VIRIDIS, t)
+}
+
+// `t` (0 to 1) of the way along evenly spaced colour stops, blending between
+// the two either side (see also visualization.rs's colormaps)
+pub fn along_stops(stops: &[(f32, f32, f32)], t: f32) -> Color {
+    let along = t.max(0.).min(1.) * (stops.len() - 1) as f32;
+    let i = (along as usize).min(stops.len() - 2);
+    let f = along - i as f32;
+    let ((r0, g0, b0), (r1, g1, b1)) = (stops[i], stops[i + 1]);
+    Color::new(r0 + (r1 - r0) * f, g0 + (g1 - g0) * f, b0 + (b1 - b0) * f, 1.)
+}
+
+impl Accessibility {
+    // `t` is 0 (slow) to 1 (fast). the rainbow one turns the hue by
+    // `hue_shift`, and is lit to `lightness` (see Visualization)
+    pub fn ramp(&self, t: f32, hue_shift: f32, lightness: f32) -> Color {
+        match self.palette {
+            Palette::Rainbow => {
+                let hue = (0.66 * (1. - t.max(0.).min(1.)) + hue_shift).rem_euclid(1.);
+                color::hsl_to_rgb(hue, 1., lightness)
+            }
+            Palette::Colorblind => viridis(t),
+        }
+    }
+
+    // particles (and anything standing in for them) are colored by how fast they go
+    pub fn speed_color(&self, velocity: Vec2) -> Color {
+        match self.palette {
+            Palette::Rainbow => color::hsl_to_rgb(1.8 - velocity.length() / 6., 1., 0.5),
+            Palette::Colorblind => viridis(velocity.length() / 6.),
+        }
+    }
+
+    // the colours for positive and negative, in the signed views
+    pub fn signed_colors(&self) -> (Color, Color) {
+        match self.palette {
+            Palette::Rainbow => (Color::new(1., 0.2, 0.2, 1.), Color::new(0.2, 0.4, 1., 1.)),
+            Palette::Colorblind => (Color::new(0.9, 0.6, 0., 1.), Color::new(0., 0.45, 0.7, 1.)),
+        }
+    }
+
+    // a box behind a line of text at (x, baseline y), in high contrast mode
+    pub fn text_backing(&self, x: f32, y: f32, width: f32, size: f32) {
+        if self.high_contrast {
+            draw_rectangle(x - 4., y - size * 0.8, width + 8., size, Color::new(0., 0., 0., 0.85));
+        }
+    }
+}
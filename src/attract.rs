@@ -0,0 +1,144 @@
+// the attract mode: left alone on the start screen for IDLE_SECONDS, the game
+// plays itself behind the menu, the boat steered by the autopilot round the
+// checkpoints (or wherever it fancies, if the level hasn't any), firing now
+// and then to stir the water up. any key, click, touch or mouse movement puts
+// it away again and the world's reset for a real game. the attract run is
+// silent, and its score doesn't count.
+
+use macroquad::prelude::*;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::boat::PlayerId;
+use crate::clock::TICK;
+use crate::game_state::Phase;
+use crate::grid::GridGeometry;
+use crate::input::BoatControls;
+use crate::level::Level;
+use crate::simulation::Simulation;
+use crate::{Boat, TURN_RATE};
+
+// how long the start screen waits before playing itself
+const IDLE_SECONDS: f64 = 15.;
+// a wandering autopilot picks somewhere new this often, or when it gets there
+const WANDER_SECONDS: f32 = 6.;
+const ARRIVED: f32 = 30.;
+// chance a tick of holding fire, and of letting go
+const FIRE_CHANCE: f32 = 0.01;
+const RELEASE_CHANCE: f32 = 0.02;
+
+#[derive(Component, Default)]
+pub struct Autopilot {
+    pub on: bool,
+    target: Option<Vec2>,
+    // ticks until a wandering autopilot picks a new target
+    wander_ticks: u32,
+    firing: bool,
+}
+
+// steers the first player's boat in place of their controls (after they've
+// been read)
+pub fn autopilot(mut autopilot: UniqueViewMut<Autopilot>,
+                 mut controls: ViewMut<BoatControls>,
+                 boats: View<Boat>,
+                 ids: View<PlayerId>,
+                 level: UniqueView<Level>,
+                 grid: UniqueView<GridGeometry>) {
+    if !autopilot.on {
+        return;
+    }
+    let (controls, player) = match (&mut controls, &boats, &ids).iter().find(|(_, _, id)| id.is_first()) {
+        Some((controls, player, _)) => (controls, player),
+        None => return,
+    };
+    let at = Vec2::new(player.loc.x as f32, player.loc.y as f32);
+    let target = match level.next_checkpoint() {
+        Some(checkpoint) => checkpoint,
+        None => {
+            let arrived = autopilot.target.map_or(true, |target| target.distance(at) < ARRIVED);
+            if arrived || autopilot.wander_ticks == 0 {
+                autopilot.target = Some(Vec2::new(rand::gen_range(0., grid.width), rand::gen_range(0., grid.height)));
+                autopilot.wander_ticks = (WANDER_SECONDS / TICK) as u32;
+            }
+            autopilot.wander_ticks -= 1;
+            autopilot.target.unwrap()
+        }
+    };
+    let to_target = target - at;
+    let off_course = crate::angle_between(player.heading(), to_target.y.atan2(to_target.x));
+    controls.turn = (off_course / TURN_RATE).max(-1.).min(1.);
+    // ease off while it's pointing the wrong way
+    controls.thrust = if off_course.abs() < 1. { 1. } else { 0.3 };
+    let flip = if autopilot.firing { RELEASE_CHANCE } else { FIRE_CHANCE };
+    if rand::gen_range(0., 1.) < flip {
+        autopilot.firing = !autopilot.firing;
+    }
+    controls.fire = autopilot.firing;
+    controls.commands.clear();
+}
+
+#[derive(PartialEq)]
+pub enum AttractFrame {
+    // nothing's happening, the menu's as usual
+    Idle,
+    // the world's been drawn, for the menu to go over
+    Playing,
+    // put away by some input, which shouldn't start a game as well
+    Interrupted,
+}
+
+pub struct AttractMode {
+    last_input: f64,
+    last_mouse: (f32, f32),
+    running: bool,
+}
+
+impl AttractMode {
+    pub fn new() -> AttractMode {
+        AttractMode { last_input: get_time(), last_mouse: mouse_position(), running: false }
+    }
+
+    fn any_input(&mut self) -> bool {
+        let mouse = mouse_position();
+        let moved = mouse != self.last_mouse;
+        self.last_mouse = mouse;
+        moved
+            || get_last_key_pressed().is_some()
+            || is_mouse_button_pressed(MouseButton::Left)
+            || is_mouse_button_pressed(MouseButton::Right)
+            || !touches().is_empty()
+    }
+
+    // once a frame on the start screen, before the menu's drawn
+    pub fn update(&mut self, sim: &mut Simulation) -> AttractFrame {
+        if self.any_input() {
+            self.last_input = get_time();
+            if self.running {
+                self.running = false;
+                sim.reset();
+                return AttractFrame::Interrupted;
+            }
+            return AttractFrame::Idle;
+        }
+        if !self.running {
+            if get_time() - self.last_input < IDLE_SECONDS {
+                return AttractFrame::Idle;
+            }
+            self.running = true;
+            sim.reset();
+            sim.world.run(|mut autopilot: UniqueViewMut<Autopilot>| autopilot.on = true);
+        }
+        clear_background(BLACK);
+        // a game over just starts it round again
+        if sim.step().is_err() || sim.phase() != Phase::Playing {
+            sim.reset();
+            sim.world.run(|mut autopilot: UniqueViewMut<Autopilot>| autopilot.on = true);
+        }
+        AttractFrame::Playing
+    }
+
+    // the wait starts over once a game's played
+    pub fn restart(&mut self) {
+        self.last_input = get_time();
+        self.running = false;
+    }
+}
@@ -0,0 +1,241 @@
+// sound effects. the game listens to the event bus (see events.rs) rather
+// than the systems playing sounds themselves: after each step the main loop
+// hands the step's events over, and each one that makes a noise plays it.
+// the engine hums for as long as Thrust keeps coming, louder the harder it's
+// pushed, and the hull splashes when the boat goes fast from one cell into
+// the next. O mutes everything (the music too) and unmutes it again.
+//
+// there are no sound files: the effects are synthesized into little WAVs at
+// startup. each kind has its own volume, along with an overall one, in the
+// [audio] section of settings.toml:
+//
+//   [audio]
+//   master = 0.8
+//   engine = 0.3
+//   weapons = 1.0
+//   impacts = 1.0
+//   jingles = 0.6
+//   splashes = 0.5
+//   music = 0.5
+//   muted = false     (to start with)
+//
+// like the GPU backend this lives in the main loop, since loading sounds has
+// to be awaited.
+
+use std::f32::consts::PI;
+
+use macroquad::audio::{load_sound_from_bytes, play_sound, set_sound_volume, stop_sound, PlaySoundParams, Sound};
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{UniqueView, World};
+
+use crate::events::{Event, Events};
+use crate::keymap::{Action, KeyBindings};
+use crate::settings::Settings;
+
+const SAMPLE_RATE: u32 = 22050;
+// the engine keeps going this long after the last Thrust, so frames that
+// don't run a tick don't make it stutter
+const ENGINE_HOLD_SECONDS: f64 = 0.1;
+// how loud the engine is, of its full volume, at the least thrust
+const ENGINE_IDLE: f32 = 0.3;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub engine: f32,
+    pub weapons: f32,
+    pub impacts: f32,
+    pub jingles: f32,
+    pub splashes: f32,
+    // the background music, see music.rs
+    pub music: f32,
+    pub muted: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> AudioSettings {
+        AudioSettings { master: 0.8, engine: 0.3, weapons: 1., impacts: 1., jingles: 0.6, splashes: 0.5, music: 0.5,
+                        muted: false }
+    }
+}
+
+// 16 bit mono PCM, from samples running -1 to 1
+pub fn wav(samples: &[f32]) -> Vec<u8> {
+    let data_len = samples.len() as u32 * 2;
+    let mut bytes = Vec::with_capacity(44 + data_len as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        bytes.extend_from_slice(&((sample.max(-1.).min(1.) * 32767.) as i16).to_le_bytes());
+    }
+    bytes
+}
+
+// `seconds` of samples, from a function of the time and how far through (0 to 1)
+pub fn synth(seconds: f32, mut f: impl FnMut(f32, f32) -> f32) -> Vec<f32> {
+    let count = (seconds * SAMPLE_RATE as f32) as usize;
+    (0..count).map(|i| f(i as f32 / SAMPLE_RATE as f32, i as f32 / count as f32)).collect()
+}
+
+// its own little generator, so making the sounds doesn't use up the game's random numbers
+pub struct Noise(pub u32);
+
+impl Noise {
+    pub fn next(&mut self) -> f32 {
+        self.0 = self.0.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (self.0 >> 8) as f32 / (1 << 23) as f32 - 1.
+    }
+}
+
+fn engine_samples() -> Vec<f32> {
+    // low rumble: smoothed noise over a 55Hz throb. a whole number of throbs,
+    // so it loops without a click
+    let mut noise = Noise(1);
+    let mut smoothed = 0.;
+    synth(1., |t, _| {
+        smoothed += (noise.next() - smoothed) * 0.05;
+        smoothed * 2. * (0.6 + 0.4 * (2. * PI * 55. * t).sin())
+    })
+}
+
+fn fire_samples() -> Vec<f32> {
+    // a square wave dropping from 880Hz to 220Hz
+    let mut phase = 0.;
+    synth(0.15, |_, through| {
+        phase += (880. - 660. * through) / SAMPLE_RATE as f32;
+        let square = if phase.fract() < 0.5 { 1. } else { -1. };
+        square * 0.4 * (1. - through)
+    })
+}
+
+fn impact_samples() -> Vec<f32> {
+    // a burst of noise with a thump under it
+    let mut noise = Noise(2);
+    synth(0.25, |t, through| {
+        let decay = (1. - through).powi(3);
+        (noise.next() * 0.6 + (2. * PI * 70. * t).sin()) * decay
+    })
+}
+
+fn splash_samples() -> Vec<f32> {
+    // noise that starts bright and gets duller as it dies away, like water
+    // falling back
+    let mut noise = Noise(3);
+    let mut smoothed = 0.;
+    synth(0.4, |_, through| {
+        smoothed += (noise.next() - smoothed) * (0.6 - 0.5 * through);
+        smoothed * 1.5 * (1. - through).powi(2)
+    })
+}
+
+fn jingle_samples() -> Vec<f32> {
+    // an arpeggio up a major chord
+    const NOTES: [f32; 4] = [523.25, 659.25, 783.99, 1046.5];
+    synth(0.8, |t, through| {
+        let note = ((through * NOTES.len() as f32) as usize).min(NOTES.len() - 1);
+        let within = (through * NOTES.len() as f32).fract();
+        (2. * PI * NOTES[note] * t).sin() * 0.5 * (1. - within * 0.7)
+    })
+}
+
+pub struct Audio {
+    settings: AudioSettings,
+    engine: Sound,
+    fire: Sound,
+    impact: Sound,
+    jingle: Sound,
+    splash: Sound,
+    // when the engine last had a Thrust, while it's running
+    engine_since: Option<f64>,
+    // what it's playing at
+    engine_volume: f32,
+    muted: bool,
+}
+
+impl Audio {
+    pub async fn load(settings: &Settings) -> Audio {
+        Audio {
+            settings: settings.audio,
+            engine: load_sound_from_bytes(&wav(&engine_samples())).await.unwrap(),
+            fire: load_sound_from_bytes(&wav(&fire_samples())).await.unwrap(),
+            impact: load_sound_from_bytes(&wav(&impact_samples())).await.unwrap(),
+            jingle: load_sound_from_bytes(&wav(&jingle_samples())).await.unwrap(),
+            splash: load_sound_from_bytes(&wav(&splash_samples())).await.unwrap(),
+            engine_since: None,
+            engine_volume: 0.,
+            muted: settings.audio.muted,
+        }
+    }
+
+    // for the music to follow
+    pub fn muted(&self) -> bool {
+        self.muted
+    }
+
+    fn play(&self, sound: Sound, volume: f32) {
+        if self.muted {
+            return;
+        }
+        play_sound(sound, PlaySoundParams { looped: false, volume: volume * self.settings.master });
+    }
+
+    // once a frame, before the steps
+    pub fn handle_keys(&mut self, world: &World) {
+        if world.borrow::<UniqueView<KeyBindings>>().map_or(false, |keys| keys.pressed(Action::Mute)) {
+            self.muted = !self.muted;
+        }
+    }
+
+    // after each step, while the step's events are still on the bus
+    pub fn handle_events(&mut self, world: &World) {
+        let now = date::now();
+        // the hardest it was pushed this frame
+        let mut thrust: Option<f32> = None;
+        if let Ok(events) = world.borrow::<UniqueView<Events>>() {
+            for event in events.iter() {
+                match event {
+                    Event::Thrust { amount } => thrust = Some(thrust.unwrap_or(0.).max(*amount)),
+                    Event::Splash { strength } => self.play(self.splash, self.settings.splashes * (0.3 + strength * 0.7)),
+                    Event::Fired { charge } => self.play(self.fire, self.settings.weapons * (0.5 + charge / 2.)),
+                    Event::Impact { strength } => self.play(self.impact, self.settings.impacts * strength),
+                    Event::EnemyDestroyed { .. } => self.play(self.impact, self.settings.impacts),
+                    Event::LevelComplete => self.play(self.jingle, self.settings.jingles),
+                    Event::PickedUp { .. } => self.play(self.jingle, self.settings.jingles * 0.5),
+                    Event::Command(_) | Event::ParticlesSunk { .. } => {}
+                }
+            }
+        }
+        match thrust {
+            Some(amount) if !self.muted => {
+                let volume = self.settings.engine * self.settings.master * (ENGINE_IDLE + (1. - ENGINE_IDLE) * amount.min(1.));
+                if self.engine_since.is_none() {
+                    play_sound(self.engine, PlaySoundParams { looped: true, volume });
+                } else if (volume - self.engine_volume).abs() > 0.01 {
+                    set_sound_volume(self.engine, volume);
+                }
+                self.engine_volume = volume;
+                self.engine_since = Some(now);
+            }
+            _ => {
+                let held = thrust.is_some() || self.engine_since.map_or(false, |since| now - since <= ENGINE_HOLD_SECONDS);
+                if self.engine_since.is_some() && (self.muted || !held) {
+                    stop_sound(self.engine);
+                    self.engine_since = None;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// collects lines and dots into a single mesh so they go to the GPU in one draw
+// call, instead of one draw_line/draw_circle call each
+
+use macroquad::models::{Mesh, Vertex};
+use macroquad::prelude::*;
+
+// segments used for the dots; they're tiny, so this is plenty
+const CIRCLE_SEGMENTS: usize = 8;
+
+pub struct MeshBatch {
+    // more than one mesh only when a frame outgrows u16 indices
+    meshes: Vec<Mesh>,
+}
+
+impl MeshBatch {
+    pub fn new() -> MeshBatch {
+        MeshBatch { meshes: vec![] }
+    }
+
+    // the mesh to append `vertex_count` more vertices to, starting a new one if needed
+    fn mesh_for(&mut self, vertex_count: usize) -> &mut Mesh {
+        let full = match self.meshes.last() {
+            Some(mesh) => mesh.vertices.len() + vertex_count > u16::MAX as usize,
+            None => true,
+        };
+        if full {
+            self.meshes.push(Mesh { vertices: vec![], indices: vec![], texture: None });
+        }
+        self.meshes.last_mut().unwrap()
+    }
+
+    pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        let len = pythag(x2 - x1, y2 - y1);
+        if len == 0. {
+            return;
+        }
+        // offset to either side of the line, half the thickness each way
+        let nx = -(y2 - y1) / len * thickness / 2.;
+        let ny = (x2 - x1) / len * thickness / 2.;
+        let mesh = self.mesh_for(4);
+        let base = mesh.vertices.len() as u16;
+        for (x, y) in [(x1 + nx, y1 + ny), (x2 + nx, y2 + ny), (x2 - nx, y2 - ny), (x1 - nx, y1 - ny)].iter() {
+            mesh.vertices.push(vertex(*x, *y, color));
+        }
+        mesh.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    // a line with a little arrowhead at (x2, y2)
+    pub fn arrow(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, thickness: f32, color: Color) {
+        self.line(x1, y1, x2, y2, thickness, color);
+        let len = pythag(x2 - x1, y2 - y1);
+        if len == 0. {
+            return;
+        }
+        let head = len.min(6.) / len;
+        let (bx, by) = ((x1 - x2) * head, (y1 - y2) * head);
+        // the two barbs are the shaft direction rotated +-30 degrees
+        let (sin, cos) = (0.5f32, 0.866f32);
+        self.line(x2, y2, x2 + bx * cos - by * sin, y2 + bx * sin + by * cos, thickness, color);
+        self.line(x2, y2, x2 + bx * cos + by * sin, y2 - bx * sin + by * cos, thickness, color);
+    }
+
+    pub fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: Color) {
+        let mesh = self.mesh_for(4);
+        let base = mesh.vertices.len() as u16;
+        for (x, y) in [(x, y), (x + w, y), (x + w, y + h), (x, y + h)].iter() {
+            mesh.vertices.push(vertex(*x, *y, color));
+        }
+        mesh.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+    }
+
+    pub fn circle(&mut self, x: f32, y: f32, radius: f32, color: Color) {
+        let mesh = self.mesh_for(CIRCLE_SEGMENTS + 1);
+        let center = mesh.vertices.len() as u16;
+        mesh.vertices.push(vertex(x, y, color));
+        for i in 0..CIRCLE_SEGMENTS {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::PI * 2.;
+            mesh.vertices.push(vertex(x + radius * angle.cos(), y + radius * angle.sin(), color));
+            let next = (i + 1) % CIRCLE_SEGMENTS;
+            mesh.indices.extend_from_slice(&[center, center + 1 + i as u16, center + 1 + next as u16]);
+        }
+    }
+
+    // draw everything collected so far and start over
+    pub fn draw(&mut self) {
+        for mesh in self.meshes.iter() {
+            draw_mesh(mesh);
+        }
+        self.meshes.clear();
+    }
+}
+
+fn pythag(dx: f32, dy: f32) -> f32 {
+    (dx * dx + dy * dy).sqrt()
+}
+
+fn vertex(x: f32, y: f32, color: Color) -> Vertex {
+    Vertex { position: vec3(x, y, 0.), uv: vec2(0., 0.), color }
+}
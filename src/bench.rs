@@ -0,0 +1,138 @@
+// `--bench [steps]`: a reproducible performance number to track across commits
+//
+// runs the sim part of the step (no input, no drawing, no window) for a fixed
+// number of steps at a few particle counts, from a fixed random seed, and
+// prints a table of the average time per system and the overall steps/sec.
+// add --soa to bench the struct-of-arrays layout instead, and `--telemetry
+// PATH` to write the results as json too (for scripts, see telemetry.rs).
+
+use macroquad::miniquad::date;
+use macroquad::rand;
+use shipyard::{EntitiesViewMut, IntoIter, UniqueView, UniqueViewMut, ViewMut};
+
+use crate::demo::path_after;
+use crate::grid::GridGeometry;
+use crate::pool::ParticlePool;
+use crate::profiling;
+use crate::simulation::{ParticleLayout, Simulation};
+use crate::{new_particle, Particle};
+
+const PARTICLE_COUNTS: [usize; 4] = [1_000, 10_000, 50_000, 200_000];
+const DEFAULT_STEPS: usize = 300;
+// steps run (and thrown away) before timing starts
+const WARMUP_STEPS: usize = 10;
+
+struct BenchResult {
+    particles: usize,
+    steps_per_second: f64,
+    // average milliseconds per step, per system
+    systems: Vec<(&'static str, f64)>,
+}
+
+fn steps_from_args() -> usize {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|arg| arg == "--bench")
+        .and_then(|i| args.get(i + 1))
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_STEPS)
+}
+
+fn bench_one(layout: ParticleLayout, particles: usize, steps: usize) -> BenchResult {
+    rand::srand(0);
+    let sim = Simulation::new(layout);
+    sim.world.run(|mut pool: UniqueViewMut<ParticlePool>,
+                   grid: UniqueView<GridGeometry>,
+                   mut entities: EntitiesViewMut,
+                   mut all_particles: ViewMut<Particle>| {
+        let existing = all_particles.iter().count();
+        pool.spawn_bulk(&mut entities, &mut all_particles,
+                        (existing..particles).map(|_| new_particle(&grid)));
+    });
+
+    for _ in 0..WARMUP_STEPS {
+        sim.step_headless().unwrap();
+    }
+    profiling::take_samples();
+
+    let start = date::now();
+    for _ in 0..steps {
+        sim.step_headless().unwrap();
+    }
+    let elapsed = date::now() - start;
+
+    let mut systems: Vec<(&'static str, f64)> = vec![];
+    for (name, seconds) in profiling::take_samples() {
+        match systems.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += seconds,
+            None => systems.push((name, seconds)),
+        }
+    }
+    for (_, total) in systems.iter_mut() {
+        *total = *total / steps as f64 * 1000.;
+    }
+    BenchResult { particles, steps_per_second: steps as f64 / elapsed, systems }
+}
+
+pub fn run(layout: ParticleLayout) {
+    let steps = steps_from_args();
+    println!("benchmarking {:?} layout, {} steps per run", layout, steps);
+    let results: Vec<BenchResult> = PARTICLE_COUNTS.iter()
+        .map(|particles| {
+            println!("  {} particles...", particles);
+            bench_one(layout, *particles, steps)
+        })
+        .collect();
+
+    // systems down the side, particle counts across the top
+    let mut names: Vec<&'static str> = vec![];
+    for result in results.iter() {
+        for (name, _) in result.systems.iter() {
+            if !names.contains(name) {
+                names.push(name);
+            }
+        }
+    }
+    print!("\n{:<28}", "ms per step");
+    for result in results.iter() {
+        print!("{:>12}", result.particles);
+    }
+    println!();
+    for name in names {
+        print!("{:<28}", name);
+        for result in results.iter() {
+            match result.systems.iter().find(|(n, _)| *n == name) {
+                Some((_, ms)) => print!("{:>12.3}", ms),
+                None => print!("{:>12}", "-"),
+            }
+        }
+        println!();
+    }
+    print!("{:<28}", "steps/sec");
+    for result in results.iter() {
+        print!("{:>12.1}", result.steps_per_second);
+    }
+    println!();
+    if let Some(path) = path_after("--telemetry") {
+        write_report(&path, layout, steps, &results);
+    }
+}
+
+fn write_report(path: &str, layout: ParticleLayout, steps: usize, results: &[BenchResult]) {
+    let runs: Vec<serde_json::Value> = results.iter()
+        .map(|result| {
+            let systems: Vec<serde_json::Value> = result.systems.iter()
+                .map(|(name, ms)| serde_json::json!({ "name": name, "ms_per_step": ms }))
+                .collect();
+            serde_json::json!({
+                "particles": result.particles,
+                "steps_per_second": result.steps_per_second,
+                "systems": systems,
+            })
+        })
+        .collect();
+    let report = serde_json::json!({ "layout": format!("{:?}", layout), "steps": steps, "runs": runs });
+    match std::fs::write(path, serde_json::to_string_pretty(&report).unwrap()) {
+        Ok(()) => println!("wrote the results to {}", path),
+        Err(err) => println!("couldn't write {}: {}", path, err),
+    }
+}
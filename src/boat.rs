@@ -0,0 +1,274 @@
+// the boat: where it is, how it's moving, and the turtle it draws its hull
+// with. a player's boat is an entity with a PlayerId and the rest of what
+// each player has their own of (controls, shot charge, boost, wake, score and
+// collider, see spawn_player), steered by steer_player from its boat controls
+// (see input.rs), dragged by the flow and pushing its wake back into it (see
+// wake.rs) and moved by update_player. there's one, or two with --two-player
+// (see two_player.rs). other boats (see net.rs and server.rs) go through the
+// same steer and advance.
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntitiesViewMut, IntoIter, UniqueView, UniqueViewMut, View, ViewMut, World};
+
+use crate::clock::SimClock;
+use crate::collision::{Collider, BOAT_RADIUS};
+use crate::effects::Effects;
+use crate::events::{Event, Events};
+use crate::fluid::Cells;
+use crate::geometry::wrap_coord;
+use crate::grid::GridGeometry;
+use crate::input::{BoatCommand, BoatControls};
+use crate::level::Level;
+use crate::pickups::Boost;
+use crate::profiling;
+use crate::score::Score;
+use crate::turtle::{new_turtle, Turtle};
+use crate::turtle_path::TurtlePath;
+use crate::tuning::Tuning;
+use crate::wake::Wake;
+use crate::weapons::ShotCharge;
+use crate::weather::Weather;
+use crate::{lerp, Point2, Real, Screen};
+
+#[derive(Component)]
+pub struct Boat {
+    pub loc: Point2,
+    pub vel: Vec2,
+    pub health: f32, // or some other per-boat state
+    t: Turtle,
+    // which way the hull is drawn, in radians. it trails after the steering
+    // direction and the way the boat's actually moving, so it doesn't look
+    // like it's sliding sideways
+    drawn_heading: f32,
+}
+
+// which player a boat's steered by: the first on the arrows (and the pad, the
+// mouse, the touch controls and the autopilot), the second on W, A, E and Q
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct PlayerId(pub u8);
+
+impl PlayerId {
+    pub fn is_first(self) -> bool {
+        self.0 == 1
+    }
+}
+
+// below this speed the hull is drawn pointing where it's steered, since the
+// direction of motion is mostly noise
+const HULL_SETTLE_SPEED: f32 = 0.2;
+// how much of the way from the steering direction to the direction of motion
+// the hull is drawn
+const HULL_MOTION_WEIGHT: f32 = 0.5;
+
+// the turn from angle `from` to angle `to` (radians), the short way round
+pub fn angle_between(from: f32, to: f32) -> f32 {
+    let tau = std::f32::consts::PI * 2.;
+    (to - from + std::f32::consts::PI).rem_euclid(tau) - std::f32::consts::PI
+}
+
+// the hull, from the boat's middle: out to the bow, then round the outline
+pub fn hull() -> TurtlePath {
+    TurtlePath::new("hull")
+        .pen_up()
+        .forward(20.)
+        .pen_down()
+        .turn_right(150.)
+        .forward(15.) //right angle
+        .turn_right(30.)
+        .forward(20.) //right side
+        .turn_right(90.)
+        .forward(15.) // bottom
+        .turn_right(90.)
+        .forward(20.) //left side
+        .turn_right(30.)
+        .forward(15.) //left angle
+}
+
+pub fn new_boat(x: f32, y: f32, vx: f32, vy: f32) -> Boat {
+    Boat { loc: Point2 {x: x as Real, y: y as Real}, vel: Vec2::new(vx, vy), health: 1., t: new_turtle(), drawn_heading: 0.}
+}
+
+impl Boat {
+    // where it's drawn: `alpha` of the way from the last tick's spot to this
+    // one's (see clock.rs)
+    pub fn drawn_loc(&self, alpha: f32) -> Vec2 {
+        Vec2::new(self.loc.x as f32, self.loc.y as f32) - self.vel * (1. - alpha)
+    }
+
+    // draws the hull with a copy of the boat's turtle (for its colour), so
+    // rendering only needs to read the boat
+    pub fn render(&self, alpha: f32) {
+        let at = self.drawn_loc(alpha);
+        let mut t = self.t.clone();
+        t.direction = self.drawn_heading;
+        t.move_to(at.x, at.y);
+        hull().replay(&mut t, 1.);
+    }
+
+    // `amount` is how hard, 0 to 1, and `blend` how much of the velocity a
+    // full thrust swings round each step, and `power` the speed it adds on
+    // top of what we've already got (both see tuning.rs)
+    pub fn thrust(&mut self, amount: f32, blend: f32, power: f32) {
+        // we want to thrust in the direction we're pointed, not in the direction we're moving
+        // so will lerp our velocity between the movement vector and the direction vector (scaled by |vel|)
+        let thrust_mag = power + (self.vel.x * self.vel.x + self.vel.y * self.vel.y).sqrt();
+        let thrust_x = self.t.direction.cos() * thrust_mag;
+        let thrust_y = self.t.direction.sin() * thrust_mag;
+        self.vel.x = lerp (self.vel.x, thrust_x, blend * amount);
+        self.vel.y = lerp (self.vel.y, thrust_y, blend * amount);
+    }
+    // what the hull's drawn in
+    pub fn set_color(&mut self, color: Color) {
+        self.t.set_color(color);
+    }
+    pub fn turn(&mut self, degrees: f32) {
+        self.t.direction += degrees;
+    }
+    // which way the bow points, in radians
+    pub fn heading(&self) -> f32 {
+        self.t.direction
+    }
+
+    // once a step: swing the drawn hull `follow` (0 to 1) of the way toward
+    // somewhere between the steering direction and the direction of motion
+    pub fn settle_heading(&mut self, follow: f32) {
+        let steering = self.t.direction;
+        let speed = self.vel.length();
+        let target = if speed > HULL_SETTLE_SPEED {
+            let moving = self.vel.y.atan2(self.vel.x);
+            steering + angle_between(steering, moving) * HULL_MOTION_WEIGHT
+        } else {
+            steering
+        };
+        self.drawn_heading += angle_between(self.drawn_heading, target) * follow.max(0.).min(1.);
+    }
+
+    // move one step along the velocity, wrapping round the screen and
+    // bouncing back off anything solid
+    pub fn advance(&mut self, map: &Cells, grid: &GridGeometry) {
+        let old_loc = self.loc;
+        self.loc.x += self.vel.x as Real;
+        self.loc.y += self.vel.y as Real;
+        self.loc.x = wrap_coord(self.loc.x, grid.width as Real);
+        self.loc.y = wrap_coord(self.loc.y, grid.height as Real);
+        // ran aground
+        if map.all_cells[grid.index_at(self.loc.x as f32, self.loc.y as f32)].is_solid() {
+            self.loc = old_loc;
+            self.vel = -self.vel * 0.5;
+        }
+    }
+}
+
+// in init_world: a player's boat, and everything else that player has their
+// own of alongside it
+pub fn spawn_player(world: &World, id: PlayerId, boat: Boat) {
+    world.run(|mut entities: EntitiesViewMut,
+               mut boats: ViewMut<Boat>,
+               mut ids: ViewMut<PlayerId>,
+               mut controls: ViewMut<BoatControls>,
+               mut shots: ViewMut<ShotCharge>,
+               mut boosts: ViewMut<Boost>,
+               mut wakes: ViewMut<Wake>,
+               mut scores: ViewMut<Score>,
+               mut colliders: ViewMut<Collider>| {
+        let center = Vec2::new(boat.loc.x as f32, boat.loc.y as f32);
+        entities.add_entity((&mut boats, &mut ids, &mut controls, &mut shots, &mut boosts, &mut wakes, &mut scores,
+                             &mut colliders),
+                            (boat, id, BoatControls::default(), ShotCharge::default(), Boost::default(),
+                             Wake::default(), Score::default(), Collider { center, radius: BOAT_RADIUS }));
+    });
+}
+
+// world space: each player's boat, with its number over it when there's more
+// than one
+pub fn draw_boats(_screen: UniqueViewMut<Screen>, boats: View<Boat>, ids: View<PlayerId>, clock: UniqueView<SimClock>) {
+    let several = ids.iter().count() > 1;
+    for (boat, id) in (&boats, &ids).iter() {
+        boat.render(clock.alpha());
+        if several {
+            let at = boat.drawn_loc(clock.alpha());
+            draw_text(&format!("{}", id.0), at.x - 4., at.y - BOAT_RADIUS - 4., 16., WHITE);
+        }
+    }
+}
+
+// faster than this (pixels a tick) and crossing into a new cell splashes,
+// loudest from the second
+const SPLASH_SPEED: f32 = 1.5;
+const SPLASH_FULL_SPEED: f32 = 4.;
+
+// radians a frame the boat turns at full rudder
+pub const TURN_RATE: f32 = 0.1;
+// how hard a dash shoves the boat sideways
+const DASH_SPEED: f32 = 3.;
+
+// act on each player's boat controls (see input.rs), split from
+// handle_key_presses so the boats aren't tied up with the mode toggles (and
+// vice versa) in the parallel workload
+pub fn steer_player(mut boats: ViewMut<Boat>,
+                    mut effects:UniqueViewMut<Effects>,
+                    mut controls: ViewMut<BoatControls>,
+                    mut events: UniqueViewMut<Events>,
+                    tuning: UniqueView<Tuning>,
+                    weather: UniqueView<Weather>,
+                    level: UniqueView<Level>)
+{
+    let _t = profiling::scope("steer_player");
+    let control = weather.control(level.seconds());
+    for (boat, controls) in (&mut boats, &mut controls).iter() {
+        if controls.thrust > 0. {
+            events.send(Event::Thrust { amount: controls.thrust });
+        }
+        steer(boat, controls, &mut effects, &tuning, control);
+    }
+}
+
+// one tick of a boat's controls, for the player and anyone else's boat (see
+// net.rs). `control` (0 to 1) is how much the turning and thrust take, less
+// in a storm (see weather.rs)
+pub fn steer(boat: &mut Boat, controls: &mut BoatControls, effects: &mut Effects, tuning: &Tuning, control: f32) {
+    boat.turn(TURN_RATE * controls.turn * control);
+    if controls.thrust > 0. {
+        boat.thrust(controls.thrust, tuning.thrust_blend * control, tuning.thrust_power);
+        // puff of wake off the stern, kicked back and a little to either side
+        let heading = Vec2::new(boat.t.direction.cos(), boat.t.direction.sin());
+        let stern = Vec2::new(boat.loc.x as f32, boat.loc.y as f32) - heading * 5.;
+        let kick = -heading * 0.5 + Vec2::new(-heading.y, heading.x) * effects.gen_range(-0.3, 0.3);
+        effects.wake_puff(stern, kick);
+    }
+    // (firing is spawn_projectiles's job, see weapons.rs)
+    // used up, so a frame that runs two ticks doesn't dash twice
+    for command in controls.commands.drain(..) {
+        match command {
+            BoatCommand::Dash { side } => {
+                let heading = boat.heading();
+                let starboard = Vec2::new(-heading.sin(), heading.cos());
+                let vel = boat.vel;
+                boat.vel = vel + starboard * side * DASH_SPEED;
+            }
+            BoatCommand::QuickFlip { side } => boat.turn(std::f32::consts::PI * side * control),
+        }
+    }
+}
+
+// moves each player's boat, which splashes crossing into a new cell fast, and
+// its collider with it
+pub fn update_player(mut boats: ViewMut<Boat>,
+                     mut colliders: ViewMut<Collider>,
+                     map: UniqueView<Cells>,
+                     grid: UniqueView<GridGeometry>,
+                     tuning: UniqueView<Tuning>,
+                     mut events: UniqueViewMut<Events>)
+{
+    let _t = profiling::scope("update_player");
+    for (boat, collider) in (&mut boats, &mut colliders).iter() {
+        let from_cell = grid.index_at(boat.loc.x as f32, boat.loc.y as f32);
+        boat.advance(&map, &grid);
+        let speed = boat.vel.length();
+        if speed > SPLASH_SPEED && grid.index_at(boat.loc.x as f32, boat.loc.y as f32) != from_cell {
+            events.send(Event::Splash { strength: ((speed - SPLASH_SPEED) / (SPLASH_FULL_SPEED - SPLASH_SPEED)).min(1.) });
+        }
+        boat.settle_heading(tuning.hull_turn);
+        collider.center = Vec2::new(boat.loc.x as f32, boat.loc.y as f32);
+    }
+}
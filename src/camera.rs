@@ -0,0 +1,213 @@
+// the view onto the world. everything in the world (particles, cells, the
+// boat, effects) is drawn through it, between begin_world_view and
+// end_world_view in the render workload; the HUD and overlays after that are
+// in plain screen pixels. anything that takes a mouse position as a place in
+// the world wants mouse_world rather than macroquad's mouse_position.
+//
+// the scroll wheel zooms, keeping whatever's under the cursor under the
+// cursor (ctrl+scroll is left to the tools, see tools.rs). how fast and how
+// far it zooms come from the [camera] section of the settings file.
+//
+// dragging with the middle button (or space and the left button) pans. let go
+// mid-drag and the view keeps drifting for a bit. the view never leaves the
+// world, so at 1x there's nowhere to pan to.
+//
+// the world's usually the window's size (see window.rs), but a replay's is
+// the size it was recorded at, so the view's scaled up or down to fit the
+// window, keeping its shape, with black bars down the sides or along the top
+// and bottom where the two are different shapes.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::settings::Settings;
+use crate::Screen;
+
+// how much of the pan's drift is left after each frame
+const PAN_FRICTION: f32 = 0.9;
+// below this (world units a frame) the drift stops
+const MIN_PAN_SPEED: f32 = 0.05;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct CameraSettings {
+    // how much one notch of the wheel zooms by (0.1 is 10%)
+    pub zoom_speed: f32,
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> CameraSettings {
+        CameraSettings { zoom_speed: 0.1, min_zoom: 1., max_zoom: 8. }
+    }
+}
+
+#[derive(Component)]
+pub struct Camera {
+    // screen pixels per world unit
+    pub zoom: f32,
+    // the world point in the middle of the screen
+    pub target: Vec2,
+    settings: CameraSettings,
+    // where the pan grabbed the screen last frame, while there's a pan going
+    grab: Option<Vec2>,
+    // world units a frame the view is drifting by
+    drift: Vec2,
+    // a panel on screen has the mouse (set by the panel each frame)
+    pub ui_has_mouse: bool,
+    // how big the world is (see config.rs), all of which is on screen at zoom 1
+    world: Vec2,
+    // how big the window is, in pixels (kept up to date by track_screen)
+    pub window: Vec2,
+}
+
+impl Camera {
+    pub fn new(settings: &Settings, world: Vec2) -> Camera {
+        Camera {
+            zoom: 1.,
+            target: world / 2.,
+            settings: settings.camera,
+            grab: None,
+            drift: Vec2::new(0., 0.),
+            ui_has_mouse: false,
+            world,
+            window: world,
+        }
+    }
+
+    // the world's a new size (see window.rs): the view stays on the same part
+    // of it
+    pub fn resize_world(&mut self, world: Vec2) {
+        self.target *= world / self.world;
+        self.drift = Vec2::new(0., 0.);
+        self.world = world;
+        self.clamp_to_world();
+    }
+
+    // window pixels per screen unit: as big as the view can be and still fit
+    // in the window
+    fn pixel_scale(&self) -> f32 {
+        let scale = self.window / self.world;
+        scale.x.min(scale.y)
+    }
+
+    // the part of the window the view's drawn in, in window pixels (the rest
+    // is the bars)
+    pub fn viewport(&self) -> Rect {
+        let size = self.world * self.pixel_scale();
+        Rect::new((self.window.x - size.x) / 2., (self.window.y - size.y) / 2., size.x, size.y)
+    }
+
+    // window pixels per world unit, for drawing world sized things in the
+    // overlays
+    pub fn pixels_per_unit(&self) -> f32 {
+        self.zoom * self.pixel_scale()
+    }
+
+    // the mouse in screen units rather than window pixels
+    fn mouse_screen(&self) -> Vec2 {
+        let (mouse_x, mouse_y) = mouse_position();
+        let viewport = self.viewport();
+        (Vec2::new(mouse_x, mouse_y) - Vec2::new(viewport.x, viewport.y)) / self.pixel_scale()
+    }
+
+    // the part of the world on screen
+    pub fn visible_rect(&self) -> Rect {
+        let size = self.world / self.zoom;
+        Rect::new(self.target.x - size.x / 2., self.target.y - size.y / 2., size.x, size.y)
+    }
+
+    // from window pixels
+    pub fn screen_to_world(&self, point: Vec2) -> Vec2 {
+        let (visible, viewport) = (self.visible_rect(), self.viewport());
+        Vec2::new(visible.x, visible.y) + (point - Vec2::new(viewport.x, viewport.y)) / self.pixels_per_unit()
+    }
+
+    // to window pixels
+    pub fn world_to_screen(&self, point: Vec2) -> Vec2 {
+        let (visible, viewport) = (self.visible_rect(), self.viewport());
+        Vec2::new(viewport.x, viewport.y) + (point - Vec2::new(visible.x, visible.y)) * self.pixels_per_unit()
+    }
+
+    pub fn mouse_world(&self) -> Vec2 {
+        let (mouse_x, mouse_y) = mouse_position();
+        self.screen_to_world(Vec2::new(mouse_x, mouse_y))
+    }
+
+    pub fn is_panning(&self) -> bool {
+        self.grab.is_some()
+    }
+
+    // the tools leave the mouse buttons alone while the camera's panning or
+    // the mouse is over a panel
+    pub fn tools_have_mouse(&self) -> bool {
+        !self.is_panning() && !self.ui_has_mouse
+    }
+
+    // keep the view inside the world, stopping any drift into the edge
+    fn clamp_to_world(&mut self) {
+        let half = self.world / self.zoom / 2.;
+        let world = self.world;
+        let clamped = Vec2::new(self.target.x.max(half.x).min(world.x - half.x),
+                                self.target.y.max(half.y).min(world.y - half.y));
+        if clamped.x != self.target.x {
+            self.drift.x = 0.;
+        }
+        if clamped.y != self.target.y {
+            self.drift.y = 0.;
+        }
+        self.target = clamped;
+    }
+}
+
+pub fn zoom_camera(mut camera: UniqueViewMut<Camera>, _screen: UniqueView<Screen>) {
+    let (_, wheel) = mouse_wheel();
+    let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+    if wheel == 0. || ctrl {
+        return;
+    }
+    let mouse = camera.mouse_screen();
+    let under_mouse = camera.mouse_world();
+    let settings = camera.settings;
+    camera.zoom = (camera.zoom * (1. + settings.zoom_speed).powf(wheel.signum()))
+        .max(settings.min_zoom).min(settings.max_zoom);
+    // move the view so the same world point ends up back under the cursor
+    camera.target = under_mouse - (mouse - camera.world / 2.) / camera.zoom;
+    camera.clamp_to_world();
+}
+
+pub fn pan_camera(mut camera: UniqueViewMut<Camera>, _screen: UniqueView<Screen>) {
+    let grabbing = is_mouse_button_down(MouseButton::Middle)
+        || (is_key_down(KeyCode::Space) && is_mouse_button_down(MouseButton::Left));
+    if grabbing {
+        let mouse = camera.mouse_screen();
+        if let Some(last) = camera.grab {
+            // the world moves with the mouse, so the view moves against it
+            let moved = (last - mouse) / camera.zoom;
+            camera.target += moved;
+            camera.drift = moved;
+        }
+        camera.grab = Some(mouse);
+    } else {
+        camera.grab = None;
+        let drift = camera.drift;
+        camera.target += drift;
+        camera.drift = if drift.length() < MIN_PAN_SPEED { Vec2::new(0., 0.) } else { drift * PAN_FRICTION };
+    }
+    camera.clamp_to_world();
+}
+
+pub fn begin_world_view(_screen: UniqueViewMut<Screen>, camera: UniqueView<Camera>) {
+    let viewport = camera.viewport();
+    set_camera(&Camera2D {
+        viewport: Some((viewport.x.round() as i32, viewport.y.round() as i32,
+                        viewport.w.round() as i32, viewport.h.round() as i32)),
+        ..Camera2D::from_display_rect(camera.visible_rect())
+    });
+}
+
+pub fn end_world_view(_screen: UniqueViewMut<Screen>) {
+    set_default_camera();
+}
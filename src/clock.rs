@@ -0,0 +1,128 @@
+// the sim runs on a fixed timestep: each run of the Sim workload is one tick
+// of TICK seconds, and the clock works out how many ticks a frame owes from
+// how long the frame took. the speed controls scale how fast time goes into
+// that accumulator, not the length of a tick, so the sim behaves the same at
+// any speed, there's just more or less of it per frame.
+//
+// the frames don't line up with the ticks (a 144Hz screen draws two or three
+// frames a tick, and at 1/8 speed it's more like twenty), so the things that
+// move are drawn part way along their last tick's step, by how much of the
+// next tick the accumulator's got through (see alpha). that's a tick behind,
+// but smooth at any frame rate and speed.
+//
+//   P       pause / unpause
+//   .       single step while paused, otherwise twice as fast
+//   ,       half as fast
+//   1 2 3   half speed, normal, 4x fast-forward
+//
+// `,` and `.` go between 1/8 and 8x, for watching the flow field in slow
+// motion (or skipping through the dull bits).
+//
+// (all rebindable, see keymap.rs)
+
+use shipyard::Component;
+
+// seconds of sim time per tick
+pub const TICK: f32 = 1. / 60.;
+// a slow frame at 4x shouldn't turn into a pile of ticks that makes the next
+// frame slower still, so past this the clock just lets the time go
+const MAX_TICKS_PER_FRAME: u32 = 8;
+
+pub const HALF_SPEED: f32 = 0.5;
+pub const FAST_FORWARD: f32 = 4.;
+// as far as `,` and `.` go either way
+const MIN_SPEED: f32 = 1. / 8.;
+const MAX_SPEED: f32 = 8.;
+
+#[derive(Component)]
+pub struct SimClock {
+    pub speed: f32,
+    pub paused: bool,
+    step_requested: bool,
+    // sim time owed but not yet ticked off
+    accumulator: f32,
+    // however many ticks the step's caller lets it run, however long the frame
+    // took (for the uncapped pacing mode, which ticks as fast as it can on
+    // purpose, see pacing.rs)
+    pub free_running: bool,
+}
+
+impl Default for SimClock {
+    fn default() -> SimClock {
+        SimClock { speed: 1., paused: false, step_requested: false, accumulator: 0., free_running: false }
+    }
+}
+
+impl SimClock {
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.accumulator = 0.;
+    }
+
+    pub fn single_step(&mut self) {
+        self.paused = true;
+        self.step_requested = true;
+    }
+
+    // `.`: a step when paused, otherwise speeding up
+    pub fn step_or_faster(&mut self) {
+        if self.paused {
+            self.single_step();
+        } else {
+            self.speed = (self.speed * 2.).min(MAX_SPEED);
+        }
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2.).max(MIN_SPEED);
+    }
+
+    // picking a speed also unpauses
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+        self.paused = false;
+    }
+
+    // how many ticks to run this frame
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        if self.paused {
+            let ticks = self.step_requested as u32;
+            self.step_requested = false;
+            return ticks;
+        }
+        if self.free_running {
+            return u32::MAX;
+        }
+        self.accumulator += frame_time * self.speed;
+        let ticks = (self.accumulator / TICK).floor() as u32;
+        self.accumulator -= ticks as f32 * TICK;
+        if ticks > MAX_TICKS_PER_FRAME {
+            self.accumulator = 0.;
+            MAX_TICKS_PER_FRAME
+        } else {
+            ticks
+        }
+    }
+
+    // how far into the next tick the frame's got, 0 to 1, for drawing things
+    // part of the way from where they were last tick to where they are now.
+    // (paused, or a tick a frame, they're just drawn where they are)
+    pub fn alpha(&self) -> f32 {
+        if self.paused || self.free_running {
+            1.
+        } else {
+            (self.accumulator / TICK).min(1.)
+        }
+    }
+
+    // what the HUD says about it, if anything (nothing at normal speed)
+    pub fn label(&self) -> Option<String> {
+        if self.paused {
+            Some("paused".to_owned())
+        } else if self.speed != 1. {
+            Some(format!("{}x speed", self.speed))
+        } else {
+            None
+        }
+    }
+}
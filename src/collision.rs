@@ -0,0 +1,117 @@
+// circle colliders, found with a quadtree broad phase and a circle-circle
+// narrow phase. gameplay systems give their entities a Collider (and keep its
+// center up to date), then read this step's hits out of Collisions. the world
+// wraps, so two colliders either side of an edge touch across it (see
+// geometry.rs).
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View};
+
+use crate::events::{Event, Events};
+use crate::geometry::wrapped_dist;
+use crate::grid::GridGeometry;
+use crate::pickups::Pickup;
+use crate::profiling;
+use crate::quadtree::Quadtree;
+use crate::Boat;
+
+// roughly the size of the boat's hull
+pub const BOAT_RADIUS: f32 = 12.;
+
+#[derive(Component)]
+pub struct Collider {
+    pub center: Vec2,
+    pub radius: f32,
+}
+
+impl Collider {
+    fn bounds(&self) -> Rect {
+        Rect::new(self.center.x - self.radius, self.center.y - self.radius,
+                  self.radius * 2., self.radius * 2.)
+    }
+
+    fn touches(&self, center: Vec2, radius: f32, size: Vec2) -> bool {
+        wrapped_dist(self.center, center, size) < self.radius + radius
+    }
+}
+
+// everything in `tree` near `bounds`, including across the edges: a box
+// sticking out past one is looked for again a world's width (or height) over
+fn query_wrapped(tree: &Quadtree<EntityId>, bounds: Rect, size: Vec2, out: &mut Vec<EntityId>) {
+    let shift = |start: f32, length: f32, world: f32| {
+        if start < 0. {
+            world
+        } else if start + length > world {
+            -world
+        } else {
+            0.
+        }
+    };
+    let (dx, dy) = (shift(bounds.x, bounds.w, size.x), shift(bounds.y, bounds.h, size.y));
+    for x in [0., dx].iter().take(if dx == 0. { 1 } else { 2 }) {
+        for y in [0., dy].iter().take(if dy == 0. { 1 } else { 2 }) {
+            tree.query(&Rect { x: bounds.x + x, y: bounds.y + y, ..bounds }, out);
+        }
+    }
+    // (a collider can turn up under more than one of those)
+    out.sort_unstable();
+    out.dedup();
+}
+
+#[derive(Component, Default)]
+pub struct Collisions {
+    // every pair of colliders that overlap, each pair once
+    pub pairs: Vec<(EntityId, EntityId)>,
+    // the pairs with a player's boat in, the boat first
+    pub boat_hits: Vec<(EntityId, EntityId)>,
+}
+
+pub fn detect_collisions(colliders: View<Collider>,
+                         pickups: View<Pickup>,
+                         boats: View<Boat>,
+                         mut collisions: UniqueViewMut<Collisions>,
+                         mut events: UniqueViewMut<Events>,
+                         grid: UniqueView<GridGeometry>) {
+    let _t = profiling::scope("detect_collisions");
+    let collisions = &mut *collisions;
+    collisions.pairs.clear();
+    // kept for a moment, to tell new hits from ones still touching
+    let touching = std::mem::take(&mut collisions.boat_hits);
+
+    let mut tree = Quadtree::new(Rect::new(0., 0., grid.width, grid.height));
+    for (id, collider) in colliders.iter().with_id() {
+        tree.insert(id, collider.bounds());
+    }
+
+    let size = grid.size();
+    let mut candidates = vec![];
+    for (id, collider) in colliders.iter().with_id() {
+        candidates.clear();
+        query_wrapped(&tree, collider.bounds(), size, &mut candidates);
+        for other_id in candidates.iter() {
+            // each pair shows up from both sides, keep one of them
+            if *other_id <= id {
+                continue;
+            }
+            let other = colliders.get(*other_id).unwrap();
+            if collider.touches(other.center, other.radius, size) {
+                collisions.pairs.push((id, *other_id));
+            }
+        }
+    }
+
+    for (a, b) in collisions.pairs.iter() {
+        let hit = if boats.get(*a).is_ok() {
+            (*a, *b)
+        } else if boats.get(*b).is_ok() {
+            (*b, *a)
+        } else {
+            continue;
+        };
+        // running over a pickup isn't a bump (see pickups.rs)
+        if !touching.contains(&hit) && pickups.get(hit.1).is_err() {
+            events.send(Event::Impact { strength: 0.5 });
+        }
+        collisions.boat_hits.push(hit);
+    }
+}
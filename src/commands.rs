@@ -0,0 +1,152 @@
+// the console's commands: parsing a typed line into a Command, and carrying
+// the commands out once they come back round on the event bus.
+//
+//   spawn particles N    N more particles, scattered at random
+//   set NAME VALUE       one of the tuning panel's values (see tuning::NAMES)
+//   preset NAME          vortex_pair, or still (stops all the flow)
+//   clear                removes every particle and vortex
+//   seed N               reseeds the random numbers
+//   export               writes the flow out (see export.rs, which handles it)
+//   screenshot           saves the next frame (see video.rs)
+//   restart              starts the level over
+//   quit                 ends the game, like esc (both see game_state.rs)
+//   help                 lists these
+
+use macroquad::prelude::*;
+use shipyard::{EntitiesViewMut, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, ViewMut};
+
+use crate::console::Console;
+use crate::events::{Event, Events};
+use crate::governor::MAX_PARTICLES;
+use crate::grid::GridGeometry;
+use crate::pool::ParticlePool;
+use crate::tools::{Vortex, Vortices, MAX_SWIRL};
+use crate::tuning::{self, Tuning};
+use crate::video::Capture;
+use crate::{new_particle, Cells, Particle};
+
+const PRESETS: [&str; 2] = ["vortex_pair", "still"];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    SpawnParticles(usize),
+    Set(String, f32),
+    Preset(String),
+    Clear,
+    Seed(u64),
+    Export,
+    Screenshot,
+    Restart,
+    Quit,
+    Help,
+}
+
+fn number<T: std::str::FromStr>(word: Option<&str>, what: &str) -> Result<T, String> {
+    let word = word.ok_or_else(|| format!("missing {}", what))?;
+    word.parse().map_err(|_| format!("{} isn't a valid {}", word, what))
+}
+
+pub fn parse(line: &str) -> Result<Command, String> {
+    let mut words = line.split_whitespace();
+    let command = match words.next() {
+        Some("spawn") => match words.next() {
+            Some("particles") => Command::SpawnParticles(number(words.next(), "count")?),
+            _ => return Err("usage: spawn particles N".to_string()),
+        },
+        Some("set") => {
+            let name = words.next().ok_or("usage: set NAME VALUE")?.to_string();
+            if !tuning::NAMES.contains(&name.as_str()) {
+                return Err(format!("no setting {}; try one of {}", name, tuning::NAMES.join(", ")));
+            }
+            Command::Set(name, number(words.next(), "value")?)
+        }
+        Some("preset") => {
+            let name = words.next().ok_or("usage: preset NAME")?;
+            if !PRESETS.contains(&name) {
+                return Err(format!("no preset {}; try one of {}", name, PRESETS.join(", ")));
+            }
+            Command::Preset(name.to_string())
+        }
+        Some("clear") => Command::Clear,
+        Some("seed") => Command::Seed(number(words.next(), "seed")?),
+        Some("export") => Command::Export,
+        Some("screenshot") => Command::Screenshot,
+        Some("restart") => Command::Restart,
+        Some("quit") => Command::Quit,
+        Some("help") => Command::Help,
+        Some(other) => return Err(format!("unknown command {} (try help)", other)),
+        None => return Err("".to_string()),
+    };
+    match words.next() {
+        Some(extra) => Err(format!("didn't expect {}", extra)),
+        None => Ok(command),
+    }
+}
+
+// runs after the console, so a command typed this frame happens this frame
+pub fn run_commands(events: UniqueView<Events>,
+                    mut console: UniqueViewMut<Console>,
+                    mut tuning: UniqueViewMut<Tuning>,
+                    mut vortices: UniqueViewMut<Vortices>,
+                    mut map: UniqueViewMut<Cells>,
+                    grid: UniqueView<GridGeometry>,
+                    mut pool: UniqueViewMut<ParticlePool>,
+                    mut entities: EntitiesViewMut,
+                    mut particles: ViewMut<Particle>,
+                    mut capture: UniqueViewMut<Capture>) {
+    for event in events.iter() {
+        let command = match event {
+            Event::Command(command) => command,
+            _ => continue,
+        };
+        match command {
+            Command::SpawnParticles(count) => {
+                let existing = particles.iter().count();
+                let count = (*count).min(MAX_PARTICLES.saturating_sub(existing));
+                pool.spawn_bulk(&mut entities, &mut particles, (0..count).map(|_| new_particle(&grid)));
+                console.print(format!("spawned {} particles", count));
+            }
+            Command::Set(name, value) => {
+                tuning.set(name, *value);
+                console.print(format!("{} = {}", name, value));
+            }
+            Command::Preset(name) => {
+                if name == "vortex_pair" {
+                    // side by side, spinning opposite ways
+                    let y = grid.height / 2.;
+                    vortices.0.push(Vortex::new(Vec2::new(grid.width / 3., y), MAX_SWIRL));
+                    vortices.0.push(Vortex::new(Vec2::new(grid.width * 2. / 3., y), -MAX_SWIRL));
+                } else {
+                    vortices.0.clear();
+                    for cell_ix in 0..grid.len() {
+                        if map.all_cells.is_allocated(cell_ix) {
+                            map.all_cells[cell_ix].flow_v = Vec2::new(0., 0.);
+                        }
+                    }
+                }
+                console.print(format!("preset {}", name));
+            }
+            Command::Clear => {
+                let all: Vec<EntityId> = particles.iter().with_id().map(|(id, _)| id).collect();
+                pool.despawn_bulk(&mut particles, &all);
+                vortices.0.clear();
+                console.print(format!("cleared {} particles", all.len()));
+            }
+            Command::Seed(seed) => {
+                rand::srand(*seed);
+                console.print(format!("seeded with {}", seed));
+            }
+            // export_field picks this one up, and request_transitions these
+            Command::Export | Command::Restart | Command::Quit => {}
+            Command::Screenshot => {
+                capture.screenshot = true;
+                console.print("screenshot saved with the next frame".to_string());
+            }
+            Command::Help => {
+                console.print("spawn particles N, set NAME VALUE, preset NAME, clear, seed N, export, screenshot, restart, quit".to_string());
+                console.print(format!("settings: {}", tuning::NAMES.join(", ")));
+                console.print(format!("presets: {}", PRESETS.join(", ")));
+            }
+        }
+    }
+}
@@ -0,0 +1,143 @@
+// the sim's own numbers, which used to be baked in: how big the world is, the
+// grid's resolution, how many particles it starts with, and how the particles
+// and the cells pull on each other. they're read from sim.toml (or the file
+// after `--config`), every one optional:
+//
+//   width = 1280
+//   height = 720
+//   cols = 40
+//   rows = 24
+//   particles = 500
+//   particle_batch = 200
+//   viscosity = 0.2
+//   cell_to_particle = 0.05
+//   particle_to_cell = 0.2
+//
+// and any of them can be given on the command line instead, which wins over
+// the file: `--width 1280`, `--cols 40`, `--cell-to-particle 0.05` and so on
+// (the same names, with dashes).
+//
+// the window opens at the world's size. a level's [grid] (see scenario.rs)
+// still picks its own cols and rows. the viscosity and the two pull rates are
+// where the tuning panel (see tuning.rs) starts, over anything it's saved;
+// left out, the panel's own values stand. particle_batch is how many
+// particles 0 adds and 9 takes away (see tools.rs's run_tool_commands). the
+// config's read again whenever the world's reset, so an edited file takes
+// effect from the next game. once the tuning panel's changed the particle
+// count or the grid's size, though, its numbers stand instead, until the game
+// is restarted.
+
+use std::fs;
+use std::str::FromStr;
+
+use macroquad::prelude::*;
+use serde::Deserialize;
+use shipyard::Component;
+
+use crate::demo::path_after;
+use crate::tuning::Tuning;
+use crate::{CELLS_X, CELLS_Y, HEIGHT, WIDTH};
+
+pub const CONFIG_PATH: &str = "sim.toml";
+
+// what the world starts with, unless it's told otherwise
+const DEFAULT_PARTICLES: usize = 8;
+const DEFAULT_PARTICLE_BATCH: usize = 100;
+
+#[derive(Component, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct SimConfig {
+    // the world, in pixels
+    pub width: f32,
+    pub height: f32,
+    // the grid
+    pub cols: usize,
+    pub rows: usize,
+    pub particles: usize,
+    pub particle_batch: usize,
+    // how much of the difference from its neighbours' flow a cell takes on
+    // each step, 0 to 1 (none by default: the particles do the mixing)
+    pub viscosity: Option<f32>,
+    pub cell_to_particle: Option<f32>,
+    pub particle_to_cell: Option<f32>,
+    // set by the tuning panel, so a reset keeps this rather than reading the
+    // file again
+    #[serde(skip)]
+    pub edited: bool,
+}
+
+impl Default for SimConfig {
+    fn default() -> SimConfig {
+        SimConfig {
+            width: WIDTH as f32,
+            height: HEIGHT as f32,
+            cols: CELLS_X as usize,
+            rows: CELLS_Y as usize,
+            particles: DEFAULT_PARTICLES,
+            particle_batch: DEFAULT_PARTICLE_BATCH,
+            viscosity: None,
+            cell_to_particle: None,
+            particle_to_cell: None,
+            edited: false,
+        }
+    }
+}
+
+// the value after `flag`, if it's there and makes sense
+fn arg<T: FromStr>(flag: &str) -> Option<T> {
+    let value = path_after(flag)?;
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        warn!("couldn't make sense of {} {}", flag, value);
+    }
+    parsed
+}
+
+impl SimConfig {
+    // a missing file is all the defaults; a broken one gets reported and
+    // treated the same way
+    pub fn load(path: &str) -> SimConfig {
+        match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+                warn!("couldn't read config {}: {}", path, err);
+                SimConfig::default()
+            }),
+            Err(_) => SimConfig::default(),
+        }
+    }
+
+    // the file, with the command line over it
+    pub fn from_args() -> SimConfig {
+        let path = path_after("--config").unwrap_or_else(|| CONFIG_PATH.to_owned());
+        let mut config = SimConfig::load(&path);
+        if let Some(width) = arg("--width") { config.width = width; }
+        if let Some(height) = arg("--height") { config.height = height; }
+        if let Some(cols) = arg("--cols") { config.cols = cols; }
+        if let Some(rows) = arg("--rows") { config.rows = rows; }
+        if let Some(particles) = arg("--particles") { config.particles = particles; }
+        if let Some(batch) = arg("--particle-batch") { config.particle_batch = batch; }
+        if let Some(viscosity) = arg("--viscosity") { config.viscosity = Some(viscosity); }
+        if let Some(rate) = arg("--cell-to-particle") { config.cell_to_particle = Some(rate); }
+        if let Some(rate) = arg("--particle-to-cell") { config.particle_to_cell = Some(rate); }
+        // nothing that would leave the world or the grid with no size
+        config.width = config.width.max(1.);
+        config.height = config.height.max(1.);
+        config.cols = config.cols.max(1);
+        config.rows = config.rows.max(1);
+        config.viscosity = config.viscosity.map(|viscosity| viscosity.max(0.).min(1.));
+        config
+    }
+
+    // put the viscosity and pull rates it gives into the tuning
+    pub fn tune(&self, tuning: &mut Tuning) {
+        if let Some(viscosity) = self.viscosity {
+            tuning.viscosity = viscosity;
+        }
+        if let Some(rate) = self.cell_to_particle {
+            tuning.cell_to_particle = rate;
+        }
+        if let Some(rate) = self.particle_to_cell {
+            tuning.particle_to_cell = rate;
+        }
+    }
+}
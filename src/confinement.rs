@@ -0,0 +1,64 @@
+// vorticity confinement: the grid's averaging (and any viscosity) wears the
+// swirls in the flow down within a few seconds, so each step this puts back
+// some of what's lost. the curl of every water cell's flow says how fast it's
+// spinning; the force pushes each cell across the slope of that, toward the
+// middle of the swirl it's part of, which spins small eddies up and keeps them
+// turning instead of smearing them out.
+//
+// how hard is the tuning panel's vorticity (see tuning.rs, H and J change it
+// too), none by default. it runs on the next grid after the particles' flow
+// has gone in, before the projection (see projection.rs) if that's on, so
+// what it adds is still made divergence free.
+
+use macroquad::prelude::*;
+use shipyard::{UniqueView, UniqueViewMut};
+
+use crate::grid::GridGeometry;
+use crate::profiling;
+use crate::tuning::Tuning;
+use crate::NextCells;
+
+// changes smaller than this aren't written, so still water stays unallocated
+// (see sparse_grid.rs)
+const NEGLIGIBLE: f32 = 1e-5;
+
+pub fn confine_vorticity(mut next: UniqueViewMut<NextCells>,
+                         grid: UniqueView<GridGeometry>,
+                         tuning: UniqueView<Tuning>) {
+    let _t = profiling::scope("confine_vorticity");
+    if tuning.vorticity <= 0. {
+        return;
+    }
+    let cells = &mut next.0.all_cells;
+    let solid: Vec<bool> = (0..grid.len()).map(|cell_ix| cells.get(cell_ix).is_solid()).collect();
+    let flow: Vec<Vec2> = (0..grid.len()).map(|cell_ix| cells.get(cell_ix).flow_v).collect();
+    let (dx, dy) = (grid.cell_width, grid.cell_height);
+    let at = |cell_ix: usize| if solid[cell_ix] { Vec2::new(0., 0.) } else { flow[cell_ix] };
+    let curl: Vec<f32> = (0..grid.len())
+        .map(|cell_ix| {
+            if solid[cell_ix] {
+                return 0.;
+            }
+            let [left, right, up, down] = grid.neighbours(cell_ix);
+            (at(right).y - at(left).y) / (2. * dx) - (at(down).x - at(up).x) / (2. * dy)
+        })
+        .collect();
+
+    for cell_ix in 0..grid.len() {
+        if solid[cell_ix] || curl[cell_ix] == 0. {
+            continue;
+        }
+        let [left, right, up, down] = grid.neighbours(cell_ix);
+        // which way the spin gets stronger
+        let slope = Vec2::new((curl[right].abs() - curl[left].abs()) / (2. * dx),
+                              (curl[down].abs() - curl[up].abs()) / (2. * dy));
+        if slope.length() < NEGLIGIBLE {
+            continue;
+        }
+        let toward = slope.normalize();
+        let force = Vec2::new(toward.y, -toward.x) * curl[cell_ix] * tuning.vorticity * dx.min(dy);
+        if force.length() > NEGLIGIBLE {
+            cells[cell_ix].flow_v += force;
+        }
+    }
+}
@@ -0,0 +1,121 @@
+// the console: ` (backquote, the tilde key) opens a line at the top of the
+// screen to type commands into (see commands.rs for what they are). enter
+// sends the line off on the event bus, up and down step back through what's
+// been typed before, and ` or esc closes it again. while it's open the game
+// doesn't see the keyboard.
+
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::commands;
+use crate::events::{Event, Events};
+use crate::keymap::KeyBindings;
+use crate::Screen;
+use crate::window::ScreenInfo;
+
+const CONSOLE_KEY: KeyCode = KeyCode::GraveAccent;
+// lines of output kept, and shown
+const LOG_LINES: usize = 100;
+const SHOWN_LINES: usize = 8;
+const LINE_HEIGHT: f32 = 18.;
+
+#[derive(Component, Default)]
+pub struct Console {
+    open: bool,
+    input: String,
+    log: Vec<String>,
+    // lines typed before, and which one up/down is on
+    history: Vec<String>,
+    recalled: Option<usize>,
+}
+
+impl Console {
+    pub fn print(&mut self, line: String) {
+        self.log.push(line);
+        if self.log.len() > LOG_LINES {
+            self.log.remove(0);
+        }
+    }
+
+    fn recall(&mut self, back: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+        let last = self.history.len() - 1;
+        self.recalled = match (self.recalled, back) {
+            (None, true) => Some(last),
+            (None, false) => None,
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i < last => Some(i + 1),
+            (Some(_), false) => None,
+        };
+        self.input = self.recalled.map_or(String::new(), |i| self.history[i].clone());
+    }
+}
+
+// runs right after rebind_keys, so it can take the keyboard before anything
+// else reads it
+pub fn read_console(mut console: UniqueViewMut<Console>,
+                    mut events: UniqueViewMut<Events>,
+                    mut keys: UniqueViewMut<KeyBindings>,
+                    _screen: UniqueView<Screen>) {
+    if !console.open {
+        keys.set_captured(false);
+        if is_key_pressed(CONSOLE_KEY) {
+            console.open = true;
+            keys.set_captured(true);
+        }
+        return;
+    }
+    // stays captured on the closing frame too, so the esc doesn't also quit
+    if is_key_pressed(CONSOLE_KEY) || is_key_pressed(KeyCode::Escape) {
+        console.open = false;
+        console.input.clear();
+        return;
+    }
+
+    while let Some(c) = get_char_pressed() {
+        if c == '`' || c == '~' || c.is_control() {
+            continue;
+        }
+        console.input.push(c);
+    }
+    if is_key_pressed(KeyCode::Backspace) {
+        console.input.pop();
+    }
+    if is_key_pressed(KeyCode::Up) {
+        console.recall(true);
+    }
+    if is_key_pressed(KeyCode::Down) {
+        console.recall(false);
+    }
+    if is_key_pressed(KeyCode::Enter) {
+        let line = console.input.trim().to_string();
+        console.input.clear();
+        console.recalled = None;
+        if line.is_empty() {
+            return;
+        }
+        console.print(format!("> {}", line));
+        console.history.push(line.clone());
+        match commands::parse(&line) {
+            Ok(command) => events.send(Event::Command(command)),
+            Err(err) => console.print(err),
+        }
+    }
+}
+
+pub fn draw_console(_screen: UniqueViewMut<Screen>, screen: UniqueView<ScreenInfo>, console: UniqueView<Console>) {
+    if !console.open {
+        return;
+    }
+    let height = LINE_HEIGHT * (SHOWN_LINES + 1) as f32 + 8.;
+    draw_rectangle(0., 0., screen.width, height, Color::new(0., 0., 0., 0.85));
+    draw_line(0., height, screen.width, height, 1., GRAY);
+    let shown = console.log.iter().rev().take(SHOWN_LINES).rev();
+    for (i, line) in shown.enumerate() {
+        draw_text(line, 8., LINE_HEIGHT * (i + 1) as f32, 16., LIGHTGRAY);
+    }
+    let prompt = format!("> {}_", console.input);
+    draw_text(&prompt, 8., LINE_HEIGHT * (SHOWN_LINES + 1) as f32, 16., WHITE);
+}
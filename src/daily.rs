@@ -0,0 +1,146 @@
+// the daily challenge: `--daily` plays a level made from today's date, so
+// everyone playing on the same day gets the same world. the date picks the
+// seed, the seed lays out the islands (see terrain.rs) and picks a couple of
+// modifiers (a storm, a current, winter, ...) from MODIFIERS, and the random
+// numbers start from it too. the date (UTC), the seed and the modifiers show
+// at the top of the screen, along with the best score so far today.
+//
+// daily scores are kept apart from the others, in their own file (see
+// scores::record_daily), and only for the one day: the first run of a new
+// day starts the list afresh.
+
+use macroquad::miniquad::date;
+use shipyard::Component;
+
+use crate::audio::Noise;
+use crate::scenario::Scenario;
+use crate::scores;
+use crate::terrain::TerrainDef;
+use crate::weather::{CurrentDef, SeasonsDef, StormDef};
+
+// how many modifiers a day gets
+const MODIFIER_COUNT: usize = 2;
+
+struct Modifier {
+    name: &'static str,
+    apply: fn(&mut Scenario, &mut Noise),
+}
+
+const MODIFIERS: [Modifier; 5] = [
+    Modifier { name: "stormy", apply: |scenario, noise| {
+        let at = 30. + 20. * noise.next().abs();
+        scenario.storms.push(StormDef { at, duration: 30., ramp: 5., intensity: 0.8 });
+    } },
+    Modifier { name: "currents", apply: |scenario, noise| {
+        let angle = noise.next() * std::f32::consts::PI;
+        for i in 0..2 {
+            let at = 15. + 45. * i as f32;
+            scenario.currents.push(CurrentDef { at, duration: 20., vx: angle.cos() * 2., vy: angle.sin() * 2., ramp: 5. });
+        }
+    } },
+    Modifier { name: "winter", apply: |scenario, _| {
+        scenario.seasons = Some(SeasonsDef { start: 0.75, ..SeasonsDef::default() });
+    } },
+    Modifier { name: "hunted", apply: |scenario, _| {
+        if let Some(terrain) = scenario.terrain.as_mut() {
+            terrain.enemy_spawns += 3;
+        }
+    } },
+    Modifier { name: "archipelago", apply: |scenario, _| {
+        if let Some(terrain) = scenario.terrain.as_mut() {
+            terrain.sea_level = 0.5;
+        }
+    } },
+];
+
+struct Challenge {
+    date: String,
+    seed: u64,
+    modifiers: Vec<&'static str>,
+    best: Option<u32>,
+}
+
+#[derive(Component, Default)]
+pub struct Daily {
+    today: Option<Challenge>,
+}
+
+// year, month and day from days since 1970-01-01 (the proleptic gregorian
+// calendar, see http://howardhinnant.github.io/date_algorithms.html)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+// splitmix64, so neighbouring days get unrelated seeds
+fn seed_for(days: i64) -> u64 {
+    let mut z = (days as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+impl Daily {
+    pub fn from_args() -> Daily {
+        if !std::env::args().any(|arg| arg == "--daily") {
+            return Daily::default();
+        }
+        let days = (date::now() / 86_400.).floor() as i64;
+        let (year, month, day) = civil_from_days(days);
+        let date = format!("{:04}-{:02}-{:02}", year, month, day);
+        let seed = seed_for(days);
+        // a shuffle of the modifiers' indices, the first few of which are today's
+        let mut noise = Noise(seed as u32);
+        let mut order: Vec<usize> = (0..MODIFIERS.len()).collect();
+        for i in (1..order.len()).rev() {
+            let j = (((noise.next() + 1.) / 2. * (i + 1) as f32) as usize).min(i);
+            order.swap(i, j);
+        }
+        let mut modifiers: Vec<&'static str> = order[..MODIFIER_COUNT].iter().map(|i| MODIFIERS[*i].name).collect();
+        modifiers.sort_unstable();
+        let best = scores::daily_best(&date);
+        Daily { today: Some(Challenge { date, seed, modifiers, best }) }
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.today.as_ref().map(|today| today.seed)
+    }
+
+    // the day's level: a race round the islands, with its modifiers
+    pub fn scenario(&self) -> Option<Scenario> {
+        let today = self.today.as_ref()?;
+        let terrain = TerrainDef { seed: today.seed as u32, sea_level: 0.58, checkpoints: 4, enemy_spawns: 0 };
+        let mut scenario = Scenario { terrain: Some(terrain), ..Scenario::default() };
+        // its own noise, so the modifiers don't depend on each other's draws
+        let mut noise = Noise((today.seed >> 32) as u32);
+        for modifier in MODIFIERS.iter().filter(|m| today.modifiers.contains(&m.name)) {
+            (modifier.apply)(&mut scenario, &mut noise);
+        }
+        Some(scenario)
+    }
+
+    pub fn label(&self) -> Option<String> {
+        let today = self.today.as_ref()?;
+        let best = today.best.map_or("no runs yet today".to_owned(), |best| format!("today's best {}", best));
+        Some(format!("daily {}  seed {}  {}  ({})", today.date, today.seed, today.modifiers.join(", "), best))
+    }
+
+    // file the run's score under today, instead of with the others
+    pub fn record(&self, score: u32) -> bool {
+        match self.today.as_ref() {
+            Some(today) => {
+                scores::record_daily(&today.date, score);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,358 @@
+// replays: `--record PATH` writes down every player's boat controls for every
+// sim tick, along with the random seed the run started from, and `--play PATH`
+// starts a run from the same seed and feeds the recorded ticks back in place
+// of the live controls. the recording is saved when the game's over. runs of
+// identical ticks are stored once with a repeat count, which keeps the files
+// small (the boat spends a lot of its time doing the same thing).
+//
+// everything is counted in ticks rather than frames, so a replay plays out
+// the same whatever the frame rate, and the clock's controls work on it like
+// on a live game (P pauses, . single steps, and , . 1 2 3 set the speed). [
+// and ] jump back and forward SCRUB_SECONDS; going back restores the nearest
+// keyframe (a saved state, see state.rs, taken every KEYFRAME_TICKS while
+// playing) and runs the sim forward from there.
+//
+// the random numbers are reseeded from the run's seed and the tick count
+// before every tick, recording or playing, so it doesn't matter what the
+// input and drawing did with them in between.
+//
+// besides what goes through BoatControls, the mouse tools (spray, brush,
+// wall, erase, edit, and carrying and flinging the boat), the touch controls'
+// stirring, the particle hotkeys (S, =, -, 0, 9 and C) and the right-click
+// vortices are recorded, since they all go through the tick as ToolCommands
+// (see tools.rs). the console and OSC still work straight on the world each
+// frame, so they aren't. while a replay's playing the live tools and hotkeys
+// are ignored, and once it runs out the controls go back to the keyboard
+// (scrubbing back still works).
+//
+// with the parallel feature the sim systems that draw random numbers still
+// take turns at it (see Randomness), so a replay plays out the same either
+// way. in case it doesn't (a recording from another build, say), there's a
+// checksum of the world every KEYFRAME_TICKS in the file, and a replay that
+// stops matching it says so, in the log and on the HUD.
+
+use std::fs;
+
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View, ViewMut, World};
+
+use crate::boat::{Boat, PlayerId};
+use crate::clock::TICK;
+use crate::input::{BoatCommand, BoatControls};
+use crate::keymap::{Action, KeyBindings};
+use crate::state::SavedState;
+use crate::tools::{ToolCommand, ToolCommands};
+use crate::Particle;
+
+const KEYFRAME_TICKS: usize = 300;
+const SCRUB_SECONDS: f32 = 5.;
+
+fn one() -> u32 {
+    1
+}
+
+fn is_one(n: &u32) -> bool {
+    *n == 1
+}
+
+// the controls of a boat other than the first player's (whose are the
+// frame's own, so one player files read the same as ever)
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct OtherBoat {
+    pub player: u8,
+    pub turn: f32,
+    pub thrust: f32,
+    pub fire: bool,
+    #[serde(default)]
+    pub commands: Vec<BoatCommand>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct DemoFrame {
+    pub turn: f32,
+    pub thrust: f32,
+    pub fire: bool,
+    #[serde(default)]
+    pub commands: Vec<BoatCommand>,
+    // the second player's, with --two-player (see two_player.rs)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub others: Vec<OtherBoat>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ToolCommand>,
+    // how many ticks in a row this was
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub repeat: u32,
+}
+
+impl DemoFrame {
+    fn same_controls(&self, other: &DemoFrame) -> bool {
+        DemoFrame { repeat: 1, ..self.clone() } == DemoFrame { repeat: 1, ..other.clone() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct DemoFile {
+    seed: u64,
+    // the world's checksum at every KEYFRAME_TICKS'th tick (before the
+    // frames, toml wants plain values ahead of tables)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    checks: Vec<u32>,
+    #[serde(default)]
+    frames: Vec<DemoFrame>,
+}
+
+enum DemoMode {
+    Off,
+    Recording { path: String },
+    // `ticks` is the file's frames with the repeats written out, one per tick
+    Playing { ticks: Vec<DemoFrame>, keyframes: Vec<(usize, SavedState)> },
+}
+
+#[derive(Component)]
+pub struct Demo {
+    mode: DemoMode,
+    file: DemoFile,
+    // ticks recorded or played so far
+    tick: usize,
+    // the tick a replay was found to have drifted from its recording at
+    out_of_step: Option<usize>,
+}
+
+pub fn path_after(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1)).cloned()
+}
+
+impl Demo {
+    pub fn from_args() -> Demo {
+        if let Some(path) = path_after("--play") {
+            let loaded = fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|text| toml::from_str::<DemoFile>(&text).map_err(|err| err.to_string()));
+            match loaded {
+                Ok(file) => {
+                    let ticks = file.frames.iter()
+                        .flat_map(|frame| (0..frame.repeat).map(move |_| DemoFrame { repeat: 1, ..frame.clone() }))
+                        .collect();
+                    return Demo { mode: DemoMode::Playing { ticks, keyframes: vec![] }, file, tick: 0, out_of_step: None };
+                }
+                Err(err) => warn!("couldn't read demo {}: {}", path, err),
+            }
+        } else if let Some(path) = path_after("--record") {
+            let file = DemoFile { seed: date::now() as u64, checks: vec![], frames: vec![] };
+            return Demo { mode: DemoMode::Recording { path }, file, tick: 0, out_of_step: None };
+        }
+        Demo { mode: DemoMode::Off, file: DemoFile::default(), tick: 0, out_of_step: None }
+    }
+
+    // the seed the run has to start from, if there's a demo going
+    pub fn seed(&self) -> Option<u64> {
+        match self.mode {
+            DemoMode::Off => None,
+            _ => Some(self.file.seed),
+        }
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self.mode, DemoMode::Playing { .. })
+    }
+
+    pub fn label(&self) -> Option<String> {
+        let seconds = self.tick as f32 * TICK;
+        match &self.mode {
+            DemoMode::Off => None,
+            DemoMode::Recording { .. } => Some(format!("recording {:.1}s", seconds)),
+            DemoMode::Playing { ticks, .. } => {
+                let drifted = self.out_of_step
+                    .map_or(String::new(), |tick| format!("  out of step since {:.1}s!", tick as f32 * TICK));
+                Some(format!("replay {:.1}s / {:.1}s  ([ ] scrub){}", seconds, ticks.len() as f32 * TICK, drifted))
+            }
+        }
+    }
+
+    // write out the recording, if there is one
+    pub fn save(&self) {
+        if let DemoMode::Recording { path } = &self.mode {
+            let result = toml::to_string(&self.file)
+                .map_err(|err| err.to_string())
+                .and_then(|text| fs::write(path, text).map_err(|err| err.to_string()));
+            if let Err(err) = result {
+                warn!("couldn't save demo {}: {}", path, err);
+            }
+        }
+    }
+
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    // where [ or ] asks to go, if either was pressed during a replay
+    pub fn scrub_target(&self, keys: &KeyBindings) -> Option<usize> {
+        let length = match &self.mode {
+            DemoMode::Playing { ticks, .. } => ticks.len(),
+            _ => return None,
+        };
+        let jump = (SCRUB_SECONDS / TICK) as usize;
+        if keys.pressed(Action::ScrubBack) {
+            Some(self.tick.saturating_sub(jump))
+        } else if keys.pressed(Action::ScrubForward) {
+            Some((self.tick + jump).min(length))
+        } else {
+            None
+        }
+    }
+}
+
+// called before every sim tick (see Simulation::run_tick)
+pub fn before_tick(world: &World) {
+    let (tick, seed, keyframe_due) = match world.borrow::<UniqueView<Demo>>() {
+        Ok(demo) => match &demo.mode {
+            DemoMode::Off => return,
+            DemoMode::Recording { .. } => (demo.tick, demo.file.seed, false),
+            DemoMode::Playing { keyframes, .. } => {
+                let due = demo.tick % KEYFRAME_TICKS == 0 && !keyframes.iter().any(|(tick, _)| *tick == demo.tick);
+                (demo.tick, demo.file.seed, due)
+            }
+        },
+        Err(_) => return,
+    };
+    let tick_seed = seed.wrapping_add(tick as u64);
+    if tick % KEYFRAME_TICKS == 0 {
+        check_in_step(world, tick);
+    }
+    if keyframe_due {
+        let state = SavedState::capture(world, tick_seed);
+        if let Ok(mut demo) = world.borrow::<UniqueViewMut<Demo>>() {
+            if let DemoMode::Playing { keyframes, .. } = &mut demo.mode {
+                keyframes.push((tick, state));
+            }
+        }
+    }
+    rand::srand(tick_seed);
+}
+
+// a fingerprint of where the boats and the particles are
+fn checksum(world: &World) -> u32 {
+    fn mix(sum: u64, bits: u64) -> u64 {
+        (sum ^ bits).wrapping_mul(0x100000001b3)
+    }
+    world.run(|boats: View<Boat>, particles: View<Particle>| {
+        let mut sum = 0xcbf29ce484222325;
+        for boat in boats.iter() {
+            sum = mix(sum, boat.loc.x.to_bits() as u64);
+            sum = mix(sum, boat.loc.y.to_bits() as u64);
+        }
+        for particle in particles.iter() {
+            sum = mix(sum, particle.position.x.to_bits() as u64);
+            sum = mix(sum, particle.position.y.to_bits() as u64);
+        }
+        (sum ^ (sum >> 32)) as u32
+    })
+}
+
+// every KEYFRAME_TICKS: write the checksum down, or check it against the
+// recording's
+fn check_in_step(world: &World, tick: usize) {
+    let sum = checksum(world);
+    let mut demo = match world.borrow::<UniqueViewMut<Demo>>() {
+        Ok(demo) => demo,
+        Err(_) => return,
+    };
+    let index = tick / KEYFRAME_TICKS;
+    match demo.mode {
+        DemoMode::Off => {}
+        DemoMode::Recording { .. } => {
+            if demo.file.checks.len() == index {
+                demo.file.checks.push(sum);
+            }
+        }
+        DemoMode::Playing { .. } => {
+            let drifted = demo.file.checks.get(index).map_or(false, |recorded| *recorded != sum);
+            if drifted && demo.out_of_step.map_or(true, |since| tick < since) {
+                warn!("the replay's out of step with its recording at {:.1}s", tick as f32 * TICK);
+                demo.out_of_step = Some(tick);
+            }
+        }
+    }
+}
+
+// go back to the last keyframe at or before `target`, if that's any help
+// getting there (the caller ticks forward the rest of the way)
+pub fn rewind(world: &World, target: usize) {
+    let mut restored = None;
+    if let Ok(demo) = world.borrow::<UniqueView<Demo>>() {
+        if let DemoMode::Playing { keyframes, .. } = &demo.mode {
+            let best = keyframes.iter()
+                .filter(|(tick, _)| *tick <= target)
+                .max_by_key(|(tick, _)| *tick);
+            if let Some((tick, state)) = best {
+                if target < demo.tick || *tick > demo.tick {
+                    if let Err(err) = state.restore(world) {
+                        warn!("couldn't rewind the replay: {}", err);
+                        return;
+                    }
+                    restored = Some(*tick);
+                }
+            }
+        }
+    }
+    if let (Some(tick), Ok(mut demo)) = (restored, world.borrow::<UniqueViewMut<Demo>>()) {
+        demo.tick = tick;
+    }
+}
+
+// first thing in each sim tick: records the controls, or swaps in the recorded ones
+pub fn record_or_play(mut demo: UniqueViewMut<Demo>,
+                      mut controls: ViewMut<BoatControls>,
+                      ids: View<PlayerId>,
+                      mut tools: UniqueViewMut<ToolCommands>) {
+    let demo = &mut *demo;
+    match &demo.mode {
+        DemoMode::Off => return,
+        DemoMode::Recording { .. } => {
+            let mut frame = DemoFrame { turn: 0., thrust: 0., fire: false, commands: vec![], others: vec![],
+                                        tools: tools.0.clone(), repeat: 1 };
+            for (controls, id) in (&controls, &ids).iter() {
+                if id.is_first() {
+                    frame.turn = controls.turn;
+                    frame.thrust = controls.thrust;
+                    frame.fire = controls.fire;
+                    frame.commands = controls.commands.clone();
+                } else {
+                    frame.others.push(OtherBoat { player: id.0, turn: controls.turn, thrust: controls.thrust,
+                                                  fire: controls.fire, commands: controls.commands.clone() });
+                }
+            }
+            frame.others.sort_by_key(|other| other.player);
+            match demo.file.frames.last_mut() {
+                Some(last) if last.same_controls(&frame) => last.repeat += 1,
+                _ => demo.file.frames.push(frame),
+            }
+        }
+        DemoMode::Playing { ticks, .. } => match ticks.get(demo.tick) {
+            Some(frame) => {
+                for (controls, id) in (&mut controls, &ids).iter() {
+                    if id.is_first() {
+                        controls.turn = frame.turn;
+                        controls.thrust = frame.thrust;
+                        controls.fire = frame.fire;
+                        controls.commands = frame.commands.clone();
+                        continue;
+                    }
+                    // (idle, if the recording hasn't got this one)
+                    let other = frame.others.iter().find(|other| other.player == id.0);
+                    controls.turn = other.map_or(0., |other| other.turn);
+                    controls.thrust = other.map_or(0., |other| other.thrust);
+                    controls.fire = other.map_or(false, |other| other.fire);
+                    controls.commands = other.map_or(vec![], |other| other.commands.clone());
+                }
+                tools.0 = frame.tools.clone();
+            }
+            // ran out: the live controls carry on from here
+            None => return,
+        },
+    }
+    demo.tick += 1;
+}
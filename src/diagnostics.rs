@@ -0,0 +1,26 @@
+// counters for things that shouldn't happen (but might), shown in the debug HUD
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use shipyard::{Component, UniqueViewMut};
+
+// bumped from the hot loops, which may be running on several threads and
+// don't have the Diagnostics unique; collect_diagnostics drains it each step
+static OUT_OF_RANGE: AtomicUsize = AtomicUsize::new(0);
+
+// a position outside the domain had to be clamped onto the grid
+pub fn count_out_of_range(count: usize) {
+    OUT_OF_RANGE.fetch_add(count, Ordering::Relaxed);
+}
+
+#[derive(Component, Default)]
+pub struct Diagnostics {
+    pub out_of_range_last_step: usize,
+    pub out_of_range_total: usize,
+}
+
+pub fn collect_diagnostics(mut diagnostics: UniqueViewMut<Diagnostics>) {
+    let count = OUT_OF_RANGE.swap(0, Ordering::Relaxed);
+    diagnostics.out_of_range_last_step = count;
+    diagnostics.out_of_range_total += count;
+}
@@ -0,0 +1,74 @@
+// extra boats for watching the flow coupling (F6 drops one at the cursor).
+// nobody steers them: each step a drifter's velocity is dragged toward the
+// flow of the cell it's in (by the tuning panel's hull drag), and it turns to
+// face the way it's going. they collide like anything else with a Collider.
+// a scenario's [[spawns]] can drop them in as well.
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntitiesViewMut, IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::camera::Camera;
+use crate::clock::SimClock;
+use crate::collision::{Collider, BOAT_RADIUS};
+use crate::grid::GridGeometry;
+use crate::keymap::{Action, KeyBindings};
+use crate::profiling;
+use crate::tuning::Tuning;
+use crate::{new_boat, Boat, Cells, Screen};
+
+// no more than this many at once; past that F6 does nothing
+const MAX_DRIFTERS: usize = 32;
+// slower than this and a drifter keeps the heading it had
+const MIN_TURNING_SPEED: f32 = 0.05;
+
+#[derive(Component)]
+pub struct Drifter(pub Boat);
+
+impl Drifter {
+    // and its Collider, to add with it
+    pub fn new(x: f32, y: f32) -> (Drifter, Collider) {
+        (Drifter(new_boat(x, y, 0., 0.)), Collider { center: Vec2::new(x, y), radius: BOAT_RADIUS })
+    }
+}
+
+pub fn spawn_drifter(keys: UniqueView<KeyBindings>,
+                     camera: UniqueView<Camera>,
+                     grid: UniqueView<GridGeometry>,
+                     mut entities: EntitiesViewMut,
+                     mut drifters: ViewMut<Drifter>,
+                     mut colliders: ViewMut<Collider>,
+                     _screen: UniqueView<Screen>) {
+    if !keys.pressed(Action::SpawnDrifter) || drifters.iter().count() >= MAX_DRIFTERS {
+        return;
+    }
+    let mouse = camera.mouse_world();
+    let (x, y) = (mouse.x.max(0.).min(grid.width - 1.), mouse.y.max(0.).min(grid.height - 1.));
+    entities.add_entity((&mut drifters, &mut colliders), Drifter::new(x, y));
+}
+
+pub fn update_drifters(mut drifters: ViewMut<Drifter>,
+                       mut colliders: ViewMut<Collider>,
+                       map: UniqueView<Cells>,
+                       grid: UniqueView<GridGeometry>,
+                       tuning: UniqueView<Tuning>) {
+    let _t = profiling::scope("update_drifters");
+    for (drifter, collider) in (&mut drifters, &mut colliders).iter() {
+        let boat = &mut drifter.0;
+        let flow = map.sample_flow(&grid, boat.loc.x as f32, boat.loc.y as f32);
+        boat.vel += (flow - boat.vel) * tuning.hull_drag;
+        if boat.vel.length() > MIN_TURNING_SPEED {
+            let heading = boat.vel.y.atan2(boat.vel.x);
+            boat.turn(heading - boat.heading());
+        }
+        boat.advance(&map, &grid);
+        boat.settle_heading(tuning.hull_turn);
+        collider.center = Vec2::new(boat.loc.x as f32, boat.loc.y as f32);
+    }
+}
+
+// world space
+pub fn draw_drifters(_screen: UniqueViewMut<Screen>, drifters: View<Drifter>, clock: UniqueView<SimClock>) {
+    for drifter in drifters.iter() {
+        drifter.0.render(clock.alpha());
+    }
+}
@@ -0,0 +1,207 @@
+// short-lived visual effects (sparks, wake puffs, floating score text)
+//
+// there can be hundreds of these a frame, each living a second or so, so
+// rather than making them entities they live in a fixed-size arena: slots
+// are allocated once, and a full arena just drops new effects. handles carry
+// a generation so a stale handle to a recycled slot is caught instead of
+// silently pointing at someone else's effect.
+
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::accessibility::Accessibility;
+use crate::profiling;
+use crate::Screen;
+
+// how many effects can be alive at once
+const EFFECT_CAPACITY: usize = 1024;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+struct Slot<T> {
+    generation: u32,
+    value: Option<T>,
+}
+
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+}
+
+impl<T> Arena<T> {
+    pub fn with_capacity(capacity: usize) -> Arena<T> {
+        Arena {
+            slots: (0..capacity).map(|_| Slot { generation: 0, value: None }).collect(),
+            // reversed so slots get handed out from the front
+            free: (0..capacity as u32).rev().collect(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    // None when the arena is full
+    pub fn insert(&mut self, value: T) -> Option<Handle> {
+        let index = self.free.pop()?;
+        let slot = &mut self.slots[index as usize];
+        slot.value = Some(value);
+        Some(Handle { index, generation: slot.generation })
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        let slot = self.slots.get(handle.index as usize)?;
+        if slot.generation == handle.generation { slot.value.as_ref() } else { None }
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation == handle.generation { slot.value.as_mut() } else { None }
+    }
+
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        let slot = self.slots.get_mut(handle.index as usize)?;
+        if slot.generation != handle.generation || slot.value.is_none() {
+            return None;
+        }
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.index);
+        slot.value.take()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+
+    // keep only the values for which `keep` returns true (it may also update them)
+    pub fn retain<F: FnMut(&mut T) -> bool>(&mut self, mut keep: F) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            if let Some(value) = slot.value.as_mut() {
+                if !keep(value) {
+                    slot.value = None;
+                    slot.generation = slot.generation.wrapping_add(1);
+                    self.free.push(index as u32);
+                }
+            }
+        }
+    }
+}
+
+pub enum EffectKind {
+    Spark,
+    WakePuff,
+    ScoreText(i32),
+}
+
+pub struct Effect {
+    pub kind: EffectKind,
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub color: Color,
+    // both in frames
+    pub age: f32,
+    pub lifetime: f32,
+}
+
+impl Effect {
+    // 1 when new, fading to 0 at the end of its life
+    fn remaining(&self) -> f32 {
+        1. - self.age / self.lifetime
+    }
+}
+
+// with a random number generator of its own, so the look of things doesn't
+// use up the sim's numbers (see Randomness)
+#[derive(Component)]
+pub struct Effects(pub Arena<Effect>, rand::RandGenerator);
+
+impl Default for Effects {
+    fn default() -> Effects {
+        let dice = rand::RandGenerator::new();
+        dice.srand(date::now() as u64);
+        Effects(Arena::with_capacity(EFFECT_CAPACITY), dice)
+    }
+}
+
+impl Effects {
+    // somewhere from `low` to `high`, off the effects' own generator
+    pub fn gen_range(&self, low: f32, high: f32) -> f32 {
+        self.1.gen_range(low, high)
+    }
+
+    // a burst of sparks flying out from `at`
+    pub fn sparks(&mut self, at: Vec2, count: usize, color: Color) {
+        for _ in 0..count {
+            let angle = self.gen_range(0., std::f32::consts::PI * 2.);
+            let speed = self.gen_range(0.5, 3.);
+            self.0.insert(Effect {
+                kind: EffectKind::Spark,
+                pos: at,
+                vel: Vec2::new(angle.cos(), angle.sin()) * speed,
+                color,
+                age: 0.,
+                lifetime: self.gen_range(15., 40.),
+            });
+        }
+    }
+
+    pub fn wake_puff(&mut self, at: Vec2, vel: Vec2) {
+        self.0.insert(Effect { kind: EffectKind::WakePuff, pos: at, vel, color: WHITE, age: 0., lifetime: 45. });
+    }
+
+    pub fn score_text(&mut self, at: Vec2, points: i32) {
+        self.0.insert(Effect {
+            kind: EffectKind::ScoreText(points),
+            pos: at,
+            vel: Vec2::new(0., -0.5),
+            color: YELLOW,
+            age: 0.,
+            lifetime: 60.,
+        });
+    }
+}
+
+pub fn update_effects(mut effects: UniqueViewMut<Effects>) {
+    let _t = profiling::scope("update_effects");
+    effects.0.retain(|effect| {
+        effect.pos += effect.vel;
+        effect.vel *= 0.96;
+        effect.age += 1.;
+        effect.age < effect.lifetime
+    });
+}
+
+pub fn draw_effects(_screen: UniqueViewMut<Screen>, effects: UniqueView<Effects>, access: UniqueView<Accessibility>) {
+    let _t = profiling::scope("draw_effects");
+    for effect in effects.0.iter() {
+        let mut color = effect.color;
+        color.a = effect.remaining();
+        match effect.kind {
+            // the sparks are the flashiest thing there is, they go entirely
+            EffectKind::Spark if access.reduce_flashing => {}
+            EffectKind::Spark => {
+                let tail = effect.pos - effect.vel * 2.;
+                draw_line(effect.pos.x, effect.pos.y, tail.x, tail.y, 1., color);
+            }
+            EffectKind::WakePuff => {
+                color.a *= 0.4;
+                draw_circle_lines(effect.pos.x, effect.pos.y, 2. + effect.age * 0.15, 0.5, color);
+            }
+            EffectKind::ScoreText(points) => {
+                if access.high_contrast {
+                    color = Color { a: 1., ..YELLOW };
+                }
+                draw_text(&format!("+{}", points), effect.pos.x, effect.pos.y, 18., color);
+            }
+        }
+    }
+}
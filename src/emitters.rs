@@ -0,0 +1,169 @@
+// emitters put particles into the world at a steady rate, fanned out either
+// side of their aim by their spread, and sinks take them out within their
+// radius. both are placed with the edit tool (see tools.rs), where ctrl+scroll
+// over one sets its spread or radius, and saved in the scenario file. in
+// debug mode (D) each is labelled with its numbers, for setting up a steady
+// flow to study.
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntitiesViewMut, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::clock::TICK;
+use crate::events::{Event, Events};
+use crate::geometry::wrapped_dist;
+use crate::grid::GridGeometry;
+use crate::level::Level;
+use crate::pool::ParticlePool;
+use crate::profiling;
+use crate::scenario::{EmitterDef, SinkDef};
+use crate::weather::Weather;
+use crate::{new_particle_at, CellType, Cells, GameMode, GameModeInfo, Particle, Randomness, Screen};
+
+pub const DEFAULT_EMITTER_RATE: f32 = 60.;
+pub const DEFAULT_SINK_RADIUS: f32 = 20.;
+// how far the emitted particles wander either side of the aim, in radians
+pub const DEFAULT_EMITTER_SPREAD: f32 = 0.15;
+pub const MAX_EMITTER_SPREAD: f32 = std::f32::consts::PI;
+pub const MIN_SINK_RADIUS: f32 = 5.;
+pub const MAX_SINK_RADIUS: f32 = 200.;
+
+#[derive(Component)]
+pub struct Emitter {
+    pub pos: Vec2,
+    pub velocity: Vec2,
+    pub rate: f32,
+    pub spread: f32,
+    // particles owed, topped up each frame
+    budget: f32,
+}
+
+impl Emitter {
+    pub fn new(pos: Vec2, velocity: Vec2) -> Emitter {
+        Emitter { pos, velocity, rate: DEFAULT_EMITTER_RATE, spread: DEFAULT_EMITTER_SPREAD, budget: 0. }
+    }
+
+    pub fn from_def(def: &EmitterDef) -> Emitter {
+        Emitter {
+            pos: Vec2::new(def.x, def.y),
+            velocity: Vec2::new(def.vx, def.vy),
+            rate: def.rate,
+            spread: def.spread.to_radians().max(0.).min(MAX_EMITTER_SPREAD),
+            budget: 0.,
+        }
+    }
+
+    pub fn to_def(&self) -> EmitterDef {
+        EmitterDef { x: self.pos.x, y: self.pos.y, vx: self.velocity.x, vy: self.velocity.y, rate: self.rate,
+                     spread: self.spread.to_degrees() }
+    }
+}
+
+#[derive(Component)]
+pub struct Sink {
+    pub pos: Vec2,
+    pub radius: f32,
+}
+
+impl Sink {
+    pub fn from_def(def: &SinkDef) -> Sink {
+        Sink { pos: Vec2::new(def.x, def.y), radius: def.radius }
+    }
+
+    pub fn to_def(&self) -> SinkDef {
+        SinkDef { x: self.pos.x, y: self.pos.y, radius: self.radius }
+    }
+}
+
+pub fn run_emitters(mut emitters: ViewMut<Emitter>,
+                    grid: UniqueView<GridGeometry>,
+                    mut pool: UniqueViewMut<ParticlePool>,
+                    mut entities: EntitiesViewMut,
+                    mut particles: ViewMut<Particle>,
+                    weather: UniqueView<Weather>,
+                    level: UniqueView<Level>,
+                    _random: UniqueViewMut<Randomness>) {
+    let _t = profiling::scope("run_emitters");
+    let season_rate = weather.spawn_rate(level.seconds());
+    for emitter in (&mut emitters).iter() {
+        let budget = emitter.budget + emitter.rate * season_rate * TICK;
+        let count = budget.floor();
+        emitter.budget = budget - count;
+        let speed = emitter.velocity.length();
+        let direction = emitter.velocity.y.atan2(emitter.velocity.x);
+        let (x, y, spread) = (emitter.pos.x, emitter.pos.y, emitter.spread);
+        pool.spawn_bulk(&mut entities, &mut particles, (0..count as usize).map(|_| {
+            let angle = direction + rand::gen_range(-spread, spread);
+            new_particle_at(&grid, x, y, angle.cos() * speed, angle.sin() * speed)
+        }));
+    }
+}
+
+pub fn run_sinks(sinks: View<Sink>,
+                 grid: UniqueView<GridGeometry>,
+                 mut level: UniqueViewMut<Level>,
+                 mut events: UniqueViewMut<Events>,
+                 mut pool: UniqueViewMut<ParticlePool>,
+                 mut particles: ViewMut<Particle>) {
+    let _t = profiling::scope("run_sinks");
+    if sinks.iter().next().is_none() {
+        return;
+    }
+    let swallowed: Vec<EntityId> = particles.iter().with_id()
+        .filter(|(_, particle)| {
+            let pos = Vec2::new(particle.position.x as f32, particle.position.y as f32);
+            sinks.iter().any(|sink| wrapped_dist(pos, sink.pos, grid.size()) < sink.radius)
+        })
+        .map(|(id, _)| id)
+        .collect();
+    pool.despawn_bulk(&mut particles, &swallowed);
+    level.sunk += swallowed.len();
+    if !swallowed.is_empty() {
+        events.send(Event::ParticlesSunk { count: swallowed.len() });
+    }
+}
+
+// particles that get into an outflow cell (see fluid.rs's CellType) leave the
+// world. unlike a sink's, they don't count toward the level or the score
+pub fn drain_outflows(map: UniqueView<Cells>,
+                      mut pool: UniqueViewMut<ParticlePool>,
+                      mut particles: ViewMut<Particle>) {
+    let _t = profiling::scope("drain_outflows");
+    let drained: Vec<EntityId> = particles.iter().with_id()
+        .filter(|(_, particle)| map.all_cells[particle.cell_ix].kind == CellType::Outflow)
+        .map(|(id, _)| id)
+        .collect();
+    pool.despawn_bulk(&mut particles, &drained);
+}
+
+pub fn draw_emitters(_screen: UniqueViewMut<Screen>,
+                     emitters: View<Emitter>,
+                     sinks: View<Sink>,
+                     game_mode: UniqueView<GameModeInfo>) {
+    let debug = game_mode.game_mode == GameMode::Debug;
+    for emitter in emitters.iter() {
+        let tip = emitter.pos + emitter.velocity * 8.;
+        draw_circle(emitter.pos.x, emitter.pos.y, 4., GREEN);
+        draw_line(emitter.pos.x, emitter.pos.y, tip.x, tip.y, 1., GREEN);
+        // the edges of the fan
+        let length = (tip - emitter.pos).length();
+        let direction = emitter.velocity.y.atan2(emitter.velocity.x);
+        for side in [-emitter.spread, emitter.spread].iter() {
+            let angle = direction + side;
+            let edge = emitter.pos + Vec2::new(angle.cos(), angle.sin()) * length;
+            draw_line(emitter.pos.x, emitter.pos.y, edge.x, edge.y, 0.5, DARKGREEN);
+        }
+        if debug {
+            let label = format!("{:.0}/s, speed {:.2}, spread {:.0}", emitter.rate, emitter.velocity.length(),
+                                emitter.spread.to_degrees());
+            draw_text(&label, emitter.pos.x + 6., emitter.pos.y - 6., 14., GREEN);
+        }
+    }
+    for sink in sinks.iter() {
+        draw_circle_lines(sink.pos.x, sink.pos.y, sink.radius, 1., RED);
+        draw_circle(sink.pos.x, sink.pos.y, 3., RED);
+        if debug {
+            let label = format!("radius {:.0}", sink.radius);
+            draw_text(&label, sink.pos.x + 6., sink.pos.y - 6., 14., RED);
+        }
+    }
+}
@@ -0,0 +1,271 @@
+// enemy boats. they come from the level's spawn table (see scenario.rs) at
+// the times it gives, and after that in waves, if the level has [waves]. an
+// enemy's a boat like the players', drawn red: each step it turns toward the
+// nearest player's boat (the short way, across the edges if that's nearer)
+// and thrusts through the same steer, while the flow drags it along like a
+// drifter, so a strong current can carry one right past.
+//
+// running into a player's boat costs that boat health, after which the enemy
+// backs off for a moment before coming round again. a shot takes health off
+// an enemy by how charged it was (see weapons.rs), and one out of health
+// sinks, leaving wreckage and sparks in the water (see ParticleKind). a boat
+// out of health sinks too, and once they all have it's the game over (see
+// score.rs).
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntitiesViewMut, EntityId, IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::clock::{SimClock, TICK};
+use crate::collision::{Collider, Collisions, BOAT_RADIUS};
+use crate::effects::Effects;
+use crate::events::{Event, Events};
+use crate::geometry::{wrap_position, wrapped_delta, wrapped_dist};
+use crate::grid::GridGeometry;
+use crate::input::BoatControls;
+use crate::level::Level;
+use crate::pool::ParticlePool;
+use crate::profiling;
+use crate::scenario::{Scenario, WaveDef};
+use crate::stats::RunStats;
+use crate::tuning::Tuning;
+use crate::weapons::Projectile;
+use crate::{angle_between, new_boat, new_particle_at, steer, Boat, Cells, Particle, ParticleKind, Randomness, Screen, TURN_RATE};
+
+// how hard an enemy leans on the throttle (the player's is 1)
+const ENEMY_THRUST: f32 = 0.5;
+// the health a ram takes off a player's boat
+const RAM_DAMAGE: f32 = 0.2;
+// how long an enemy backs off after a ram, in seconds
+const RETREAT_SECONDS: f32 = 1.5;
+// waves don't turn up any nearer a player than this
+const SAFE_DISTANCE: f32 = 150.;
+// what a sunk enemy leaves, and how fast it flies apart
+const WRECKAGE: usize = 12;
+const WRECK_SPARKS: usize = 20;
+const WRECKAGE_SPEED: f32 = 1.5;
+const SPARK_SPEED: f32 = 4.;
+
+#[derive(Component)]
+pub struct Enemy {
+    pub boat: Boat,
+    // ticks left backing off after ramming a player
+    retreat: u32,
+}
+
+impl Enemy {
+    // it's run into a player's boat: unless it's already backing off from the
+    // last time, it bounces off and goes off to come round again, and the
+    // boat takes RAM_DAMAGE
+    pub fn ram(&mut self) -> bool {
+        if self.retreat > 0 {
+            return false;
+        }
+        let vel = self.boat.vel;
+        self.boat.vel = -vel;
+        self.retreat = (RETREAT_SECONDS / TICK) as u32;
+        true
+    }
+}
+
+// the waves to come, if the level has any
+#[derive(Component, Default)]
+pub struct EnemyWaves {
+    def: Option<WaveDef>,
+    next_at: f32,
+    size: u32,
+}
+
+impl EnemyWaves {
+    pub fn new(scenario: &Scenario) -> EnemyWaves {
+        match scenario.waves.clone() {
+            Some(def) => EnemyWaves { next_at: def.first, size: def.size, def: Some(def) },
+            None => EnemyWaves::default(),
+        }
+    }
+}
+
+// somewhere in open water, out of the players' way (or as near to it as a
+// few tries get)
+fn wave_spot(players: &[Vec2], map: &Cells, grid: &GridGeometry) -> Vec2 {
+    let mut spot = Vec2::new(0., 0.);
+    for _ in 0..20 {
+        spot = Vec2::new(rand::gen_range(0., grid.width), rand::gen_range(0., grid.height));
+        let water = !map.all_cells[grid.index_at(spot.x, spot.y)].is_solid();
+        if water && players.iter().all(|player| wrapped_dist(*player, spot, grid.size()) > SAFE_DISTANCE) {
+            break;
+        }
+    }
+    spot
+}
+
+// the way to the nearest of `players` from `from`, if there's any left
+fn to_nearest(from: Vec2, players: &[Vec2], size: Vec2) -> Option<Vec2> {
+    players.iter()
+        .map(|player| wrapped_delta(from, *player, size))
+        .min_by(|a, b| a.length().total_cmp(&b.length()))
+}
+
+fn boat_spots(boats: &View<Boat>) -> Vec<Vec2> {
+    boats.iter().map(|boat| Vec2::new(boat.loc.x as f32, boat.loc.y as f32)).collect()
+}
+
+pub fn spawn_enemies(mut level: UniqueViewMut<Level>,
+                     mut waves: UniqueViewMut<EnemyWaves>,
+                     boats: View<Boat>,
+                     map: UniqueView<Cells>,
+                     grid: UniqueView<GridGeometry>,
+                     mut entities: EntitiesViewMut,
+                     mut enemies: ViewMut<Enemy>,
+                     mut colliders: ViewMut<Collider>,
+                     _random: UniqueViewMut<Randomness>) {
+    let _t = profiling::scope("spawn_enemies");
+    let now = level.seconds();
+    let mut spots = vec![];
+    let (due, later): (Vec<_>, Vec<_>) = level.enemies.drain(..).partition(|spawn| spawn.at <= now);
+    level.enemies = later;
+    for spawn in due {
+        spots.extend((0..spawn.count).map(|_| Vec2::new(spawn.x, spawn.y)));
+    }
+    let players = boat_spots(&boats);
+    if let Some(def) = waves.def.clone() {
+        if now >= waves.next_at {
+            spots.extend((0..waves.size).map(|_| wave_spot(&players, &map, &grid)));
+            waves.next_at += def.every.max(TICK);
+            waves.size += def.growth;
+        }
+    }
+    for spot in spots {
+        let mut boat = new_boat(spot.x, spot.y, 0., 0.);
+        boat.set_color(RED);
+        // facing the nearest player, so it doesn't start off with a turn
+        if let Some(to_player) = to_nearest(spot, &players, grid.size()) {
+            boat.turn(to_player.y.atan2(to_player.x));
+        }
+        entities.add_entity((&mut enemies, &mut colliders),
+                            (Enemy { boat, retreat: 0 }, Collider { center: spot, radius: BOAT_RADIUS }));
+    }
+}
+
+pub fn update_enemies(mut enemies: ViewMut<Enemy>,
+                      mut colliders: ViewMut<Collider>,
+                      mut effects: UniqueViewMut<Effects>,
+                      boats: View<Boat>,
+                      map: UniqueView<Cells>,
+                      grid: UniqueView<GridGeometry>,
+                      tuning: UniqueView<Tuning>) {
+    let _t = profiling::scope("update_enemies");
+    let players = boat_spots(&boats);
+    for (enemy, collider) in (&mut enemies, &mut colliders).iter() {
+        let mut controls = BoatControls::default();
+        let from = Vec2::new(enemy.boat.loc.x as f32, enemy.boat.loc.y as f32);
+        if enemy.retreat > 0 {
+            enemy.retreat -= 1;
+        } else if let Some(to_player) = to_nearest(from, &players, grid.size()) {
+            let off_course = angle_between(enemy.boat.heading(), to_player.y.atan2(to_player.x));
+            controls.turn = (off_course / TURN_RATE).max(-1.).min(1.);
+            controls.thrust = ENEMY_THRUST;
+        }
+        let boat = &mut enemy.boat;
+        steer(boat, &mut controls, &mut effects, &tuning, 1.);
+        let flow = map.sample_flow(&grid, boat.loc.x as f32, boat.loc.y as f32);
+        boat.vel += (flow - boat.vel) * tuning.hull_drag;
+        boat.advance(&map, &grid);
+        boat.settle_heading(tuning.hull_turn);
+        collider.center = Vec2::new(boat.loc.x as f32, boat.loc.y as f32);
+    }
+}
+
+// after detect_collisions: shots into enemies, and enemies into the players
+pub fn resolve_enemy_hits(collisions: UniqueView<Collisions>,
+                          mut enemies: ViewMut<Enemy>,
+                          mut projectiles: ViewMut<Projectile>,
+                          mut colliders: ViewMut<Collider>,
+                          mut entities: EntitiesViewMut,
+                          mut boats: ViewMut<Boat>,
+                          mut effects: UniqueViewMut<Effects>,
+                          mut events: UniqueViewMut<Events>,
+                          mut stats: UniqueViewMut<RunStats>) {
+    let _t = profiling::scope("resolve_enemy_hits");
+    let mut sunk: Vec<EntityId> = vec![];
+    let mut spent: Vec<EntityId> = vec![];
+    for (a, b) in collisions.pairs.iter() {
+        let (enemy_id, shot_id) = if enemies.get(*a).is_ok() { (*a, *b) } else { (*b, *a) };
+        if spent.contains(&shot_id) || sunk.contains(&enemy_id) {
+            continue;
+        }
+        let (damage, by) = match (projectiles.get(shot_id), enemies.get(enemy_id)) {
+            (Ok(shot), Ok(_)) => (shot.damage, shot.owner),
+            _ => continue,
+        };
+        spent.push(shot_id);
+        let enemy = (&mut enemies).get(enemy_id).unwrap();
+        enemy.boat.health -= damage;
+        let at = Vec2::new(enemy.boat.loc.x as f32, enemy.boat.loc.y as f32);
+        effects.sparks(at, 6, ORANGE);
+        if enemy.boat.health <= 0. {
+            effects.sparks(at, 30, RED);
+            events.send(Event::EnemyDestroyed { at, by });
+            stats.enemies_destroyed += 1;
+            sunk.push(enemy_id);
+        }
+    }
+    for id in spent {
+        projectiles.remove(id);
+        colliders.remove(id);
+        entities.delete_unchecked(id);
+    }
+    for id in sunk {
+        enemies.remove(id);
+        colliders.remove(id);
+        entities.delete_unchecked(id);
+    }
+
+    for (boat_id, id) in collisions.boat_hits.iter() {
+        if let Ok(enemy) = (&mut enemies).get(*id) {
+            if enemy.ram() {
+                if let Ok(boat) = (&mut boats).get(*boat_id) {
+                    boat.health = (boat.health - RAM_DAMAGE).max(0.);
+                }
+            }
+        }
+    }
+}
+
+// the enemies resolve_enemy_hits sank this tick come apart into particles.
+// after the particles are scattered back (see particle_store.rs), since it
+// adds to them
+pub fn scatter_wreckage(events: UniqueView<Events>,
+                        grid: UniqueView<GridGeometry>,
+                        mut pool: UniqueViewMut<ParticlePool>,
+                        mut entities: EntitiesViewMut,
+                        mut particles: ViewMut<Particle>,
+                        _random: UniqueViewMut<Randomness>) {
+    let _t = profiling::scope("scatter_wreckage");
+    for event in events.this_tick() {
+        if let Event::EnemyDestroyed { at, .. } = event {
+            let pieces = (0..WRECKAGE).map(|_| (ParticleKind::Debris, WRECKAGE_SPEED))
+                .chain((0..WRECK_SPARKS).map(|_| (ParticleKind::Spark, SPARK_SPEED)));
+            pool.spawn_bulk(&mut entities, &mut particles, pieces.map(|(kind, top_speed)| {
+                let angle = rand::gen_range(0., std::f32::consts::PI * 2.);
+                let speed = rand::gen_range(0.2, 1.) * top_speed;
+                let at = wrap_position(*at, grid.size());
+                new_particle_at(&grid, at.x, at.y, angle.cos() * speed, angle.sin() * speed).of_kind(kind)
+            }));
+        }
+    }
+}
+
+// world space: the boats, with how much health they've left over each one
+// that's been hit
+pub fn draw_enemies(_screen: UniqueViewMut<Screen>, enemies: View<Enemy>, clock: UniqueView<SimClock>) {
+    for enemy in enemies.iter() {
+        enemy.boat.render(clock.alpha());
+        let health = enemy.boat.health.max(0.);
+        if health < 1. {
+            let at = enemy.boat.drawn_loc(clock.alpha());
+            let (x, y) = (at.x - BOAT_RADIUS, at.y - BOAT_RADIUS - 6.);
+            draw_rectangle(x, y, BOAT_RADIUS * 2., 3., DARKGRAY);
+            draw_rectangle(x, y, BOAT_RADIUS * 2. * health, 3., RED);
+        }
+    }
+}
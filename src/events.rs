@@ -0,0 +1,65 @@
+// the event bus: a system that wants something done somewhere else sends an
+// Event instead of reaching into that part of the world itself, and whatever
+// handles it reads it later in the frame. events last one frame: the bus is
+// emptied at the start of each frame's input. a frame can run several sim
+// ticks, so anything counting per tick (see score.rs) reads this_tick instead.
+
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueViewMut};
+
+use crate::commands::Command;
+use crate::pickups::PickupKind;
+
+#[derive(Clone, Debug)]
+pub enum Event {
+    // typed into the console
+    Command(Command),
+    // the boat's engine ran this tick, at 0 to 1
+    Thrust { amount: f32 },
+    // the boat went from one cell into the next fast: 0 (just fast enough) to 1
+    Splash { strength: f32 },
+    // a shot left the boat, charged 0 to 1
+    Fired { charge: f32 },
+    // a shot hit a wall, or something ran into the boat: 0 (a tap) to 1
+    Impact { strength: f32 },
+    // an enemy boat was sunk, here, by this player's shot
+    EnemyDestroyed { at: Vec2, by: u8 },
+    // a sink swallowed this many particles
+    ParticlesSunk { count: usize },
+    LevelComplete,
+    // a player's boat (1, or 2 in two player mode) ran over a pickup, here
+    PickedUp { kind: PickupKind, at: Vec2, player: u8 },
+}
+
+#[derive(Component, Default)]
+pub struct Events {
+    sent: Vec<Event>,
+    // where the current tick's events start
+    tick_start: usize,
+}
+
+impl Events {
+    pub fn send(&mut self, event: Event) {
+        self.sent.push(event);
+    }
+
+    // everything sent so far this frame, oldest first
+    pub fn iter(&self) -> impl Iterator<Item = &Event> + '_ {
+        self.sent.iter()
+    }
+
+    // just the ones sent since the current sim tick started
+    pub fn this_tick(&self) -> impl Iterator<Item = &Event> + '_ {
+        self.sent[self.tick_start..].iter()
+    }
+}
+
+pub fn clear_events(mut events: UniqueViewMut<Events>) {
+    events.sent.clear();
+    events.tick_start = 0;
+}
+
+// first thing in each sim tick
+pub fn start_tick(mut events: UniqueViewMut<Events>) {
+    events.tick_start = events.sent.len();
+}
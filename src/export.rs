@@ -0,0 +1,165 @@
+// exporting the flow for analysis elsewhere: F8 (or `export` in the console)
+// writes the grid and the particles out as CSV, for matplotlib and friends,
+// and as legacy VTK, for ParaView. each export gets the next number:
+//
+//   export-0.csv              col,row,x,y,vx,vy,pressure,solid per cell
+//   export-0-particles.csv    x,y,vx,vy per particle
+//   export-0.vtk              the grid, as structured points
+//   export-0-particles.vtk    the particles, as polydata
+//
+// `--export PREFIX` changes the "export" part, and also exports once more on
+// the way out (esc), which is handy at the end of a --play replay.
+//
+// the sim itself doesn't have a pressure field, so it's worked out at export
+// time the same way the GPU backend's projection does it: jacobi iterations
+// on the pressure poisson equation, with the divergence as the source.
+
+use std::fmt::Write as _;
+use std::fs;
+
+use macroquad::prelude::*;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View};
+
+use crate::commands::Command;
+use crate::console::Console;
+use crate::demo::path_after;
+use crate::events::{Event, Events};
+use crate::grid::GridGeometry;
+use crate::keymap::{Action, KeyBindings};
+use crate::{Cells, Particle};
+
+const DEFAULT_PREFIX: &str = "export";
+const PRESSURE_ITERATIONS: usize = 40;
+
+#[derive(Component)]
+pub struct Exporter {
+    prefix: String,
+    // exports so far, for numbering the files
+    count: usize,
+    on_exit: bool,
+}
+
+impl Exporter {
+    pub fn from_args() -> Exporter {
+        match path_after("--export") {
+            Some(prefix) => Exporter { prefix, count: 0, on_exit: true },
+            None => Exporter { prefix: DEFAULT_PREFIX.to_string(), count: 0, on_exit: false },
+        }
+    }
+
+    // writes the four files, and says what the grid one's called
+    fn export(&mut self, map: &Cells, grid: &GridGeometry, particles: &View<Particle>) -> Result<String, String> {
+        let name = format!("{}-{}", self.prefix, self.count);
+        self.count += 1;
+        let pressure = pressure(map, grid);
+        let particles: Vec<(Vec2, Vec2)> = particles.iter()
+            .map(|p| (Vec2::new(p.position.x as f32, p.position.y as f32), p.velocity))
+            .collect();
+
+        let mut csv = String::from("col,row,x,y,vx,vy,pressure,solid\n");
+        for cell_ix in 0..grid.len() {
+            let (cell, center) = (&map.all_cells[cell_ix], grid.center(cell_ix));
+            writeln!(csv, "{},{},{},{},{},{},{},{}", cell_ix % grid.cols, cell_ix / grid.cols, center.x, center.y,
+                     cell.flow_v.x, cell.flow_v.y, pressure[cell_ix], cell.is_solid() as u8).unwrap();
+        }
+        let mut particle_csv = String::from("x,y,vx,vy\n");
+        for (position, velocity) in particles.iter() {
+            writeln!(particle_csv, "{},{},{},{}", position.x, position.y, velocity.x, velocity.y).unwrap();
+        }
+
+        // vtk points run x fastest, then y, same as the cell index
+        let mut vtk = String::new();
+        writeln!(vtk, "# vtk DataFile Version 3.0\nflow field\nASCII\nDATASET STRUCTURED_POINTS").unwrap();
+        writeln!(vtk, "DIMENSIONS {} {} 1", grid.cols, grid.rows).unwrap();
+        writeln!(vtk, "ORIGIN {} {} 0", grid.cell_width / 2., grid.cell_height / 2.).unwrap();
+        writeln!(vtk, "SPACING {} {} 1", grid.cell_width, grid.cell_height).unwrap();
+        writeln!(vtk, "POINT_DATA {}\nVECTORS velocity float", grid.len()).unwrap();
+        for cell_ix in 0..grid.len() {
+            let flow = map.all_cells[cell_ix].flow_v;
+            writeln!(vtk, "{} {} 0", flow.x, flow.y).unwrap();
+        }
+        writeln!(vtk, "SCALARS pressure float 1\nLOOKUP_TABLE default").unwrap();
+        for p in pressure.iter() {
+            writeln!(vtk, "{}", p).unwrap();
+        }
+        writeln!(vtk, "SCALARS solid int 1\nLOOKUP_TABLE default").unwrap();
+        for cell_ix in 0..grid.len() {
+            writeln!(vtk, "{}", map.all_cells[cell_ix].is_solid() as u8).unwrap();
+        }
+
+        let mut particle_vtk = String::new();
+        writeln!(particle_vtk, "# vtk DataFile Version 3.0\nparticles\nASCII\nDATASET POLYDATA").unwrap();
+        writeln!(particle_vtk, "POINTS {} float", particles.len()).unwrap();
+        for (position, _) in particles.iter() {
+            writeln!(particle_vtk, "{} {} 0", position.x, position.y).unwrap();
+        }
+        writeln!(particle_vtk, "VERTICES {} {}", particles.len(), particles.len() * 2).unwrap();
+        for i in 0..particles.len() {
+            writeln!(particle_vtk, "1 {}", i).unwrap();
+        }
+        writeln!(particle_vtk, "POINT_DATA {}\nVECTORS velocity float", particles.len()).unwrap();
+        for (_, velocity) in particles.iter() {
+            writeln!(particle_vtk, "{} {} 0", velocity.x, velocity.y).unwrap();
+        }
+
+        for (path, text) in [(format!("{}.csv", name), csv),
+                             (format!("{}-particles.csv", name), particle_csv),
+                             (format!("{}.vtk", name), vtk),
+                             (format!("{}-particles.vtk", name), particle_vtk)].iter() {
+            fs::write(path, text).map_err(|err| format!("couldn't write {}: {}", path, err))?;
+        }
+        Ok(format!("{}.csv", name))
+    }
+}
+
+// the pressure that would make the flow divergence free (solids held at zero)
+fn pressure(map: &Cells, grid: &GridGeometry) -> Vec<f32> {
+    let cells = &map.all_cells;
+    let divergence: Vec<f32> = (0..grid.len())
+        .map(|cell_ix| {
+            let [left, right, up, down] = grid.neighbours(cell_ix);
+            (cells[right].flow_v.x - cells[left].flow_v.x) / (2. * grid.cell_width)
+                + (cells[down].flow_v.y - cells[up].flow_v.y) / (2. * grid.cell_height)
+        })
+        .collect();
+    let h2 = grid.cell_width * grid.cell_height;
+    let mut pressure = vec![0f32; grid.len()];
+    let mut next = vec![0f32; grid.len()];
+    for _ in 0..PRESSURE_ITERATIONS {
+        for cell_ix in 0..grid.len() {
+            if cells[cell_ix].is_solid() {
+                next[cell_ix] = 0.;
+                continue;
+            }
+            let around: f32 = grid.neighbours(cell_ix).iter().map(|n| pressure[*n]).sum();
+            next[cell_ix] = (around - divergence[cell_ix] * h2) / 4.;
+        }
+        std::mem::swap(&mut pressure, &mut next);
+    }
+    pressure
+}
+
+// runs before handle_key_presses, which ends the game on esc
+pub fn export_field(mut exporter: UniqueViewMut<Exporter>,
+                    mut console: UniqueViewMut<Console>,
+                    events: UniqueView<Events>,
+                    keys: UniqueView<KeyBindings>,
+                    map: UniqueView<Cells>,
+                    grid: UniqueView<GridGeometry>,
+                    particles: View<Particle>) {
+    let asked = events.iter().any(|event| matches!(event, Event::Command(Command::Export)));
+    let exiting = exporter.on_exit && keys.pressed(Action::Exit);
+    if !(asked || exiting || keys.pressed(Action::Export)) {
+        return;
+    }
+    match exporter.export(&map, &grid, &particles) {
+        Ok(path) => {
+            info!("exported the flow to {}", path);
+            console.print(format!("exported to {}", path));
+        }
+        Err(err) => {
+            warn!("{}", err);
+            console.print(err);
+        }
+    }
+}
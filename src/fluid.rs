@@ -0,0 +1,343 @@
+// the flow field: a grid of cells, each with the mean velocity of the water
+// in it. the particles write into next step's grid (update_grid_flow), the
+// cells take on their particles' flow and go to sleep when nothing's happening
+// (apply_grid_updates), and the grids are swapped at the end of the step.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::batch::MeshBatch;
+use crate::grid::GridGeometry;
+#[cfg(feature = "parallel")]
+use crate::kernels::CHUNK;
+use crate::particle::Particle;
+use crate::profiling;
+use crate::sparse_grid::SparseGrid;
+use crate::tuning::Tuning;
+use crate::{lerp, pythag_dist};
+
+// cells that have been quiet for this many steps go to sleep: no flow update
+// or debug rendering until they, or a neighbour, see activity again
+const QUIESCENT_FRAMES: u32 = 120;
+// flow changes smaller than this (per step) don't count as activity
+const QUIESCENT_FLOW_CHANGE: f32 = 1e-4;
+
+// how much of its flow a cell with no depth at all loses a step
+const SHALLOW_DRAG: f32 = 0.1;
+
+// the grid as of the end of the last step: everything that reads the flow
+// field this step reads this one. only the chunks with something going on in
+// them are allocated (see sparse_grid.rs)
+#[derive(Component, Clone)]
+pub struct Cells {
+    pub all_cells: SparseGrid<FluidCell>,
+}
+
+// the grid being written this step. it's swapped with Cells at the end of the
+// step, so the writers never hold up (or race) the readers.
+#[derive(Component)]
+pub struct NextCells(pub Cells);
+
+// what a cell is made of. solid cells hold no flow, and particles and the
+// boat bounce off them. an inflow holds the flow it was painted with (see
+// tools.rs's wall tool) whatever the particles do, so it keeps pushing water
+// in, and an outflow lets water out of the world: any particle that gets into
+// one is taken away (see emitters.rs's drain_outflows), and the pressure
+// solve (see projection.rs) holds its pressure at nothing. the edges still
+// wrap round, so walls, inflows and outflows are how a level gets a channel
+// with a start and an end.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum CellType {
+    Water,
+    Solid,
+    Inflow,
+    Outflow,
+}
+
+#[derive(Clone)]
+pub struct FluidCell {
+    pub kind: CellType,
+    pub flow_v: Vec2,
+    pub flow_updates: Vec2, 
+    pub particle_count: u32,
+    // steps since anything happened here (a particle visit or the flow moving)
+    pub quiet_frames: u32,
+    // how deep the water is, 0 to 1. shallow water drags on the flow (see
+    // terrain.rs, which is the only thing that makes any)
+    pub depth: f32,
+}
+
+impl Cells {
+    // the flow at a point, blended from the four cell centres round it
+    // (wrapping round the edges like everything else), so it changes smoothly
+    // from one cell to the next instead of jumping at the boundaries
+    pub fn sample_flow(&self, grid: &GridGeometry, x: f32, y: f32) -> Vec2 {
+        let (fx, fy) = (x / grid.cell_width - 0.5, y / grid.cell_height - 0.5);
+        let (col, row) = (fx.floor(), fy.floor());
+        let (tx, ty) = (fx - col, fy - row);
+        let flow = |dc: i64, dr: i64| {
+            let c = (col as i64 + dc).rem_euclid(grid.cols as i64) as usize;
+            let r = (row as i64 + dr).rem_euclid(grid.rows as i64) as usize;
+            self.all_cells[r * grid.cols + c].flow_v
+        };
+        let top = flow(0, 0) + (flow(1, 0) - flow(0, 0)) * tx;
+        let bottom = flow(0, 1) + (flow(1, 1) - flow(0, 1)) * tx;
+        top + (bottom - top) * ty
+    }
+
+    // fold a finished accumulator into the cells' pending flow updates
+    pub fn add_flow_updates(&mut self, acc: &FlowAccumulator) {
+        for cell_ix in 0..self.all_cells.len() {
+            if acc.counts[cell_ix] > 0 {
+                self.all_cells[cell_ix].update_flow(acc.flow_sums[cell_ix], acc.counts[cell_ix]);
+            }
+        }
+    }
+
+    // the same flow on a grid of another resolution (see refine.rs): each of
+    // `to`'s cells takes the flow at its centre (blended, like sample_flow
+    // does for the particles), and the kind and depth of the cell of `from`
+    // it's in. it starts awake, with no updates pending
+    pub fn resample(&self, from: &GridGeometry, to: &GridGeometry) -> Cells {
+        let mut all_cells = SparseGrid::new(to.cols, to.rows, FluidCell::empty());
+        for cell_ix in 0..to.len() {
+            let center = to.center(cell_ix);
+            let old = self.all_cells.get(from.index_at(center.x, center.y));
+            let cell = FluidCell { kind: old.kind,
+                                   flow_v: self.sample_flow(from, center.x, center.y),
+                                   flow_updates: Vec2::new(0., 0.),
+                                   particle_count: 0,
+                                   quiet_frames: 0,
+                                   depth: old.depth };
+            // (still water stays unallocated)
+            if !cell.is_empty() {
+                all_cells[cell_ix] = cell;
+            }
+        }
+        Cells { all_cells }
+    }
+}
+
+impl FluidCell {
+    // cache an update to this cell's flow according to the particles in it
+    // (summed velocities and how many particles contributed)
+    pub fn update_flow(&mut self, flow_sum: Vec2, count: u32) {
+        self.flow_updates.x += flow_sum.x;
+        self.flow_updates.y += flow_sum.y;
+        self.particle_count += count;
+    }
+
+    // apply the updates to this cell (call once per timestep), taking on
+    // `blend` of the particles' mean velocity
+    pub fn apply_flow_update(&mut self, blend: f32) {
+        if self.particle_count > 0 {
+            self.flow_v.x = lerp (self.flow_v.x, self.flow_updates.x / self.particle_count as f32, blend);
+            self.flow_v.y = lerp (self.flow_v.y, self.flow_updates.y / self.particle_count as f32, blend);
+            self.flow_updates.x = 0.;
+            self.flow_updates.y = 0.;
+            self.particle_count = 0;
+        }
+    }
+
+    // what the unallocated parts of the grid read as: still, and asleep
+    pub fn empty() -> FluidCell {
+        FluidCell { kind: CellType::Water,
+                    flow_v: Vec2::new(0., 0.),
+                    flow_updates: Vec2::new(0., 0.),
+                    particle_count: 0,
+                    quiet_frames: QUIESCENT_FRAMES,
+                    depth: 1. }
+    }
+
+    // nothing here worth keeping a chunk allocated for
+    pub fn is_empty(&self) -> bool {
+        self.kind == CellType::Water && self.particle_count == 0 && self.flow_v.length() < QUIESCENT_FLOW_CHANGE
+            && self.depth == 1.
+    }
+
+    pub fn is_solid(&self) -> bool {
+        self.kind == CellType::Solid
+    }
+
+    pub fn is_asleep(&self) -> bool {
+        self.quiet_frames >= QUIESCENT_FRAMES
+    }
+
+    pub fn render(&self, center: Vec2, batch: &mut MeshBatch) {
+        let cell_middle_x = center.x;
+        let cell_middle_y = center.y;
+        let cell_vector_size = 20.;
+        batch.circle(cell_middle_x, cell_middle_y, 0.8, WHITE);
+        match self.kind {
+            CellType::Inflow => batch.circle(cell_middle_x, cell_middle_y, 4., Color::new(0.2, 0.9, 0.3, 0.6)),
+            CellType::Outflow => batch.circle(cell_middle_x, cell_middle_y, 4., Color::new(0.9, 0.2, 0.2, 0.6)),
+            CellType::Water | CellType::Solid => {}
+        }
+        batch.line(cell_middle_x, cell_middle_y, cell_middle_x + self.flow_v.x * cell_vector_size, cell_middle_y + self.flow_v.y * cell_vector_size,  0.5, WHITE);
+        //draw_line(cell_middle_x + 5., cell_middle_y, cell_middle_x + 5. + self.flow_updates.x * cell_vector_size, cell_middle_y + self.flow_updates.y * cell_vector_size,  0.7, DARKGREEN);
+
+    }
+}
+
+// per-thread scratch space for scattering particle velocities into the grid.
+// each rayon task fills its own copy for a fixed run of particles, and the
+// copies are summed at the end, in order, so no two threads ever write to the
+// same cell and the totals come out the same to the bit every run (see
+// par_sum).
+#[derive(Clone)]
+pub struct FlowAccumulator {
+    pub flow_sums: Vec<Vec2>,
+    pub counts: Vec<u32>,
+}
+
+impl FlowAccumulator {
+    pub fn new(len: usize) -> FlowAccumulator {
+        FlowAccumulator { flow_sums: vec![Vec2::new(0., 0.); len], counts: vec![0; len] }
+    }
+
+    pub fn add(self, particle: &Particle) -> FlowAccumulator {
+        self.add_at(particle.get_cell_index(), particle.velocity, particle.kind.weight())
+    }
+
+    // `weight` is how many water particles' worth it counts for (see ParticleKind)
+    pub fn add_at(mut self, cell_index: usize, velocity: Vec2, weight: u32) -> FlowAccumulator {
+        self.flow_sums[cell_index] += velocity * weight as f32;
+        self.counts[cell_index] += weight;
+        self
+    }
+
+    pub fn merge(mut self, other: FlowAccumulator) -> FlowAccumulator {
+        for i in 0..self.counts.len() {
+            self.flow_sums[i] += other.flow_sums[i];
+            self.counts[i] += other.counts[i];
+        }
+        self
+    }
+
+    // particles 0..count, `add`ed CHUNK at a time on the rayon pool. a plain
+    // fold/reduce splits the work wherever the threads happen to steal it, so
+    // the floats get added up in a different order (and round differently)
+    // from run to run; fixed chunks merged in order don't
+    #[cfg(feature = "parallel")]
+    pub fn par_sum<F>(len: usize, count: usize, add: F) -> FlowAccumulator
+    where
+        F: Fn(FlowAccumulator, usize) -> FlowAccumulator + Sync,
+    {
+        let chunks = (count + CHUNK - 1) / CHUNK;
+        let sums: Vec<FlowAccumulator> = (0..chunks).into_par_iter()
+            .map(|chunk| (chunk * CHUNK..((chunk + 1) * CHUNK).min(count)).fold(FlowAccumulator::new(len), &add))
+            .collect();
+        sums.into_iter().fold(FlowAccumulator::new(len), FlowAccumulator::merge)
+    }
+}
+
+pub fn new_cells(grid: &GridGeometry) -> Cells {
+    let mut ret = SparseGrid::new(grid.cols, grid.rows, FluidCell::empty());
+    for i in 0 .. grid.len() {
+        ret[i] = FluidCell{ kind: CellType::Water,
+                            flow_v: Vec2::new(rand::gen_range(-1., 1.), rand::gen_range(-1., 1.)), 
+                            flow_updates: Vec2::new (0.,0.),
+                            particle_count: 0, 
+                            quiet_frames: 0,
+                            depth: 1.,
+                        };
+    }
+    Cells{all_cells: ret}
+
+}
+
+// have the particles update the cells they're in
+pub fn update_grid_flow(particles: View<Particle>, mut next:UniqueViewMut<NextCells>) {
+    let _t = profiling::scope("update_grid_flow");
+    let len = next.0.all_cells.len();
+    // (the components can't be split into fixed chunks, so what each one adds
+    // is collected first, which keeps their order)
+    #[cfg(feature = "parallel")]
+    let acc = {
+        let adds: Vec<(usize, Vec2, u32)> = particles.par_iter()
+            .map(|particle| (particle.get_cell_index(), particle.velocity, particle.kind.weight()))
+            .collect();
+        FlowAccumulator::par_sum(len, adds.len(), |acc, i| acc.add_at(adds[i].0, adds[i].1, adds[i].2))
+    };
+    #[cfg(not(feature = "parallel"))]
+    let acc = particles.iter().fold(FlowAccumulator::new(len), FlowAccumulator::add);
+
+    next.0.add_flow_updates(&acc);
+}
+
+// apply the updates to the cells, starting from last step's flow.
+// also tracks which cells are quiet, skipping the ones that have gone to sleep
+pub fn apply_grid_updates(map:UniqueView<Cells>,
+                          mut next:UniqueViewMut<NextCells>,
+                          grid: UniqueView<GridGeometry>,
+                          tuning: UniqueView<Tuning>) {
+    let _t = profiling::scope("apply_grid_updates");
+    let mut woken = vec![];
+    for cell_ix in 0..map.all_cells.len() {
+        // still water on both sides, leave it unallocated
+        if !map.all_cells.is_allocated(cell_ix) && !next.0.all_cells.is_allocated(cell_ix) {
+            continue;
+        }
+        let cell = &map.all_cells[cell_ix];
+        let next_cell = &mut next.0.all_cells[cell_ix];
+        next_cell.kind = cell.kind;
+        next_cell.flow_v = cell.flow_v;
+        next_cell.quiet_frames = cell.quiet_frames;
+        next_cell.depth = cell.depth;
+        if cell.is_solid() {
+            next_cell.flow_v = Vec2::new(0., 0.);
+            next_cell.flow_updates = Vec2::new(0., 0.);
+            next_cell.particle_count = 0;
+            continue;
+        }
+        // an inflow keeps its flow, and its neighbours awake to take it
+        if cell.kind == CellType::Inflow {
+            next_cell.flow_updates = Vec2::new(0., 0.);
+            next_cell.particle_count = 0;
+            next_cell.quiet_frames = 0;
+            woken.push(cell_ix);
+            continue;
+        }
+        // no particles came through, so nothing to apply (and nothing to wake it)
+        if cell.is_asleep() && next_cell.particle_count == 0 {
+            continue;
+        }
+        let visited = next_cell.particle_count > 0;
+        next_cell.apply_flow_update(tuning.particle_to_cell);
+        next_cell.flow_v *= 1. - SHALLOW_DRAG * (1. - cell.depth);
+        // viscosity evens the flow out with the cells round it (a wall's
+        // still, so it drags on the water beside it)
+        if tuning.viscosity > 0. {
+            let neighbours = grid.neighbours(cell_ix);
+            let around = neighbours.iter().fold(Vec2::new(0., 0.), |sum, n| sum + map.all_cells[*n].flow_v)
+                / neighbours.len() as f32;
+            next_cell.flow_v += (around - next_cell.flow_v) * tuning.viscosity;
+        }
+        let flow_change = pythag_dist(cell.flow_v.x, cell.flow_v.y, next_cell.flow_v.x, next_cell.flow_v.y);
+        if visited || flow_change > QUIESCENT_FLOW_CHANGE {
+            if next_cell.is_asleep() {
+                woken.push(cell_ix);
+            }
+            next_cell.quiet_frames = 0;
+        } else {
+            next_cell.quiet_frames += 1;
+        }
+    }
+    // a cell waking up wakes its neighbours too, since that's where the flow goes next
+    for cell_ix in woken {
+        for neighbour in grid.neighbours(cell_ix).iter() {
+            next.0.all_cells[*neighbour].quiet_frames = 0;
+        }
+    }
+    next.0.all_cells.free_chunks_where(FluidCell::is_empty);
+}
+
+// publish this step's grid, the old one becomes next step's scratch
+pub fn swap_grid_buffers(mut map:UniqueViewMut<Cells>, mut next:UniqueViewMut<NextCells>) {
+    let _t = profiling::scope("swap_grid_buffers");
+    std::mem::swap(&mut map.all_cells, &mut next.0.all_cells);
+}
@@ -0,0 +1,273 @@
+// force fields: steady pushes on the water that stay put for the whole level,
+// from the scenario file's [[force_fields]] or placed in debug mode.
+//
+//   kind = "wind"       the whole map blows toward vx, vy (a constant crosswind)
+//   kind = "attractor"  the water is drawn in toward x, y, within radius
+//   kind = "repulsor"   and pushed out away from it
+//   kind = "vortex"     and spun round it, anticlockwise (a whirlpool)
+//
+// all of them take strength (the speed, in pixels a step, the water's pushed
+// toward at the middle, fading out to nothing at the radius; wind's is its
+// vx, vy, the same everywhere). each tick every cell a field reaches takes on
+// FIELD_PULL of the difference between its flow and what the field wants
+// there, so they add up like an acceleration but never run away with the
+// water. the particles and the boats feel them through the flow.
+//
+// in debug mode (D), K at the cursor puts down one of the kind L picks:
+// hold K and move the mouse before letting go to aim a wind (the drag's
+// direction and length, like an emitter's) or size anything else's radius.
+// K over one that's already there takes it away. either way they're written
+// back to the scenario file, like the edit tool's emitters and sinks.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, EntitiesViewMut, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::camera::Camera;
+use crate::geometry::wrapped_delta;
+use crate::grid::GridGeometry;
+use crate::keymap::{Action, KeyBindings};
+use crate::level::Level;
+use crate::profiling;
+use crate::scenario::Scenario;
+use crate::{Cells, GameMode, GameModeInfo, Screen};
+
+// how much of the way to what a field wants the flow goes a tick
+const FIELD_PULL: f32 = 0.05;
+const DEFAULT_STRENGTH: f32 = 2.;
+const DEFAULT_RADIUS: f32 = 80.;
+const MIN_RADIUS: f32 = 10.;
+// a placed wind's speed per pixel of drag, and the most it can have
+const WIND_SPEED_PER_PIXEL: f32 = 0.02;
+const MAX_WIND_SPEED: f32 = 4.;
+// how near the cursor has to be to a field's middle to take it away
+const PICK_RADIUS: f32 = 8.;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldKind {
+    Wind,
+    Attractor,
+    Repulsor,
+    Vortex,
+}
+
+impl FieldKind {
+    fn next(self) -> FieldKind {
+        match self {
+            FieldKind::Wind => FieldKind::Attractor,
+            FieldKind::Attractor => FieldKind::Repulsor,
+            FieldKind::Repulsor => FieldKind::Vortex,
+            FieldKind::Vortex => FieldKind::Wind,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FieldKind::Wind => "wind",
+            FieldKind::Attractor => "attractor",
+            FieldKind::Repulsor => "repulsor",
+            FieldKind::Vortex => "vortex",
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            FieldKind::Wind => LIGHTGRAY,
+            FieldKind::Attractor => SKYBLUE,
+            FieldKind::Repulsor => ORANGE,
+            FieldKind::Vortex => VIOLET,
+        }
+    }
+}
+
+fn default_strength() -> f32 {
+    DEFAULT_STRENGTH
+}
+
+fn default_radius() -> f32 {
+    DEFAULT_RADIUS
+}
+
+// (it's its own entry in the scenario file too; there's nothing about one
+// that changes as it runs)
+#[derive(Component, Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct ForceField {
+    pub kind: FieldKind,
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+    // a wind's flow
+    #[serde(default)]
+    pub vx: f32,
+    #[serde(default)]
+    pub vy: f32,
+    #[serde(default = "default_strength")]
+    pub strength: f32,
+    #[serde(default = "default_radius")]
+    pub radius: f32,
+}
+
+impl ForceField {
+    fn pos(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+
+    // the flow it wants `offset` from its middle (None out of its reach)
+    fn wants(&self, offset: Vec2) -> Option<Vec2> {
+        if self.kind == FieldKind::Wind {
+            return Some(Vec2::new(self.vx, self.vy));
+        }
+        let distance = offset.length();
+        if distance == 0. || distance > self.radius {
+            return None;
+        }
+        let out = offset / distance;
+        let speed = self.strength * (1. - distance / self.radius);
+        Some(match self.kind {
+            FieldKind::Attractor => -out * speed,
+            FieldKind::Repulsor => out * speed,
+            _ => Vec2::new(-out.y, out.x) * speed,
+        })
+    }
+}
+
+// what K puts down, and where it started if it's held
+#[derive(Component)]
+pub struct FieldPlacer {
+    pub kind: FieldKind,
+    pending: Option<Vec2>,
+}
+
+impl Default for FieldPlacer {
+    fn default() -> FieldPlacer {
+        FieldPlacer { kind: FieldKind::Wind, pending: None }
+    }
+}
+
+// in the sim, with the vortices
+pub fn apply_force_fields(fields: View<ForceField>,
+                          mut map: UniqueViewMut<Cells>,
+                          grid: UniqueView<GridGeometry>) {
+    let _t = profiling::scope("apply_force_fields");
+    for field in fields.iter() {
+        let reach = match field.kind {
+            FieldKind::Wind => (0..map.all_cells.len()).collect(),
+            _ => grid.cells_within(field.pos(), field.radius),
+        };
+        for cell_ix in reach {
+            let wants = match field.wants(wrapped_delta(field.pos(), grid.center(cell_ix), grid.size())) {
+                Some(wants) => wants,
+                None => continue,
+            };
+            let cell = &mut map.all_cells[cell_ix];
+            if cell.is_solid() {
+                continue;
+            }
+            cell.flow_v += (wants - cell.flow_v) * FIELD_PULL;
+            cell.quiet_frames = 0;
+        }
+    }
+}
+
+pub fn place_force_fields(mut placer: UniqueViewMut<FieldPlacer>,
+                          mut entities: EntitiesViewMut,
+                          mut fields: ViewMut<ForceField>,
+                          keys: UniqueView<KeyBindings>,
+                          game_mode: UniqueView<GameModeInfo>,
+                          camera: UniqueView<Camera>,
+                          level: UniqueView<Level>,
+                          _screen: UniqueView<Screen>) {
+    if game_mode.game_mode != GameMode::Debug {
+        placer.pending = None;
+        return;
+    }
+    if keys.pressed(Action::FieldKind) {
+        placer.kind = placer.kind.next();
+    }
+    let mouse = camera.mouse_world();
+    let mut changed = false;
+    if keys.pressed(Action::PlaceField) {
+        let under = (&fields).iter().with_id().find(|(_, field)| (field.pos() - mouse).length() < PICK_RADIUS);
+        match under {
+            Some((id, _)) => {
+                fields.remove(id);
+                entities.delete_unchecked(id);
+                changed = true;
+            }
+            None => placer.pending = Some(mouse),
+        }
+    }
+    if !keys.down(Action::PlaceField) {
+        if let Some(pos) = placer.pending.take() {
+            let drag = mouse - pos;
+            let mut field = ForceField { kind: placer.kind, x: pos.x, y: pos.y, vx: 0., vy: 0.,
+                                         strength: DEFAULT_STRENGTH, radius: DEFAULT_RADIUS };
+            match (placer.kind, drag.length() < 2.) {
+                // a tap without much of a drag blows right
+                (FieldKind::Wind, true) => field.vx = 1.,
+                (FieldKind::Wind, false) => {
+                    let wind = drag.normalize() * (drag.length() * WIND_SPEED_PER_PIXEL).min(MAX_WIND_SPEED);
+                    field.vx = wind.x;
+                    field.vy = wind.y;
+                }
+                (_, true) => {}
+                (_, false) => field.radius = drag.length().max(MIN_RADIUS),
+            }
+            entities.add_entity(&mut fields, field);
+            changed = true;
+        }
+    }
+
+    if let (true, Some(path)) = (changed, &level.save_path) {
+        Scenario::save_force_fields(path, (&fields).iter());
+    }
+}
+
+// world space: a ring for each one's reach with arrows showing which way it
+// pushes, and a wind's arrow from where it was put. in debug mode they're
+// labelled, and the one being put down is shown under the cursor
+pub fn draw_force_fields(_screen: UniqueViewMut<Screen>,
+                         fields: View<ForceField>,
+                         placer: UniqueView<FieldPlacer>,
+                         game_mode: UniqueView<GameModeInfo>,
+                         camera: UniqueView<Camera>) {
+    let debug = game_mode.game_mode == GameMode::Debug;
+    for field in fields.iter() {
+        let at = field.pos();
+        let color = field.kind.color();
+        let faint = Color::new(color.r, color.g, color.b, 0.4);
+        if field.kind == FieldKind::Wind {
+            let tip = at + Vec2::new(field.vx, field.vy) * 12.;
+            draw_line(at.x, at.y, tip.x, tip.y, 2., faint);
+            draw_circle(tip.x, tip.y, 2., faint);
+        } else {
+            draw_circle_lines(at.x, at.y, field.radius, 1., faint);
+            // a few arrows halfway out
+            for i in 0..8 {
+                let angle = i as f32 * std::f32::consts::PI / 4.;
+                let offset = Vec2::new(angle.cos(), angle.sin()) * field.radius * 0.5;
+                if let Some(wants) = field.wants(offset) {
+                    let from = at + offset;
+                    let to = from + wants / wants.length().max(0.01) * 6.;
+                    draw_line(from.x, from.y, to.x, to.y, 1., faint);
+                }
+            }
+        }
+        draw_circle(at.x, at.y, 3., color);
+        if debug {
+            let label = match field.kind {
+                FieldKind::Wind => format!("wind {:.2}, {:.2}", field.vx, field.vy),
+                kind => format!("{} {:.1}, radius {:.0}", kind.name(), field.strength, field.radius),
+            };
+            draw_text(&label, at.x + 6., at.y - 6., 14., color);
+        }
+    }
+    if debug {
+        let mouse = camera.mouse_world();
+        let from = placer.pending.unwrap_or(mouse);
+        draw_line(from.x, from.y, mouse.x, mouse.y, 1., placer.kind.color());
+        draw_text(&format!("K: {}", placer.kind.name()), mouse.x + 8., mouse.y + 14., 14., placer.kind.color());
+    }
+}
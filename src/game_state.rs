@@ -0,0 +1,101 @@
+// how the game goes from playing to over (or round again). nothing in the
+// workloads ends the game itself: the systems that notice something send a
+// GameEvent, and after each workload the state machine (update_game_state)
+// works out what it means. the main loop then asks the Simulation what phase
+// the game's in and acts on it: a game over goes back to the start screen
+// with the score on the tables, a restart starts the level over.
+//
+//   PlayerDied       the boat's out of health (see score.rs)
+//   ScoreChanged     the score's moved on, to this; the state machine keeps
+//                    the last one for the game over
+//   RequestExit      esc, or `quit` in the console: the game ends, like dying
+//   RequestRestart   R, or `restart` in the console: the same level again,
+//                    straight away, without the score going on the tables
+//
+// unlike the event bus (see events.rs), which lasts the frame and can be read
+// by anyone, this is a queue for the one reader: update_game_state takes
+// everything out of it as it goes, so nothing's acted on twice however many
+// ticks the frame runs. once the game's over (or restarting) the sim stops
+// ticking for the rest of the frame.
+
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::commands::Command;
+use crate::events::{Event, Events};
+use crate::keymap::{Action, KeyBindings};
+use crate::Screen;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameEvent {
+    PlayerDied,
+    ScoreChanged { points: u32 },
+    RequestExit,
+    RequestRestart,
+}
+
+#[derive(Component, Default)]
+pub struct GameEvents {
+    queue: Vec<GameEvent>,
+}
+
+impl GameEvents {
+    pub fn send(&mut self, event: GameEvent) {
+        self.queue.push(event);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Phase {
+    Playing,
+    // with the final score
+    Over { score: u32 },
+    Restarting,
+}
+
+impl Default for Phase {
+    fn default() -> Phase {
+        Phase::Playing
+    }
+}
+
+#[derive(Component, Default)]
+pub struct GameState {
+    pub phase: Phase,
+    score: u32,
+}
+
+// in the input: the keys and console commands that end or restart the game
+pub fn request_transitions(keys: UniqueView<KeyBindings>,
+                           events: UniqueView<Events>,
+                           mut game_events: UniqueViewMut<GameEvents>,
+                           _screen: UniqueView<Screen>) {
+    // esc goes back to the start screen (where another esc quits, see main.rs)
+    if keys.pressed(Action::Exit) {
+        game_events.send(GameEvent::RequestExit);
+    }
+    if keys.pressed(Action::Restart) {
+        game_events.send(GameEvent::RequestRestart);
+    }
+    for event in events.iter() {
+        match event {
+            Event::Command(Command::Quit) => game_events.send(GameEvent::RequestExit),
+            Event::Command(Command::Restart) => game_events.send(GameEvent::RequestRestart),
+            _ => {}
+        }
+    }
+}
+
+// after the input and each sim tick (see Simulation::step). once the game's
+// left playing, whatever else was in the queue is dropped
+pub fn update_game_state(mut game_events: UniqueViewMut<GameEvents>, mut state: UniqueViewMut<GameState>) {
+    for event in game_events.queue.drain(..) {
+        if state.phase != Phase::Playing {
+            continue;
+        }
+        match event {
+            GameEvent::ScoreChanged { points } => state.score = points,
+            GameEvent::PlayerDied | GameEvent::RequestExit => state.phase = Phase::Over { score: state.score },
+            GameEvent::RequestRestart => state.phase = Phase::Restarting,
+        }
+    }
+}
@@ -0,0 +1,161 @@
+// gamepad input, through gilrs
+//
+// gilrs wants polling from one place, so like the GPU backend this lives in
+// the main loop rather than in the world: `poll` drains gilrs' events before
+// each step and copies the first connected pad's state into the GamepadState
+// unique, which read_controls (input.rs) folds in with the keyboard. the stick
+// and trigger steer the boat; the buttons are bound to actions like keys are
+// (see keymap.rs), so anything that asks the key bindings about an action
+// hears about the pad too. the defaults, changed in the [pad] section of the
+// settings file by the button names below:
+//   south (A)   - fire           east (B)    - quick flip
+//   left bumper - fine control   start       - pause
+//   select      - debug view     d-pad up    - field view
+//   d-pad left  - half speed     d-pad down  - normal speed
+//   d-pad right - fast forward
+// there's no gilrs on the wasm build, where the pad always reads as missing.
+
+#[cfg(not(target_arch = "wasm32"))]
+use gilrs::{Axis, Button, Gilrs};
+use shipyard::{Component, UniqueViewMut, World};
+
+// the buttons that can be bound, named by where they are rather than what's
+// printed on them (south is A on an xbox pad and cross on a playstation one)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+pub const PAD_BUTTONS: [PadButton; 12] = [
+    PadButton::South,
+    PadButton::East,
+    PadButton::North,
+    PadButton::West,
+    PadButton::LeftBumper,
+    PadButton::RightBumper,
+    PadButton::Select,
+    PadButton::Start,
+    PadButton::DPadUp,
+    PadButton::DPadDown,
+    PadButton::DPadLeft,
+    PadButton::DPadRight,
+];
+
+impl PadButton {
+    // what it's called in the settings file
+    pub fn name(self) -> &'static str {
+        match self {
+            PadButton::South => "south",
+            PadButton::East => "east",
+            PadButton::North => "north",
+            PadButton::West => "west",
+            PadButton::LeftBumper => "left_bumper",
+            PadButton::RightBumper => "right_bumper",
+            PadButton::Select => "select",
+            PadButton::Start => "start",
+            PadButton::DPadUp => "dpad_up",
+            PadButton::DPadDown => "dpad_down",
+            PadButton::DPadLeft => "dpad_left",
+            PadButton::DPadRight => "dpad_right",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<PadButton> {
+        PAD_BUTTONS.iter().copied().find(|button| button.name() == name)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn gilrs_button(self) -> Button {
+        match self {
+            PadButton::South => Button::South,
+            PadButton::East => Button::East,
+            PadButton::North => Button::North,
+            PadButton::West => Button::West,
+            PadButton::LeftBumper => Button::LeftTrigger,
+            PadButton::RightBumper => Button::RightTrigger,
+            PadButton::Select => Button::Select,
+            PadButton::Start => Button::Start,
+            PadButton::DPadUp => Button::DPadUp,
+            PadButton::DPadDown => Button::DPadDown,
+            PadButton::DPadLeft => Button::DPadLeft,
+            PadButton::DPadRight => Button::DPadRight,
+        }
+    }
+}
+
+// stick travel that's ignored, so a pad at rest doesn't drift the boat around
+const DEADZONE: f32 = 0.2;
+
+#[derive(Component, Default)]
+pub struct GamepadState {
+    pub connected: bool,
+    // left stick x, -1 (left) to 1 (right), deadzone already taken out
+    pub turn: f32,
+    // right trigger, 0 to 1
+    pub thrust: f32,
+    // the buttons held, and the ones that went down this frame
+    pub down: Vec<PadButton>,
+    pub pressed: Vec<PadButton>,
+}
+
+pub struct Gamepads {
+    #[cfg(not(target_arch = "wasm32"))]
+    gilrs: Option<Gilrs>,
+    // last frame's buttons, to tell which are new
+    last_down: Vec<PadButton>,
+}
+
+impl Gamepads {
+    pub fn new() -> Gamepads {
+        #[cfg(not(target_arch = "wasm32"))]
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(err) => {
+                macroquad::logging::warn!("no gamepad support: {}", err);
+                None
+            }
+        };
+        Gamepads {
+            #[cfg(not(target_arch = "wasm32"))]
+            gilrs,
+            last_down: vec![],
+        }
+    }
+
+    pub fn poll(&mut self, world: &World) {
+        let mut state = world.borrow::<UniqueViewMut<GamepadState>>().unwrap();
+        *state = GamepadState::default();
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(gilrs) = self.gilrs.as_mut() {
+            // gilrs only updates its cached state as the events are drained
+            while gilrs.next_event().is_some() {}
+            if let Some((_, pad)) = gilrs.gamepads().find(|(_, pad)| pad.is_connected()) {
+                let stick = pad.value(Axis::LeftStickX);
+                state.connected = true;
+                state.turn = if stick.abs() < DEADZONE {
+                    0.
+                } else {
+                    (stick - DEADZONE * stick.signum()) / (1. - DEADZONE)
+                };
+                state.thrust = pad.button_data(Button::RightTrigger2).map_or(0., |data| data.value());
+                state.down = PAD_BUTTONS.iter().copied()
+                    .filter(|button| pad.is_pressed(button.gilrs_button()))
+                    .collect();
+            }
+        }
+        let last_down = &self.last_down;
+        state.pressed = state.down.iter().copied().filter(|button| !last_down.contains(button)).collect();
+        self.last_down = state.down.clone();
+    }
+}
@@ -0,0 +1,40 @@
+// the world's a torus: off the right edge is back on the left, off the bottom
+// back at the top. anything measuring between two points has to take that
+// into account, or forces and hits stop working across the edges. these do
+// the sums for a world `size` wide and high (see GridGeometry::size).
+//
+// (with a walled border, see scenario.rs's Boundary, nothing gets near enough
+// to an edge for the short way round to matter, so they're safe there too.)
+
+use macroquad::prelude::*;
+
+use crate::Real;
+
+// wrap a coordinate onto [0, size)
+pub fn wrap_coord(mut v: Real, size: Real) -> Real {
+    while v < 0. {
+        v += size;
+    }
+    while v >= size {
+        v -= size;
+    }
+    v
+}
+
+// a position back onto the world
+pub fn wrap_position(at: Vec2, size: Vec2) -> Vec2 {
+    Vec2::new(at.x.rem_euclid(size.x) % size.x, at.y.rem_euclid(size.y) % size.y)
+}
+
+// the way from `from` to `to`, across the edges if that's shorter
+pub fn wrapped_delta(from: Vec2, to: Vec2, size: Vec2) -> Vec2 {
+    let mut delta = to - from;
+    delta.x -= size.x * (delta.x / size.x).round();
+    delta.y -= size.y * (delta.y / size.y).round();
+    delta
+}
+
+// how far apart two points are, the short way round
+pub fn wrapped_dist(a: Vec2, b: Vec2, size: Vec2) -> f32 {
+    wrapped_delta(a, b, size).length()
+}
@@ -0,0 +1,68 @@
+// keeps the frame rate up by adjusting how many tracer particles there are
+//
+// while frames are coming in on budget the governor keeps adding particles,
+// and when they start running long it culls them, so the particle count
+// settles at whatever this machine can hold at the target frame rate.
+// toggled with G, off by default.
+
+use shipyard::{Component, EntitiesViewMut, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, ViewMut};
+
+use crate::grid::GridGeometry;
+use crate::pool::ParticlePool;
+use crate::profiling;
+use crate::{lerp, new_particle, FrameTime, Particle, Randomness};
+
+const MIN_PARTICLES: usize = 8;
+pub const MAX_PARTICLES: usize = 200_000;
+// frames to wait between adjustments, so each one has time to show up in the frame time
+const ADJUST_EVERY: u32 = 30;
+
+#[derive(Component)]
+pub struct Governor {
+    pub enabled: bool,
+    pub target_fps: f32,
+    // exponential moving average of the frame time, in seconds
+    pub smoothed_frame_time: f32,
+    cooldown: u32,
+}
+
+impl Default for Governor {
+    fn default() -> Governor {
+        Governor { enabled: false, target_fps: 60., smoothed_frame_time: 1. / 60., cooldown: ADJUST_EVERY }
+    }
+}
+
+pub fn govern_particle_count(mut governor: UniqueViewMut<Governor>,
+                             frame_time: UniqueView<FrameTime>,
+                             grid: UniqueView<GridGeometry>,
+                             mut pool: UniqueViewMut<ParticlePool>,
+                             mut entities: EntitiesViewMut,
+                             mut particles: ViewMut<Particle>,
+                             _random: UniqueViewMut<Randomness>) {
+    let _t = profiling::scope("govern_particle_count");
+    governor.smoothed_frame_time = lerp(governor.smoothed_frame_time, frame_time.0, 0.05);
+    if !governor.enabled {
+        return;
+    }
+    if governor.cooldown > 0 {
+        governor.cooldown -= 1;
+        return;
+    }
+    governor.cooldown = ADJUST_EVERY;
+
+    // grow while we're making the target (a vsynced frame sits right on it),
+    // shrink once frames clearly run long
+    let budget = 1. / governor.target_fps;
+    let count = particles.iter().count();
+    let step = (count / 10).max(MIN_PARTICLES);
+    if governor.smoothed_frame_time > budget * 1.2 && count > MIN_PARTICLES {
+        let cull: Vec<EntityId> = particles.iter().with_id()
+            .map(|(id, _)| id)
+            .take(step.min(count - MIN_PARTICLES))
+            .collect();
+        pool.despawn_bulk(&mut particles, &cull);
+    } else if governor.smoothed_frame_time < budget * 1.05 && count < MAX_PARTICLES {
+        let spawn = step.min(MAX_PARTICLES - count);
+        pool.spawn_bulk(&mut entities, &mut particles, (0..spawn).map(|_| new_particle(&grid)));
+    }
+}
@@ -0,0 +1,432 @@
+// optional GPU backend for the grid (run with --gpu)
+//
+// miniquad has no compute shaders, so the solve is done the old-fashioned way:
+// the flow field lives in small render targets (one texel per cell) and each
+// stage is a full-target quad drawn with a fragment shader, ping-ponging
+// between two targets. the stages are
+//   advect     - semi-lagrangian, each cell pulls its velocity from upstream
+//   diffuse    - a few jacobi iterations of the viscosity solve
+//   project    - divergence, jacobi pressure iterations, subtract the gradient
+//
+// the pressure solve is warm started: the last frame's pressure is kept as the
+// first guess for this one, which for a slowly changing flow is already close.
+// how close gets checked every few solves with a residual pass (into the same
+// target each time) that's read back along with that solve's result, and the
+// iteration count follows it, dropping while the residual is under tolerance
+// and climbing back when it isn't. reading a texture back stalls on the GPU,
+// so the solves in between don't. (the readback can't happen mid-solve since
+// the passes are only queued, so the early exit lags a frame behind.)
+//
+// the targets are one texel per cell of whatever the grid is, so when it
+// changes size (a level, the config, refine.rs or a window resize) they're
+// made again at the new one, starting over from a zero pressure guess.
+//
+// render targets are plain RGBA8, so every float is packed into two bytes
+// (R,G = vx, B,A = vy; scalars only use R,G). all lookups go through
+// gl_FragCoord rather than the quad's uvs, so it doesn't matter which way up
+// the render target cameras draw.
+//
+// GL calls have to happen on the main thread, so this runs from the main loop
+// rather than as a system: `upload_and_solve` after the workload each frame,
+// and `read_back` before the next one (by which time macroquad has flushed the
+// queued passes), copying the solved field back into the cells.
+
+use macroquad::prelude::*;
+use shipyard::{UniqueView, UniqueViewMut, World};
+
+use crate::grid::GridGeometry;
+use crate::memory;
+use crate::Cells;
+
+// largest |velocity| the 16 bit packing can represent
+const MAX_SPEED: f32 = 8.;
+
+const DIFFUSION_ITERATIONS: usize = 4;
+// the pressure iteration count starts here and moves between the bounds
+const PRESSURE_ITERATIONS: usize = 20;
+const MIN_PRESSURE_ITERATIONS: usize = 2;
+const MAX_PRESSURE_ITERATIONS: usize = 40;
+// largest per-cell residual we're happy to stop at
+const RESIDUAL_TOLERANCE: f32 = 1e-3;
+// how many solves go by between residual checks
+const RESIDUAL_EVERY: u32 = 8;
+const VISCOSITY: f32 = 0.05;
+
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+}
+"#;
+
+// shared by all the fragment shaders: packing and wrapped lookups
+const COMMON: &str = r#"#version 100
+precision highp float;
+
+uniform sampler2D Texture;
+uniform vec2 grid_size;
+uniform float max_speed;
+
+float unpack(vec2 bytes) {
+    float n = bytes.x * 255. * 256. + bytes.y * 255.;
+    return (n / 65535. * 2. - 1.) * max_speed;
+}
+
+vec2 pack(float v) {
+    float n = floor(clamp(v / max_speed * .5 + .5, 0., 1.) * 65535. + .5);
+    float hi = floor(n / 256.);
+    return vec2(hi, n - hi * 256.) / 255.;
+}
+
+// fetch a texel by integer cell coordinate, wrapping around the edges
+vec4 fetch(sampler2D tex, vec2 cell) {
+    vec2 wrapped = mod(cell, grid_size);
+    return texture2D(tex, (floor(wrapped) + .5) / grid_size);
+}
+
+vec2 velocity(sampler2D tex, vec2 cell) {
+    vec4 t = fetch(tex, cell);
+    return vec2(unpack(t.rg), unpack(t.ba));
+}
+
+float scalar(sampler2D tex, vec2 cell) {
+    return unpack(fetch(tex, cell).rg);
+}
+
+vec4 pack_velocity(vec2 v) {
+    return vec4(pack(v.x), pack(v.y));
+}
+"#;
+
+// writes a zero velocity/scalar everywhere, used for the very first pressure guess
+const ZERO_SHADER: &str = r#"
+void main() {
+    gl_FragColor = vec4(pack(0.), pack(0.));
+}
+"#;
+
+const ADVECT_SHADER: &str = r#"
+uniform vec2 cell_size;
+
+void main() {
+    vec2 cell = floor(gl_FragCoord.xy);
+    // velocities are in pixels per frame, convert to cells per frame
+    vec2 from = cell - velocity(Texture, cell) / cell_size;
+    vec2 base = floor(from);
+    vec2 f = from - base;
+    vec2 v = mix(mix(velocity(Texture, base), velocity(Texture, base + vec2(1., 0.)), f.x),
+                 mix(velocity(Texture, base + vec2(0., 1.)), velocity(Texture, base + vec2(1., 1.)), f.x),
+                 f.y);
+    gl_FragColor = pack_velocity(v);
+}
+"#;
+
+const DIFFUSE_SHADER: &str = r#"
+uniform sampler2D Base;
+uniform float viscosity;
+
+void main() {
+    vec2 cell = floor(gl_FragCoord.xy);
+    vec2 neighbours = velocity(Texture, cell + vec2(1., 0.)) + velocity(Texture, cell - vec2(1., 0.))
+                    + velocity(Texture, cell + vec2(0., 1.)) + velocity(Texture, cell - vec2(0., 1.));
+    vec2 v = (velocity(Base, cell) + viscosity * neighbours) / (1. + 4. * viscosity);
+    gl_FragColor = pack_velocity(v);
+}
+"#;
+
+const DIVERGENCE_SHADER: &str = r#"
+void main() {
+    vec2 cell = floor(gl_FragCoord.xy);
+    float div = .5 * (velocity(Texture, cell + vec2(1., 0.)).x - velocity(Texture, cell - vec2(1., 0.)).x
+                    + velocity(Texture, cell + vec2(0., 1.)).y - velocity(Texture, cell - vec2(0., 1.)).y);
+    gl_FragColor = vec4(pack(div), 0., 0.);
+}
+"#;
+
+const PRESSURE_SHADER: &str = r#"
+uniform sampler2D Divergence;
+
+void main() {
+    vec2 cell = floor(gl_FragCoord.xy);
+    float p = scalar(Texture, cell + vec2(1., 0.)) + scalar(Texture, cell - vec2(1., 0.))
+            + scalar(Texture, cell + vec2(0., 1.)) + scalar(Texture, cell - vec2(0., 1.));
+    gl_FragColor = vec4(pack((p - scalar(Divergence, cell)) * .25), 0., 0.);
+}
+"#;
+
+// how far the pressure is from solving the poisson equation, per cell
+const RESIDUAL_SHADER: &str = r#"
+uniform sampler2D Divergence;
+
+void main() {
+    vec2 cell = floor(gl_FragCoord.xy);
+    float p = scalar(Texture, cell + vec2(1., 0.)) + scalar(Texture, cell - vec2(1., 0.))
+            + scalar(Texture, cell + vec2(0., 1.)) + scalar(Texture, cell - vec2(0., 1.));
+    float r = p - 4. * scalar(Texture, cell) - scalar(Divergence, cell);
+    gl_FragColor = vec4(pack(abs(r)), 0., 0.);
+}
+"#;
+
+const GRADIENT_SHADER: &str = r#"
+uniform sampler2D Pressure;
+
+void main() {
+    vec2 cell = floor(gl_FragCoord.xy);
+    vec2 grad = .5 * vec2(scalar(Pressure, cell + vec2(1., 0.)) - scalar(Pressure, cell - vec2(1., 0.)),
+                          scalar(Pressure, cell + vec2(0., 1.)) - scalar(Pressure, cell - vec2(0., 1.)));
+    gl_FragColor = pack_velocity(velocity(Texture, cell) - grad);
+}
+"#;
+
+fn new_material(body: &str, extra_uniforms: Vec<(&str, UniformType)>, textures: Vec<&str>) -> Material {
+    let mut uniforms = vec![
+        ("grid_size".to_owned(), UniformType::Float2),
+        ("max_speed".to_owned(), UniformType::Float1),
+    ];
+    uniforms.extend(extra_uniforms.into_iter().map(|(name, ty)| (name.to_owned(), ty)));
+    let material = load_material(
+        VERTEX_SHADER,
+        &format!("{}{}", COMMON, body),
+        MaterialParams {
+            uniforms,
+            textures: textures.into_iter().map(|name| name.to_owned()).collect(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    material.set_uniform("max_speed", MAX_SPEED);
+    material
+}
+
+fn new_target(cols: usize, rows: usize) -> RenderTarget {
+    let target = render_target(cols as u32, rows as u32);
+    target.texture.set_filter(FilterMode::Nearest);
+    target
+}
+
+// everything that's the size of the grid
+struct Targets {
+    cols: usize,
+    rows: usize,
+    upload: Texture2D,
+    velocity: [RenderTarget; 2],
+    base: RenderTarget,
+    divergence: RenderTarget,
+    pressure: [RenderTarget; 2],
+    residual: RenderTarget,
+}
+
+impl Targets {
+    fn new(cols: usize, rows: usize) -> Targets {
+        let upload = Texture2D::from_rgba8(cols as u16, rows as u16, &vec![0; cols * rows * 4]);
+        upload.set_filter(FilterMode::Nearest);
+        // the upload texture plus 7 render targets, all RGBA8
+        memory::set_render_target_bytes(8 * cols * rows * 4);
+        Targets {
+            cols,
+            rows,
+            upload,
+            velocity: [new_target(cols, rows), new_target(cols, rows)],
+            base: new_target(cols, rows),
+            divergence: new_target(cols, rows),
+            pressure: [new_target(cols, rows), new_target(cols, rows)],
+            residual: new_target(cols, rows),
+        }
+    }
+
+    fn delete(&self) {
+        self.upload.delete();
+        for target in self.velocity.iter().chain(self.pressure.iter()) {
+            target.delete();
+        }
+        self.base.delete();
+        self.divergence.delete();
+        self.residual.delete();
+    }
+}
+
+fn pack(v: f32) -> [u8; 2] {
+    let n = ((v / MAX_SPEED * 0.5 + 0.5).max(0.).min(1.) * 65535. + 0.5) as u16;
+    [(n >> 8) as u8, (n & 0xff) as u8]
+}
+
+fn unpack(hi: u8, lo: u8) -> f32 {
+    let n = ((hi as u32) << 8 | lo as u32) as f32;
+    (n / 65535. * 2. - 1.) * MAX_SPEED
+}
+
+pub struct GpuFluid {
+    targets: Targets,
+    zero: Material,
+    advect: Material,
+    diffuse: Material,
+    divergence_mat: Material,
+    pressure_mat: Material,
+    gradient: Material,
+    residual_mat: Material,
+    // which velocity target holds the result of the last solve, if there is one
+    result: Option<usize>,
+    // which pressure target holds the last solve's pressure, once there's been one
+    last_pressure: Option<usize>,
+    pressure_iterations: usize,
+    // solves since the last residual check, and whether this solve's is queued
+    since_residual: u32,
+    residual_queued: bool,
+}
+
+impl GpuFluid {
+    pub fn new(grid: &GridGeometry) -> GpuFluid {
+        let diffuse = new_material(DIFFUSE_SHADER, vec![("viscosity", UniformType::Float1)], vec!["Base"]);
+        diffuse.set_uniform("viscosity", VISCOSITY);
+        let gpu = GpuFluid {
+            targets: Targets::new(grid.cols, grid.rows),
+            zero: new_material(ZERO_SHADER, vec![], vec![]),
+            advect: new_material(ADVECT_SHADER, vec![("cell_size", UniformType::Float2)], vec![]),
+            diffuse,
+            divergence_mat: new_material(DIVERGENCE_SHADER, vec![], vec![]),
+            pressure_mat: new_material(PRESSURE_SHADER, vec![], vec!["Divergence"]),
+            gradient: new_material(GRADIENT_SHADER, vec![], vec!["Pressure"]),
+            residual_mat: new_material(RESIDUAL_SHADER, vec![], vec!["Divergence"]),
+            result: None,
+            last_pressure: None,
+            pressure_iterations: PRESSURE_ITERATIONS,
+            since_residual: 0,
+            residual_queued: false,
+        };
+        gpu.set_grid_uniforms(grid);
+        gpu
+    }
+
+    fn set_grid_uniforms(&self, grid: &GridGeometry) {
+        let size = vec2(grid.cols as f32, grid.rows as f32);
+        for material in [&self.zero, &self.advect, &self.diffuse, &self.divergence_mat, &self.pressure_mat,
+                         &self.gradient, &self.residual_mat].iter() {
+            material.set_uniform("grid_size", size);
+        }
+        self.advect.set_uniform("cell_size", vec2(grid.cell_width, grid.cell_height));
+    }
+
+    // the grid's changed size since the targets were made: make them again
+    // (the cell size goes into the advection even when the count hasn't, so
+    // the uniforms are set either way)
+    fn fit(&mut self, grid: &GridGeometry) {
+        self.set_grid_uniforms(grid);
+        if (self.targets.cols, self.targets.rows) == (grid.cols, grid.rows) {
+            return;
+        }
+        self.targets.delete();
+        self.targets = Targets::new(grid.cols, grid.rows);
+        self.result = None;
+        self.last_pressure = None;
+        self.residual_queued = false;
+    }
+
+    // draw `source` through `material` into every texel of `target`
+    fn pass(&self, material: &Material, source: Texture2D, target: RenderTarget) {
+        let (w, h) = (self.targets.cols as f32, self.targets.rows as f32);
+        set_camera(&Camera2D {
+            zoom: vec2(2. / w, 2. / h),
+            target: vec2(w / 2., h / 2.),
+            render_target: Some(target),
+            ..Default::default()
+        });
+        gl_use_material(*material);
+        draw_texture_ex(source, 0., 0., WHITE, DrawTextureParams {
+            dest_size: Some(vec2(w, h)),
+            ..Default::default()
+        });
+        gl_use_default_material();
+    }
+
+    // pack the cells' flow into the upload texture and queue up a full solve
+    pub fn upload_and_solve(&mut self, world: &World) {
+        let grid = world.borrow::<UniqueView<GridGeometry>>().unwrap();
+        self.fit(&grid);
+        let cells = world.borrow::<UniqueViewMut<Cells>>().unwrap();
+        let mut bytes = Vec::with_capacity(cells.all_cells.len() * 4);
+        for cell in cells.all_cells.iter() {
+            bytes.extend_from_slice(&pack(cell.flow_v.x));
+            bytes.extend_from_slice(&pack(cell.flow_v.y));
+        }
+        let t = &self.targets;
+        t.upload.update(&Image { bytes, width: t.cols as u16, height: t.rows as u16 });
+
+        // advect into the diffusion's right hand side, which is its first
+        // guess too
+        self.pass(&self.advect, t.upload, t.base);
+        self.diffuse.set_texture("Base", t.base.texture);
+        self.pass(&self.diffuse, t.base.texture, t.velocity[0]);
+        let mut current = 0;
+        for _ in 1..DIFFUSION_ITERATIONS {
+            self.pass(&self.diffuse, t.velocity[current].texture, t.velocity[1 - current]);
+            current = 1 - current;
+        }
+
+        // make it divergence free
+        self.pass(&self.divergence_mat, t.velocity[current].texture, t.divergence);
+        self.pressure_mat.set_texture("Divergence", t.divergence.texture);
+        // start from last frame's pressure if there is one
+        let mut p = match self.last_pressure {
+            Some(p) => p,
+            None => {
+                self.pass(&self.zero, t.upload, t.pressure[0]);
+                0
+            }
+        };
+        for _ in 0..self.pressure_iterations {
+            self.pass(&self.pressure_mat, t.pressure[p].texture, t.pressure[1 - p]);
+            p = 1 - p;
+        }
+        self.gradient.set_texture("Pressure", t.pressure[p].texture);
+        self.pass(&self.gradient, t.velocity[current].texture, t.velocity[1 - current]);
+        let check = self.since_residual + 1 >= RESIDUAL_EVERY;
+        if check {
+            self.residual_mat.set_texture("Divergence", t.divergence.texture);
+            self.pass(&self.residual_mat, t.pressure[p].texture, t.residual);
+        }
+
+        set_default_camera();
+        self.result = Some(1 - current);
+        self.last_pressure = Some(p);
+        self.since_residual = if check { 0 } else { self.since_residual + 1 };
+        self.residual_queued = check;
+    }
+
+    // copy the last solve's result back into the cells (unless they're not
+    // the size it was solved at any more, after a reset onto another level)
+    pub fn read_back(&mut self, world: &World) {
+        let grid = world.borrow::<UniqueView<GridGeometry>>().unwrap();
+        if (self.targets.cols, self.targets.rows) != (grid.cols, grid.rows) {
+            self.result = None;
+            self.residual_queued = false;
+        }
+        if let Some(result) = self.result.take() {
+            let image = self.targets.velocity[result].texture.get_texture_data();
+            let mut cells = world.borrow::<UniqueViewMut<Cells>>().unwrap();
+            for (cell_ix, texel) in image.bytes.chunks_exact(4).enumerate() {
+                let cell = &mut cells.all_cells[cell_ix];
+                cell.flow_v.x = unpack(texel[0], texel[1]);
+                cell.flow_v.y = unpack(texel[2], texel[3]);
+            }
+
+            if !self.residual_queued {
+                return;
+            }
+            self.residual_queued = false;
+            let residual = self.targets.residual.texture.get_texture_data().bytes.chunks_exact(4)
+                .map(|texel| unpack(texel[0], texel[1]))
+                .fold(0., f32::max);
+            self.pressure_iterations = if residual < RESIDUAL_TOLERANCE {
+                (self.pressure_iterations - 1).max(MIN_PRESSURE_ITERATIONS)
+            } else {
+                (self.pressure_iterations + 4).min(MAX_PRESSURE_ITERATIONS)
+            };
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// everything about the grid's shape that the hot paths need, worked out once
+// up front instead of redoing the divides on every lookup. rebuild it (and the
+// cells) with GridGeometry::new if the grid ever changes size.
+
+use macroquad::prelude::*;
+use shipyard::Component;
+
+use crate::diagnostics;
+use crate::geometry::wrapped_dist;
+
+#[derive(Component)]
+pub struct GridGeometry {
+    pub cols: usize,
+    pub rows: usize,
+    pub width: f32,
+    pub height: f32,
+    pub cell_width: f32,
+    pub cell_height: f32,
+    pub inv_cell_width: f32,
+    pub inv_cell_height: f32,
+    // where each cell's glyphs are drawn
+    centers: Vec<Vec2>,
+    // the four cells sharing an edge with each cell, wrapping around the screen
+    neighbours: Vec<[usize; 4]>,
+}
+
+impl GridGeometry {
+    pub fn new(width: f32, height: f32, cols: usize, rows: usize) -> GridGeometry {
+        let cell_width = width / cols as f32;
+        let cell_height = height / rows as f32;
+        let mut centers = Vec::with_capacity(cols * rows);
+        let mut neighbours = Vec::with_capacity(cols * rows);
+        for y in 0..rows {
+            for x in 0..cols {
+                centers.push(Vec2::new(cell_width * (x as f32 + 0.5), cell_height * (y as f32 + 0.5)));
+                neighbours.push([y * cols + (x + cols - 1) % cols,
+                                 y * cols + (x + 1) % cols,
+                                 ((y + rows - 1) % rows) * cols + x,
+                                 ((y + 1) % rows) * cols + x]);
+            }
+        }
+        GridGeometry {
+            cols,
+            rows,
+            width,
+            height,
+            cell_width,
+            cell_height,
+            inv_cell_width: 1. / cell_width,
+            inv_cell_height: 1. / cell_height,
+            centers,
+            neighbours,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cols * self.rows
+    }
+
+    // the world's width and height, for the wrapping sums (see geometry.rs)
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+
+    // which cell a (wrapped) world position falls in
+    pub fn index_at(&self, x: f32, y: f32) -> usize {
+        let (col, clamped_x) = grid_coord(x * self.inv_cell_width, self.cols);
+        let (row, clamped_y) = grid_coord(y * self.inv_cell_height, self.rows);
+        if clamped_x || clamped_y {
+            diagnostics::count_out_of_range(1);
+        }
+        row * self.cols + col
+    }
+
+    // is (x, y) inside cell `cell_ix`? (same mapping as index_at)
+    pub fn contains(&self, cell_ix: usize, x: f32, y: f32) -> bool {
+        cell_ix % self.cols == grid_coord(x * self.inv_cell_width, self.cols).0
+            && cell_ix / self.cols == grid_coord(y * self.inv_cell_height, self.rows).0
+    }
+
+    // what the precomputed tables take up
+    pub fn table_bytes(&self) -> usize {
+        self.centers.capacity() * std::mem::size_of::<Vec2>()
+            + self.neighbours.capacity() * std::mem::size_of::<[usize; 4]>()
+    }
+
+    // the cells whose centers are within `radius` of `point`, the short way
+    // round the edges (so measure from `point` to them with wrapped_delta)
+    pub fn cells_within(&self, point: Vec2, radius: f32) -> Vec<usize> {
+        let size = self.size();
+        let mut cells = vec![];
+        for row in wrapped_span((point.y - radius) * self.inv_cell_height, (point.y + radius) * self.inv_cell_height,
+                                self.rows) {
+            for col in wrapped_span((point.x - radius) * self.inv_cell_width, (point.x + radius) * self.inv_cell_width,
+                                    self.cols) {
+                let cell_ix = row * self.cols + col;
+                if wrapped_dist(point, self.centers[cell_ix], size) < radius {
+                    cells.push(cell_ix);
+                }
+            }
+        }
+        cells
+    }
+
+    pub fn center(&self, cell_ix: usize) -> Vec2 {
+        self.centers[cell_ix]
+    }
+
+    pub fn neighbours(&self, cell_ix: usize) -> [usize; 4] {
+        self.neighbours[cell_ix]
+    }
+}
+
+// the columns (or rows) from the one `from` falls in to the one `to` does,
+// both already scaled to cell units, wrapped back onto the grid. a span wider
+// than the grid only has each one once
+pub fn wrapped_span(from: f32, to: f32, cells: usize) -> impl Iterator<Item = usize> {
+    let (first, last) = (from.floor() as i64, to.floor() as i64);
+    let last = last.min(first + cells as i64 - 1);
+    (first..=last).map(move |i| i.rem_euclid(cells as i64) as usize)
+}
+
+// which column (or row) a coordinate already scaled to cell units falls in.
+// positions off the grid (which wrapping should never produce, short of NaNs)
+// are clamped onto it and flagged.
+fn grid_coord(scaled: f32, cells: usize) -> (usize, bool) {
+    let c = scaled.floor();
+    if !(c >= 0.) {
+        (0, true)
+    } else if c >= cells as f32 {
+        (cells - 1, true)
+    } else {
+        (c as usize, false)
+    }
+}
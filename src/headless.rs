@@ -0,0 +1,138 @@
+// `--headless [steps]`: runs the game's own world (the config, the level, the
+// particle count it starts with) for a number of sim steps (600 by default)
+// with no window, then prints how long it took, the time per system, and
+// checksums of where everything ended up: the particles, the grid's flow, and
+// the boat and score. `--seed N` picks the random seed (0 otherwise).
+//
+// for CI: the timings catch a slowdown, and the checksums a change in what
+// the sim does, since the same build, seed and steps give the same numbers,
+// with the parallel feature or without (the systems that draw random numbers
+// take turns, see Randomness, and the parallel grid sums come out the same
+// every time).
+// unlike --bench (see bench.rs) it's the one world as it comes, not a sweep
+// over particle counts.
+
+use macroquad::miniquad::date;
+use macroquad::rand;
+use shipyard::{IntoIter, UniqueView, View};
+
+use crate::demo::path_after;
+use crate::game_state::Phase;
+use crate::grid::GridGeometry;
+use crate::profiling;
+use crate::score::Score;
+use crate::simulation::{ParticleLayout, Simulation};
+use crate::{Boat, Cells, Particle};
+
+const DEFAULT_STEPS: usize = 600;
+
+// FNV-1a, over the bits of the numbers, so any change at all shows
+struct Checksum(u64);
+
+impl Checksum {
+    fn new() -> Checksum {
+        Checksum(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn add_u64(&mut self, value: u64) {
+        for byte in value.to_le_bytes().iter() {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn add(&mut self, value: f32) {
+        self.add_u64(value.to_bits() as u64);
+    }
+}
+
+struct Checksums {
+    particles: usize,
+    particle_sum: u64,
+    grid_sum: u64,
+    boat_sum: u64,
+}
+
+fn checksums(sim: &Simulation) -> Checksums {
+    sim.world.run(|particles: View<Particle>,
+                   map: UniqueView<Cells>,
+                   grid: UniqueView<GridGeometry>,
+                   boats: View<Boat>,
+                   scores: View<Score>| {
+        let mut particle_sum = Checksum::new();
+        let mut count = 0;
+        for particle in particles.iter() {
+            particle_sum.add(particle.position.x as f32);
+            particle_sum.add(particle.position.y as f32);
+            particle_sum.add(particle.velocity.x);
+            particle_sum.add(particle.velocity.y);
+            count += 1;
+        }
+        let mut grid_sum = Checksum::new();
+        for cell_ix in 0..grid.len() {
+            let cell = map.all_cells.get(cell_ix);
+            grid_sum.add(cell.flow_v.x);
+            grid_sum.add(cell.flow_v.y);
+            grid_sum.add_u64(cell.is_solid() as u64);
+        }
+        let mut boat_sum = Checksum::new();
+        for boat in boats.iter() {
+            boat_sum.add(boat.loc.x as f32);
+            boat_sum.add(boat.loc.y as f32);
+            boat_sum.add(boat.vel.x);
+            boat_sum.add(boat.vel.y);
+            boat_sum.add(boat.health);
+        }
+        for score in scores.iter() {
+            boat_sum.add_u64(score.points as u64);
+        }
+        Checksums { particles: count, particle_sum: particle_sum.0, grid_sum: grid_sum.0, boat_sum: boat_sum.0 }
+    })
+}
+
+pub fn run(layout: ParticleLayout) {
+    let steps = path_after("--headless").and_then(|n| n.parse().ok()).unwrap_or(DEFAULT_STEPS);
+    let seed = path_after("--seed").and_then(|n| n.parse().ok()).unwrap_or(0);
+    rand::srand(seed);
+    let sim = Simulation::new(layout);
+    println!("running {} steps headless, {:?} layout, seed {}", steps, layout, seed);
+    profiling::take_samples();
+
+    let start = date::now();
+    let mut ran = 0;
+    for _ in 0..steps {
+        if let Err(err) = sim.step_headless() {
+            println!("the sim stopped after {} steps: {:?}", ran, err);
+            break;
+        }
+        ran += 1;
+        if let Phase::Over { score } = sim.phase() {
+            println!("the game ended after {} steps, scoring {}", ran, score);
+            break;
+        }
+    }
+    let elapsed = date::now() - start;
+
+    let mut systems: Vec<(&'static str, f64)> = vec![];
+    for (name, seconds) in profiling::take_samples() {
+        match systems.iter_mut().find(|(n, _)| *n == name) {
+            Some((_, total)) => *total += seconds,
+            None => systems.push((name, seconds)),
+        }
+    }
+    systems.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    let per_step = |seconds: f64| seconds / ran.max(1) as f64 * 1000.;
+
+    println!("\n{:<28}{:>12}", "ms per step", "");
+    for (name, seconds) in systems.iter() {
+        println!("{:<28}{:>12.3}", name, per_step(*seconds));
+    }
+    println!("{:<28}{:>12.3}", "total", per_step(elapsed));
+    println!("{:<28}{:>12.1}", "steps/sec", ran as f64 / elapsed.max(1e-9));
+
+    let sums = checksums(&sim);
+    println!("\nafter {} steps: {} particles", ran, sums.particles);
+    println!("particles checksum  {:016x}", sums.particle_sum);
+    println!("grid checksum       {:016x}", sums.grid_sum);
+    println!("boat/score checksum {:016x}", sums.boat_sum);
+}
@@ -0,0 +1,103 @@
+// the text along the top: each player's score and boat's health (the first
+// player's in the top left corner, the second's in the top right) and the
+// frame rate (and particle count) all the time, and under them the debug mode
+// overlay
+
+use macroquad::prelude::*;
+use shipyard::{IntoIter, IntoWithId, UniqueView, UniqueViewMut, View};
+
+use crate::accessibility::Accessibility;
+use crate::boat::PlayerId;
+use crate::diagnostics::Diagnostics;
+use crate::governor::Governor;
+use crate::memory::{format_bytes, MemoryStats};
+use crate::pickups::Boost;
+use crate::profiling;
+use crate::score::Score;
+use crate::window::ScreenInfo;
+use crate::{Boat, GameMode, GameModeInfo, Particle, Screen};
+
+// how far down the debug overlay starts, under the rest
+const HUD_HEIGHT: f32 = 60.;
+const HEALTH_BAR_WIDTH: f32 = 100.;
+
+pub fn draw_hud(_screen: UniqueViewMut<Screen>,
+                screen: UniqueView<ScreenInfo>,
+                scores: View<Score>,
+                ids: View<PlayerId>,
+                boats: View<Boat>,
+                boosts: View<Boost>,
+                particles: View<Particle>,
+                access: UniqueView<Accessibility>) {
+    let _t = profiling::scope("draw_hud");
+    let several = ids.iter().count() > 1;
+    for (id, (score, player)) in (&scores, &ids).iter().with_id() {
+        let score_line = if several {
+            format!("player {}: {}", player.0, score.points)
+        } else {
+            format!("score {}", score.points)
+        };
+        let width = measure_text(&score_line, None, 20, 1.).width;
+        let (text_x, x) = if player.is_first() {
+            (8., 8.)
+        } else {
+            (screen.width - width - 8., screen.width - HEALTH_BAR_WIDTH - 8.)
+        };
+        access.text_backing(text_x, 20., width, 20.);
+        draw_text(&score_line, text_x, 20., 20., WHITE);
+        // the bar goes from green through yellow to red as the health runs
+        // out, and stays empty once the boat's sunk
+        let health = boats.get(id).map_or(0., |boat| boat.health.max(0.).min(1.));
+        let color = if health > 0.5 { GREEN } else if health > 0.25 { YELLOW } else { RED };
+        draw_rectangle(x, 28., HEALTH_BAR_WIDTH, 8., DARKGRAY);
+        draw_rectangle(x, 28., HEALTH_BAR_WIDTH * health, 8., color);
+        draw_rectangle_lines(x, 28., HEALTH_BAR_WIDTH, 8., 1., WHITE);
+        // a boost (see pickups.rs) running down, just under it
+        if let Ok(boost) = boosts.get(id) {
+            if boost.ticks > 0 && health > 0. {
+                draw_rectangle(x, 38., HEALTH_BAR_WIDTH * boost.left(), 3., YELLOW);
+            }
+        }
+    }
+    let fps_line = format!("{} fps, {} particles", get_fps(), particles.iter().count());
+    access.text_backing(8., 52., measure_text(&fps_line, None, 16, 1.).width, 16.);
+    draw_text(&fps_line, 8., 52., 16., WHITE);
+}
+
+pub fn draw_debug_hud(_screen: UniqueViewMut<Screen>,
+                      game_mode: UniqueView<GameModeInfo>,
+                      diagnostics: UniqueView<Diagnostics>,
+                      governor: UniqueView<Governor>,
+                      memory: UniqueView<MemoryStats>,
+                      particles: View<Particle>,
+                      access: UniqueView<Accessibility>) {
+    let _t = profiling::scope("draw_debug_hud");
+    if game_mode.game_mode != GameMode::Debug {
+        return;
+    }
+    let governor_line = if governor.enabled {
+        format!("governor (G): on, target {} fps, frame {:.1} ms",
+                governor.target_fps, governor.smoothed_frame_time * 1000.)
+    } else {
+        "governor (G): off".to_owned()
+    };
+    let lines = [
+        format!("particles: {}", particles.iter().count()),
+        governor_line,
+        format!("out of range cell lookups: {} (total {})",
+                diagnostics.out_of_range_last_step, diagnostics.out_of_range_total),
+        format!("entities: {} particles ({} pooled ids), {} colliders, {}/{} effects",
+                memory.particles, memory.pooled_ids, memory.colliders, memory.effects, memory.effect_capacity),
+        format!("memory: particles {}, soa store {}, effects {}",
+                format_bytes(memory.particle_bytes), format_bytes(memory.particle_store_bytes),
+                format_bytes(memory.effect_bytes)),
+        format!("        grid {} ({}/{} chunks), render targets {}",
+                format_bytes(memory.grid_bytes), memory.grid_chunks, memory.grid_total_chunks,
+                format_bytes(memory.render_target_bytes)),
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        let y = HUD_HEIGHT + 16. + i as f32 * 14.;
+        access.text_backing(8., y, measure_text(line, None, 16, 1.).width, 14.);
+        draw_text(line, 8., y, 16., WHITE);
+    }
+}
@@ -0,0 +1,96 @@
+// setting the world up from a picture, so initial conditions can be painted
+// in any image editor. the image is stretched over the grid, each cell taking
+// the pixel under its center.
+//
+// `--init-image PATH` sets the starting flow: a pixel's hue is the direction
+// (red flows right, then round through green, cyan and blue the way the y
+// axis points, i.e. clockwise on screen) and its brightness the speed. grey,
+// black and white pixels, having no hue to speak of, are still water.
+//
+// `--mask PATH` takes the walls from a black-and-white image: dark pixels are
+// solid, light ones water (a transparent pixel counts as light).
+
+use std::fs;
+
+use macroquad::prelude::*;
+
+use crate::demo::path_after;
+use crate::grid::GridGeometry;
+use crate::{CellType, Cells};
+
+// flow speed of a fully bright pixel
+const MAX_IMAGE_SPEED: f32 = 2.;
+// less saturated than this and a pixel is grey, so still
+const MIN_SATURATION: f32 = 0.1;
+// darker than this and a mask pixel is a wall
+const MASK_THRESHOLD: f32 = 0.5;
+
+fn load(path: &str) -> Option<Image> {
+    match fs::read(path) {
+        Ok(bytes) => Some(Image::from_file_with_format(&bytes, None)),
+        Err(err) => {
+            warn!("couldn't read image {}: {}", path, err);
+            None
+        }
+    }
+}
+
+// the pixel under the center of each cell
+fn sample(image: &Image, grid: &GridGeometry, cell_ix: usize) -> Color {
+    let center = grid.center(cell_ix);
+    let x = (center.x / grid.width * image.width as f32) as u32;
+    let y = (center.y / grid.height * image.height as f32) as u32;
+    image.get_pixel(x.min(image.width as u32 - 1), y.min(image.height as u32 - 1))
+}
+
+// hue (0 to 1 round the colour wheel), saturation, value
+fn hsv(color: Color) -> (f32, f32, f32) {
+    let max = color.r.max(color.g).max(color.b);
+    let min = color.r.min(color.g).min(color.b);
+    let chroma = max - min;
+    let saturation = if max > 0. { chroma / max } else { 0. };
+    let hue = if chroma == 0. {
+        0.
+    } else if max == color.r {
+        ((color.g - color.b) / chroma).rem_euclid(6.) / 6.
+    } else if max == color.g {
+        ((color.b - color.r) / chroma + 2.) / 6.
+    } else {
+        ((color.r - color.g) / chroma + 4.) / 6.
+    };
+    (hue, saturation, max)
+}
+
+pub fn init_flow_from_args(map: &mut Cells, grid: &GridGeometry) {
+    let image = match path_after("--init-image").and_then(|path| load(&path)) {
+        Some(image) => image,
+        None => return,
+    };
+    for cell_ix in 0..grid.len() {
+        let (hue, saturation, value) = hsv(sample(&image, grid, cell_ix));
+        let cell = &mut map.all_cells[cell_ix];
+        cell.flow_v = if saturation < MIN_SATURATION {
+            Vec2::new(0., 0.)
+        } else {
+            let angle = hue * std::f32::consts::PI * 2.;
+            Vec2::new(angle.cos(), angle.sin()) * value * MAX_IMAGE_SPEED
+        };
+        cell.quiet_frames = 0;
+    }
+}
+
+pub fn mask_from_args(map: &mut Cells, grid: &GridGeometry) {
+    let image = match path_after("--mask").and_then(|path| load(&path)) {
+        Some(image) => image,
+        None => return,
+    };
+    for cell_ix in 0..grid.len() {
+        let pixel = sample(&image, grid, cell_ix);
+        let brightness = (pixel.r + pixel.g + pixel.b) / 3.;
+        if pixel.a > 0.5 && brightness < MASK_THRESHOLD {
+            let cell = &mut map.all_cells[cell_ix];
+            cell.kind = CellType::Solid;
+            cell.flow_v = Vec2::new(0., 0.);
+        }
+    }
+}
@@ -0,0 +1,252 @@
+// what each player is asking their boat to do this step, whatever it came
+// from. every player's boat has its own BoatControls. read_controls fills the
+// first player's in from the keyboard (through the key bindings) and the
+// gamepad (keyboard wins where both are in use), and the second player's (see
+// two_player.rs) from their keys; read_touch lays the touch controls over the
+// first player's, and the boat systems only ever look at these. the modifiers
+// (shift for fine control, ctrl to flip round) are applied to the first
+// player's here too, on top of either.
+//
+// there are two control schemes, picked in the [controls] section of the
+// settings file:
+//   tank      - (the default) left/right turn, up thrusts, space fires
+//   mouse_aim - the boat turns to face the cursor, the left button thrusts and
+//               the right button fires. this takes the mouse buttons over
+//               while the boat tool is picked; the other tools work as usual
+//               and leave the boat to the keyboard and gamepad
+//
+// besides the steady controls there are one-off commands, like the dash that
+// recognize_gestures sends the first player's boat when a turn key is
+// double-tapped.
+//
+// the keys that aren't for the boat (debug view, pausing and the sim speed,
+// and the governor and profiler toggles) are handle_key_presses'; esc and
+// restart go to the state machine (see game_state.rs).
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::boat::{Boat, PlayerId, TURN_RATE};
+use crate::camera::Camera;
+use crate::clock::{SimClock, FAST_FORWARD, HALF_SPEED};
+use crate::gamepad::GamepadState;
+use crate::governor::Governor;
+use crate::keymap::{Action, KeyBindings};
+use crate::profiling::{self, Profiler};
+use crate::tools::{Tool, ToolMode};
+use crate::{FrameTime, GameMode, GameModeInfo, Screen};
+
+// holding the fine control modifier (shift) scales the controls down to this
+const FINE_CONTROL_SCALE: f32 = 0.25;
+// a second tap of the same turn key within this many seconds is a dash, and
+// after a dash there's no other for a while
+const DOUBLE_TAP_SECONDS: f64 = 0.25;
+const DASH_COOLDOWN_SECONDS: f64 = 1.;
+
+// one-off things asked of the boat, kept until the next tick's used them (so
+// a frame that doesn't tick doesn't lose them)
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum BoatCommand {
+    // a sideways shove, -1 to port, 1 to starboard
+    Dash { side: f32 },
+    // half way round (ctrl while steering), -1 to port, 1 to starboard
+    QuickFlip { side: f32 },
+}
+
+// a player's, alongside their boat
+#[derive(Component, Default)]
+pub struct BoatControls {
+    // -1 (hard left) to 1 (hard right)
+    pub turn: f32,
+    // 0 to 1
+    pub thrust: f32,
+    pub fire: bool,
+    pub commands: Vec<BoatCommand>,
+}
+
+// watches the turn keys for a double tap
+#[derive(Component, Default)]
+pub struct DashGesture {
+    // when each turn key (left, right) was last tapped
+    last_tap: [Option<f64>; 2],
+    cooldown_until: f64,
+}
+
+#[derive(Component, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ControlScheme {
+    Tank,
+    MouseAim,
+}
+
+impl Default for ControlScheme {
+    fn default() -> ControlScheme {
+        ControlScheme::Tank
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ControlSettings {
+    pub scheme: ControlScheme,
+}
+
+impl ControlScheme {
+    // does the scheme have the mouse buttons right now? (the tools check this
+    // before doing anything with them)
+    pub fn has_mouse(self, tool: Tool) -> bool {
+        self == ControlScheme::MouseAim && tool == Tool::Boat
+    }
+
+    // (turn, thrust, fire) from the keyboard and gamepad, keyboard first (the
+    // pad's fire button comes through the bindings, like a key)
+    fn read_tank(keys: &KeyBindings, gamepad: &GamepadState) -> (f32, f32, bool) {
+        let key_turn = if keys.down(Action::TurnLeft) {
+            -1.
+        } else if keys.down(Action::TurnRight) {
+            1.
+        } else {
+            0.
+        };
+        let key_thrust = if keys.down(Action::Thrust) { 1. } else { 0. };
+        (if key_turn != 0. { key_turn } else { gamepad.turn },
+         if key_thrust != 0. { key_thrust } else { gamepad.thrust },
+         keys.down(Action::Fire))
+    }
+
+    // the second player's (turn, thrust, fire), off their own keys
+    fn read_second(keys: &KeyBindings) -> (f32, f32, bool) {
+        let turn = if keys.down(Action::SecondTurnLeft) {
+            -1.
+        } else if keys.down(Action::SecondTurnRight) {
+            1.
+        } else {
+            0.
+        };
+        (turn, if keys.down(Action::SecondThrust) { 1. } else { 0. }, keys.down(Action::SecondFire))
+    }
+
+    // turn towards `aim` (as much as a frame's turn allows), thrust and fire
+    // on the buttons
+    fn read_mouse_aim(player: &Boat, aim: Vec2) -> (f32, f32, bool) {
+        let to_aim = aim - Vec2::new(player.loc.x as f32, player.loc.y as f32);
+        let mut off_course = to_aim.y.atan2(to_aim.x) - player.heading();
+        // the short way round
+        while off_course > std::f32::consts::PI { off_course -= std::f32::consts::PI * 2.; }
+        while off_course < -std::f32::consts::PI { off_course += std::f32::consts::PI * 2.; }
+        let turn = (off_course / TURN_RATE).max(-1.).min(1.);
+        let thrust = if is_mouse_button_down(MouseButton::Left) { 1. } else { 0. };
+        (turn, thrust, is_mouse_button_down(MouseButton::Right))
+    }
+}
+
+pub fn read_controls(mut controls: ViewMut<BoatControls>,
+                     ids: View<PlayerId>,
+                     boats: View<Boat>,
+                     gamepad: UniqueView<GamepadState>,
+                     keys: UniqueView<KeyBindings>,
+                     scheme: UniqueView<ControlScheme>,
+                     tool_mode: UniqueView<ToolMode>,
+                     camera: UniqueView<Camera>,
+                     _screen: UniqueView<Screen>) {
+    for (controls, id, player) in (&mut controls, &ids, &boats).iter() {
+        let (turn, thrust, fire) = if !id.is_first() {
+            ControlScheme::read_second(&keys)
+        } else if scheme.has_mouse(tool_mode.tool) && camera.tools_have_mouse() {
+            ControlScheme::read_mouse_aim(player, camera.mouse_world())
+        } else {
+            ControlScheme::read_tank(&keys, &gamepad)
+        };
+        controls.turn = turn;
+        controls.thrust = thrust;
+        controls.fire = fire;
+        if !id.is_first() {
+            continue;
+        }
+
+        // the modifiers work the same on whatever the controls came from
+        if keys.down(Action::FineControl) {
+            controls.turn *= FINE_CONTROL_SCALE;
+            controls.thrust *= FINE_CONTROL_SCALE;
+        }
+        if keys.pressed(Action::QuickFlip) && controls.turn != 0. {
+            controls.commands.push(BoatCommand::QuickFlip { side: controls.turn.signum() });
+        }
+    }
+}
+
+// the first player's turn keys
+pub fn recognize_gestures(mut gesture: UniqueViewMut<DashGesture>,
+                          mut controls: ViewMut<BoatControls>,
+                          ids: View<PlayerId>,
+                          keys: UniqueView<KeyBindings>,
+                          _screen: UniqueView<Screen>) {
+    let now = get_time();
+    let controls = match (&mut controls, &ids).iter().find(|(_, id)| id.is_first()) {
+        Some((controls, _)) => controls,
+        None => return,
+    };
+    for (i, (action, side)) in [(Action::TurnLeft, -1.), (Action::TurnRight, 1.)].iter().enumerate() {
+        if !keys.pressed(*action) {
+            continue;
+        }
+        let double = gesture.last_tap[i].map_or(false, |last| now - last < DOUBLE_TAP_SECONDS);
+        if double && now >= gesture.cooldown_until {
+            controls.commands.push(BoatCommand::Dash { side: *side });
+            gesture.cooldown_until = now + DASH_COOLDOWN_SECONDS;
+            gesture.last_tap[i] = None;
+        } else {
+            gesture.last_tap[i] = Some(now);
+        }
+    }
+}
+
+// handle key presses for game mode changes
+// (reads macroquad's input state, so it borrows the screen like steer_player)
+pub fn handle_key_presses(mut game_mode: UniqueViewMut<GameModeInfo>,
+                          mut clock: UniqueViewMut<SimClock>,
+                          mut governor:UniqueViewMut<Governor>,
+                          mut profiler:UniqueViewMut<Profiler>,
+                          keys: UniqueView<KeyBindings>,
+                          _screen: UniqueView<Screen>,)
+{
+    let _t = profiling::scope("handle_key_presses");
+    if keys.pressed(Action::Debug) {
+        if game_mode.game_mode == GameMode::Debug{
+            game_mode.game_mode = GameMode::Default
+        }else{
+            game_mode.game_mode = GameMode::Debug
+        }
+    }
+    if keys.pressed(Action::Pause) {
+        clock.toggle_pause();
+    }
+    if keys.pressed(Action::SingleStep) {
+        clock.step_or_faster();
+    }
+    if keys.pressed(Action::SlowDown) {
+        clock.slower();
+    }
+    if keys.pressed(Action::HalfSpeed) {
+        clock.set_speed(HALF_SPEED);
+    }
+    if keys.pressed(Action::NormalSpeed) {
+        clock.set_speed(1.);
+    }
+    if keys.pressed(Action::FastForward) {
+        clock.set_speed(FAST_FORWARD);
+    }
+    if keys.pressed(Action::Governor) {
+        governor.enabled = !governor.enabled;
+    }
+    if keys.pressed(Action::Profiler) {
+        profiler.enabled = !profiler.enabled;
+    }
+}
+
+// how long the last frame took (reads macroquad's context, so it stays off the
+// screen's toes like the drawing systems do)
+pub fn measure_frame_time(mut frame_time: UniqueViewMut<FrameTime>, _screen: UniqueView<Screen>) {
+    frame_time.0 = get_frame_time();
+}
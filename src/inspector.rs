@@ -0,0 +1,151 @@
+// the inspect tool: click on something to see what it's up to. clicking near
+// a player's boat picks the boat, near a particle picks the closest particle, and
+// anywhere else picks the cell under the cursor. the pick is outlined in the
+// world and its fields are shown (live, every frame) in a panel in the top
+// right until something else is picked, or it goes away.
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View};
+
+use crate::camera::Camera;
+use crate::collision::BOAT_RADIUS;
+use crate::geometry::wrapped_dist;
+use crate::grid::GridGeometry;
+use crate::tools::{Tool, ToolMode};
+use crate::window::ScreenInfo;
+use crate::{Boat, Cells, Particle, Screen};
+
+// how close a click has to be to a particle to pick it
+const PICK_RADIUS: f32 = 6.;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Selection {
+    Boat(EntityId),
+    Particle(EntityId),
+    Cell(usize),
+}
+
+#[derive(Component, Default)]
+pub struct Inspector {
+    pub selection: Option<Selection>,
+}
+
+pub fn pick_entity(mut inspector: UniqueViewMut<Inspector>,
+                   tool_mode: UniqueView<ToolMode>,
+                   camera: UniqueView<Camera>,
+                   grid: UniqueView<GridGeometry>,
+                   boats: View<Boat>,
+                   particles: View<Particle>,
+                   _screen: UniqueView<Screen>) {
+    if tool_mode.tool != Tool::Inspect
+        || !is_mouse_button_pressed(MouseButton::Left)
+        || !camera.tools_have_mouse() {
+        return;
+    }
+    let mouse = camera.mouse_world();
+    let boat = boats.iter().with_id()
+        .find(|(_, boat)| (mouse - Vec2::new(boat.loc.x as f32, boat.loc.y as f32)).length() < BOAT_RADIUS);
+    if let Some((id, _)) = boat {
+        inspector.selection = Some(Selection::Boat(id));
+        return;
+    }
+    // only the particles in the cells around the click are worth a look
+    let cell_reach = Vec2::new(grid.cell_width, grid.cell_height).length() / 2.;
+    let mut nearby = vec![false; grid.len()];
+    for cell_ix in grid.cells_within(mouse, PICK_RADIUS + cell_reach) {
+        nearby[cell_ix] = true;
+    }
+    let closest = particles.iter().with_id()
+        .filter(|(_, particle)| nearby[particle.cell_ix])
+        .map(|(id, particle)| {
+            (id, wrapped_dist(Vec2::new(particle.position.x as f32, particle.position.y as f32), mouse, grid.size()))
+        })
+        .filter(|(_, distance)| *distance < PICK_RADIUS)
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+    inspector.selection = match closest {
+        Some((id, _)) => Some(Selection::Particle(id)),
+        None if mouse.x >= 0. && mouse.x < grid.width && mouse.y >= 0. && mouse.y < grid.height => {
+            Some(Selection::Cell(grid.index_at(mouse.x, mouse.y)))
+        }
+        None => None,
+    };
+}
+
+// drawn in screen pixels, after the camera's done
+pub fn draw_inspector(_screen: UniqueViewMut<Screen>,
+                      screen: UniqueView<ScreenInfo>,
+                      mut inspector: UniqueViewMut<Inspector>,
+                      camera: UniqueView<Camera>,
+                      grid: UniqueView<GridGeometry>,
+                      map: UniqueView<Cells>,
+                      boats: View<Boat>,
+                      particles: View<Particle>) {
+    let selection = match inspector.selection {
+        // the grid's been made coarser since (see refine.rs)
+        Some(Selection::Cell(cell_ix)) if cell_ix >= grid.len() => {
+            inspector.selection = None;
+            return;
+        }
+        Some(selection) => selection,
+        None => return,
+    };
+    let lines: Vec<String> = match selection {
+        Selection::Boat(id) => {
+            let player = match boats.get(id) {
+                Ok(player) => player,
+                // it's sunk
+                Err(_) => {
+                    inspector.selection = None;
+                    return;
+                }
+            };
+            let at = camera.world_to_screen(Vec2::new(player.loc.x as f32, player.loc.y as f32));
+            draw_circle_lines(at.x, at.y, BOAT_RADIUS * camera.pixels_per_unit(), 1., YELLOW);
+            vec![
+                "boat".to_owned(),
+                format!("position {:.1}, {:.1}", player.loc.x, player.loc.y),
+                format!("velocity {:.2}, {:.2}", player.vel.x, player.vel.y),
+                format!("health {:.2}", player.health),
+            ]
+        }
+        Selection::Particle(id) => {
+            let particle = match particles.get(id) {
+                Ok(particle) => particle,
+                // it's been despawned
+                Err(_) => {
+                    inspector.selection = None;
+                    return;
+                }
+            };
+            let at = camera.world_to_screen(Vec2::new(particle.position.x as f32, particle.position.y as f32));
+            draw_circle_lines(at.x, at.y, 4. * camera.pixels_per_unit().max(1.), 1., YELLOW);
+            vec![
+                format!("particle {:?}", id),
+                format!("position {:.1}, {:.1}", particle.position.x, particle.position.y),
+                format!("velocity {:.2}, {:.2}", particle.velocity.x, particle.velocity.y),
+                format!("cell {}", particle.cell_ix),
+            ]
+        }
+        Selection::Cell(cell_ix) => {
+            let center = grid.center(cell_ix);
+            let corner = camera.world_to_screen(center - Vec2::new(grid.cell_width, grid.cell_height) / 2.);
+            draw_rectangle_lines(corner.x, corner.y, grid.cell_width * camera.pixels_per_unit(), grid.cell_height * camera.pixels_per_unit(),
+                                 1., YELLOW);
+            let cell = map.all_cells.get(cell_ix);
+            vec![
+                format!("cell {} ({}, {})", cell_ix, cell_ix % grid.cols, cell_ix / grid.cols),
+                format!("kind {:?}", cell.kind),
+                format!("flow_v {:.3}, {:.3}", cell.flow_v.x, cell.flow_v.y),
+                format!("particles {}", cell.particle_count),
+                format!("quiet for {} steps", cell.quiet_frames),
+            ]
+        }
+    };
+
+    let (width, row) = (200., 14.);
+    let left = screen.width - width - 8.;
+    draw_rectangle(left, 8., width, row * lines.len() as f32 + 8., Color::new(0., 0., 0., 0.7));
+    for (i, line) in lines.iter().enumerate() {
+        draw_text(line, left + 6., 8. + row * (i + 1) as f32, 16., WHITE);
+    }
+}
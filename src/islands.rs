@@ -0,0 +1,116 @@
+// the solid cells drawn as land instead of grey squares: each cell is a
+// sandy blob with grass on top (overlapping, so a clump of cells reads as one
+// island), rocks scattered round the shores and little l-system trees (see
+// lsystem.rs) on the cells with land all round them. shallow water (see
+// terrain.rs) is drawn paler, the shallower it is.
+//
+// where the rocks and trees go, and how they lean, comes from `--island-seed
+// N` (1 otherwise) and the cell, not from the game's random numbers, so the
+// same level always looks the same. it's worked out again whenever the solid
+// cells change (painting walls, loading a level).
+
+use std::collections::BTreeMap;
+
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::audio::Noise;
+use crate::batch::MeshBatch;
+use crate::demo::path_after;
+use crate::grid::GridGeometry;
+use crate::lsystem::LSystem;
+use crate::profiling;
+use crate::{Cells, Screen};
+
+const SAND: Color = Color { r: 0.76, g: 0.7, b: 0.5, a: 1. };
+const GRASS: Color = Color { r: 0.3, g: 0.5, b: 0.25, a: 1. };
+const ROCK: Color = Color { r: 0.45, g: 0.45, b: 0.48, a: 1. };
+const TRUNK: Color = Color { r: 0.35, g: 0.25, b: 0.15, a: 1. };
+const SHALLOWS: Color = Color { r: 0.55, g: 0.85, b: 0.9, a: 0.4 };
+// blob sizes, as a fraction of the cell
+const SAND_RADIUS: f32 = 0.75;
+const GRASS_RADIUS: f32 = 0.55;
+// chances a shore cell gets rocks, and an inland cell a tree
+const ROCK_CHANCE: f32 = 0.4;
+const TREE_CHANCE: f32 = 0.6;
+
+#[derive(Component)]
+pub struct Islands {
+    seed: u32,
+    // which cells were solid when the shapes were last worked out
+    solid: Vec<bool>,
+    circles: Vec<(Vec2, f32, Color)>,
+    lines: Vec<(Vec2, Vec2, Color)>,
+}
+
+impl Islands {
+    pub fn from_args() -> Islands {
+        let seed = path_after("--island-seed").and_then(|n| n.parse().ok()).unwrap_or(1);
+        Islands { seed, solid: vec![], circles: vec![], lines: vec![] }
+    }
+
+    fn build(&mut self, map: &Cells, grid: &GridGeometry) {
+        self.circles.clear();
+        self.lines.clear();
+        let size = grid.cell_width.min(grid.cell_height);
+        let tree = LSystem {
+            axiom: "X".to_owned(),
+            rules: [('X', "F[+X][-X]FX".to_owned()), ('F', "FF".to_owned())].iter().cloned().collect::<BTreeMap<_, _>>(),
+            iterations: 3,
+            angle: 25.,
+        };
+        for (cell_ix, cell) in map.all_cells.iter().enumerate() {
+            if !cell.is_solid() && cell.depth < 1. {
+                let color = Color { a: SHALLOWS.a * (1. - cell.depth), ..SHALLOWS };
+                self.circles.push((grid.center(cell_ix), size * SAND_RADIUS, color));
+            }
+        }
+        // the sand all goes down next, so the grass covers the seams
+        for (cell_ix, _) in self.solid.iter().enumerate().filter(|(_, solid)| **solid) {
+            self.circles.push((grid.center(cell_ix), size * SAND_RADIUS, SAND));
+        }
+        for (cell_ix, _) in self.solid.iter().enumerate().filter(|(_, solid)| **solid) {
+            let center = grid.center(cell_ix);
+            let mut noise = Noise(self.seed.wrapping_mul(2_654_435_761).wrapping_add(cell_ix as u32));
+            // warm it up, neighbouring cells start out much alike
+            for _ in 0..4 {
+                noise.next();
+            }
+            self.circles.push((center, size * GRASS_RADIUS, GRASS));
+            let inland = grid.neighbours(cell_ix).iter().all(|n| self.solid[*n]);
+            if inland && (noise.next() + 1.) / 2. < TREE_CHANCE {
+                let lean = noise.next() * 15.;
+                let base = center + Vec2::new(noise.next(), noise.next()) * size * 0.2;
+                for (from, to) in tree.lines(base, -90. + lean, size / 24.) {
+                    self.lines.push((from, to, TRUNK));
+                }
+            } else if !inland && (noise.next() + 1.) / 2. < ROCK_CHANCE {
+                for _ in 0..3 {
+                    let at = center + Vec2::new(noise.next(), noise.next()) * size * 0.5;
+                    self.circles.push((at, size * (0.08 + 0.05 * noise.next().abs()), ROCK));
+                }
+            }
+        }
+    }
+}
+
+// world space, before the particles and the boat
+pub fn draw_islands(mut islands: UniqueViewMut<Islands>,
+                    map: UniqueView<Cells>,
+                    grid: UniqueView<GridGeometry>,
+                    _screen: UniqueViewMut<Screen>) {
+    let _t = profiling::scope("draw_islands");
+    let solid: Vec<bool> = map.all_cells.iter().map(|cell| cell.is_solid()).collect();
+    if solid != islands.solid {
+        islands.solid = solid;
+        islands.build(&map, &grid);
+    }
+    let mut batch = MeshBatch::new();
+    for (center, radius, color) in islands.circles.iter() {
+        batch.circle(center.x, center.y, *radius, *color);
+    }
+    for (from, to, color) in islands.lines.iter() {
+        batch.line(from.x, from.y, to.x, to.y, 1., *color);
+    }
+    batch.draw();
+}
@@ -0,0 +1,118 @@
+// inner loops of the struct-of-arrays particle path
+//
+// with the `simd` feature these run 8 particles at a time on f32x8 lanes (from
+// the `wide` crate); the tail that doesn't fill a whole vector, and builds
+// without the feature, go through the plain scalar versions. positions are
+// f64 with the `f64` feature, and the position kernels stay scalar then.
+
+#[cfg(feature = "simd")]
+use std::convert::TryInto;
+#[cfg(feature = "simd")]
+use wide::f32x8;
+
+use crate::grid::GridGeometry;
+use crate::{lerp, wrap_coord, Real};
+#[cfg(all(feature = "simd", not(feature = "f64")))]
+use crate::diagnostics;
+
+#[cfg(feature = "simd")]
+const LANES: usize = 8;
+
+// how many particles each rayon task takes at once (a multiple of the lane count)
+#[cfg(feature = "parallel")]
+pub const CHUNK: usize = 4096;
+
+#[cfg(feature = "simd")]
+fn load(s: &[f32]) -> f32x8 {
+    f32x8::new(s.try_into().unwrap())
+}
+
+// pos += vel, wrapped onto [0, size)
+pub fn advect(pos: &mut [Real], vel: &[f32], size: Real) {
+    #[cfg(all(feature = "simd", not(feature = "f64")))]
+    {
+        let n = pos.len() / LANES * LANES;
+        let size_v = f32x8::splat(size);
+        let inv_size = f32x8::splat(1. / size);
+        for (p, v) in pos[..n].chunks_exact_mut(LANES).zip(vel[..n].chunks_exact(LANES)) {
+            let moved = load(p) + load(v);
+            let wrapped = moved - (moved * inv_size).floor() * size_v;
+            // rounding can land a tiny negative exactly on `size`
+            let wrapped = wrapped.cmp_ge(size_v).blend(wrapped - size_v, wrapped);
+            p.copy_from_slice(&wrapped.to_array());
+        }
+        advect_scalar(&mut pos[n..], &vel[n..], size);
+    }
+    #[cfg(not(all(feature = "simd", not(feature = "f64"))))]
+    advect_scalar(pos, vel, size);
+}
+
+fn advect_scalar(pos: &mut [Real], vel: &[f32], size: Real) {
+    for (p, v) in pos.iter_mut().zip(vel.iter()) {
+        *p = wrap_coord(*p + *v as Real, size);
+    }
+}
+
+// vel = lerp(vel, target, fraction), lane by lane
+pub fn lerp_toward(vel: &mut [f32], target: &[f32], fraction: f32) {
+    #[cfg(feature = "simd")]
+    {
+        let n = vel.len() / LANES * LANES;
+        let fraction_v = f32x8::splat(fraction);
+        for (v, t) in vel[..n].chunks_exact_mut(LANES).zip(target[..n].chunks_exact(LANES)) {
+            let start = load(v);
+            let lerped = start + (load(t) - start) * fraction_v;
+            v.copy_from_slice(&lerped.to_array());
+        }
+        lerp_toward_scalar(&mut vel[n..], &target[n..], fraction);
+    }
+    #[cfg(not(feature = "simd"))]
+    lerp_toward_scalar(vel, target, fraction);
+}
+
+fn lerp_toward_scalar(vel: &mut [f32], target: &[f32], fraction: f32) {
+    for (v, t) in vel.iter_mut().zip(target.iter()) {
+        *v = lerp(*v, *t, fraction);
+    }
+}
+
+// the cell each position falls in, same mapping as GridGeometry::index_at
+pub fn cell_indices(grid: &GridGeometry, x: &[Real], y: &[Real], out: &mut [usize]) {
+    #[cfg(all(feature = "simd", not(feature = "f64")))]
+    {
+        let n = x.len() / LANES * LANES;
+        let scale_x = f32x8::splat(grid.inv_cell_width);
+        let scale_y = f32x8::splat(grid.inv_cell_height);
+        let zero = f32x8::splat(0.);
+        let max_col = f32x8::splat((grid.cols - 1) as f32);
+        let max_row = f32x8::splat((grid.rows - 1) as f32);
+        let cells_x = f32x8::splat(grid.cols as f32);
+        let mut out_of_range = 0;
+        for ((xs, ys), o) in x[..n].chunks_exact(LANES)
+            .zip(y[..n].chunks_exact(LANES))
+            .zip(out[..n].chunks_exact_mut(LANES))
+        {
+            let col = (load(xs) * scale_x).floor();
+            let row = (load(ys) * scale_y).floor();
+            let clamped_col = col.max(zero).min(max_col);
+            let clamped_row = row.max(zero).min(max_row);
+            out_of_range += (col.cmp_ne(clamped_col) | row.cmp_ne(clamped_row)).move_mask().count_ones();
+            let cell = clamped_row * cells_x + clamped_col;
+            for (o, c) in o.iter_mut().zip(cell.to_array().iter()) {
+                *o = *c as usize;
+            }
+        }
+        if out_of_range > 0 {
+            diagnostics::count_out_of_range(out_of_range as usize);
+        }
+        cell_indices_scalar(grid, &x[n..], &y[n..], &mut out[n..]);
+    }
+    #[cfg(not(all(feature = "simd", not(feature = "f64"))))]
+    cell_indices_scalar(grid, x, y, out);
+}
+
+fn cell_indices_scalar(grid: &GridGeometry, x: &[Real], y: &[Real], out: &mut [usize]) {
+    for ((x, y), o) in x.iter().zip(y.iter()).zip(out.iter_mut()) {
+        *o = grid.index_at(*x as f32, *y as f32);
+    }
+}
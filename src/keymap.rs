@@ -0,0 +1,471 @@
+// key bindings: which key does what. everything that used to check a KeyCode
+// directly asks the KeyBindings unique about an Action instead, and the
+// bindings come from (and are saved back to) the settings file.
+//
+// F1 opens the rebinding screen: up/down to pick an action, enter and then the
+// new key to rebind it, esc (or F1 again) to close it and save. F1 itself
+// can't be rebound, so there's always a way back in.
+//
+// an action can have a gamepad button as well as a key (see gamepad.rs), and
+// either one does it. the buttons are shown on the rebinding screen but only
+// changed in the settings file's [pad] section (a button name, or "" for
+// none).
+
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::gamepad::{GamepadState, PadButton};
+use crate::settings::{Settings, SETTINGS_PATH};
+use crate::Screen;
+use crate::window::ScreenInfo;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    TurnLeft,
+    TurnRight,
+    Thrust,
+    Fire,
+    FineControl,
+    QuickFlip,
+    SecondTurnLeft,
+    SecondTurnRight,
+    SecondThrust,
+    SecondFire,
+    Debug,
+    Pause,
+    SingleStep,
+    SlowDown,
+    HalfSpeed,
+    NormalSpeed,
+    FastForward,
+    Governor,
+    Profiler,
+    Pacing,
+    NextTool,
+    Tuning,
+    SpawnBurst,
+    DoubleParticles,
+    HalveParticles,
+    AddParticles,
+    RemoveParticles,
+    ClearParticles,
+    FieldView,
+    ParticleStyle,
+    Colormap,
+    Trails,
+    SpawnDrifter,
+    SaveState,
+    LoadState,
+    ScrubBack,
+    ScrubForward,
+    Export,
+    RecordVideo,
+    Screenshot,
+    LessViscosity,
+    MoreViscosity,
+    LessVorticity,
+    MoreVorticity,
+    PlaceField,
+    FieldKind,
+    FinerGrid,
+    CoarserGrid,
+    Mute,
+    Restart,
+    Exit,
+}
+
+const ACTIONS: [Action; 51] = [
+    Action::TurnLeft,
+    Action::TurnRight,
+    Action::Thrust,
+    Action::Fire,
+    Action::FineControl,
+    Action::QuickFlip,
+    Action::SecondTurnLeft,
+    Action::SecondTurnRight,
+    Action::SecondThrust,
+    Action::SecondFire,
+    Action::Debug,
+    Action::Pause,
+    Action::SingleStep,
+    Action::SlowDown,
+    Action::HalfSpeed,
+    Action::NormalSpeed,
+    Action::FastForward,
+    Action::Governor,
+    Action::Profiler,
+    Action::Pacing,
+    Action::NextTool,
+    Action::Tuning,
+    Action::SpawnBurst,
+    Action::DoubleParticles,
+    Action::HalveParticles,
+    Action::AddParticles,
+    Action::RemoveParticles,
+    Action::ClearParticles,
+    Action::FieldView,
+    Action::ParticleStyle,
+    Action::Colormap,
+    Action::Trails,
+    Action::SpawnDrifter,
+    Action::SaveState,
+    Action::LoadState,
+    Action::ScrubBack,
+    Action::ScrubForward,
+    Action::Export,
+    Action::RecordVideo,
+    Action::Screenshot,
+    Action::LessViscosity,
+    Action::MoreViscosity,
+    Action::LessVorticity,
+    Action::MoreVorticity,
+    Action::PlaceField,
+    Action::FieldKind,
+    Action::FinerGrid,
+    Action::CoarserGrid,
+    Action::Mute,
+    Action::Restart,
+    Action::Exit,
+];
+
+const REBIND_KEY: KeyCode = KeyCode::F1;
+
+impl Action {
+    // what it's called in the settings file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::TurnLeft => "turn_left",
+            Action::TurnRight => "turn_right",
+            Action::Thrust => "thrust",
+            Action::Fire => "fire",
+            Action::FineControl => "fine_control",
+            Action::QuickFlip => "quick_flip",
+            Action::SecondTurnLeft => "second_turn_left",
+            Action::SecondTurnRight => "second_turn_right",
+            Action::SecondThrust => "second_thrust",
+            Action::SecondFire => "second_fire",
+            Action::Debug => "debug",
+            Action::Pause => "pause",
+            Action::SingleStep => "single_step",
+            Action::SlowDown => "slow_down",
+            Action::HalfSpeed => "half_speed",
+            Action::NormalSpeed => "normal_speed",
+            Action::FastForward => "fast_forward",
+            Action::Governor => "governor",
+            Action::Profiler => "profiler",
+            Action::Pacing => "pacing",
+            Action::NextTool => "next_tool",
+            Action::Tuning => "tuning",
+            Action::SpawnBurst => "spawn_burst",
+            Action::DoubleParticles => "double_particles",
+            Action::HalveParticles => "halve_particles",
+            Action::AddParticles => "add_particles",
+            Action::RemoveParticles => "remove_particles",
+            Action::ClearParticles => "clear_particles",
+            Action::FieldView => "field_view",
+            Action::ParticleStyle => "particle_style",
+            Action::Colormap => "colormap",
+            Action::Trails => "trails",
+            Action::SpawnDrifter => "spawn_drifter",
+            Action::SaveState => "save_state",
+            Action::LoadState => "load_state",
+            Action::ScrubBack => "scrub_back",
+            Action::ScrubForward => "scrub_forward",
+            Action::Export => "export",
+            Action::RecordVideo => "record_video",
+            Action::Screenshot => "screenshot",
+            Action::LessViscosity => "less_viscosity",
+            Action::MoreViscosity => "more_viscosity",
+            Action::LessVorticity => "less_vorticity",
+            Action::MoreVorticity => "more_vorticity",
+            Action::PlaceField => "place_field",
+            Action::FieldKind => "field_kind",
+            Action::FinerGrid => "finer_grid",
+            Action::CoarserGrid => "coarser_grid",
+            Action::Mute => "mute",
+            Action::Restart => "restart",
+            Action::Exit => "exit",
+        }
+    }
+
+    fn default_key(&self) -> KeyCode {
+        match self {
+            Action::TurnLeft => KeyCode::Left,
+            Action::TurnRight => KeyCode::Right,
+            Action::Thrust => KeyCode::Up,
+            Action::Fire => KeyCode::Space,
+            Action::FineControl => KeyCode::LeftShift,
+            Action::QuickFlip => KeyCode::LeftControl,
+            // the second player's, in two player mode (see two_player.rs)
+            Action::SecondTurnLeft => KeyCode::A,
+            Action::SecondTurnRight => KeyCode::E,
+            Action::SecondThrust => KeyCode::W,
+            Action::SecondFire => KeyCode::Q,
+            Action::Debug => KeyCode::D,
+            Action::Pause => KeyCode::P,
+            Action::SingleStep => KeyCode::Period,
+            Action::SlowDown => KeyCode::Comma,
+            Action::HalfSpeed => KeyCode::Key1,
+            Action::NormalSpeed => KeyCode::Key2,
+            Action::FastForward => KeyCode::Key3,
+            Action::Governor => KeyCode::G,
+            Action::Profiler => KeyCode::F3,
+            // vsync, capped or uncapped (see pacing.rs)
+            Action::Pacing => KeyCode::F4,
+            Action::NextTool => KeyCode::T,
+            Action::Tuning => KeyCode::F2,
+            Action::SpawnBurst => KeyCode::S,
+            Action::DoubleParticles => KeyCode::Equal,
+            Action::HalveParticles => KeyCode::Minus,
+            Action::AddParticles => KeyCode::Key0,
+            Action::RemoveParticles => KeyCode::Key9,
+            Action::ClearParticles => KeyCode::C,
+            Action::FieldView => KeyCode::V,
+            Action::ParticleStyle => KeyCode::B,
+            Action::Colormap => KeyCode::M,
+            Action::Trails => KeyCode::N,
+            Action::SpawnDrifter => KeyCode::F6,
+            Action::SaveState => KeyCode::F5,
+            Action::LoadState => KeyCode::F9,
+            Action::ScrubBack => KeyCode::LeftBracket,
+            Action::ScrubForward => KeyCode::RightBracket,
+            Action::Export => KeyCode::F8,
+            Action::RecordVideo => KeyCode::F7,
+            Action::Screenshot => KeyCode::F12,
+            Action::LessViscosity => KeyCode::Y,
+            Action::MoreViscosity => KeyCode::U,
+            Action::LessVorticity => KeyCode::H,
+            Action::MoreVorticity => KeyCode::J,
+            Action::PlaceField => KeyCode::K,
+            Action::FieldKind => KeyCode::L,
+            // the grid's resolution, in the debug view (see refine.rs)
+            Action::FinerGrid => KeyCode::X,
+            Action::CoarserGrid => KeyCode::Z,
+            Action::Mute => KeyCode::O,
+            Action::Restart => KeyCode::R,
+            Action::Exit => KeyCode::Escape,
+        }
+    }
+
+    fn default_button(&self) -> Option<PadButton> {
+        match self {
+            Action::Fire => Some(PadButton::South),
+            Action::QuickFlip => Some(PadButton::East),
+            Action::FineControl => Some(PadButton::LeftBumper),
+            Action::Pause => Some(PadButton::Start),
+            Action::Debug => Some(PadButton::Select),
+            Action::FieldView => Some(PadButton::DPadUp),
+            Action::HalfSpeed => Some(PadButton::DPadLeft),
+            Action::NormalSpeed => Some(PadButton::DPadDown),
+            Action::FastForward => Some(PadButton::DPadRight),
+            _ => None,
+        }
+    }
+}
+
+// the keys that can be bound, which is also the list key names are parsed
+// against (a key's name is just its Debug name: "A", "Key1", "Left", "F3"...)
+const BINDABLE_KEYS: &[KeyCode] = &[
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+    KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+    KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+    KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Key0, KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4,
+    KeyCode::Key5, KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9,
+    KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5, KeyCode::F6, KeyCode::F7,
+    KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11, KeyCode::F12,
+    KeyCode::Left, KeyCode::Right, KeyCode::Up, KeyCode::Down,
+    KeyCode::Space, KeyCode::Enter, KeyCode::Escape, KeyCode::Tab, KeyCode::Backspace,
+    KeyCode::LeftShift, KeyCode::RightShift, KeyCode::LeftControl, KeyCode::RightControl,
+    KeyCode::LeftAlt, KeyCode::RightAlt,
+    KeyCode::Comma, KeyCode::Period, KeyCode::Slash, KeyCode::Semicolon,
+    KeyCode::LeftBracket, KeyCode::RightBracket, KeyCode::Minus, KeyCode::Equal,
+];
+
+// a modifier bound to one side counts on either side of the keyboard
+fn either_side(key: KeyCode) -> [KeyCode; 2] {
+    match key {
+        KeyCode::LeftShift | KeyCode::RightShift => [KeyCode::LeftShift, KeyCode::RightShift],
+        KeyCode::LeftControl | KeyCode::RightControl => [KeyCode::LeftControl, KeyCode::RightControl],
+        KeyCode::LeftAlt | KeyCode::RightAlt => [KeyCode::LeftAlt, KeyCode::RightAlt],
+        key => [key, key],
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    format!("{:?}", key)
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    BINDABLE_KEYS.iter().copied().find(|key| key_name(*key) == name)
+}
+
+#[derive(Component)]
+pub struct KeyBindings {
+    // indexed like ACTIONS
+    keys: [KeyCode; ACTIONS.len()],
+    // the pad's buttons, and which of their actions they're doing this frame
+    // (see read_pad), all indexed like ACTIONS too
+    buttons: [Option<PadButton>; ACTIONS.len()],
+    pad_down: [bool; ACTIONS.len()],
+    pad_pressed: [bool; ACTIONS.len()],
+    // the rebinding screen, if it's up: which row is picked, and whether it's
+    // waiting for the new key
+    screen_open: bool,
+    selected: usize,
+    waiting: bool,
+    // set for the frame the screen closes on, so the key that closed it
+    // doesn't also go through to the game
+    just_closed: bool,
+    // something else (the console) has the keyboard
+    captured: bool,
+}
+
+impl KeyBindings {
+    // anything the settings file doesn't mention (or names a key we don't
+    // know) keeps its default
+    pub fn from_settings(settings: &Settings) -> KeyBindings {
+        let mut keys = [KeyCode::Space; ACTIONS.len()];
+        for (i, action) in ACTIONS.iter().enumerate() {
+            keys[i] = match settings.keys.get(action.name()) {
+                Some(name) => parse_key(name).unwrap_or_else(|| {
+                    warn!("unknown key {:?} for {}, using the default", name, action.name());
+                    action.default_key()
+                }),
+                None => action.default_key(),
+            };
+        }
+        let mut buttons = [None; ACTIONS.len()];
+        for (i, action) in ACTIONS.iter().enumerate() {
+            buttons[i] = match settings.pad.get(action.name()) {
+                Some(name) if name.is_empty() => None,
+                Some(name) => PadButton::parse(name).or_else(|| {
+                    warn!("unknown pad button {:?} for {}, using the default", name, action.name());
+                    action.default_button()
+                }),
+                None => action.default_button(),
+            };
+        }
+        KeyBindings {
+            keys,
+            buttons,
+            pad_down: [false; ACTIONS.len()],
+            pad_pressed: [false; ACTIONS.len()],
+            screen_open: false,
+            selected: 0,
+            waiting: false,
+            just_closed: false,
+            captured: false,
+        }
+    }
+
+    // write the bindings back, leaving the rest of the settings file alone
+    fn save(&self) {
+        let mut settings = Settings::load(SETTINGS_PATH);
+        for (i, action) in ACTIONS.iter().enumerate() {
+            settings.keys.insert(action.name().to_string(), key_name(self.keys[i]));
+        }
+        settings.save(SETTINGS_PATH);
+    }
+
+    pub fn key(&self, action: Action) -> KeyCode {
+        self.keys[Self::index(action)]
+    }
+
+    pub fn set_captured(&mut self, captured: bool) {
+        self.captured = captured;
+    }
+
+    // while the rebinding screen (or the console) is up the game doesn't see any keys
+    fn listening(&self) -> bool {
+        !self.screen_open && !self.just_closed && !self.captured
+    }
+
+    fn index(action: Action) -> usize {
+        ACTIONS.iter().position(|a| *a == action).unwrap()
+    }
+
+    pub fn down(&self, action: Action) -> bool {
+        self.listening()
+            && (self.pad_down[Self::index(action)] || either_side(self.key(action)).iter().any(|key| is_key_down(*key)))
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.listening()
+            && (self.pad_pressed[Self::index(action)] || either_side(self.key(action)).iter().any(|key| is_key_pressed(*key)))
+    }
+}
+
+// after rebind_keys: what the pad's buttons are doing, as actions
+pub fn read_pad(mut bindings: UniqueViewMut<KeyBindings>, pad: UniqueView<GamepadState>) {
+    let bindings = &mut *bindings;
+    for (i, button) in bindings.buttons.iter().enumerate() {
+        bindings.pad_down[i] = button.map_or(false, |button| pad.down.contains(&button));
+        bindings.pad_pressed[i] = button.map_or(false, |button| pad.pressed.contains(&button));
+    }
+}
+
+// runs first in the input workload, so it's settled whether the screen is up
+// before anything else looks at the keys
+pub fn rebind_keys(mut bindings: UniqueViewMut<KeyBindings>, _screen: UniqueView<Screen>) {
+    bindings.just_closed = false;
+    if !bindings.screen_open {
+        if is_key_pressed(REBIND_KEY) && !bindings.captured {
+            bindings.screen_open = true;
+            bindings.waiting = false;
+        }
+        return;
+    }
+
+    if bindings.waiting {
+        if let Some(key) = get_last_key_pressed() {
+            if key == KeyCode::Escape {
+                bindings.waiting = false;
+            } else if BINDABLE_KEYS.contains(&key) {
+                let selected = bindings.selected;
+                bindings.keys[selected] = key;
+                bindings.waiting = false;
+            }
+        }
+    } else if is_key_pressed(KeyCode::Escape) || is_key_pressed(REBIND_KEY) {
+        bindings.screen_open = false;
+        bindings.just_closed = true;
+        bindings.save();
+    } else if is_key_pressed(KeyCode::Up) {
+        bindings.selected = (bindings.selected + ACTIONS.len() - 1) % ACTIONS.len();
+    } else if is_key_pressed(KeyCode::Down) {
+        bindings.selected = (bindings.selected + 1) % ACTIONS.len();
+    } else if is_key_pressed(KeyCode::Enter) {
+        bindings.waiting = true;
+    }
+}
+
+pub fn draw_key_bindings(_screen: UniqueViewMut<Screen>,
+                         screen: UniqueView<ScreenInfo>,
+                         bindings: UniqueView<KeyBindings>) {
+    if !bindings.screen_open {
+        return;
+    }
+    let (left, top, width) = (screen.width / 2. - 180., 60., 360.);
+    // squeezed up if that's what it takes to fit them all on the screen
+    let row_height = ((screen.height - top - 8.) / (ACTIONS.len() + 3) as f32).min(22.);
+    let height = row_height * (ACTIONS.len() + 3) as f32;
+    draw_rectangle(left, top, width, height, Color::new(0., 0., 0., 0.8));
+    draw_rectangle_lines(left, top, width, height, 1., GRAY);
+    draw_text("key bindings", left + 10., top + row_height, 20., WHITE);
+    for (i, action) in ACTIONS.iter().enumerate() {
+        let y = top + row_height * (i + 2) as f32;
+        let color = if i == bindings.selected { YELLOW } else { WHITE };
+        let key = if i == bindings.selected && bindings.waiting {
+            "press a key...".to_string()
+        } else {
+            key_name(bindings.keys[i])
+        };
+        draw_text(&action.name().replace('_', " "), left + 10., y, 18., color);
+        draw_text(&key, left + 170., y, 18., color);
+        if let Some(button) = bindings.buttons[i] {
+            draw_text(&button.name().replace('_', " "), left + 260., y, 16., GRAY);
+        }
+    }
+    draw_text("up/down pick, enter rebind, esc done", left + 10., top + height - 6., 16., GRAY);
+}
@@ -0,0 +1,124 @@
+// how the level (see scenario.rs) is going: time survived, particles sunk,
+// pickups collected and checkpoints passed, checked against its win condition, if it has one. the
+// status line says what's left to do, and then that it's done.
+
+use macroquad::prelude::*;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View};
+
+use crate::clock::TICK;
+use crate::events::{Event, Events};
+use crate::scenario::{EnemySpawn, Scenario, SpawnEvent, WinCondition};
+use crate::{new_turtle, Boat, LineStyle, Screen};
+
+// how close the boat has to come to a checkpoint to have passed it
+const CHECKPOINT_RADIUS: f32 = 24.;
+
+#[derive(Component, Default)]
+pub struct Level {
+    win: Option<WinCondition>,
+    // not spawned yet (see enemies.rs)
+    pub enemies: Vec<EnemySpawn>,
+    // and everything else that's still to turn up (see spawns.rs)
+    pub spawns: Vec<SpawnEvent>,
+    ticks: u32,
+    // where the edit tool saves the level's placements (nowhere for a preset)
+    pub save_path: Option<String>,
+    // particles the sinks have swallowed
+    pub sunk: usize,
+    // pickups the boat's run over
+    pub collected: u32,
+    // to be passed in order
+    checkpoints: Vec<Vec2>,
+    next_checkpoint: usize,
+    won: bool,
+}
+
+impl Level {
+    pub fn new(scenario: &Scenario, save_path: Option<String>) -> Level {
+        Level {
+            win: scenario.win.clone(),
+            enemies: scenario.enemies.clone(),
+            spawns: scenario.spawns.clone(),
+            save_path,
+            checkpoints: scenario.checkpoints.iter().map(|[x, y]| Vec2::new(*x, *y)).collect(),
+            ..Level::default()
+        }
+    }
+
+    pub fn seconds(&self) -> f32 {
+        self.ticks as f32 * TICK
+    }
+
+    // where the boat's headed next, in a race
+    pub fn next_checkpoint(&self) -> Option<Vec2> {
+        self.checkpoints.get(self.next_checkpoint).copied()
+    }
+
+    pub fn label(&self) -> Option<String> {
+        let win = self.win.as_ref()?;
+        if self.won {
+            return Some("level complete!".to_owned());
+        }
+        let mut goals = vec![];
+        if let Some(seconds) = win.survive_seconds {
+            goals.push(format!("survive {:.0}s", (seconds - self.seconds()).max(0.)));
+        }
+        if let Some(particles) = win.sink_particles {
+            goals.push(format!("sink {} particles", particles.saturating_sub(self.sunk)));
+        }
+        if let Some(pickups) = win.collect_pickups {
+            goals.push(format!("collect {} pickups", pickups.saturating_sub(self.collected)));
+        }
+        if self.next_checkpoint < self.checkpoints.len() {
+            goals.push(format!("checkpoint {}/{}", self.next_checkpoint + 1, self.checkpoints.len()));
+        }
+        Some(goals.join(", "))
+    }
+}
+
+// (a checkpoint's passed by whichever player's boat gets there)
+pub fn check_win(mut level: UniqueViewMut<Level>, mut events: UniqueViewMut<Events>, boats: View<Boat>) {
+    level.ticks += 1;
+    if let Some(checkpoint) = level.checkpoints.get(level.next_checkpoint) {
+        let reached = boats.iter()
+            .any(|boat| checkpoint.distance(Vec2::new(boat.loc.x as f32, boat.loc.y as f32)) < CHECKPOINT_RADIUS);
+        if reached {
+            level.next_checkpoint += 1;
+        }
+    }
+    let won = match &level.win {
+        Some(win) => {
+            win.survive_seconds.map_or(true, |seconds| level.seconds() >= seconds)
+                && win.sink_particles.map_or(true, |particles| level.sunk >= particles)
+                && win.collect_pickups.map_or(true, |pickups| level.collected >= pickups)
+                && level.next_checkpoint >= level.checkpoints.len()
+        }
+        None => false,
+    };
+    if won && !level.won {
+        events.send(Event::LevelComplete);
+    }
+    level.won = level.won || won;
+}
+
+// world space: the next checkpoint a dashed yellow ring with its number, the
+// ones after it dotted grey ones
+pub fn draw_checkpoints(_screen: UniqueViewMut<Screen>, level: UniqueView<Level>) {
+    for (i, at) in level.checkpoints.iter().enumerate().skip(level.next_checkpoint) {
+        let mut t = new_turtle();
+        if i == level.next_checkpoint {
+            t.set_color(YELLOW);
+            t.set_line_width(2.);
+            t.set_line_style(LineStyle::Dashed { dash: 8., gap: 5. });
+        } else {
+            t.set_color(GRAY);
+            t.set_line_style(LineStyle::Dotted { spacing: 6. });
+        }
+        // the circle's centre is off to the turtle's right, so start at the
+        // top facing right
+        t.move_to(at.x, at.y - CHECKPOINT_RADIUS);
+        t.write(&format!("{}", i + 1), 16.);
+        t.pen_down();
+        t.circle(CHECKPOINT_RADIUS);
+    }
+}
@@ -0,0 +1,339 @@
+// A simple interactive fluid-dynamics simulation
+// Colin McNamee <colinomcnamee@gmail.com>
+// Dylan McNamee <dylan.mcnamee@gmail.com>
+//
+// the simulation as a library: the world, its systems and the Simulation that
+// steps them (see simulation.rs), with nothing tied to macroquad's main loop,
+// so it can be stepped headless (see bench.rs, headless.rs and server.rs) or from a test.
+// main.rs is the game around it: the window, the start screen, and the frame
+// loop.
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntitiesViewMut, UniqueView, ViewMut, World};
+
+pub mod accessibility;
+pub mod attract;
+pub mod audio;
+pub mod batch;
+pub mod bench;
+pub mod boat;
+pub mod camera;
+pub mod clock;
+pub mod collision;
+pub mod commands;
+pub mod config;
+pub mod confinement;
+pub mod console;
+pub mod daily;
+pub mod demo;
+pub mod diagnostics;
+pub mod drifters;
+pub mod effects;
+pub mod emitters;
+pub mod enemies;
+pub mod events;
+pub mod export;
+pub mod fluid;
+pub mod force_fields;
+pub mod game_state;
+pub mod gamepad;
+pub mod geometry;
+pub mod governor;
+pub mod gpu;
+pub mod grid;
+pub mod headless;
+pub mod hud;
+pub mod image_import;
+pub mod input;
+pub mod inspector;
+pub mod islands;
+pub mod kernels;
+pub mod keymap;
+pub mod level;
+pub mod lsystem;
+pub mod memory;
+pub mod music;
+pub mod net;
+pub mod neighbours;
+pub mod osc;
+pub mod pacing;
+pub mod particle;
+pub mod particle_store;
+pub mod pickups;
+pub mod pool;
+pub mod presets;
+pub mod profiling;
+pub mod projection;
+pub mod refine;
+pub mod quadtree;
+pub mod render;
+pub mod scenario;
+pub mod score;
+pub mod scores;
+pub mod server;
+pub mod settings;
+pub mod simulation;
+pub mod spawns;
+pub mod sparse_grid;
+pub mod state;
+pub mod stats;
+pub mod telemetry;
+pub mod terrain;
+pub mod tools;
+pub mod touch;
+pub mod trails;
+pub mod trajectories;
+pub mod turtle;
+pub mod turtle_path;
+pub mod turtles;
+pub mod tuning;
+pub mod two_player;
+pub mod video;
+pub mod visualization;
+pub mod visualizer;
+pub mod wake;
+pub mod weapons;
+pub mod weather;
+pub mod window;
+
+// the core types, where the rest of the crate has always found them
+pub use boat::{angle_between, new_boat, steer, Boat, TURN_RATE};
+pub use fluid::{new_cells, CellType, Cells, FlowAccumulator, FluidCell, NextCells};
+pub use geometry::wrap_coord;
+pub use particle::{new_particle, new_particle_at, Particle, ParticleKind};
+pub use turtle::{new_turtle, rad_to_deg, LineStyle, Turtle};
+
+use attract::Autopilot;
+use boat::PlayerId;
+use collision::Collisions;
+use daily::Daily;
+use demo::Demo;
+use diagnostics::Diagnostics;
+use effects::Effects;
+use enemies::EnemyWaves;
+use events::Events;
+use game_state::{GameEvents, GameState};
+use export::Exporter;
+use force_fields::FieldPlacer;
+use gamepad::GamepadState;
+use governor::Governor;
+use input::DashGesture;
+use inspector::Inspector;
+use islands::Islands;
+use keymap::KeyBindings;
+use level::Level;
+use memory::MemoryStats;
+use neighbours::ParticleIndex;
+use camera::Camera;
+use clock::SimClock;
+use config::SimConfig;
+use console::Console;
+use osc::OscInput;
+use particle_store::ParticleStore;
+use pickups::Pickups;
+use pool::ParticlePool;
+use profiling::Profiler;
+use projection::Projection;
+use refine::GridRefiner;
+use scenario::{scenario_path, Scenario};
+use settings::{Settings, SETTINGS_PATH};
+use stats::RunStats;
+use telemetry::Telemetry;
+use tools::{ParticleDragger, ToolCommands, ToolMode, Vortices};
+use touch::TouchControls;
+use trails::TrailCanvas;
+use trajectories::Trajectories;
+use tuning::{Tuning, TuningPanel};
+use two_player::TwoPlayer;
+use video::Capture;
+use visualization::Visualization;
+use weather::Weather;
+use window::ScreenInfo;
+
+// the world's size and the grid's, unless the config says otherwise (see
+// config.rs)
+pub const WIDTH: i32 = 640;
+pub const HEIGHT: i32 = 360;
+
+pub const CELLS_X: i32 = 20;
+pub const CELLS_Y: i32 = 12;
+
+// how many particle entities to allocate up front for the pool
+const PARTICLE_POOL_RESERVE: usize = 1024;
+
+// the scalar positions are integrated in. f32 normally, or f64 with the `f64`
+// feature, for long runs where the drift from adding up small velocities every
+// step starts to show. (velocities, the grid and all the drawing stay f32.)
+#[cfg(not(feature = "f64"))]
+pub type Real = f32;
+#[cfg(feature = "f64")]
+pub type Real = f64;
+
+#[derive(Clone, Copy)]
+pub struct Point2 {
+    pub x: Real,
+    pub y: Real,
+}
+#[derive(Component, PartialEq)]
+pub struct GameModeInfo{
+    pub game_mode: GameMode,
+}
+#[derive(PartialEq)]
+
+pub enum GameMode {
+    Debug,
+    Default,
+}
+
+// stands in for macroquad's frame. macroquad draws through one global context,
+// so every system that draws borrows this mutably, which keeps the (possibly
+// multithreaded) scheduler from ever running two of them at once
+#[derive(Component)]
+pub struct Screen;
+
+// the same for macroquad's random numbers: every sim system that draws any
+// borrows this mutably, so they take turns in the workload's order rather
+// than racing each other for the generator, and a tick comes out the same
+// each time it's run from the same seed (see demo.rs)
+#[derive(Component)]
+pub struct Randomness;
+
+// how long the last frame took, in seconds
+#[derive(Component, Default)]
+pub struct FrameTime(pub f32);
+
+pub fn lerp (start: f32, target: f32, fraction: f32) -> f32 {
+    start + (target - start) * fraction
+}
+
+pub fn pythag_dist(x1: f32, y1: f32, x2: f32, y2: f32,) -> f32 {
+    let xd = x2 - x1;
+    let yd = y2 - y1;
+    (xd * xd + yd * yd).sqrt()
+}
+
+// `preset` picks one of the built in levels, otherwise it's the scenario file
+pub fn init_world(world: &mut World, preset: Option<usize>) {
+    let _ = world.remove_unique::<Particle>();
+
+    // a demo (being recorded or played back) pins the random seed, so it has
+    // to be set before anything random happens
+    let demo = Demo::from_args();
+    if let Some(seed) = demo.seed() {
+        rand::srand(seed);
+    }
+    world.add_unique(demo).unwrap();
+    // and so does the daily challenge, unless a preset was picked instead
+    let daily = match preset {
+        Some(_) => Daily::default(),
+        None => Daily::from_args(),
+    };
+    if let Some(seed) = daily.seed() {
+        rand::srand(seed);
+    }
+
+    // create the grid
+    // world.add_unique( ... ).unwrap();
+
+    let (mut scenario, save_path) = match (preset, daily.scenario(), Scenario::generated()) {
+        (Some(i), _, _) => (presets::PRESETS[i].scenario(), None),
+        (None, Some(today), _) => (today, None),
+        (None, None, Some(generated)) => (generated, None),
+        (None, None, None) => (Scenario::load(&scenario_path()), Some(scenario_path())),
+    };
+    world.add_unique(daily).unwrap();
+    // (clearing the world leaves its uniques, so the last config is still here)
+    let config = match world.borrow::<UniqueView<SimConfig>>() {
+        Ok(config) if config.edited => *config,
+        _ => SimConfig::from_args(),
+    };
+    let grid = scenario.grid(config.width, config.height, config.cols, config.rows);
+    scenario.place_terrain(&grid);
+    let spawn = scenario.spawn_point(&grid);
+    // the scenario's count, unless it's been changed in the tuning panel since
+    let starting = match (scenario.particles, config.edited) {
+        (Some(count), false) => count,
+        _ => config.particles,
+    };
+    let mut pool = ParticlePool::default();
+    world.run(|mut entities: EntitiesViewMut, mut particles: ViewMut<Particle>| {
+        pool.reserve(&mut entities, PARTICLE_POOL_RESERVE);
+        pool.spawn_bulk(&mut entities, &mut particles, (0..starting).map(|_| new_particle(&grid)));
+    });
+    world.add_unique(pool).unwrap();
+    let mut cells = new_cells(&grid);
+    image_import::init_flow_from_args(&mut cells, &grid);
+    image_import::mask_from_args(&mut cells, &grid);
+    scenario.build_walls(&mut cells, &grid);
+    world.add_unique(grid).unwrap();
+    world.add_unique(config).unwrap();
+    world.add_unique(NextCells(cells.clone())).unwrap();
+    world.add_unique(cells).unwrap();
+    world.add_unique(ParticleStore::default()).unwrap();
+    world.add_unique(Diagnostics::default()).unwrap();
+    world.add_unique(MemoryStats::default()).unwrap();
+    world.add_unique(Governor::default()).unwrap();
+    world.add_unique(Projection::from_args()).unwrap();
+    world.add_unique(Profiler::default()).unwrap();
+    world.add_unique(Effects::default()).unwrap();
+    world.add_unique(Collisions::default()).unwrap();
+    world.add_unique(Screen).unwrap();
+    world.add_unique(Randomness).unwrap();
+    world.add_unique(ScreenInfo::default()).unwrap();
+    world.add_unique(FrameTime::default()).unwrap();
+    world.add_unique(GamepadState::default()).unwrap();
+    world.add_unique(Autopilot::default()).unwrap();
+    world.add_unique(DashGesture::default()).unwrap();
+    world.add_unique(Events::default()).unwrap();
+    world.add_unique(GameEvents::default()).unwrap();
+    world.add_unique(GameState::default()).unwrap();
+    world.add_unique(Console::default()).unwrap();
+    world.add_unique(Exporter::from_args()).unwrap();
+    world.add_unique(Capture::default()).unwrap();
+    world.add_unique(Trajectories::from_args()).unwrap();
+    world.add_unique(Telemetry::from_args()).unwrap();
+    world.add_unique(RunStats::default()).unwrap();
+    world.add_unique(OscInput::from_args()).unwrap();
+    world.add_unique(TouchControls::default()).unwrap();
+    world.add_unique(ParticleDragger::default()).unwrap();
+    world.add_unique(ToolMode::default()).unwrap();
+    world.add_unique(Vortices::default()).unwrap();
+    world.add_unique(FieldPlacer::default()).unwrap();
+    world.add_unique(ToolCommands::default()).unwrap();
+    world.add_unique(ParticleIndex::default()).unwrap();
+    world.add_unique(Inspector::default()).unwrap();
+    world.add_unique(Islands::from_args()).unwrap();
+    world.add_unique(GridRefiner::from_args()).unwrap();
+    world.add_unique(TuningPanel::default()).unwrap();
+    world.add_unique(GameModeInfo{game_mode: GameMode::Default}).unwrap();
+    world.add_unique(SimClock::default()).unwrap();
+    let settings = Settings::load(SETTINGS_PATH);
+    let mut tuning = Tuning::from_settings(&settings);
+    config.tune(&mut tuning);
+    world.add_unique(tuning).unwrap();
+    world.add_unique(KeyBindings::from_settings(&settings)).unwrap();
+    world.add_unique(Camera::new(&settings, Vec2::new(config.width, config.height))).unwrap();
+    world.add_unique(settings.controls.scheme).unwrap();
+    world.add_unique(settings.accessibility).unwrap();
+    world.add_unique(Visualization::new(&settings.visualization)).unwrap();
+    world.add_unique(TrailCanvas::default()).unwrap();
+    world.add_unique(Weather::new(&scenario)).unwrap();
+    world.add_unique(EnemyWaves::new(&scenario)).unwrap();
+    world.add_unique(Pickups::default()).unwrap();
+    world.add_unique(Level::new(&scenario, save_path)).unwrap();
+    scenario.spawn_into(world);
+    boat::spawn_player(world, PlayerId(1), new_boat(spawn.x, spawn.y, 0., 0.));
+    let two_player = TwoPlayer::from_args();
+    if two_player.0 {
+        two_player::spawn_second_player(world, spawn);
+    }
+    world.add_unique(two_player).unwrap();
+    turtles::spawn_turtles(world);
+}
+
+// TODO: define Renderable trait with render function
+// impl render(&self) for Particle {
+// }
+
+pub fn clean_up(/* mut all_storages: AllStoragesViewMut */) {
+}
@@ -0,0 +1,150 @@
+// L-systems: a string of symbols (the axiom) rewritten by rules, again and
+// again, then read out as turtle moves. a handful of rules makes coastlines,
+// seaweed and fractal rocks. the symbols the turtle understands:
+//
+//   F G    forward a step, drawing
+//   f      forward a step without drawing
+//   + -    turn right / left by the angle
+//   [ ]    push / pop the turtle's state, for branches
+//
+// anything else (X, Y, ...) is only there for the rules to rewrite. levels use
+// them in the scenario file, either as solid obstacles or as decoration:
+//
+//   [[obstacles]]
+//   shape = "lsystem"
+//   x = 100
+//   y = 300
+//   heading = -90      # degrees, 0 is to the right
+//   step = 6
+//   width = 8          # how thick the walls are
+//   lsystem = { axiom = "F", rules = { F = "F[+F]F[-F]F" }, iterations = 2, angle = 25 }
+//
+//   [[decorations]]    # the same, minus width, plus color = [r, g, b]
+
+use std::collections::BTreeMap;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::{new_turtle, Screen, Turtle};
+
+// past this many symbols expansion stops, so a typo in the iterations doesn't
+// take all the memory
+const MAX_SYMBOLS: usize = 100_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LSystem {
+    pub axiom: String,
+    // each symbol's replacement. symbols without one stay as they are
+    #[serde(default)]
+    pub rules: BTreeMap<char, String>,
+    pub iterations: u32,
+    // degrees turned by + and -
+    pub angle: f32,
+}
+
+impl LSystem {
+    pub fn expand(&self) -> String {
+        let mut symbols = self.axiom.clone();
+        for _ in 0..self.iterations {
+            let mut next = String::with_capacity(symbols.len() * 2);
+            for symbol in symbols.chars() {
+                match self.rules.get(&symbol) {
+                    Some(replacement) => next.push_str(replacement),
+                    None => next.push(symbol),
+                }
+            }
+            if next.len() > MAX_SYMBOLS {
+                warn!("l-system {} is too big after {} iterations, stopping there", self.axiom, self.iterations);
+                break;
+            }
+            symbols = next;
+        }
+        symbols
+    }
+
+    // drive `turtle` through the expanded string, `step` pixels a move,
+    // calling `on_line` with each line it draws
+    pub fn run(&self, turtle: &mut Turtle, step: f32, mut on_line: impl FnMut(Vec2, Vec2)) {
+        for symbol in self.expand().chars() {
+            match symbol {
+                'F' | 'G' => {
+                    let from = Vec2::new(turtle.loc.x as f32, turtle.loc.y as f32);
+                    turtle.forward(step);
+                    on_line(from, Vec2::new(turtle.loc.x as f32, turtle.loc.y as f32));
+                }
+                'f' => {
+                    let pen_down = turtle.pen_down;
+                    turtle.pen_up();
+                    turtle.forward(step);
+                    turtle.pen_down = pen_down;
+                }
+                '+' => turtle.turn_right(self.angle),
+                '-' => turtle.turn_left(self.angle),
+                '[' => turtle.push(),
+                ']' => turtle.pop(),
+                _ => {}
+            }
+        }
+    }
+
+    // the lines it makes from (x, y), facing `heading` degrees, without drawing them
+    pub fn lines(&self, at: Vec2, heading: f32, step: f32) -> Vec<(Vec2, Vec2)> {
+        let mut turtle = new_turtle();
+        turtle.move_to(at.x, at.y);
+        turtle.turn_right(heading);
+        let mut lines = vec![];
+        self.run(&mut turtle, step, |from, to| lines.push((from, to)));
+        lines
+    }
+}
+
+// how far `point` is from the line from `a` to `b`
+pub fn distance_to_line(point: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let along = b - a;
+    let t = if along.length_squared() > 0. { ((point - a).dot(along) / along.length_squared()).max(0.).min(1.) } else { 0. };
+    (point - (a + along * t)).length()
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Decoration {
+    pub x: f32,
+    pub y: f32,
+    #[serde(default)]
+    pub heading: f32,
+    pub step: f32,
+    #[serde(default = "seaweed")]
+    pub color: [f32; 3],
+    pub lsystem: LSystem,
+}
+
+fn seaweed() -> [f32; 3] {
+    [0.2, 0.6, 0.3]
+}
+
+// the level's decorations, worked out once when it loads
+#[derive(Component, Default)]
+pub struct Decorations {
+    lines: Vec<(Vec2, Vec2, Color)>,
+}
+
+impl Decorations {
+    pub fn new(defs: &[Decoration]) -> Decorations {
+        let mut lines = vec![];
+        for def in defs.iter() {
+            let color = Color::new(def.color[0], def.color[1], def.color[2], 1.);
+            for (from, to) in def.lsystem.lines(Vec2::new(def.x, def.y), def.heading, def.step) {
+                lines.push((from, to, color));
+            }
+        }
+        Decorations { lines }
+    }
+}
+
+// world space, under everything else
+pub fn draw_decorations(_screen: UniqueViewMut<Screen>, decorations: UniqueView<Decorations>) {
+    for (from, to, color) in decorations.lines.iter() {
+        draw_line(from.x, from.y, to.x, to.y, 1.5, *color);
+    }
+}
@@ -0,0 +1,80 @@
+// rough memory accounting for the debug HUD: how many of each thing there
+// are and how much room they're holding onto, so growth from spawning, pools
+// and such shows up before it's a problem. sizes are what the containers have
+// allocated (capacity), not what's in use.
+
+use std::mem::size_of;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View};
+
+use crate::collision::Collider;
+use crate::effects::{Effect, Effects};
+use crate::grid::GridGeometry;
+use crate::particle_store::ParticleStore;
+use crate::pool::ParticlePool;
+use crate::{Cells, GameMode, GameModeInfo, NextCells, Particle};
+
+// the GPU backend lives outside the world, so it reports its render targets here
+static RENDER_TARGET_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_render_target_bytes(bytes: usize) {
+    RENDER_TARGET_BYTES.store(bytes, Ordering::Relaxed);
+}
+
+#[derive(Component, Default)]
+pub struct MemoryStats {
+    pub particles: usize,
+    pub pooled_ids: usize,
+    pub colliders: usize,
+    pub effects: usize,
+    pub effect_capacity: usize,
+    pub particle_bytes: usize,
+    pub particle_store_bytes: usize,
+    pub effect_bytes: usize,
+    // both cell buffers plus the geometry tables
+    pub grid_bytes: usize,
+    // how much of the grid is allocated
+    pub grid_chunks: usize,
+    pub grid_total_chunks: usize,
+    pub render_target_bytes: usize,
+}
+
+// only bothers while the HUD is up
+pub fn collect_memory_stats(mut stats: UniqueViewMut<MemoryStats>,
+                            game_mode: UniqueView<GameModeInfo>,
+                            particles: View<Particle>,
+                            colliders: View<Collider>,
+                            pool: UniqueView<ParticlePool>,
+                            store: UniqueView<ParticleStore>,
+                            effects: UniqueView<Effects>,
+                            map: UniqueView<Cells>,
+                            next: UniqueView<NextCells>,
+                            grid: UniqueView<GridGeometry>) {
+    if game_mode.game_mode != GameMode::Debug {
+        return;
+    }
+    stats.particles = particles.iter().count();
+    stats.pooled_ids = pool.available();
+    stats.colliders = colliders.iter().count();
+    stats.effects = effects.0.len();
+    stats.effect_capacity = effects.0.capacity();
+    stats.particle_bytes = (stats.particles + stats.pooled_ids) * size_of::<Particle>();
+    stats.particle_store_bytes = store.capacity_bytes();
+    stats.effect_bytes = stats.effect_capacity * size_of::<Effect>();
+    stats.grid_bytes = map.all_cells.bytes() + next.0.all_cells.bytes() + grid.table_bytes();
+    stats.grid_chunks = map.all_cells.allocated_chunks();
+    stats.grid_total_chunks = map.all_cells.total_chunks();
+    stats.render_target_bytes = RENDER_TARGET_BYTES.load(Ordering::Relaxed);
+}
+
+// bytes as something readable
+pub fn format_bytes(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{:.1} KB", bytes as f32 / 1024.)
+    } else {
+        format!("{:.1} MB", bytes as f32 / (1024. * 1024.))
+    }
+}
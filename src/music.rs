@@ -0,0 +1,148 @@
+// background music that follows the game: three looping layers, all the same
+// length and started together so they stay in time, faded in and out by a
+// director listening to the event bus (like the sound effects, see audio.rs).
+//
+//   pad     always there, for drifting about
+//   bass    comes in once there's some fighting (shots, things hitting things)
+//   drums   come in when it's really going
+//
+// the fighting raises a tension that dies back down over a few seconds of
+// quiet, and the layers follow it. like the effects the loops are
+// synthesized at startup, and the volume is `music` in the [audio] section of
+// settings.toml.
+
+use std::f32::consts::PI;
+
+use macroquad::audio::{load_sound_from_bytes, play_sound, set_sound_volume, PlaySoundParams, Sound};
+use macroquad::prelude::*;
+use shipyard::{UniqueView, World};
+
+use crate::audio::{synth, wav, AudioSettings, Noise};
+use crate::events::{Event, Events};
+
+// 120 beats a minute, 16 beats (4 bars, one chord each) to the loop
+const BEAT_SECONDS: f32 = 0.5;
+const LOOP_BEATS: usize = 16;
+// a minor, F, C, G
+const CHORDS: [[f32; 3]; 4] = [
+    [220., 261.63, 329.63],
+    [174.61, 220., 261.63],
+    [261.63, 329.63, 392.],
+    [196., 246.94, 293.66],
+];
+// tension each event adds, and how much drains away a second
+const SHOT_TENSION: f32 = 0.15;
+const IMPACT_TENSION: f32 = 0.25;
+const TENSION_DECAY: f32 = 0.08;
+// tension where the bass and the drums come in
+const BASS_TENSION: f32 = 0.2;
+const DRUMS_TENSION: f32 = 0.6;
+// volume a second a layer fades by
+const FADE_SPEED: f32 = 0.5;
+
+fn chord_at(beat: f32) -> &'static [f32; 3] {
+    &CHORDS[(beat / 4.) as usize % CHORDS.len()]
+}
+
+fn pad_samples() -> Vec<f32> {
+    let seconds = BEAT_SECONDS * LOOP_BEATS as f32;
+    synth(seconds, |t, _| {
+        let beat = t / BEAT_SECONDS;
+        // swell into each chord, so the changes aren't clicks
+        let within = (beat % 4.) / 4.;
+        let swell = (within * PI).sin().max(0.2);
+        chord_at(beat).iter().map(|f| (2. * PI * f * t).sin()).sum::<f32>() * 0.12 * swell
+    })
+}
+
+fn bass_samples() -> Vec<f32> {
+    let seconds = BEAT_SECONDS * LOOP_BEATS as f32;
+    synth(seconds, |t, _| {
+        let beat = t / BEAT_SECONDS;
+        // the chord's root an octave down, plucked on every half beat
+        let root = chord_at(beat)[0] / 2.;
+        let pluck = (1. - (beat * 2.).fract()).powi(2);
+        let wave = (2. * PI * root * t).sin() + 0.3 * (4. * PI * root * t).sin();
+        wave * 0.35 * pluck
+    })
+}
+
+fn drum_samples() -> Vec<f32> {
+    let seconds = BEAT_SECONDS * LOOP_BEATS as f32;
+    let mut noise = Noise(3);
+    synth(seconds, |t, _| {
+        let beat = t / BEAT_SECONDS;
+        let since_beat = beat.fract() * BEAT_SECONDS;
+        // a kick on the beat, a falling sine
+        let kick = (2. * PI * (60. + 120. * (-since_beat * 30.).exp()) * since_beat).sin() * (-since_beat * 12.).exp();
+        // a hat on the off beat
+        let since_off = (beat + 0.5).fract() * BEAT_SECONDS;
+        let hat = noise.next() * (-since_off * 60.).exp() * 0.3;
+        kick * 0.8 + hat
+    })
+}
+
+struct Layer {
+    sound: Sound,
+    volume: f32,
+    // the tension it comes in at
+    threshold: f32,
+}
+
+pub struct MusicDirector {
+    layers: Vec<Layer>,
+    tension: f32,
+    settings: AudioSettings,
+    // faded out, with the sound effects (see audio.rs)
+    pub muted: bool,
+}
+
+impl MusicDirector {
+    pub async fn load(settings: AudioSettings) -> MusicDirector {
+        let mut layers = vec![];
+        for (samples, threshold) in [(pad_samples(), 0.), (bass_samples(), BASS_TENSION), (drum_samples(), DRUMS_TENSION)].iter() {
+            let sound = load_sound_from_bytes(&wav(samples)).await.unwrap();
+            layers.push(Layer { sound, volume: 0., threshold: *threshold });
+        }
+        // all started now, silent, so they stay in step
+        for layer in layers.iter() {
+            play_sound(layer.sound, PlaySoundParams { looped: true, volume: 0. });
+        }
+        MusicDirector { layers, tension: 0., settings, muted: settings.muted }
+    }
+
+    // after each step, with the rest of the audio
+    pub fn handle_events(&mut self, world: &World) {
+        if let Ok(events) = world.borrow::<UniqueView<Events>>() {
+            for event in events.iter() {
+                match event {
+                    Event::Fired { .. } => self.tension += SHOT_TENSION,
+                    Event::Impact { strength } => self.tension += IMPACT_TENSION * strength,
+                    Event::EnemyDestroyed { .. } => self.tension += IMPACT_TENSION,
+                    _ => {}
+                }
+            }
+        }
+        self.tension = self.tension.min(1.);
+    }
+
+    // once a frame
+    pub fn update(&mut self) {
+        let dt = get_frame_time();
+        self.tension = (self.tension - TENSION_DECAY * dt).max(0.);
+        let full = if self.muted { 0. } else { self.settings.music * self.settings.master };
+        for layer in self.layers.iter_mut() {
+            let target = if self.tension >= layer.threshold { full } else { 0. };
+            let step = FADE_SPEED * full * dt;
+            let volume = if layer.volume < target {
+                (layer.volume + step).min(target)
+            } else {
+                (layer.volume - step).max(target)
+            };
+            if volume != layer.volume {
+                layer.volume = volume;
+                set_sound_volume(layer.sound, volume);
+            }
+        }
+    }
+}
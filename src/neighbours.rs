@@ -0,0 +1,125 @@
+// which particles are near which, without checking every pair. the index
+// sorts the particles by the grid cell they're in (the same cells as the
+// flow, see grid.rs), so a query only looks through the cells a circle
+// overlaps. it's rebuilt by whichever system wants it, at the start of its
+// step, since the particles have moved since last time:
+//
+//   index.rebuild(particles.iter().with_id(), &grid);
+//   index.neighbours_within(&grid, at, radius, &mut near);
+//
+// queries wrap round the edges like everything else, so a particle on the
+// left edge has neighbours on the right. repel_particles below is the one
+// user so far: with the tuning panel's repulsion up, particles closer than
+// REPEL_RADIUS push each other apart, which keeps them from bunching into
+// single points where the flow converges.
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, ViewMut};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::geometry::wrapped_delta;
+use crate::grid::{wrapped_span, GridGeometry};
+use crate::profiling;
+use crate::tuning::Tuning;
+use crate::Particle;
+
+// how near particles have to be to push each other apart
+const REPEL_RADIUS: f32 = 6.;
+
+#[derive(Component, Default)]
+pub struct ParticleIndex {
+    // where each cell's particles start in `entries` (one more than there are
+    // cells, the last being the end)
+    starts: Vec<usize>,
+    entries: Vec<(EntityId, Vec2)>,
+}
+
+impl ParticleIndex {
+    // a counting sort on the particles' cells
+    pub fn rebuild<'a, I>(&mut self, particles: I, grid: &GridGeometry)
+    where
+        I: IntoIterator<Item = (EntityId, &'a Particle)>,
+    {
+        let unsorted: Vec<(usize, EntityId, Vec2)> = particles.into_iter()
+            .map(|(id, particle)| (particle.cell_ix, id, Vec2::new(particle.position.x as f32, particle.position.y as f32)))
+            .collect();
+        self.starts.clear();
+        self.starts.resize(grid.len() + 1, 0);
+        for (cell_ix, _, _) in unsorted.iter() {
+            self.starts[cell_ix + 1] += 1;
+        }
+        for cell_ix in 0..grid.len() {
+            self.starts[cell_ix + 1] += self.starts[cell_ix];
+        }
+        let mut next = self.starts.clone();
+        self.entries.clear();
+        self.entries.resize(unsorted.len(), (EntityId::dead(), Vec2::new(0., 0.)));
+        for (cell_ix, id, pos) in unsorted {
+            self.entries[next[cell_ix]] = (id, pos);
+            next[cell_ix] += 1;
+        }
+    }
+
+    // every particle within `radius` of `at` (`at` included, if it's one),
+    // with where it is relative to `at`, the short way round the edges
+    pub fn neighbours_within(&self, grid: &GridGeometry, at: Vec2, radius: f32, out: &mut Vec<(EntityId, Vec2)>) {
+        out.clear();
+        if self.starts.len() != grid.len() + 1 {
+            return;
+        }
+        let size = grid.size();
+        for row in wrapped_span((at.y - radius) * grid.inv_cell_height, (at.y + radius) * grid.inv_cell_height, grid.rows) {
+            for col in wrapped_span((at.x - radius) * grid.inv_cell_width, (at.x + radius) * grid.inv_cell_width, grid.cols) {
+                let cell_ix = row * grid.cols + col;
+                for (id, pos) in self.entries[self.starts[cell_ix]..self.starts[cell_ix + 1]].iter() {
+                    let offset = wrapped_delta(at, *pos, size);
+                    if offset.length() < radius {
+                        out.push((*id, offset));
+                    }
+                }
+            }
+        }
+    }
+
+    // how crowded it is around `at`: each particle within `radius` counts
+    // for more the nearer it is (1 right on top, falling to 0 at the edge,
+    // like sph's smoothing kernels)
+    pub fn density_at(&self, grid: &GridGeometry, at: Vec2, radius: f32) -> f32 {
+        let mut near = vec![];
+        self.neighbours_within(grid, at, radius, &mut near);
+        near.iter().map(|(_, offset)| (1. - offset.length_squared() / (radius * radius)).powi(3)).sum()
+    }
+}
+
+// after the particles' velocities are set from the flow: each particle's
+// pushed away from the ones too near it, harder the nearer they are
+pub fn repel_particles(mut index: UniqueViewMut<ParticleIndex>,
+                      mut particles: ViewMut<Particle>,
+                      grid: UniqueView<GridGeometry>,
+                      tuning: UniqueView<Tuning>) {
+    let _t = profiling::scope("repel_particles");
+    if tuning.repulsion <= 0. {
+        return;
+    }
+    index.rebuild((&particles).iter().with_id(), &grid);
+    let (index, grid, strength) = (&*index, &*grid, tuning.repulsion);
+    let push = |&(id, at): &(EntityId, Vec2)| {
+        let mut near = vec![];
+        index.neighbours_within(grid, at, REPEL_RADIUS, &mut near);
+        let push: Vec2 = near.iter()
+            .filter(|(other, offset)| *other != id && offset.length() > 0.)
+            .map(|(_, offset)| -offset.normalize() * (1. - offset.length() / REPEL_RADIUS))
+            .fold(Vec2::new(0., 0.), |sum, push| sum + push);
+        (id, push * strength)
+    };
+    #[cfg(feature = "parallel")]
+    let pushes: Vec<(EntityId, Vec2)> = index.entries.par_iter().map(push).collect();
+    #[cfg(not(feature = "parallel"))]
+    let pushes: Vec<(EntityId, Vec2)> = index.entries.iter().map(push).collect();
+    for (id, push) in pushes {
+        if let Ok(particle) = (&mut particles).get(id) {
+            particle.velocity += push;
+        }
+    }
+}
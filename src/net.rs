@@ -0,0 +1,308 @@
+// two players over a LAN: `--host PORT` waits for someone to `--join
+// HOST:PORT`, and then both games run the same world in lockstep. nothing
+// but the boat controls goes over the wire (a DemoFrame per tick, the same as
+// a replay, see demo.rs): the host hands out a random seed when the other
+// player joins, both worlds start over from it, and every tick is reseeded
+// from it, so as long as both sides run each tick with the same controls they
+// stay the same.
+//
+// the host's boat is the first player's on both sides, and the joining
+// player's is a RemoteBoat.
+// each side's controls are sent INPUT_DELAY ticks ahead of when they're used,
+// and a tick doesn't run until the other side's controls for it are in, so a
+// slow or lost connection holds the sim up rather than letting the two drift
+// apart. every packet carries all the controls the other side hasn't said it
+// has yet, so a lost one just gets made up for by the next.
+//
+// like a replay, only BoatControls and the ToolCommands (the mouse tools, the
+// particle hotkeys and right-click vortices, see tools.rs) are shared, the
+// host's edits going first: the console would put the two worlds out of
+// step, so leave it alone. the remote boat can't fire (yet). this lives in
+// the main loop rather than the world since the connection has to outlast the
+// world being reset.
+
+use std::net::{SocketAddr, UdpSocket};
+
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, EntitiesViewMut, IntoIter, UniqueView, UniqueViewMut, View, ViewMut, World};
+
+use crate::boat::PlayerId;
+use crate::clock::SimClock;
+use crate::collision::{Collider, BOAT_RADIUS};
+use crate::demo::{path_after, DemoFrame};
+use crate::effects::Effects;
+use crate::input::BoatControls;
+use crate::level::Level;
+use crate::simulation::Simulation;
+use crate::tools::ToolCommands;
+use crate::tuning::Tuning;
+use crate::weather::Weather;
+use crate::grid::GridGeometry;
+use crate::profiling;
+use crate::{new_boat, steer, Boat, Cells, Screen};
+
+// ticks between a player's controls being read and used (at 60 a second)
+const INPUT_DELAY: usize = 4;
+// no more than this many ticks of controls go in one packet
+const MAX_FRAMES_PER_PACKET: usize = 64;
+// a quiet connection this long is reported as lost
+const TIMEOUT_SECONDS: f64 = 5.;
+const MAX_PACKET: usize = 65536;
+
+#[derive(Serialize, Deserialize)]
+enum Message {
+    // joiner -> host, until it gets a welcome
+    Hello,
+    Welcome { seed: u64 },
+    // controls for ticks `first` onward, and how many ticks of the other
+    // side's controls the sender has so far
+    Controls { first: usize, frames: Vec<DemoFrame>, have: usize },
+}
+
+#[derive(PartialEq)]
+enum Role {
+    Host,
+    Joining,
+}
+
+// the other player's boat, steered from their controls
+#[derive(Component)]
+pub struct RemoteBoat {
+    pub boat: Boat,
+    pub controls: BoatControls,
+}
+
+pub struct Netplay {
+    socket: UdpSocket,
+    role: Role,
+    peer: Option<SocketAddr>,
+    seed: Option<u64>,
+    // the next tick to run
+    tick: usize,
+    // controls by tick, ours and theirs; both start with INPUT_DELAY empty ticks
+    local: Vec<DemoFrame>,
+    remote: Vec<DemoFrame>,
+    // how many of ours they've said they have
+    acked: usize,
+    last_heard: f64,
+}
+
+fn empty_frame() -> DemoFrame {
+    DemoFrame { turn: 0., thrust: 0., fire: false, commands: vec![], others: vec![], tools: vec![], repeat: 1 }
+}
+
+impl Netplay {
+    // None if there's no --host or --join, or the socket couldn't be set up
+    pub fn from_args() -> Option<Netplay> {
+        let (role, bind, peer) = if let Some(port) = path_after("--host") {
+            (Role::Host, format!("0.0.0.0:{}", port), None)
+        } else if let Some(address) = path_after("--join") {
+            match address.parse::<SocketAddr>() {
+                Ok(peer) => (Role::Joining, "0.0.0.0:0".to_owned(), Some(peer)),
+                Err(err) => {
+                    warn!("can't join {}: {}", address, err);
+                    return None;
+                }
+            }
+        } else {
+            return None;
+        };
+        let socket = UdpSocket::bind(&bind).and_then(|socket| socket.set_nonblocking(true).map(|_| socket));
+        match socket {
+            Ok(socket) => Some(Netplay {
+                socket,
+                role,
+                peer,
+                seed: None,
+                tick: 0,
+                local: vec![empty_frame(); INPUT_DELAY],
+                remote: vec![empty_frame(); INPUT_DELAY],
+                acked: 0,
+                last_heard: date::now(),
+            }),
+            Err(err) => {
+                warn!("no network play, couldn't open {}: {}", bind, err);
+                None
+            }
+        }
+    }
+
+    fn send(&self, message: &Message) {
+        if let (Some(peer), Ok(bytes)) = (self.peer, serde_json::to_vec(message)) {
+            if let Err(err) = self.socket.send_to(&bytes, peer) {
+                warn!("couldn't send to {}: {}", peer, err);
+            }
+        }
+    }
+
+    // both sides start the world over from the seed the moment they connect
+    fn connect(&mut self, seed: u64, sim: &mut Simulation) {
+        info!("connected to {}", self.peer.map_or("?".to_owned(), |peer| peer.to_string()));
+        self.seed = Some(seed);
+        rand::srand(seed);
+        sim.reset();
+        let (x, y) = (screen_width() / 2., screen_height() / 2. + 40.);
+        sim.world.run(|mut entities: EntitiesViewMut,
+                       mut boats: ViewMut<RemoteBoat>,
+                       mut colliders: ViewMut<Collider>| {
+            entities.add_entity((&mut boats, &mut colliders),
+                                (RemoteBoat { boat: new_boat(x, y, 0., 0.), controls: BoatControls::default() },
+                                 Collider { center: Vec2::new(x, y), radius: BOAT_RADIUS }));
+        });
+    }
+
+    // call every frame before the step: reads whatever's arrived, and keeps
+    // knocking until there's someone on the other end
+    pub fn poll(&mut self, sim: &mut Simulation) {
+        let mut buffer = [0u8; MAX_PACKET];
+        while let Ok((len, from)) = self.socket.recv_from(&mut buffer) {
+            let message = match serde_json::from_slice::<Message>(&buffer[..len]) {
+                Ok(message) => message,
+                Err(_) => continue,
+            };
+            // once there's a peer, nobody else gets a say
+            if self.peer.map_or(false, |peer| peer != from) {
+                continue;
+            }
+            self.last_heard = date::now();
+            match message {
+                Message::Hello if self.role == Role::Host => {
+                    self.peer = Some(from);
+                    let seed = match self.seed {
+                        Some(seed) => seed,
+                        None => {
+                            let seed = date::now() as u64;
+                            self.connect(seed, sim);
+                            seed
+                        }
+                    };
+                    self.send(&Message::Welcome { seed });
+                }
+                Message::Welcome { seed } if self.role == Role::Joining && self.seed.is_none() => {
+                    self.connect(seed, sim);
+                }
+                Message::Controls { first, frames, have } => {
+                    self.acked = self.acked.max(have);
+                    let already = self.remote.len();
+                    if first <= already {
+                        self.remote.extend(frames.into_iter().skip(already - first));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if self.seed.is_none() {
+            if self.role == Role::Joining {
+                self.send(&Message::Hello);
+            }
+            return;
+        }
+        let first = self.acked.min(self.local.len());
+        let end = (first + MAX_FRAMES_PER_PACKET).min(self.local.len());
+        self.send(&Message::Controls { first, frames: self.local[first..end].to_vec(), have: self.remote.len() });
+    }
+
+    // before each tick (see Simulation::step_gated): false holds the tick
+    // back until the other side's controls for it turn up
+    pub fn before_tick(&mut self, world: &World) -> bool {
+        let seed = match self.seed {
+            Some(seed) => seed,
+            None => return false,
+        };
+        // what we're doing now, for INPUT_DELAY ticks' time
+        if self.local.len() == self.tick + INPUT_DELAY {
+            let frame = world.run(|mut controls: ViewMut<BoatControls>, ids: View<PlayerId>, mut tools: UniqueViewMut<ToolCommands>| {
+                let mut frame = empty_frame();
+                frame.tools = tools.0.drain(..).collect();
+                if let Some((controls, _)) = (&mut controls, &ids).iter().find(|(_, id)| id.is_first()) {
+                    frame.turn = controls.turn;
+                    frame.thrust = controls.thrust;
+                    frame.fire = controls.fire;
+                    frame.commands = controls.commands.drain(..).collect();
+                }
+                frame
+            });
+            self.local.push(frame);
+        }
+        let remote = match self.remote.get(self.tick) {
+            Some(remote) => remote,
+            None => return false,
+        };
+        let local = &self.local[self.tick];
+        // the host's boat is the usual one on both sides
+        let (host, joiner) = match self.role {
+            Role::Host => (local, remote),
+            Role::Joining => (remote, local),
+        };
+        world.run(|mut controls: ViewMut<BoatControls>,
+                   ids: View<PlayerId>,
+                   mut tools: UniqueViewMut<ToolCommands>,
+                   mut boats: ViewMut<RemoteBoat>| {
+            for (controls, id) in (&mut controls, &ids).iter() {
+                if id.is_first() {
+                    apply(controls, host);
+                }
+            }
+            tools.0 = host.tools.iter().chain(joiner.tools.iter()).copied().collect();
+            for boat in (&mut boats).iter() {
+                apply(&mut boat.controls, joiner);
+            }
+        });
+        rand::srand(seed.wrapping_add(self.tick as u64));
+        self.tick += 1;
+        true
+    }
+
+    // what's going on with the connection, along the bottom of the screen
+    pub fn draw_status(&self) {
+        let status = if self.seed.is_none() {
+            match self.role {
+                Role::Host => format!("waiting for a player on port {}", self.socket.local_addr().map_or(0, |a| a.port())),
+                Role::Joining => "joining...".to_owned(),
+            }
+        } else if date::now() - self.last_heard > TIMEOUT_SECONDS {
+            "lost the other player, waiting...".to_owned()
+        } else if self.remote.len() <= self.tick {
+            "waiting for the other player...".to_owned()
+        } else {
+            return;
+        };
+        let width = measure_text(&status, None, 20, 1.).width;
+        draw_text(&status, screen_width() / 2. - width / 2., screen_height() - 30., 20., YELLOW);
+    }
+}
+
+fn apply(controls: &mut BoatControls, frame: &DemoFrame) {
+    controls.turn = frame.turn;
+    controls.thrust = frame.thrust;
+    controls.fire = frame.fire;
+    controls.commands = frame.commands.clone();
+}
+
+pub fn update_remote_boats(mut boats: ViewMut<RemoteBoat>,
+                           mut colliders: ViewMut<Collider>,
+                           mut effects: UniqueViewMut<Effects>,
+                           map: UniqueView<Cells>,
+                           grid: UniqueView<GridGeometry>,
+                           tuning: UniqueView<Tuning>,
+                           weather: UniqueView<Weather>,
+                           level: UniqueView<Level>) {
+    let _t = profiling::scope("update_remote_boats");
+    let control = weather.control(level.seconds());
+    for (remote, collider) in (&mut boats, &mut colliders).iter() {
+        let RemoteBoat { boat, controls } = remote;
+        steer(boat, controls, &mut effects, &tuning, control);
+        boat.advance(&map, &grid);
+        boat.settle_heading(tuning.hull_turn);
+        collider.center = Vec2::new(boat.loc.x as f32, boat.loc.y as f32);
+    }
+}
+
+// world space
+pub fn draw_remote_boats(_screen: UniqueViewMut<Screen>, boats: View<RemoteBoat>, clock: UniqueView<SimClock>) {
+    for remote in boats.iter() {
+        remote.boat.render(clock.alpha());
+    }
+}
@@ -0,0 +1,147 @@
+// playing the sim live from a controller: `--osc PORT` listens for OSC
+// messages over UDP (TouchOSC, Lemur, a MIDI-to-OSC bridge, anything that
+// sends them) and turns them into forces on the fluid:
+//
+//   /force x y dx dy     push the flow at (x, y) in the direction (dx, dy).
+//                        x and y go 0 to 1 across the world, dx and dy -1 to 1
+//   /turbulence amount   0 to 1, random pushes all over, every frame, until
+//                        it's set back to 0
+//   /palette hue         0 to 1, turns the heatmap and streamline colours
+//                        round the colour wheel (see visualization.rs)
+//
+// arguments can be floats or ints. bundles are opened up, anything else is
+// ignored.
+
+use std::net::UdpSocket;
+
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::demo::path_after;
+use crate::grid::GridGeometry;
+use crate::profiling;
+use crate::tools::stir;
+use crate::visualization::Visualization;
+use crate::{Cells, Screen};
+
+// how far a /force reaches, in cells
+const FORCE_RADIUS_CELLS: f32 = 2.;
+// world units a frame of motion that a full /force is like
+const FORCE_STRENGTH: f32 = 8.;
+// pushes a frame at full turbulence, and how hard each is
+const TURBULENCE_PUSHES: usize = 12;
+const TURBULENCE_STRENGTH: f32 = 6.;
+const MAX_PACKET: usize = 4096;
+
+#[derive(Component, Default)]
+pub struct OscInput {
+    socket: Option<UdpSocket>,
+    turbulence: f32,
+}
+
+impl OscInput {
+    pub fn from_args() -> OscInput {
+        let port = match path_after("--osc") {
+            Some(port) => port,
+            None => return OscInput::default(),
+        };
+        let socket = UdpSocket::bind(format!("0.0.0.0:{}", port))
+            .and_then(|socket| socket.set_nonblocking(true).map(|_| socket));
+        match socket {
+            Ok(socket) => {
+                info!("listening for OSC on port {}", port);
+                OscInput { socket: Some(socket), turbulence: 0. }
+            }
+            Err(err) => {
+                warn!("couldn't listen for OSC on port {}: {}", port, err);
+                OscInput::default()
+            }
+        }
+    }
+}
+
+// an OSC string: nul terminated, padded out to a multiple of four bytes
+fn read_string(bytes: &[u8]) -> Option<(&str, &[u8])> {
+    let end = bytes.iter().position(|b| *b == 0)?;
+    let padded = (end + 4) & !3;
+    let text = std::str::from_utf8(&bytes[..end]).ok()?;
+    Some((text, bytes.get(padded..)?))
+}
+
+fn read_u32(bytes: &[u8]) -> Option<(u32, &[u8])> {
+    let word = bytes.get(..4)?;
+    Some((u32::from_be_bytes([word[0], word[1], word[2], word[3]]), &bytes[4..]))
+}
+
+// the messages in a packet, as (address, numeric arguments)
+fn decode(packet: &[u8], messages: &mut Vec<(String, Vec<f32>)>) -> Option<()> {
+    if packet.starts_with(b"#bundle\0") {
+        // the tag, then an 8 byte time tag, then size-prefixed elements
+        let mut rest = packet.get(16..)?;
+        while !rest.is_empty() {
+            let (size, after) = read_u32(rest)?;
+            let element = after.get(..size as usize)?;
+            decode(element, messages);
+            rest = &after[size as usize..];
+        }
+        return Some(());
+    }
+    let (address, rest) = read_string(packet)?;
+    let (tags, mut rest) = read_string(rest)?;
+    let mut args = vec![];
+    for tag in tags.strip_prefix(',')?.chars() {
+        let (word, after) = read_u32(rest)?;
+        rest = after;
+        match tag {
+            'f' => args.push(f32::from_bits(word)),
+            'i' => args.push(word as i32 as f32),
+            // anything wider or stranger, give up on the rest
+            _ => break,
+        }
+    }
+    messages.push((address.to_owned(), args));
+    Some(())
+}
+
+pub fn read_osc(mut osc: UniqueViewMut<OscInput>,
+                mut map: UniqueViewMut<Cells>,
+                mut vis: UniqueViewMut<Visualization>,
+                grid: UniqueView<GridGeometry>,
+                _screen: UniqueView<Screen>) {
+    let _t = profiling::scope("read_osc");
+    let mut messages = vec![];
+    if let Some(socket) = osc.socket.as_ref() {
+        let mut buffer = [0u8; MAX_PACKET];
+        while let Ok(len) = socket.recv(&mut buffer) {
+            decode(&buffer[..len], &mut messages);
+        }
+    } else {
+        return;
+    }
+    let radius = FORCE_RADIUS_CELLS * grid.cell_width.max(grid.cell_height);
+    for (address, args) in messages.iter() {
+        match (address.as_str(), args.as_slice()) {
+            ("/force", [x, y, dx, dy, ..]) => {
+                let at = Vec2::new(x * grid.width, y * grid.height);
+                stir(&mut map, &grid, at, Vec2::new(*dx, *dy) * FORCE_STRENGTH, radius);
+            }
+            ("/turbulence", [amount, ..]) => osc.turbulence = amount.max(0.).min(1.),
+            ("/palette", [hue, ..]) => vis.hue = hue.rem_euclid(1.),
+            _ => {}
+        }
+    }
+    stir_randomly(&mut map, &grid, osc.turbulence);
+}
+
+// `amount` (0 to 1) of turbulence for a frame: pushes in random directions at
+// random places (also used by the music visualizer, see visualizer.rs)
+pub fn stir_randomly(map: &mut Cells, grid: &GridGeometry, amount: f32) {
+    let radius = FORCE_RADIUS_CELLS * grid.cell_width.max(grid.cell_height);
+    let pushes = (amount * TURBULENCE_PUSHES as f32).round() as usize;
+    for _ in 0..pushes {
+        let at = Vec2::new(rand::gen_range(0., grid.width), rand::gen_range(0., grid.height));
+        let angle = rand::gen_range(0., std::f32::consts::PI * 2.);
+        let push = Vec2::new(angle.cos(), angle.sin()) * TURBULENCE_STRENGTH * amount;
+        stir(map, grid, at, push, radius);
+    }
+}
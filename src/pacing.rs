@@ -0,0 +1,138 @@
+// frame pacing, for measuring performance under the same conditions each time
+//
+//   vsync     - presented whenever the platform presents (miniquad picks the
+//               swap interval at startup, so this is as close to toggling
+//               vsync as we can get from here)
+//   capped    - sleeping off whatever's left of the frame's budget (native
+//               only, the browser won't let us sleep)
+//   uncapped  - keep ticking the sim until the frame's budget is used up, so
+//               the ticks per second show what the sim can actually do
+//
+// capped and vsync the sim runs as many ticks as the frame owes (see
+// clock.rs); uncapped the clock's free running, and it's tick_again that says
+// when the frame's had enough. either way it's only the ticks that repeat, the
+// input and the drawing are once a frame.
+//
+// pick one with --fps-cap N or --uncapped, and cycle through them with F4.
+// outside vsync mode there's a fps / ticks per second readout in the bottom left.
+//
+// this is driven from the main loop rather than as systems, since it decides
+// how many times the workload runs.
+
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+
+// what F4 switches to when there's no --fps-cap
+const DEFAULT_CAP: f32 = 30.;
+// how much of a frame uncapped mode spends stepping before it presents
+const UNCAPPED_BUDGET: f64 = 1. / 60.;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PacingMode {
+    Vsync,
+    Capped(f32),
+    Uncapped,
+}
+
+pub struct FramePacing {
+    pub mode: PacingMode,
+    cap: f32,
+    frame_start: f64,
+    // counts for the current one second window, and the rates from the last one
+    window_start: f64,
+    frames: u32,
+    ticks: u32,
+    fps: u32,
+    ticks_per_second: u32,
+    // how many times tick_again's been asked this frame
+    asked: u32,
+}
+
+impl FramePacing {
+    pub fn from_args() -> FramePacing {
+        let args: Vec<String> = std::env::args().collect();
+        let cap = args.iter().position(|arg| arg == "--fps-cap")
+            .and_then(|i| args.get(i + 1))
+            .and_then(|n| match n.parse::<f32>() {
+                Ok(cap) if cap.is_finite() && cap > 0. => Some(cap),
+                _ => {
+                    warn!("couldn't make sense of --fps-cap {}", n);
+                    None
+                }
+            });
+        let mode = if args.iter().any(|arg| arg == "--uncapped") {
+            PacingMode::Uncapped
+        } else if let Some(cap) = cap {
+            PacingMode::Capped(cap)
+        } else {
+            PacingMode::Vsync
+        };
+        let now = date::now();
+        FramePacing {
+            mode,
+            cap: cap.unwrap_or(DEFAULT_CAP),
+            frame_start: now,
+            window_start: now,
+            frames: 0,
+            ticks: 0,
+            fps: 0,
+            ticks_per_second: 0,
+            asked: 0,
+        }
+    }
+
+    pub fn cycle_mode(&mut self) {
+        self.mode = match self.mode {
+            PacingMode::Vsync => PacingMode::Capped(self.cap),
+            PacingMode::Capped(_) => PacingMode::Uncapped,
+            PacingMode::Uncapped => PacingMode::Vsync,
+        };
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame_start = date::now();
+        self.asked = 0;
+    }
+
+    // before each tick (see Simulation::step_gated): uncapped, whether there's
+    // still time left in the frame for another one (there's always the first)
+    pub fn tick_again(&mut self) -> bool {
+        self.asked += 1;
+        self.mode != PacingMode::Uncapped || self.asked == 1 || date::now() - self.frame_start < UNCAPPED_BUDGET
+    }
+
+    // the ticks the frame actually ran, for the readout
+    pub fn count_ticks(&mut self, ticks: u32) {
+        self.ticks += ticks;
+    }
+
+    // call just before next_frame: sleeps off the cap and rolls the counters over
+    pub fn end_frame(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let PacingMode::Capped(fps) = self.mode {
+            let left = 1. / fps as f64 - (date::now() - self.frame_start);
+            if left > 0. {
+                std::thread::sleep(std::time::Duration::from_secs_f64(left));
+            }
+        }
+        self.frames += 1;
+        let now = date::now();
+        if now - self.window_start >= 1. {
+            self.fps = self.frames;
+            self.ticks_per_second = self.ticks;
+            self.frames = 0;
+            self.ticks = 0;
+            self.window_start = now;
+        }
+    }
+
+    pub fn draw_readout(&self) {
+        let mode = match self.mode {
+            PacingMode::Vsync => return,
+            PacingMode::Capped(fps) => format!("capped at {}", fps),
+            PacingMode::Uncapped => "uncapped".to_owned(),
+        };
+        let line = format!("{} fps, {} ticks/s ({}, F4)", self.fps, self.ticks_per_second, mode);
+        draw_text(&line, 8., screen_height() - 8., 16., WHITE);
+    }
+}
@@ -0,0 +1,234 @@
+// the particles: the water's tracers. each one's carried along by the flow
+// where it is (update_particles_vectors), moves and bounces off anything solid
+// (move_particle), and its velocity goes back into the flow of the cell it's
+// in (see fluid.rs). particle_store.rs has the same loops over flat arrays.
+//
+// most are water, but a particle has a kind, and the others move through the
+// same flow differently: smoke off the boat's engine (see wake.rs) follows the
+// flow closely without pushing it, wreckage from a sunk enemy (see enemies.rs)
+// is heavy, coasting on and shoving the water about, and its sparks fly
+// straight out. those three burn out after their lifetime, fading as they go
+// (age_particles), and are drawn in their own colours.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, ViewMut};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::accessibility::Accessibility;
+use crate::clock::TICK;
+use crate::fluid::Cells;
+use crate::geometry::wrap_coord;
+use crate::grid::GridGeometry;
+use crate::pool::ParticlePool;
+use crate::profiling;
+use crate::tuning::Tuning;
+use crate::{lerp, Point2, Real};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ParticleKind {
+    #[default]
+    Water,
+    Smoke,
+    Debris,
+    Spark,
+}
+
+impl ParticleKind {
+    // how many water particles' worth it counts for in its cell's flow (see
+    // FlowAccumulator). 0 rides the flow without changing it
+    pub fn weight(self) -> u32 {
+        match self {
+            ParticleKind::Water => 1,
+            ParticleKind::Smoke | ParticleKind::Spark => 0,
+            ParticleKind::Debris => 3,
+        }
+    }
+
+    // times the tuning's cell_to_particle: how hard the flow pulls it along
+    pub fn drag(self) -> f32 {
+        match self {
+            ParticleKind::Water => 1.,
+            ParticleKind::Smoke => 3.,
+            ParticleKind::Debris => 0.3,
+            ParticleKind::Spark => 0.1,
+        }
+    }
+
+    // seconds until it's gone, None for never
+    pub fn lifetime(self) -> Option<f32> {
+        match self {
+            ParticleKind::Water => None,
+            ParticleKind::Smoke => Some(2.5),
+            ParticleKind::Debris => Some(8.),
+            ParticleKind::Spark => Some(0.6),
+        }
+    }
+
+    // None for water, which is coloured by how fast it's going
+    pub fn color(self) -> Option<Color> {
+        match self {
+            ParticleKind::Water => None,
+            ParticleKind::Smoke => Some(Color::new(0.7, 0.7, 0.7, 0.8)),
+            ParticleKind::Debris => Some(Color::new(0.55, 0.35, 0.2, 1.)),
+            ParticleKind::Spark => Some(Color::new(1., 0.8, 0.2, 1.)),
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Particle {
+    pub velocity: Vec2,
+    pub position: Point2,
+    pub size: f32,
+    // the cell this particle is in, kept up to date by update_pos
+    pub cell_ix: usize,
+    pub kind: ParticleKind,
+    // seconds since it was spawned (only counted for the kinds that burn out)
+    pub age: f32,
+}
+
+impl Particle {
+    pub fn update_pos(&mut self, grid: &GridGeometry, map: &Cells) -> () {
+        let (old_position, old_cell_ix) = (self.position, self.cell_ix);
+        self.position.x = self.position.x + self.velocity.x as Real;
+        self.position.y = self.position.y + self.velocity.y as Real;
+
+        // wrap position to screen
+        self.position.x = wrap_coord(self.position.x, grid.width as Real);
+        self.position.y = wrap_coord(self.position.y, grid.height as Real);
+
+        // most steps don't leave the cell, so only redo the lookup when it did
+        let (x, y) = (self.position.x as f32, self.position.y as f32);
+        if !grid.contains(self.cell_ix, x, y) {
+            self.cell_ix = grid.index_at(x, y);
+        }
+
+        // ran into something solid, bounce back off it
+        if map.all_cells[self.cell_ix].is_solid() {
+            self.position = old_position;
+            self.cell_ix = old_cell_ix;
+            self.velocity = -self.velocity;
+        }
+    }
+
+    pub fn get_cell_index(&self) -> usize {
+        self.cell_ix
+    }
+
+    // `pull` is how strongly a water particle's pulled toward the flow where it
+    // is (other kinds by their drag)
+    pub fn update_velocity_from_flow(&mut self, flow: Vec2, pull: f32) {
+        let pull = (pull * self.kind.drag()).min(1.);
+        self.velocity.x = lerp (self.velocity.x, flow.x, pull);
+        self.velocity.y = lerp (self.velocity.y, flow.y, pull);
+    }
+
+    pub fn of_kind(mut self, kind: ParticleKind) -> Particle {
+        self.kind = kind;
+        self
+    }
+
+    // the speed colour for water, or the kind's own, fading out toward the
+    // end of its lifetime
+    pub fn color(&self, access: &Accessibility) -> Color {
+        let mut color = self.kind.color().unwrap_or_else(|| access.speed_color(self.velocity));
+        if let Some(lifetime) = self.kind.lifetime() {
+            color.a *= (1. - self.age / lifetime).max(0.).min(1.);
+        }
+        color
+    }
+
+    // fn update_velocity_from_mouse(&mut self, x: f32, y: f32) {
+    //     self.velocity.x = lerp (self.velocity.x, x, 0.02);
+    //     self.velocity.y = lerp (self.velocity.y, y, 0.02);
+    // }
+
+    // where it's drawn, like Boat::drawn_loc
+    pub fn drawn_position(&self, alpha: f32) -> Vec2 {
+        Vec2::new(self.position.x as f32, self.position.y as f32) - self.velocity * (1. - alpha)
+    }
+
+    // render a particle and its tail
+    pub fn render(&self, line_length_multiplier: f32, access: &Accessibility, alpha: f32) {
+        let Vec2 { x, y } = self.drawn_position(alpha);
+        let indicator_line_x = x + self.velocity.x * line_length_multiplier;
+        let indicator_line_y = y + self.velocity.y * line_length_multiplier;
+        let line_color = self.color(access);
+        draw_line(x, y, indicator_line_x, indicator_line_y, access.particle_width, line_color);
+        // draw_line(self.position.x, self.position.y,self.position.x + 1., self.position.y + 1., 5., WHITE);
+        //TODO: lil arrows lines!
+        //draw_line(indicatorLineX, indicatorLineY, 0., 0., 0.5, BLACK);
+        //draw_circle(self.position.x, self.position.y, 1.0, BLACK);
+        // where color = BLUE, GREEN, YELLOW, ... https://docs.rs/macroquad/0.3.8/macroquad/color/index.html
+        // println!("particle: ({}, {}) v: ({}, {})", particle.position.x, particle.position.y, particle.velocity.x, particle.velocity.y );
+    }
+}
+
+/// generates a new random particle.
+pub fn new_particle(grid: &GridGeometry) -> Particle {
+    new_particle_at(grid,
+                    rand::gen_range(0., grid.width),
+                    rand::gen_range(0., grid.height),
+                    rand::gen_range(-1., 1.),
+                    rand::gen_range(-1., 1.))
+}
+
+pub fn new_particle_at(grid: &GridGeometry, x: f32, y: f32, vx: f32, vy: f32) -> Particle {
+    Particle {position: Point2 {x: x as Real, y: y as Real},
+              size: 1.,
+              velocity: Vec2::new(vx, vy),
+              cell_ix: grid.index_at(x, y),
+              kind: ParticleKind::Water,
+              age: 0.}
+}
+
+pub fn move_particle(mut particles: ViewMut<Particle>,
+                     grid: UniqueView<GridGeometry>,
+                     map: UniqueView<Cells>) {
+    let _t = profiling::scope("move_particle");
+    #[cfg(feature = "parallel")]
+    (&mut particles).par_iter().for_each(|particle| particle.update_pos(&grid, &map));
+    #[cfg(not(feature = "parallel"))]
+    for particle in (&mut particles).iter() {
+        particle.update_pos(&grid, &map);
+    }
+}
+
+// update each particle's vector according to the flow where it is
+pub fn update_particles_vectors(mut particles: ViewMut<Particle>,
+                                map:UniqueView<Cells>,
+                                grid: UniqueView<GridGeometry>,
+                                tuning: UniqueView<Tuning>) {
+    let _t = profiling::scope("update_particles_vectors");
+    let pull = tuning.cell_to_particle;
+    #[cfg(feature = "parallel")]
+    (&mut particles).par_iter().for_each(|particle| {
+        let flow = map.sample_flow(&grid, particle.position.x as f32, particle.position.y as f32);
+        particle.update_velocity_from_flow(flow, pull);
+    });
+    #[cfg(not(feature = "parallel"))]
+    for particle in (&mut particles).iter() {
+        // update particle's vector according to the flow around it
+        let flow = map.sample_flow(&grid, particle.position.x as f32, particle.position.y as f32);
+        particle.update_velocity_from_flow(flow, pull);
+    }
+}
+
+// the kinds that burn out get a tick older, and go once they're past their
+// lifetime (back to the pool, see pool.rs)
+pub fn age_particles(mut particles: ViewMut<Particle>, mut pool: UniqueViewMut<ParticlePool>) {
+    let _t = profiling::scope("age_particles");
+    let mut expired: Vec<EntityId> = vec![];
+    for (id, particle) in (&mut particles).iter().with_id() {
+        if let Some(lifetime) = particle.kind.lifetime() {
+            particle.age += TICK;
+            if particle.age >= lifetime {
+                expired.push(id);
+            }
+        }
+    }
+    pool.despawn_bulk(&mut particles, &expired);
+}
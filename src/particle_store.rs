@@ -0,0 +1,215 @@
+// struct-of-arrays copy of the particles for the hot loops
+//
+// the Particle components stay the source of truth for gameplay (spawning,
+// rendering, ...). each step they're gathered into flat Vec<f32>s, the
+// integration and grid transfer loops run over those, and the results are
+// scattered back into the components before anything else reads them.
+// particles that aren't water (see ParticleKind) are few, so the kernels
+// treat everything as water and those get put right after, one at a time.
+//
+// with the parallel feature every pass over the arrays (the kernels, sampling
+// the flow, bouncing off walls and summing into the grid) is split into
+// CHUNK sized runs on the rayon pool, and the grid sums are merged in order
+// so a step comes out the same however the threads are scheduled.
+
+use macroquad::prelude::*;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+use crate::grid::GridGeometry;
+use crate::kernels;
+use crate::profiling;
+#[cfg(feature = "parallel")]
+use crate::kernels::CHUNK;
+use crate::tuning::Tuning;
+use crate::{lerp, wrap_coord, Cells, FlowAccumulator, NextCells, Particle, ParticleKind, Real};
+
+#[derive(Component, Default)]
+pub struct ParticleStore {
+    pub x: Vec<Real>,
+    pub y: Vec<Real>,
+    pub vx: Vec<f32>,
+    pub vy: Vec<f32>,
+    pub kind: Vec<ParticleKind>,
+    // scratch filled in during the step: each particle's cell, and that cell's flow
+    pub cell_ix: Vec<usize>,
+    pub flow_x: Vec<f32>,
+    pub flow_y: Vec<f32>,
+}
+
+impl ParticleStore {
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    // what the arrays have allocated, for the memory stats
+    pub fn capacity_bytes(&self) -> usize {
+        (self.x.capacity() + self.y.capacity()) * std::mem::size_of::<Real>()
+            + (self.vx.capacity() + self.vy.capacity() + self.flow_x.capacity() + self.flow_y.capacity())
+                * std::mem::size_of::<f32>()
+            + self.cell_ix.capacity() * std::mem::size_of::<usize>()
+            + self.kind.capacity() * std::mem::size_of::<ParticleKind>()
+    }
+
+    pub fn clear(&mut self) {
+        self.x.clear();
+        self.y.clear();
+        self.vx.clear();
+        self.vy.clear();
+        self.kind.clear();
+        self.cell_ix.clear();
+        self.flow_x.clear();
+        self.flow_y.clear();
+    }
+
+    pub fn push(&mut self, particle: &Particle) {
+        self.x.push(particle.position.x);
+        self.y.push(particle.position.y);
+        self.vx.push(particle.velocity.x);
+        self.vy.push(particle.velocity.y);
+        self.kind.push(particle.kind);
+        self.cell_ix.push(particle.get_cell_index());
+        self.flow_x.push(0.);
+        self.flow_y.push(0.);
+    }
+}
+
+// copy the particle components into the store (start of the step)
+pub fn gather_particle_store(particles: View<Particle>,
+                             mut store: UniqueViewMut<ParticleStore>) {
+    let _t = profiling::scope("gather_particle_store");
+    store.clear();
+    for particle in particles.iter() {
+        store.push(particle);
+    }
+}
+
+// write positions and velocities back into the components (end of the hot loops).
+// nothing adds or removes particles between the gather and the scatter, so the
+// storage iterates in the same order both times.
+pub fn scatter_particle_store(mut particles: ViewMut<Particle>,
+                              store: UniqueView<ParticleStore>) {
+    let _t = profiling::scope("scatter_particle_store");
+    for (i, particle) in (&mut particles).iter().enumerate() {
+        particle.position.x = store.x[i];
+        particle.position.y = store.y[i];
+        particle.velocity.x = store.vx[i];
+        particle.velocity.y = store.vy[i];
+        particle.cell_ix = store.cell_ix[i];
+    }
+}
+
+// SoA version of move_particle. positions only change here, so this is also
+// where each particle's cell index gets refreshed for the rest of the step.
+pub fn soa_move_particles(mut store: UniqueViewMut<ParticleStore>,
+                          grid: UniqueView<GridGeometry>,
+                          map: UniqueView<Cells>) {
+    let _t = profiling::scope("soa_move_particles");
+    let store = &mut *store;
+    #[cfg(feature = "parallel")]
+    {
+        store.x.par_chunks_mut(CHUNK).zip(store.vx.par_chunks(CHUNK))
+            .for_each(|(x, vx)| kernels::advect(x, vx, grid.width as Real));
+        store.y.par_chunks_mut(CHUNK).zip(store.vy.par_chunks(CHUNK))
+            .for_each(|(y, vy)| kernels::advect(y, vy, grid.height as Real));
+        store.cell_ix.par_chunks_mut(CHUNK)
+            .zip(store.x.par_chunks(CHUNK).zip(store.y.par_chunks(CHUNK)))
+            .for_each(|(out, (x, y))| kernels::cell_indices(&grid, x, y, out));
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        kernels::advect(&mut store.x, &store.vx, grid.width as Real);
+        kernels::advect(&mut store.y, &store.vy, grid.height as Real);
+        kernels::cell_indices(&grid, &store.x, &store.y, &mut store.cell_ix);
+    }
+
+    #[cfg(feature = "parallel")]
+    store.x.par_chunks_mut(CHUNK)
+        .zip(store.y.par_chunks_mut(CHUNK))
+        .zip(store.vx.par_chunks_mut(CHUNK))
+        .zip(store.vy.par_chunks_mut(CHUNK))
+        .zip(store.cell_ix.par_chunks_mut(CHUNK))
+        .for_each(|((((x, y), vx), vy), cell_ix)| bounce_off_solids(&grid, &map, x, y, vx, vy, cell_ix));
+    #[cfg(not(feature = "parallel"))]
+    bounce_off_solids(&grid, &map, &mut store.x, &mut store.y, &mut store.vx, &mut store.vy, &mut store.cell_ix);
+}
+
+// anything that ran into a solid cell steps back out and bounces
+fn bounce_off_solids(grid: &GridGeometry, map: &Cells, x: &mut [Real], y: &mut [Real],
+                     vx: &mut [f32], vy: &mut [f32], cell_ix: &mut [usize]) {
+    for i in 0..x.len() {
+        if map.all_cells[cell_ix[i]].is_solid() {
+            x[i] = wrap_coord(x[i] - vx[i] as Real, grid.width as Real);
+            y[i] = wrap_coord(y[i] - vy[i] as Real, grid.height as Real);
+            vx[i] = -vx[i];
+            vy[i] = -vy[i];
+            cell_ix[i] = grid.index_at(x[i] as f32, y[i] as f32);
+        }
+    }
+}
+
+// the flow under each particle, into `flow_x` and `flow_y`
+fn sample_flows(grid: &GridGeometry, map: &Cells, x: &[Real], y: &[Real], flow_x: &mut [f32], flow_y: &mut [f32]) {
+    for i in 0..x.len() {
+        let flow = map.sample_flow(grid, x[i] as f32, y[i] as f32);
+        flow_x[i] = flow.x;
+        flow_y[i] = flow.y;
+    }
+}
+
+// SoA version of update_grid_flow
+pub fn soa_update_grid_flow(store: UniqueView<ParticleStore>,
+                            mut next: UniqueViewMut<NextCells>) {
+    let _t = profiling::scope("soa_update_grid_flow");
+    let len = next.0.all_cells.len();
+    let add = |acc: FlowAccumulator, i: usize| {
+        acc.add_at(store.cell_ix[i], Vec2::new(store.vx[i], store.vy[i]), store.kind[i].weight())
+    };
+    #[cfg(feature = "parallel")]
+    let acc = FlowAccumulator::par_sum(len, store.len(), add);
+    #[cfg(not(feature = "parallel"))]
+    let acc = (0..store.len()).fold(FlowAccumulator::new(len), add);
+
+    next.0.add_flow_updates(&acc);
+}
+
+// SoA version of update_particles_vectors: sample the flow at each particle,
+// then pull the velocities toward it
+pub fn soa_update_particles_vectors(mut store: UniqueViewMut<ParticleStore>,
+                                    map: UniqueView<Cells>,
+                                    grid: UniqueView<GridGeometry>,
+                                    tuning: UniqueView<Tuning>) {
+    let _t = profiling::scope("soa_update_particles_vectors");
+    let store = &mut *store;
+    let pull = tuning.cell_to_particle;
+    #[cfg(feature = "parallel")]
+    store.flow_x.par_chunks_mut(CHUNK)
+        .zip(store.flow_y.par_chunks_mut(CHUNK))
+        .zip(store.x.par_chunks(CHUNK).zip(store.y.par_chunks(CHUNK)))
+        .for_each(|((flow_x, flow_y), (x, y))| sample_flows(&grid, &map, x, y, flow_x, flow_y));
+    #[cfg(not(feature = "parallel"))]
+    sample_flows(&grid, &map, &store.x, &store.y, &mut store.flow_x, &mut store.flow_y);
+    // the ones that aren't water, with their velocities before the kernels
+    let others: Vec<(usize, f32, f32)> = (0..store.len())
+        .filter(|i| store.kind[*i] != ParticleKind::Water)
+        .map(|i| (i, store.vx[i], store.vy[i]))
+        .collect();
+    #[cfg(feature = "parallel")]
+    {
+        store.vx.par_chunks_mut(CHUNK).zip(store.flow_x.par_chunks(CHUNK))
+            .for_each(|(vx, fx)| kernels::lerp_toward(vx, fx, pull));
+        store.vy.par_chunks_mut(CHUNK).zip(store.flow_y.par_chunks(CHUNK))
+            .for_each(|(vy, fy)| kernels::lerp_toward(vy, fy, pull));
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        kernels::lerp_toward(&mut store.vx, &store.flow_x, pull);
+        kernels::lerp_toward(&mut store.vy, &store.flow_y, pull);
+    }
+    for (i, vx, vy) in others {
+        let pull = (pull * store.kind[i].drag()).min(1.);
+        store.vx[i] = lerp(vx, store.flow_x[i], pull);
+        store.vy[i] = lerp(vy, store.flow_y[i], pull);
+    }
+}
@@ -0,0 +1,254 @@
+// things to pick up, floating on the water: a repair that gives back some
+// health, a boost that makes the engine shove harder for a while, and gems
+// worth points. one turns up every SPAWN_SECONDS somewhere in open water (up
+// to MAX_PICKUPS at once) and drifts off on the flow like a particle does,
+// pulled toward it by the tuning panel's cell to particle pull, so the
+// current decides where they end up and whether the boat can get to them.
+// they sink again after LIFETIME_SECONDS, blinking for the last few.
+//
+// a boat picks one up by running over it (its Collider turns up in the
+// collisions' boat_hits, see collision.rs), which sends a PickedUp event with
+// whose boat it was: that player's score counts the gems (see score.rs), the
+// audio plays the jingle, and the hud shows what's left of a boost under that
+// player's health bar. the level counts them all, for a scenario that's won by
+// collecting them.
+//
+// a scenario's [[spawns]] can put them down at set times and places too.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, EntitiesViewMut, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::clock::{SimClock, TICK};
+use crate::collision::{Collider, Collisions};
+use crate::effects::Effects;
+use crate::events::{Event, Events};
+use crate::geometry::wrap_position;
+use crate::grid::GridGeometry;
+use crate::boat::PlayerId;
+use crate::input::BoatControls;
+use crate::level::Level;
+use crate::profiling;
+use crate::tuning::Tuning;
+use crate::{Boat, Cells, Randomness, Screen};
+
+const SPAWN_SECONDS: f32 = 8.;
+const MAX_PICKUPS: usize = 6;
+const LIFETIME_SECONDS: f32 = 30.;
+// they blink for this long before they go
+const BLINK_SECONDS: f32 = 4.;
+const PICKUP_RADIUS: f32 = 6.;
+// health a repair gives back (of 1)
+const REPAIR: f32 = 0.25;
+// how long a boost lasts, and how much more the engine pushes a tick at full
+// thrust while it does
+const BOOST_SECONDS: f32 = 5.;
+const BOOST_PUSH: f32 = 0.08;
+pub const GEM_POINTS: u32 = 25;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum PickupKind {
+    Repair,
+    Boost,
+    Gem,
+}
+
+impl PickupKind {
+    // gems are the common ones
+    pub fn random() -> PickupKind {
+        match rand::gen_range(0, 4) {
+            0 => PickupKind::Repair,
+            1 => PickupKind::Boost,
+            _ => PickupKind::Gem,
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            PickupKind::Repair => GREEN,
+            PickupKind::Boost => YELLOW,
+            PickupKind::Gem => SKYBLUE,
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Pickup {
+    pub kind: PickupKind,
+    pub pos: Vec2,
+    pub vel: Vec2,
+    // seconds afloat
+    age: f32,
+}
+
+#[derive(Component, Default)]
+pub struct Pickups {
+    // when (in the level's seconds) the next one turns up
+    next_at: f32,
+}
+
+// a player's boost, alongside their boat
+#[derive(Component, Default)]
+pub struct Boost {
+    // ticks of it left
+    pub ticks: u32,
+}
+
+impl Pickup {
+    // and its Collider, to add with it
+    pub fn new(kind: PickupKind, at: Vec2) -> (Pickup, Collider) {
+        (Pickup { kind, pos: at, vel: Vec2::new(0., 0.), age: 0. }, Collider { center: at, radius: PICKUP_RADIUS })
+    }
+}
+
+impl Boost {
+    // 0 to 1, how much is left
+    pub fn left(&self) -> f32 {
+        self.ticks as f32 / (BOOST_SECONDS / TICK)
+    }
+}
+
+pub fn spawn_pickups(mut pickups: UniqueViewMut<Pickups>,
+                     level: UniqueView<Level>,
+                     map: UniqueView<Cells>,
+                     grid: UniqueView<GridGeometry>,
+                     mut entities: EntitiesViewMut,
+                     mut items: ViewMut<Pickup>,
+                     mut colliders: ViewMut<Collider>,
+                     _random: UniqueViewMut<Randomness>) {
+    let _t = profiling::scope("spawn_pickups");
+    let now = level.seconds();
+    if now < pickups.next_at {
+        return;
+    }
+    pickups.next_at = now + SPAWN_SECONDS;
+    if items.iter().count() >= MAX_PICKUPS {
+        return;
+    }
+    // somewhere that isn't a wall, if a few tries find it
+    for _ in 0..20 {
+        let at = Vec2::new(rand::gen_range(0., grid.width), rand::gen_range(0., grid.height));
+        if !map.all_cells[grid.index_at(at.x, at.y)].is_solid() {
+            entities.add_entity((&mut items, &mut colliders), Pickup::new(PickupKind::random(), at));
+            return;
+        }
+    }
+}
+
+// before the boats move: the pickups drift and age, and a boost pushes its boat
+pub fn update_pickups(mut items: ViewMut<Pickup>,
+                      mut colliders: ViewMut<Collider>,
+                      mut boosts: ViewMut<Boost>,
+                      mut boats: ViewMut<Boat>,
+                      mut entities: EntitiesViewMut,
+                      controls: View<BoatControls>,
+                      map: UniqueView<Cells>,
+                      grid: UniqueView<GridGeometry>,
+                      tuning: UniqueView<Tuning>) {
+    let _t = profiling::scope("update_pickups");
+    let mut sunk: Vec<EntityId> = vec![];
+    for (id, (item, collider)) in (&mut items, &mut colliders).iter().with_id() {
+        let flow = map.sample_flow(&grid, item.pos.x, item.pos.y);
+        item.vel += (flow - item.vel) * tuning.cell_to_particle;
+        let moved = wrap_position(item.pos + item.vel, grid.size());
+        if map.all_cells[grid.index_at(moved.x, moved.y)].is_solid() {
+            item.vel = -item.vel;
+        } else {
+            item.pos = moved;
+        }
+        collider.center = item.pos;
+        item.age += TICK;
+        if item.age > LIFETIME_SECONDS {
+            sunk.push(id);
+        }
+    }
+    for id in sunk {
+        items.remove(id);
+        colliders.remove(id);
+        entities.delete_unchecked(id);
+    }
+
+    for (boost, boat, controls) in (&mut boosts, &mut boats, &controls).iter() {
+        if boost.ticks > 0 {
+            boost.ticks -= 1;
+            let heading = Vec2::new(boat.heading().cos(), boat.heading().sin());
+            let vel = boat.vel;
+            boat.vel = vel + heading * BOOST_PUSH * controls.thrust;
+        }
+    }
+}
+
+// after detect_collisions: whatever the boats have run over (the first to get
+// to one has it)
+pub fn collect_pickups(collisions: UniqueView<Collisions>,
+                       mut items: ViewMut<Pickup>,
+                       mut colliders: ViewMut<Collider>,
+                       mut entities: EntitiesViewMut,
+                       mut boats: ViewMut<Boat>,
+                       mut boosts: ViewMut<Boost>,
+                       ids: View<PlayerId>,
+                       mut effects: UniqueViewMut<Effects>,
+                       mut events: UniqueViewMut<Events>,
+                       mut level: UniqueViewMut<Level>) {
+    let _t = profiling::scope("collect_pickups");
+    for (boat_id, id) in collisions.boat_hits.iter() {
+        let (kind, at) = match items.get(*id) {
+            Ok(item) => (item.kind, item.pos),
+            Err(_) => continue,
+        };
+        let player = match ids.get(*boat_id) {
+            Ok(player) => *player,
+            Err(_) => continue,
+        };
+        match kind {
+            PickupKind::Repair => {
+                if let Ok(boat) = (&mut boats).get(*boat_id) {
+                    boat.health = (boat.health + REPAIR).min(1.);
+                }
+            }
+            PickupKind::Boost => {
+                if let Ok(boost) = (&mut boosts).get(*boat_id) {
+                    boost.ticks = (BOOST_SECONDS / TICK) as u32;
+                }
+            }
+            PickupKind::Gem => {}
+        }
+        effects.sparks(at, 10, kind.color());
+        events.send(Event::PickedUp { kind, at, player: player.0 });
+        level.collected += 1;
+        items.remove(*id);
+        colliders.remove(*id);
+        entities.delete_unchecked(*id);
+    }
+}
+
+// world space: a repair's a cross, a boost an arrow and a gem a diamond
+pub fn draw_pickups(_screen: UniqueViewMut<Screen>, items: View<Pickup>, clock: UniqueView<SimClock>) {
+    let r = PICKUP_RADIUS;
+    for item in items.iter() {
+        let left = LIFETIME_SECONDS - item.age;
+        if left < BLINK_SECONDS && (left * 4.).fract() < 0.5 {
+            continue;
+        }
+        let at = item.pos - item.vel * (1. - clock.alpha());
+        let color = item.kind.color();
+        draw_circle(at.x, at.y, r, Color::new(color.r, color.g, color.b, 0.2));
+        match item.kind {
+            PickupKind::Repair => {
+                draw_rectangle(at.x - r * 0.6, at.y - r * 0.2, r * 1.2, r * 0.4, color);
+                draw_rectangle(at.x - r * 0.2, at.y - r * 0.6, r * 0.4, r * 1.2, color);
+            }
+            PickupKind::Boost => {
+                draw_triangle(Vec2::new(at.x, at.y - r * 0.7), Vec2::new(at.x - r * 0.6, at.y + r * 0.5),
+                              Vec2::new(at.x + r * 0.6, at.y + r * 0.5), color);
+            }
+            PickupKind::Gem => {
+                draw_triangle(Vec2::new(at.x, at.y - r * 0.7), Vec2::new(at.x - r * 0.5, at.y),
+                              Vec2::new(at.x + r * 0.5, at.y), color);
+                draw_triangle(Vec2::new(at.x, at.y + r * 0.7), Vec2::new(at.x - r * 0.5, at.y),
+                              Vec2::new(at.x + r * 0.5, at.y), color);
+            }
+        }
+    }
+}
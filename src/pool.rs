@@ -0,0 +1,67 @@
+// recycles particle entities instead of creating and deleting them
+//
+// despawning a particle only strips its Particle component and parks the
+// entity id here; the next spawn reuses it. emitters and other sources of
+// short-lived particles should spawn through this (ideally in bulk) so a
+// steady stream of particles doesn't churn the entity allocator.
+
+use shipyard::{Component, EntitiesViewMut, EntityId, ViewMut};
+
+use crate::Particle;
+
+#[derive(Component, Default)]
+pub struct ParticlePool {
+    free: Vec<EntityId>,
+}
+
+impl ParticlePool {
+    // create `count` empty entities up front, ready to be spawned into
+    pub fn reserve(&mut self, entities: &mut EntitiesViewMut, count: usize) {
+        self.free.reserve(count);
+        for _ in 0..count {
+            self.free.push(entities.add_entity((), ()));
+        }
+    }
+
+    // how many parked entities are waiting to be reused
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    pub fn spawn(&mut self, entities: &mut EntitiesViewMut, particles: &mut ViewMut<Particle>,
+                 particle: Particle) -> EntityId {
+        match self.free.pop() {
+            Some(id) => {
+                entities.add_component(id, &mut *particles, particle);
+                id
+            }
+            None => entities.add_entity(&mut *particles, particle),
+        }
+    }
+
+    // spawn every particle from `new_particles`, returns how many that was
+    pub fn spawn_bulk<I>(&mut self, entities: &mut EntitiesViewMut, particles: &mut ViewMut<Particle>,
+                         new_particles: I) -> usize
+    where
+        I: IntoIterator<Item = Particle>,
+    {
+        let mut count = 0;
+        for particle in new_particles {
+            self.spawn(entities, particles, particle);
+            count += 1;
+        }
+        count
+    }
+
+    pub fn despawn(&mut self, particles: &mut ViewMut<Particle>, id: EntityId) {
+        if particles.remove(id).is_some() {
+            self.free.push(id);
+        }
+    }
+
+    pub fn despawn_bulk(&mut self, particles: &mut ViewMut<Particle>, ids: &[EntityId]) {
+        for id in ids {
+            self.despawn(particles, *id);
+        }
+    }
+}
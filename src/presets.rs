@@ -0,0 +1,49 @@
+// the levels that come with the game, built into the binary. they're ordinary
+// scenario files (see levels/), read through the same code as --level, and
+// picked from the start screen with 1-4 or a click on the name. a preset's
+// placements aren't saved back anywhere when they're edited.
+
+use macroquad::prelude::*;
+
+use crate::scenario::Scenario;
+
+pub struct Preset {
+    pub name: &'static str,
+    text: &'static str,
+}
+
+pub const PRESETS: [Preset; 4] = [
+    Preset { name: "wind tunnel", text: include_str!("../levels/wind_tunnel.toml") },
+    Preset { name: "twin vortices", text: include_str!("../levels/twin_vortices.toml") },
+    Preset { name: "river race", text: include_str!("../levels/river_race.toml") },
+    Preset { name: "mine field", text: include_str!("../levels/mine_field.toml") },
+];
+
+const MENU_KEYS: [KeyCode; 4] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+const ROW_HEIGHT: f32 = 24.;
+
+impl Preset {
+    pub fn scenario(&self) -> Scenario {
+        Scenario::parse(self.name, self.text)
+    }
+}
+
+fn row_rect(i: usize) -> Rect {
+    Rect::new(screen_width() / 2. - 100., screen_height() / 2. + 30. + ROW_HEIGHT * i as f32, 200., ROW_HEIGHT)
+}
+
+// the preset picked on the start screen this frame, if any
+pub fn picked() -> Option<usize> {
+    let (x, y) = mouse_position();
+    let clicked = is_mouse_button_pressed(MouseButton::Left);
+    (0..PRESETS.len()).find(|i| is_key_pressed(MENU_KEYS[*i]) || (clicked && row_rect(*i).contains(Vec2::new(x, y))))
+}
+
+pub fn draw_menu() {
+    let (x, y) = mouse_position();
+    for (i, preset) in PRESETS.iter().enumerate() {
+        let rect = row_rect(i);
+        let color = if rect.contains(Vec2::new(x, y)) { YELLOW } else { GRAY };
+        draw_text(&format!("{}  {}", i + 1, preset.name), rect.x, rect.y + ROW_HEIGHT - 6., 22., color);
+    }
+}
@@ -0,0 +1,123 @@
+// per-system timing, shown as an overlay (F3)
+//
+// each system opens a scope at the top (`let _t = profiling::scope("name");`)
+// which records how long it ran when it's dropped. the samples go into a
+// global list since systems may be running on several threads at once;
+// collect_profile drains them once a step into the Profiler unique, which
+// keeps a rolling window of each system's times for the overlay.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::Screen;
+use crate::window::ScreenInfo;
+
+// how many steps the rolling average covers
+const WINDOW: usize = 60;
+// overlay bar length per millisecond
+const PIXELS_PER_MS: f32 = 40.;
+
+static SAMPLES: Mutex<Vec<(&'static str, f64)>> = Mutex::new(Vec::new());
+
+pub struct ScopeTimer {
+    name: &'static str,
+    start: f64,
+}
+
+// start timing `name`; the time is recorded when the returned timer is dropped
+pub fn scope(name: &'static str) -> ScopeTimer {
+    ScopeTimer { name, start: date::now() }
+}
+
+impl Drop for ScopeTimer {
+    fn drop(&mut self) {
+        let elapsed = date::now() - self.start;
+        SAMPLES.lock().unwrap().push((self.name, elapsed));
+    }
+}
+
+pub struct SystemTiming {
+    pub name: &'static str,
+    // seconds, most recent last
+    pub history: VecDeque<f64>,
+    // over the whole run, for the telemetry report (see telemetry.rs)
+    pub total: f64,
+    pub calls: u64,
+}
+
+impl SystemTiming {
+    pub fn last_ms(&self) -> f64 {
+        self.history.back().copied().unwrap_or(0.) * 1000.
+    }
+
+    pub fn average_ms(&self) -> f64 {
+        if self.history.is_empty() {
+            0.
+        } else {
+            self.history.iter().sum::<f64>() / self.history.len() as f64 * 1000.
+        }
+    }
+
+    pub fn run_average_ms(&self) -> f64 {
+        if self.calls == 0 { 0. } else { self.total / self.calls as f64 * 1000. }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Profiler {
+    pub enabled: bool,
+    // in the order the systems first reported
+    pub systems: Vec<SystemTiming>,
+}
+
+impl Profiler {
+    fn record(&mut self, name: &'static str, seconds: f64) {
+        let timing = match self.systems.iter().position(|t| t.name == name) {
+            Some(i) => &mut self.systems[i],
+            None => {
+                self.systems.push(SystemTiming { name, history: VecDeque::with_capacity(WINDOW), total: 0., calls: 0 });
+                self.systems.last_mut().unwrap()
+            }
+        };
+        if timing.history.len() == WINDOW {
+            timing.history.pop_front();
+        }
+        timing.history.push_back(seconds);
+        timing.total += seconds;
+        timing.calls += 1;
+    }
+}
+
+// everything recorded since the last call, as (name, seconds)
+pub fn take_samples() -> Vec<(&'static str, f64)> {
+    SAMPLES.lock().unwrap().drain(..).collect()
+}
+
+pub fn collect_profile(mut profiler: UniqueViewMut<Profiler>) {
+    for (name, seconds) in take_samples() {
+        profiler.record(name, seconds);
+    }
+}
+
+pub fn draw_profiler(_screen: UniqueViewMut<Screen>, screen: UniqueView<ScreenInfo>, profiler: UniqueView<Profiler>) {
+    if !profiler.enabled {
+        return;
+    }
+    let left = screen.width - 310.;
+    let top = 10.;
+    let row = 14.;
+    draw_rectangle(left - 5., top - 5., 310., profiler.systems.len() as f32 * row + 10.,
+                   Color::new(0., 0., 0., 0.6));
+    for (i, timing) in profiler.systems.iter().enumerate() {
+        let y = top + i as f32 * row;
+        let average = timing.average_ms();
+        draw_rectangle(left + 130., y + 2., (average as f32 * PIXELS_PER_MS).min(80.), row - 4., ORANGE);
+        draw_text(timing.name, left, y + row - 3., 14., WHITE);
+        draw_text(&format!("{:.2} ms (avg {:.2})", timing.last_ms(), average),
+                  left + 215., y + row - 3., 14., WHITE);
+    }
+}
@@ -0,0 +1,103 @@
+// an incompressible solve for the grid on the cpu: `--project` (optionally
+// `--project ITERATIONS`, 20 by default). normally a cell's flow is just the
+// average of the particles that went through it, which lets the water pile up
+// and drift along together. with this on, each step the flow is made
+// divergence free afterwards: the divergence of every water cell, jacobi
+// iterations of the pressure poisson equation, and the pressure gradient
+// taken off the flow. water can't go in or out of a solid cell (its
+// neighbours see no flow there, and the same pressure as their own), so the
+// flow turns along walls and shores and curls up into vortices behind them.
+// an inflow cell's flow is left as it is (it's pushing water in, see
+// fluid.rs's CellType), and an outflow's pressure is held at nothing, so the
+// water's drawn out through it.
+//
+// the pressure is kept from step to step as the next solve's first guess, so
+// a few iterations go a long way. the gpu backend (see gpu.rs) does the same
+// on its own textures.
+
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::demo::path_after;
+use crate::grid::GridGeometry;
+use crate::profiling;
+use crate::{CellType, NextCells};
+
+const DEFAULT_ITERATIONS: usize = 20;
+// changes smaller than this aren't written, so still water stays unallocated
+// (see sparse_grid.rs)
+const NEGLIGIBLE: f32 = 1e-5;
+
+#[derive(Component, Default)]
+pub struct Projection {
+    pub enabled: bool,
+    pub iterations: usize,
+    pressure: Vec<f32>,
+}
+
+impl Projection {
+    pub fn from_args() -> Projection {
+        let enabled = std::env::args().any(|arg| arg == "--project");
+        let iterations = path_after("--project").and_then(|n| n.parse().ok()).unwrap_or(DEFAULT_ITERATIONS);
+        Projection { enabled, iterations, pressure: vec![] }
+    }
+}
+
+// after the particles' flow has gone into the next grid, before it's swapped in
+pub fn project_flow(mut projection: UniqueViewMut<Projection>,
+                    mut next: UniqueViewMut<NextCells>,
+                    grid: UniqueView<GridGeometry>) {
+    let _t = profiling::scope("project_flow");
+    if !projection.enabled {
+        return;
+    }
+    let cells = &mut next.0.all_cells;
+    let solid: Vec<bool> = cells.iter().map(|cell| cell.is_solid()).collect();
+    let kinds: Vec<CellType> = cells.iter().map(|cell| cell.kind).collect();
+    let flow: Vec<Vec2> = cells.iter().map(|cell| cell.flow_v).collect();
+    let (dx, dy) = (grid.cell_width, grid.cell_height);
+    let at = |cell_ix: usize| if solid[cell_ix] { Vec2::new(0., 0.) } else { flow[cell_ix] };
+    let divergence: Vec<f32> = (0..grid.len())
+        .map(|cell_ix| {
+            if solid[cell_ix] {
+                return 0.;
+            }
+            let [left, right, up, down] = grid.neighbours(cell_ix);
+            (at(right).x - at(left).x) / (2. * dx) + (at(down).y - at(up).y) / (2. * dy)
+        })
+        .collect();
+
+    if projection.pressure.len() != grid.len() {
+        projection.pressure = vec![0.; grid.len()];
+    }
+    let (wx, wy) = (1. / (dx * dx), 1. / (dy * dy));
+    let mut pressure = std::mem::take(&mut projection.pressure);
+    let mut scratch = pressure.clone();
+    for _ in 0..projection.iterations {
+        for cell_ix in 0..grid.len() {
+            if solid[cell_ix] || kinds[cell_ix] == CellType::Outflow {
+                scratch[cell_ix] = 0.;
+                continue;
+            }
+            let [left, right, up, down] = grid.neighbours(cell_ix);
+            // a wall has the same pressure as the water beside it, so nothing pushes through
+            let p = |n: usize| if solid[n] { pressure[cell_ix] } else { pressure[n] };
+            scratch[cell_ix] = ((p(left) + p(right)) * wx + (p(up) + p(down)) * wy - divergence[cell_ix])
+                / (2. * wx + 2. * wy);
+        }
+        std::mem::swap(&mut pressure, &mut scratch);
+    }
+
+    for cell_ix in 0..grid.len() {
+        if solid[cell_ix] || kinds[cell_ix] == CellType::Inflow {
+            continue;
+        }
+        let [left, right, up, down] = grid.neighbours(cell_ix);
+        let p = |n: usize| if solid[n] { pressure[cell_ix] } else { pressure[n] };
+        let gradient = Vec2::new((p(right) - p(left)) / (2. * dx), (p(down) - p(up)) / (2. * dy));
+        if gradient.length() > NEGLIGIBLE {
+            cells[cell_ix].flow_v -= gradient;
+        }
+    }
+    projection.pressure = pressure;
+}
@@ -0,0 +1,89 @@
+// a region quadtree over axis-aligned boxes, used as the broad phase for
+// collisions: rather than testing every pair of colliders, each one only gets
+// tested against the handful whose boxes share a node with it
+
+use macroquad::prelude::*;
+
+// a node splits once it holds more than this many items...
+const MAX_ITEMS: usize = 8;
+// ...unless it's already this deep
+const MAX_DEPTH: u32 = 6;
+
+pub struct Quadtree<T> {
+    bounds: Rect,
+    depth: u32,
+    // items that live at this node: all of them for a leaf, or the ones
+    // straddling a split line for an inner node
+    items: Vec<(T, Rect)>,
+    children: Option<Box<[Quadtree<T>; 4]>>,
+}
+
+fn contains_rect(outer: &Rect, inner: &Rect) -> bool {
+    inner.x >= outer.x && inner.y >= outer.y
+        && inner.x + inner.w <= outer.x + outer.w
+        && inner.y + inner.h <= outer.y + outer.h
+}
+
+impl<T: Copy> Quadtree<T> {
+    pub fn new(bounds: Rect) -> Quadtree<T> {
+        Quadtree::with_depth(bounds, 0)
+    }
+
+    fn with_depth(bounds: Rect, depth: u32) -> Quadtree<T> {
+        Quadtree { bounds, depth, items: vec![], children: None }
+    }
+
+    pub fn insert(&mut self, item: T, rect: Rect) {
+        if let Some(children) = self.children.as_mut() {
+            for child in children.iter_mut() {
+                if contains_rect(&child.bounds, &rect) {
+                    child.insert(item, rect);
+                    return;
+                }
+            }
+            self.items.push((item, rect));
+            return;
+        }
+        self.items.push((item, rect));
+        if self.items.len() > MAX_ITEMS && self.depth < MAX_DEPTH {
+            self.split();
+        }
+    }
+
+    fn split(&mut self) {
+        let (x, y) = (self.bounds.x, self.bounds.y);
+        let (w, h) = (self.bounds.w / 2., self.bounds.h / 2.);
+        let depth = self.depth + 1;
+        self.children = Some(Box::new([
+            Quadtree::with_depth(Rect::new(x, y, w, h), depth),
+            Quadtree::with_depth(Rect::new(x + w, y, w, h), depth),
+            Quadtree::with_depth(Rect::new(x, y + h, w, h), depth),
+            Quadtree::with_depth(Rect::new(x + w, y + h, w, h), depth),
+        ]));
+        for (item, rect) in std::mem::take(&mut self.items) {
+            self.insert(item, rect);
+        }
+    }
+
+    // every item whose box overlaps `rect` (and maybe a few more near it)
+    pub fn query(&self, rect: &Rect, out: &mut Vec<T>) {
+        if !self.bounds.overlaps(rect) {
+            return;
+        }
+        for (item, item_rect) in self.items.iter() {
+            if item_rect.overlaps(rect) {
+                out.push(*item);
+            }
+        }
+        if let Some(children) = self.children.as_ref() {
+            for child in children.iter() {
+                child.query(rect, out);
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.children = None;
+    }
+}
@@ -0,0 +1,148 @@
+// the grid's resolution, changed while it runs. with the debug view on (D), X
+// doubles the grid's columns and rows and Z halves them, within the tuning
+// panel's limits. the flow carries over (see Cells::resample: each new cell
+// takes the old grid's blended flow at its centre, and the kind and depth of
+// the old cell it's in), and the particles are put in their new cells. the
+// change goes through the ToolCommands like the particle hotkeys, so a replay
+// or a netplay peer sees it on the same tick.
+//
+// --auto-resize-grid has it resize itself: every couple of seconds it looks
+// at how crowded the cells with particles in are, and doubles the whole grid
+// when there's more than DENSE in each on average, or halves it when there's
+// fewer than SPARSE.
+//
+// neither lasts past a reset, which goes back to the config's cols and rows
+// (the tuning panel's sliders are the way to change those). the --gpu solve
+// follows it onto the new size (see gpu.rs).
+
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, ViewMut};
+
+use crate::grid::GridGeometry;
+use crate::keymap::{Action, KeyBindings};
+use crate::profiling;
+use crate::tools::{ToolCommand, ToolCommands};
+use crate::{Cells, GameMode, GameModeInfo, NextCells, Particle, Screen};
+
+// the tuning panel's grid sliders' range
+const MIN_CELLS: usize = 4;
+const MAX_COLS: usize = 160;
+const MAX_ROWS: usize = 96;
+// how often --auto-resize-grid has a look, in ticks
+const AUTO_TICKS: u32 = 120;
+// particles per occupied cell, on average, for --auto-resize-grid to go
+// finer, or coarser. far enough apart that a doubling doesn't bounce straight
+// back
+const DENSE: f32 = 40.;
+const SPARSE: f32 = 6.;
+
+#[derive(Component, Default)]
+pub struct GridRefiner {
+    pub auto: bool,
+    ticks: u32,
+}
+
+impl GridRefiner {
+    pub fn from_args() -> GridRefiner {
+        GridRefiner { auto: std::env::args().any(|arg| arg == "--auto-resize-grid"), ticks: 0 }
+    }
+}
+
+pub fn finer(cols: usize, rows: usize) -> (usize, usize) {
+    ((cols * 2).min(MAX_COLS), (rows * 2).min(MAX_ROWS))
+}
+
+pub fn coarser(cols: usize, rows: usize) -> (usize, usize) {
+    ((cols / 2).max(MIN_CELLS), (rows / 2).max(MIN_CELLS))
+}
+
+// in the input, with the debug view on
+pub fn refine_keys(mut commands: UniqueViewMut<ToolCommands>,
+                   keys: UniqueView<KeyBindings>,
+                   game_mode: UniqueView<GameModeInfo>,
+                   grid: UniqueView<GridGeometry>,
+                   _screen: UniqueView<Screen>) {
+    if game_mode.game_mode != GameMode::Debug {
+        return;
+    }
+    let (cols, rows) = if keys.pressed(Action::FinerGrid) {
+        finer(grid.cols, grid.rows)
+    } else if keys.pressed(Action::CoarserGrid) {
+        coarser(grid.cols, grid.rows)
+    } else {
+        return;
+    };
+    if (cols, rows) != (grid.cols, grid.rows) {
+        commands.0.push(ToolCommand::Resample { cols, rows });
+    }
+}
+
+// what --auto-resize-grid wants the grid to be, given the particles' cells, if it's
+// not this
+fn auto_size(grid: &GridGeometry, cells: impl Iterator<Item = usize>) -> Option<(usize, usize)> {
+    let mut counts = vec![0u32; grid.len()];
+    for cell_ix in cells {
+        counts[cell_ix] += 1;
+    }
+    let occupied = counts.iter().filter(|count| **count > 0).count();
+    if occupied == 0 {
+        return None;
+    }
+    let crowding = counts.iter().sum::<u32>() as f32 / occupied as f32;
+    let size = if crowding > DENSE {
+        finer(grid.cols, grid.rows)
+    } else if crowding < SPARSE {
+        coarser(grid.cols, grid.rows)
+    } else {
+        return None;
+    };
+    Some(size).filter(|size| *size != (grid.cols, grid.rows))
+}
+
+// first in the tick, before the rest of the ToolCommands (which might put
+// particles on the new grid)
+pub fn resample_grid(mut commands: UniqueViewMut<ToolCommands>,
+                     mut refiner: UniqueViewMut<GridRefiner>,
+                     mut grid: UniqueViewMut<GridGeometry>,
+                     mut map: UniqueViewMut<Cells>,
+                     mut next: UniqueViewMut<NextCells>,
+                     mut particles: ViewMut<Particle>) {
+    let _t = profiling::scope("resample_grid");
+    let mut asked = None;
+    commands.0.retain(|command| match command {
+        ToolCommand::Resample { cols, rows } => {
+            asked = Some((*cols, *rows));
+            false
+        }
+        _ => true,
+    });
+    let mut wanted = asked;
+    if refiner.auto {
+        refiner.ticks += 1;
+        if refiner.ticks >= AUTO_TICKS {
+            refiner.ticks = 0;
+            wanted = wanted.or_else(|| auto_size(&grid, (&particles).iter().map(|particle| particle.cell_ix)));
+        }
+    }
+    let (cols, rows) = match wanted {
+        Some(size) if size != (grid.cols, grid.rows) => size,
+        _ => return,
+    };
+
+    let resized = GridGeometry::new(grid.width, grid.height, cols, rows);
+    regrid(&mut grid, &mut map, &mut next, &mut particles, resized);
+}
+
+// the cells carried over onto `resized`, and the particles put in their new
+// ones (which for a new world size, see window.rs, they've already been
+// moved on to)
+pub fn regrid(grid: &mut GridGeometry, map: &mut Cells, next: &mut NextCells, particles: &mut ViewMut<Particle>,
+              resized: GridGeometry) {
+    // the old cells, stretched over the new world if it's a new size
+    let old = GridGeometry::new(resized.width, resized.height, grid.cols, grid.rows);
+    *map = map.resample(&old, &resized);
+    next.0 = map.clone();
+    for particle in particles.iter() {
+        particle.cell_ix = resized.index_at(particle.position.x as f32, particle.position.y as f32);
+    }
+    *grid = resized;
+}
@@ -0,0 +1,131 @@
+// drawing the world itself: the particles (or their blobs), the debug glyphs
+// and grid lines. the rest of the world (the boats too, see boat.rs) draws
+// itself in its own module's systems, see the render workload in
+// simulation.rs.
+
+use macroquad::prelude::*;
+use shipyard::{IntoIter, UniqueView, UniqueViewMut, View};
+
+use crate::accessibility::Accessibility;
+use crate::batch::MeshBatch;
+use crate::clock::SimClock;
+use crate::daily::Daily;
+use crate::demo::Demo;
+use crate::fluid::{CellType, Cells};
+use crate::grid::GridGeometry;
+use crate::level::Level;
+use crate::particle::Particle;
+use crate::profiling;
+use crate::tuning::Tuning;
+use crate::visualization::{ParticleStyle, Visualization};
+use crate::window::ScreenInfo;
+use crate::{GameMode, GameModeInfo, Screen};
+
+// cells holding more particles than this are drawn as one aggregate glyph
+const LOD_THRESHOLD: u32 = 64;
+
+// debugging utility: draw the grid lines
+fn draw_world_grid(game_mode: &GameModeInfo, grid: &GridGeometry, batch: &mut MeshBatch) {
+    if game_mode.game_mode == GameMode::Debug{
+        for x  in 1..grid.cols {
+            batch.line( x as f32 * grid.cell_width, 0., 
+                       x as f32 * grid.cell_width, grid.height, 0.5, WHITE);
+        }
+        for y  in 1..grid.rows {
+            batch.line(0., y as f32 * grid.cell_height, 
+                    grid.width, y as f32 * grid.cell_height, 0.5, WHITE);
+        }
+    }
+
+}
+
+// render a frame of the world
+// documentation here: https://docs.rs/macroquad/0.3.8/macroquad/
+pub fn render(particles: View<Particle>,
+              map: UniqueView<Cells>, 
+              grid: UniqueView<GridGeometry>,
+              game_mode: UniqueView<GameModeInfo>,
+              tuning: UniqueView<Tuning>,
+              vis: UniqueView<Visualization>,
+              access: UniqueView<Accessibility>,
+              clock: UniqueView<SimClock>,
+              _screen: UniqueViewMut<Screen> )
+{
+    let _t = profiling::scope("render");
+    let alpha = clock.alpha();
+    // the solid cells are drawn underneath, as islands (see islands.rs)
+    // level of detail: count the particles per cell, and draw the crowded cells
+    // as a single blob (sized by count, with an arrow for the mean velocity)
+    // instead of drawing every particle in them. the blobs style draws every
+    // occupied cell that way (see visualization.rs)
+    let lod_threshold = match vis.particles {
+        ParticleStyle::Blobs => 0,
+        _ => LOD_THRESHOLD,
+    };
+    let mut counts = vec![0u32; map.all_cells.len()];
+    let mut velocity_sums = vec![Vec2::new(0., 0.); map.all_cells.len()];
+    for particle in particles.iter() {
+        counts[particle.cell_ix] += 1;
+        velocity_sums[particle.cell_ix] += particle.velocity;
+    }
+    // (in trail mode they've been drawn already, see trails.rs)
+    for particle in particles.iter() {
+        if vis.trails || counts[particle.cell_ix] > lod_threshold {
+            continue;
+        }
+        match vis.particles {
+            ParticleStyle::Trails => particle.render(tuning.trail_length, &access, alpha),
+            ParticleStyle::Dots => {
+                let at = particle.drawn_position(alpha);
+                draw_circle(at.x, at.y, access.particle_width * 2., particle.color(&access))
+            }
+            ParticleStyle::Blobs | ParticleStyle::Hidden => {}
+        }
+    }
+    // the aggregate blobs, debug glyphs, and grid lines all go out as one mesh
+    let mut batch = MeshBatch::new();
+    for cell_ix in 0..counts.len() {
+        if counts[cell_ix] > lod_threshold && vis.particles != ParticleStyle::Hidden && !vis.trails {
+            let mean_velocity = velocity_sums[cell_ix] / counts[cell_ix] as f32;
+            let center = grid.center(cell_ix);
+            let mut blob_color = access.speed_color(mean_velocity);
+            blob_color.a = 0.5;
+            batch.circle(center.x, center.y, (counts[cell_ix] as f32).sqrt(), blob_color);
+            let tip = center + mean_velocity * tuning.trail_length;
+            batch.arrow(center.x, center.y, tip.x, tip.y, 1., access.speed_color(mean_velocity));
+        }
+    }
+    if game_mode.game_mode == GameMode:: Debug{
+        for (cell_ix, cell) in map.all_cells.iter().enumerate() {
+            if !cell.is_asleep() || matches!(cell.kind, CellType::Inflow | CellType::Outflow) {
+                cell.render(grid.center(cell_ix), &mut batch);
+            }
+        }
+    }
+    draw_world_grid(&game_mode, &grid, &mut batch);
+    batch.draw();
+}
+
+// status along the top of the screen (drawn outside the camera)
+pub fn draw_status(_screen: UniqueViewMut<Screen>,
+                   screen: UniqueView<ScreenInfo>,
+                   clock: UniqueView<SimClock>,
+                   demo: UniqueView<Demo>,
+                   vis: UniqueView<Visualization>,
+                   level: UniqueView<Level>,
+                   daily: UniqueView<Daily>,
+                   access: UniqueView<Accessibility>) {
+    let labels: Vec<String> = clock.label().into_iter()
+        .chain(demo.label())
+        .chain(daily.label())
+        .chain(vis.label())
+        .chain(level.label())
+        .collect();
+    for (i, label) in labels.iter().enumerate() {
+        let width = measure_text(label, None, 20, 1.).width;
+        let (x, y) = (screen.width / 2. - width / 2., 20. + i as f32 * 20.);
+        access.text_backing(x, y, width, 20.);
+        draw_text(label, x, y, 20., WHITE);
+    }
+}
+
@@ -0,0 +1,384 @@
+// the scenario file: a level, and the things placed in it that should still
+// be there next time. `--level PATH` picks the file (scenario.toml otherwise).
+// everything in it is optional:
+//
+//   [grid]             cols, rows, and boundary = "wrap" (the default: the
+//                      world wraps round at the edges) or "walls" (a solid
+//                      border)
+//   [[emitters]]       x, y, vx, vy, rate, and spread (degrees either side
+//                      of the aim, about 9 if it's left out)
+//   [[sinks]]          x, y, radius
+//   [[force_fields]]   wind, attractors, repulsors and whirlpools that stay
+//                      put for the whole level (see force_fields.rs)
+//   [[obstacles]]      solid shapes, in pixels: shape = "rect" with x, y, w, h,
+//                      shape = "circle" with x, y, radius, or shape =
+//                      "lsystem" for a fractal one (see lsystem.rs)
+//   [[decorations]]    l-systems drawn on the water, not solid
+//   [terrain]          islands generated from a seed (see terrain.rs)
+//   spawn              [x, y] where the boat starts (the middle otherwise)
+//   checkpoints        [[x, y], ...] to pass through, in order, to win
+//   [[currents]]       scheduled currents and storms (see weather.rs)
+//   [[storms]]
+//   [seasons]
+//   [[enemies]]        the spawn table: at (seconds in), x, y, count
+//   [waves]            enemies that keep coming after that (see enemies.rs):
+//                      first, every (seconds), size and growth
+//   [[spawns]]         everything else that turns up partway through: at
+//                      (seconds in), x, y, and what = "particles" (count
+//                      of them, flung out at up to speed), "pickup" (of a
+//                      kind, random if it's left out) or "drifter" (see
+//                      spawns.rs)
+//   particles          how many particles there are to start with (the
+//                      config's --particles otherwise)
+//   [win]              survive_seconds, sink_particles and/or
+//                      collect_pickups (all the ones given have to be met,
+//                      and the checkpoints too)
+//
+// the emitters and sinks are the ones placed in the edit tool, which get
+// written back to the file whenever they change (leaving the rest alone), and
+// so are the force fields put down in debug mode.
+// there are a few built in as well, see presets.rs.
+
+use std::fs;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{EntitiesViewMut, ViewMut, World};
+
+use crate::demo::path_after;
+use crate::emitters::{Emitter, Sink, DEFAULT_EMITTER_SPREAD};
+use crate::force_fields::ForceField;
+use crate::grid::GridGeometry;
+use crate::pickups::PickupKind;
+use crate::terrain::TerrainDef;
+use crate::weather::{CurrentDef, SeasonsDef, StormDef};
+use crate::lsystem::{distance_to_line, Decoration, Decorations, LSystem};
+use crate::{CellType, Cells};
+
+pub const SCENARIO_PATH: &str = "scenario.toml";
+
+// the file the level comes from, and the edit tool saves to
+pub fn scenario_path() -> String {
+    path_after("--level").unwrap_or_else(|| SCENARIO_PATH.to_string())
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Boundary {
+    Wrap,
+    Walls,
+}
+
+impl Default for Boundary {
+    fn default() -> Boundary {
+        Boundary::Wrap
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct GridDef {
+    pub cols: usize,
+    pub rows: usize,
+    #[serde(default)]
+    pub boundary: Boundary,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct EmitterDef {
+    pub x: f32,
+    pub y: f32,
+    // velocity the particles come out with
+    pub vx: f32,
+    pub vy: f32,
+    // particles per second
+    pub rate: f32,
+    #[serde(default = "default_spread")]
+    pub spread: f32,
+}
+
+fn default_spread() -> f32 {
+    DEFAULT_EMITTER_SPREAD.to_degrees()
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct SinkDef {
+    pub x: f32,
+    pub y: f32,
+    pub radius: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum Obstacle {
+    Rect { x: f32, y: f32, w: f32, h: f32 },
+    Circle { x: f32, y: f32, radius: f32 },
+    // solid within width / 2 of its lines
+    Lsystem {
+        x: f32,
+        y: f32,
+        #[serde(default)]
+        heading: f32,
+        step: f32,
+        #[serde(default = "wall_width")]
+        width: f32,
+        lsystem: LSystem,
+    },
+}
+
+fn wall_width() -> f32 {
+    8.
+}
+
+impl Obstacle {
+    // `lines` are the l-system's, worked out once by the caller (empty for
+    // the other shapes)
+    fn covers(&self, point: Vec2, lines: &[(Vec2, Vec2)]) -> bool {
+        match *self {
+            Obstacle::Rect { x, y, w, h } => Rect::new(x, y, w, h).contains(point),
+            Obstacle::Circle { x, y, radius } => (point - Vec2::new(x, y)).length() < radius,
+            Obstacle::Lsystem { width, .. } => lines.iter().any(|(a, b)| distance_to_line(point, *a, *b) < width / 2.),
+        }
+    }
+
+    fn lines(&self) -> Vec<(Vec2, Vec2)> {
+        match self {
+            Obstacle::Lsystem { x, y, heading, step, lsystem, .. } => lsystem.lines(Vec2::new(*x, *y), *heading, *step),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EnemySpawn {
+    // seconds into the level
+    pub at: f32,
+    pub x: f32,
+    pub y: f32,
+    #[serde(default = "one")]
+    pub count: u32,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(tag = "what", rename_all = "snake_case")]
+pub enum SpawnEvent {
+    Particles {
+        at: f32,
+        x: f32,
+        y: f32,
+        #[serde(default = "burst_count")]
+        count: usize,
+        #[serde(default = "burst_speed")]
+        speed: f32,
+    },
+    Pickup {
+        at: f32,
+        x: f32,
+        y: f32,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        kind: Option<PickupKind>,
+    },
+    Drifter { at: f32, x: f32, y: f32 },
+}
+
+fn burst_count() -> usize {
+    100
+}
+
+fn burst_speed() -> f32 {
+    1.
+}
+
+impl SpawnEvent {
+    // seconds into the level
+    pub fn at(&self) -> f32 {
+        match *self {
+            SpawnEvent::Particles { at, .. } | SpawnEvent::Pickup { at, .. } | SpawnEvent::Drifter { at, .. } => at,
+        }
+    }
+
+    pub fn pos(&self) -> Vec2 {
+        match *self {
+            SpawnEvent::Particles { x, y, .. } | SpawnEvent::Pickup { x, y, .. } | SpawnEvent::Drifter { x, y, .. } => {
+                Vec2::new(x, y)
+            }
+        }
+    }
+}
+
+// a wave of `size` enemies `first` seconds in, then another every `every`
+// seconds, each `growth` bigger than the last
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WaveDef {
+    pub first: f32,
+    pub every: f32,
+    pub size: u32,
+    pub growth: u32,
+}
+
+impl Default for WaveDef {
+    fn default() -> WaveDef {
+        WaveDef { first: 30., every: 30., size: 1, growth: 1 }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct WinCondition {
+    pub survive_seconds: Option<f32>,
+    pub sink_particles: Option<usize>,
+    // of any kind, over the whole level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collect_pickups: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Scenario {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spawn: Option<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checkpoints: Vec<[f32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grid: Option<GridDef>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub terrain: Option<TerrainDef>,
+    #[serde(default)]
+    pub emitters: Vec<EmitterDef>,
+    #[serde(default)]
+    pub sinks: Vec<SinkDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub force_fields: Vec<ForceField>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub obstacles: Vec<Obstacle>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decorations: Vec<Decoration>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub currents: Vec<CurrentDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub storms: Vec<StormDef>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seasons: Option<SeasonsDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub enemies: Vec<EnemySpawn>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waves: Option<WaveDef>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spawns: Vec<SpawnEvent>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub particles: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub win: Option<WinCondition>,
+}
+
+impl Scenario {
+    // a missing file is just an empty scenario; a broken one gets reported and
+    // treated the same way
+    pub fn load(path: &str) -> Scenario {
+        match fs::read_to_string(path) {
+            Ok(text) => Scenario::parse(path, &text),
+            Err(_) => Scenario::default(),
+        }
+    }
+
+    // `name` is just for the warning
+    pub fn parse(name: &str, text: &str) -> Scenario {
+        toml::from_str(text).unwrap_or_else(|err| {
+            warn!("couldn't read scenario {}: {}", name, err);
+            Scenario::default()
+        })
+    }
+
+    pub fn save(&self, path: &str) {
+        let result = toml::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|text| fs::write(path, text).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            warn!("couldn't save scenario {}: {}", path, err);
+        }
+    }
+
+    pub fn spawn_into(&self, world: &World) {
+        world.run(|mut entities: EntitiesViewMut, mut emitters: ViewMut<Emitter>, mut sinks: ViewMut<Sink>,
+                   mut fields: ViewMut<ForceField>| {
+            for def in self.emitters.iter() {
+                entities.add_entity(&mut emitters, Emitter::from_def(def));
+            }
+            for def in self.sinks.iter() {
+                entities.add_entity(&mut sinks, Sink::from_def(def));
+            }
+            for field in self.force_fields.iter() {
+                entities.add_entity(&mut fields, *field);
+            }
+        });
+        world.add_unique(Decorations::new(&self.decorations)).unwrap();
+    }
+
+    // write the edit tool's placements back, keeping the rest of the file
+    pub fn save_placements<'a>(path: &str,
+                               emitters: impl Iterator<Item = &'a Emitter>,
+                               sinks: impl Iterator<Item = &'a Sink>) {
+        let mut scenario = Scenario::load(path);
+        scenario.emitters = emitters.map(Emitter::to_def).collect();
+        scenario.sinks = sinks.map(Sink::to_def).collect();
+        scenario.save(path);
+    }
+
+    // and the force fields, the same way
+    pub fn save_force_fields<'a>(path: &str, fields: impl Iterator<Item = &'a ForceField>) {
+        let mut scenario = Scenario::load(path);
+        scenario.force_fields = fields.copied().collect();
+        scenario.save(path);
+    }
+
+    // the grid the level wants, or the default one
+    pub fn grid(&self, width: f32, height: f32, cols: usize, rows: usize) -> GridGeometry {
+        match &self.grid {
+            Some(def) => GridGeometry::new(width, height, def.cols.max(1), def.rows.max(1)),
+            None => GridGeometry::new(width, height, cols, rows),
+        }
+    }
+
+    // `--generate SEED`: a level that's all terrain
+    pub fn generated() -> Option<Scenario> {
+        Some(Scenario { terrain: Some(TerrainDef::from_args()?), ..Scenario::default() })
+    }
+
+    // fill in the spawn, checkpoints and enemies the terrain places, once the
+    // grid's known
+    pub fn place_terrain(&mut self, grid: &GridGeometry) {
+        if let Some(terrain) = self.terrain.clone() {
+            terrain.place(self, grid);
+        }
+    }
+
+    // where the boat starts
+    pub fn spawn_point(&self, grid: &GridGeometry) -> Vec2 {
+        self.spawn.map_or(Vec2::new(grid.width / 2., grid.height / 2.), |[x, y]| Vec2::new(x, y))
+    }
+
+    // make the obstacles and terrain (and the border, with walls) solid, and
+    // give the terrain's water its depth
+    pub fn build_walls(&self, map: &mut Cells, grid: &GridGeometry) {
+        let walls = self.grid.as_ref().map_or(false, |def| def.boundary == Boundary::Walls);
+        let lines: Vec<Vec<(Vec2, Vec2)>> = self.obstacles.iter().map(Obstacle::lines).collect();
+        for cell_ix in 0..grid.len() {
+            let (col, row) = (cell_ix % grid.cols, cell_ix / grid.cols);
+            let border = col == 0 || row == 0 || col == grid.cols - 1 || row == grid.rows - 1;
+            let center = grid.center(cell_ix);
+            // None without terrain, Some(None) for its land
+            let depth = self.terrain.as_ref().map(|terrain| terrain.depth(grid, cell_ix));
+            if let Some(Some(depth)) = depth {
+                map.all_cells[cell_ix].depth = depth;
+            }
+            let land = depth == Some(None);
+            if (walls && border) || land || self.obstacles.iter().zip(lines.iter()).any(|(obstacle, lines)| obstacle.covers(center, lines)) {
+                let cell = &mut map.all_cells[cell_ix];
+                cell.kind = CellType::Solid;
+                cell.flow_v = Vec2::new(0., 0.);
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+// the running score, a player's own alongside their boat. keep_score adds
+// them up at the end of every sim tick from what that tick's events say
+// happened (see events.rs): a point a second afloat, ten for every particle a
+// sink swallows, fifty for each enemy the player's shots sink (floated up over
+// the wreck), twenty five for each gem they pick up (see pickups.rs) and a
+// hundred for finishing the level. the hud (see hud.rs) shows each player's,
+// with their boat's health.
+//
+// it's keep_score that sinks a boat that's out of health (rammed too often,
+// see enemies.rs), which stops its score, and notices the game's over too:
+// once every boat's sunk it sends PlayerDied to the state machine (see
+// game_state.rs), along with the best of the scores whenever it changes. that,
+// or esc, goes back to the start screen, and record_run puts the score on the
+// tables.
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View, ViewMut, World};
+
+use crate::boat::PlayerId;
+use crate::collision::Collider;
+use crate::daily::Daily;
+use crate::demo::Demo;
+use crate::effects::Effects;
+use crate::events::{Event, Events};
+use crate::game_state::{GameEvent, GameEvents};
+use crate::level::Level;
+use crate::pickups::{PickupKind, GEM_POINTS};
+use crate::profiling;
+use crate::scores;
+use crate::Boat;
+
+const PARTICLE_POINTS: u32 = 10;
+const ENEMY_POINTS: u32 = 50;
+const LEVEL_POINTS: u32 = 100;
+
+#[derive(Component, Default)]
+pub struct Score {
+    pub points: u32,
+    // whole seconds already paid for
+    seconds: u32,
+}
+
+// the best of the players' scores, which is the game's
+pub fn best<'a>(scores: impl Iterator<Item = &'a Score>) -> u32 {
+    scores.map(|score| score.points).max().unwrap_or(0)
+}
+
+pub fn keep_score(mut scores: ViewMut<Score>,
+                  mut boats: ViewMut<Boat>,
+                  mut colliders: ViewMut<Collider>,
+                  ids: View<PlayerId>,
+                  mut effects: UniqueViewMut<Effects>,
+                  events: UniqueView<Events>,
+                  level: UniqueView<Level>,
+                  mut game_events: UniqueViewMut<GameEvents>) {
+    let _t = profiling::scope("keep_score");
+    let before = best(scores.iter());
+    let seconds = level.seconds() as u32;
+    // (a sunk boat's score stays where it was)
+    for (score, player, _) in (&mut scores, &ids, &boats).iter() {
+        if seconds > score.seconds {
+            score.points += seconds - score.seconds;
+            score.seconds = seconds;
+        }
+        for event in events.this_tick() {
+            match event {
+                Event::ParticlesSunk { count } => score.points += *count as u32 * PARTICLE_POINTS,
+                Event::EnemyDestroyed { at, by } if *by == player.0 => {
+                    score.points += ENEMY_POINTS;
+                    effects.score_text(*at, ENEMY_POINTS as i32);
+                }
+                Event::PickedUp { kind: PickupKind::Gem, at, player: by } if *by == player.0 => {
+                    score.points += GEM_POINTS;
+                    effects.score_text(*at, GEM_POINTS as i32);
+                }
+                Event::LevelComplete => score.points += LEVEL_POINTS,
+                _ => {}
+            }
+        }
+    }
+    let after = best(scores.iter());
+    if after != before {
+        game_events.send(GameEvent::ScoreChanged { points: after });
+    }
+
+    let sunk: Vec<EntityId> = (&boats).iter().with_id()
+        .filter(|(_, boat)| boat.health <= 0.)
+        .map(|(id, _)| id)
+        .collect();
+    for id in sunk {
+        if let Ok(collider) = colliders.get(id) {
+            effects.sparks(collider.center, 30, RED);
+        }
+        boats.remove(id);
+        colliders.remove(id);
+    }
+    if boats.iter().count() == 0 {
+        game_events.send(GameEvent::PlayerDied);
+    }
+}
+
+// when the game's over, before the world's reset: the replay's saved, and the
+// score goes in the high scores (a replay's isn't a new one, and a daily
+// challenge's goes in with the day's)
+pub fn record_run(world: &World, score: u32) {
+    world.run(|demo: UniqueView<Demo>, daily: UniqueView<Daily>| {
+        demo.save();
+        if !demo.is_playing() && !daily.record(score) {
+            scores::record_run(score);
+        }
+    });
+}
@@ -0,0 +1,210 @@
+// high scores. every run's score (see score.rs) goes into the local high
+// score file when the game's over, and if settings.toml names a leaderboard
+// it's sent there too:
+//
+//   [leaderboard]
+//   url = "https://example.com/fluidish"
+//   name = "dylan"
+//
+// the server side is two calls: POST {url}/scores with {"name", "score"} as
+// json, and GET {url}/scores giving back a json list of the same, best first.
+// the start screen shows the leaderboard's list, fetched on a background
+// thread so a slow server doesn't hold up the menu. with no url, no network
+// or a server that's down, it shows the local file's list instead.
+//
+// there's no networking on the wasm build, where it's always the local list.
+
+use std::fs;
+use std::sync::mpsc::{self, Receiver};
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::settings::{Settings, SETTINGS_PATH};
+
+pub const HIGH_SCORES_PATH: &str = "high_scores.toml";
+// the daily challenge's, for the one day (see daily.rs)
+pub const DAILY_SCORES_PATH: &str = "daily_scores.toml";
+// how many scores the local file keeps, and the start screen shows
+const KEEP: usize = 10;
+#[cfg(not(target_arch = "wasm32"))]
+const TIMEOUT_SECONDS: u64 = 3;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LeaderboardSettings {
+    pub url: Option<String>,
+    // what the scores are filed under, locally and online
+    pub name: String,
+}
+
+impl Default for LeaderboardSettings {
+    fn default() -> LeaderboardSettings {
+        LeaderboardSettings { url: None, name: "player".to_owned() }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ScoreEntry {
+    pub name: String,
+    pub score: u32,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct HighScores {
+    #[serde(default)]
+    scores: Vec<ScoreEntry>,
+}
+
+impl HighScores {
+    fn load() -> HighScores {
+        fs::read_to_string(HIGH_SCORES_PATH).ok()
+            .and_then(|text| toml::from_str(&text).ok())
+            .unwrap_or_default()
+    }
+
+    fn add(&mut self, entry: ScoreEntry) {
+        self.scores.push(entry);
+        self.scores.sort_by(|a, b| b.score.cmp(&a.score));
+        self.scores.truncate(KEEP);
+    }
+
+    fn save(&self) {
+        let result = toml::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|text| fs::write(HIGH_SCORES_PATH, text).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            warn!("couldn't save high scores {}: {}", HIGH_SCORES_PATH, err);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(std::time::Duration::from_secs(TIMEOUT_SECONDS)).build()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn submit(url: &str, entry: &ScoreEntry) -> Result<(), String> {
+    agent().post(&format!("{}/scores", url))
+        .send_json(entry)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn fetch(url: &str) -> Result<Vec<ScoreEntry>, String> {
+    agent().get(&format!("{}/scores", url))
+        .call()
+        .map_err(|err| err.to_string())?
+        .into_json()
+        .map_err(|err| err.to_string())
+}
+
+// called when the game's over: the local file always, the leaderboard if there
+// is one. this blocks (for up to the timeout), but it's between games anyway
+pub fn record_run(score: u32) {
+    let settings = Settings::load(SETTINGS_PATH).leaderboard;
+    let entry = ScoreEntry { name: settings.name, score };
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(url) = settings.url.as_ref() {
+        if let Err(err) = submit(url, &entry) {
+            warn!("couldn't send the score to {}: {}", url, err);
+        }
+    }
+    let mut high_scores = HighScores::load();
+    high_scores.add(entry);
+    high_scores.save();
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct DailyScores {
+    date: String,
+    #[serde(default)]
+    scores: Vec<ScoreEntry>,
+}
+
+// what's been scored on the day, or nothing if the file's from another one
+fn load_daily(date: &str) -> DailyScores {
+    fs::read_to_string(DAILY_SCORES_PATH).ok()
+        .and_then(|text| toml::from_str::<DailyScores>(&text).ok())
+        .filter(|daily| daily.date == date)
+        .unwrap_or(DailyScores { date: date.to_owned(), scores: vec![] })
+}
+
+pub fn daily_best(date: &str) -> Option<u32> {
+    load_daily(date).scores.first().map(|entry| entry.score)
+}
+
+// a daily challenge run, when it's over. these stay local
+pub fn record_daily(date: &str, score: u32) {
+    let mut daily = load_daily(date);
+    daily.scores.push(ScoreEntry { name: Settings::load(SETTINGS_PATH).leaderboard.name, score });
+    daily.scores.sort_by(|a, b| b.score.cmp(&a.score));
+    daily.scores.truncate(KEEP);
+    let result = toml::to_string(&daily)
+        .map_err(|err| err.to_string())
+        .and_then(|text| fs::write(DAILY_SCORES_PATH, text).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        warn!("couldn't save daily scores {}: {}", DAILY_SCORES_PATH, err);
+    }
+}
+
+// the list on the start screen
+pub struct Leaderboard {
+    scores: Vec<ScoreEntry>,
+    online: bool,
+    // the fetch in progress, if there is one
+    pending: Option<Receiver<Result<Vec<ScoreEntry>, String>>>,
+}
+
+impl Leaderboard {
+    // starts with the local list, and starts fetching the online one
+    pub fn fetch() -> Leaderboard {
+        let mut pending = None;
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(url) = Settings::load(SETTINGS_PATH).leaderboard.url {
+            let (sender, receiver) = mpsc::channel();
+            std::thread::spawn(move || {
+                let _ = sender.send(fetch(&url));
+            });
+            pending = Some(receiver);
+        }
+        Leaderboard { scores: HighScores::load().scores, online: false, pending }
+    }
+
+    // picks up the online list once it's arrived (the local one stays if it failed)
+    fn poll(&mut self) {
+        let received = match self.pending.as_ref() {
+            Some(receiver) => match receiver.try_recv() {
+                Ok(received) => received,
+                Err(mpsc::TryRecvError::Empty) => return,
+                Err(mpsc::TryRecvError::Disconnected) => Err("the fetch gave up".to_owned()),
+            },
+            None => return,
+        };
+        self.pending = None;
+        match received {
+            Ok(mut scores) => {
+                scores.truncate(KEEP);
+                self.scores = scores;
+                self.online = true;
+            }
+            Err(err) => warn!("couldn't fetch the leaderboard: {}", err),
+        }
+    }
+
+    // down the left of the start screen
+    pub fn draw(&mut self) {
+        self.poll();
+        if self.scores.is_empty() {
+            return;
+        }
+        let title = if self.online { "leaderboard" } else { "high scores" };
+        draw_text(title, 16., 30., 22., WHITE);
+        for (i, entry) in self.scores.iter().enumerate() {
+            let line = format!("{:2}. {:<10} {}", i + 1, entry.name, entry.score);
+            draw_text(&line, 16., 52. + 18. * i as f32, 18., GRAY);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+// running the sim on one machine and watching it on others: `--serve PORT`
+// runs the simulation headless (no window, same as --bench) at the usual 60
+// ticks a second, and streams a snapshot of it every SNAPSHOT_TICKS to anyone
+// connected. `--watch HOST:PORT` opens a window that does nothing but draw
+// the latest snapshot, so a big sim can run on the beefy machine and be shown
+// on as many screens as you like.
+//
+// snapshots go over TCP as one line of json each, kept small: the flow is
+// in thousandths of a pixel per step, positions are whole pixels, and past
+// MAX_PARTICLES the particles are thinned out evenly. nobody steers the
+// server's boats (yet).
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{IntoIter, UniqueView, View};
+
+use crate::batch::MeshBatch;
+use crate::clock::TICK;
+use crate::drifters::Drifter;
+use crate::game_state::Phase;
+use crate::grid::GridGeometry;
+use crate::simulation::{ParticleLayout, Simulation};
+use crate::{new_boat, Boat, Cells, Particle};
+
+const SNAPSHOT_TICKS: u64 = 3;
+const MAX_PARTICLES: usize = 5000;
+// a client that can't take a snapshot this fast gets dropped
+const WRITE_TIMEOUT: Duration = Duration::from_millis(100);
+const FLOW_SCALE: f32 = 1000.;
+// pixels of arrow per pixel per step of flow, like the debug view
+const ARROW_SCALE: f32 = 20.;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    tick: u64,
+    cols: usize,
+    rows: usize,
+    width: f32,
+    height: f32,
+    // per cell, row by row
+    flow: Vec<[i16; 2]>,
+    solid: Vec<usize>,
+    // x, y, heading, for each player's boat
+    boats: Vec<[f32; 3]>,
+    drifters: Vec<[f32; 3]>,
+    particles: Vec<[u16; 2]>,
+    // before the thinning out
+    particle_count: usize,
+}
+
+fn boat_summary(boat: &Boat) -> [f32; 3] {
+    [boat.loc.x as f32, boat.loc.y as f32, boat.heading()]
+}
+
+impl Snapshot {
+    fn take(sim: &Simulation, tick: u64) -> Snapshot {
+        sim.world.run(|map: UniqueView<Cells>,
+                       grid: UniqueView<GridGeometry>,
+                       boats: View<Boat>,
+                       drifters: View<Drifter>,
+                       particles: View<Particle>| {
+            let particle_count = particles.iter().count();
+            let every = (particle_count + MAX_PARTICLES - 1) / MAX_PARTICLES;
+            Snapshot {
+                tick,
+                cols: grid.cols,
+                rows: grid.rows,
+                width: grid.width,
+                height: grid.height,
+                flow: (0..grid.len())
+                    .map(|cell_ix| {
+                        let flow = map.all_cells[cell_ix].flow_v * FLOW_SCALE;
+                        [flow.x as i16, flow.y as i16]
+                    })
+                    .collect(),
+                solid: (0..grid.len()).filter(|cell_ix| map.all_cells[*cell_ix].is_solid()).collect(),
+                boats: boats.iter().map(boat_summary).collect(),
+                drifters: drifters.iter().map(|drifter| boat_summary(&drifter.0)).collect(),
+                particles: particles.iter()
+                    .step_by(every.max(1))
+                    .map(|p| [p.position.x as u16, p.position.y as u16])
+                    .collect(),
+                particle_count,
+            }
+        })
+    }
+}
+
+// --serve: runs until it's killed (or the sim fails)
+pub fn serve(port: &str) {
+    rand::srand(date::now() as u64);
+    let sim = Simulation::new(ParticleLayout::from_args());
+    let listener = TcpListener::bind(format!("0.0.0.0:{}", port))
+        .and_then(|listener| listener.set_nonblocking(true).map(|_| listener));
+    let listener = match listener {
+        Ok(listener) => listener,
+        Err(err) => {
+            eprintln!("couldn't listen on port {}: {}", port, err);
+            return;
+        }
+    };
+    println!("serving the sim on port {}", port);
+    let mut clients: Vec<TcpStream> = vec![];
+    let mut tick = 0u64;
+    let mut next_tick = date::now();
+    loop {
+        while let Ok((client, address)) = listener.accept() {
+            let ready = client.set_nonblocking(false)
+                .and_then(|_| client.set_write_timeout(Some(WRITE_TIMEOUT)))
+                .and_then(|_| client.set_nodelay(true));
+            match ready {
+                Ok(()) => {
+                    println!("{} is watching", address);
+                    clients.push(client);
+                }
+                Err(err) => eprintln!("couldn't set up {}: {}", address, err),
+            }
+        }
+
+        if let Err(err) = sim.step_headless() {
+            eprintln!("the sim stopped: {:?}", err);
+            return;
+        }
+        if sim.phase() != Phase::Playing {
+            eprintln!("the game's over");
+            return;
+        }
+        tick += 1;
+        if tick % SNAPSHOT_TICKS == 0 && !clients.is_empty() {
+            let mut line = serde_json::to_vec(&Snapshot::take(&sim, tick)).unwrap();
+            line.push(b'\n');
+            clients.retain(|mut client| match client.write_all(&line) {
+                Ok(()) => true,
+                Err(err) => {
+                    println!("dropped {}: {}", client.peer_addr().map_or("a watcher".to_owned(), |a| a.to_string()), err);
+                    false
+                }
+            });
+        }
+
+        // keep to real time, unless the sim can't keep up
+        next_tick += TICK as f64;
+        let left = next_tick - date::now();
+        if left > 0. {
+            std::thread::sleep(Duration::from_secs_f64(left));
+        } else {
+            next_tick = date::now();
+        }
+    }
+}
+
+// reads snapshots off the connection on its own thread, handing them over as they come
+fn receive(address: &str) -> Result<Receiver<Snapshot>, String> {
+    let stream = TcpStream::connect(address).map_err(|err| format!("couldn't connect to {}: {}", address, err))?;
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stream).lines() {
+            let snapshot = match line.map(|line| serde_json::from_str::<Snapshot>(&line)) {
+                Ok(Ok(snapshot)) => snapshot,
+                Ok(Err(err)) => {
+                    warn!("bad snapshot: {}", err);
+                    continue;
+                }
+                Err(_) => break,
+            };
+            if sender.send(snapshot).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(receiver)
+}
+
+fn draw_snapshot(snapshot: &Snapshot) {
+    set_camera(&Camera2D::from_display_rect(Rect::new(0., 0., snapshot.width, snapshot.height)));
+    let (cell_width, cell_height) = (snapshot.width / snapshot.cols as f32, snapshot.height / snapshot.rows as f32);
+    let mut batch = MeshBatch::new();
+    for cell_ix in snapshot.solid.iter() {
+        let (col, row) = (cell_ix % snapshot.cols, cell_ix / snapshot.cols);
+        batch.rect(col as f32 * cell_width, row as f32 * cell_height, cell_width, cell_height, DARKGRAY);
+    }
+    for (cell_ix, [x, y]) in snapshot.flow.iter().enumerate() {
+        let flow = Vec2::new(*x as f32, *y as f32) / FLOW_SCALE;
+        let center = Vec2::new(((cell_ix % snapshot.cols) as f32 + 0.5) * cell_width,
+                               ((cell_ix / snapshot.cols) as f32 + 0.5) * cell_height);
+        let tip = center + flow * ARROW_SCALE;
+        batch.arrow(center.x, center.y, tip.x, tip.y, 0.5, GRAY);
+    }
+    for [x, y] in snapshot.particles.iter() {
+        batch.circle(*x as f32, *y as f32, 1., SKYBLUE);
+    }
+    batch.draw();
+    for [x, y, heading] in snapshot.drifters.iter().chain(snapshot.boats.iter()) {
+        let mut boat = new_boat(*x, *y, 0., 0.);
+        boat.turn(heading - boat.heading());
+        boat.settle_heading(1.);
+        boat.render(1.);
+    }
+    set_default_camera();
+    draw_text(&format!("tick {}  {} particles", snapshot.tick, snapshot.particle_count), 8., 20., 20., WHITE);
+}
+
+// --watch: draws what the server sends until the window's closed (or esc)
+pub async fn watch(address: &str) {
+    let receiver = receive(address);
+    let mut latest: Option<Snapshot> = None;
+    let mut lost = false;
+    loop {
+        clear_background(BLACK);
+        match &receiver {
+            Ok(receiver) => loop {
+                match receiver.try_recv() {
+                    Ok(snapshot) => latest = Some(snapshot),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        lost = true;
+                        break;
+                    }
+                }
+            },
+            Err(err) => draw_text(err, 8., 40., 20., RED),
+        }
+        match &latest {
+            Some(snapshot) => draw_snapshot(snapshot),
+            None => draw_text(&format!("waiting for {}...", address), 8., 20., 20., WHITE),
+        }
+        if lost {
+            draw_text("lost the server", 8., 40., 20., RED);
+        }
+        if is_key_pressed(KeyCode::Escape) {
+            return;
+        }
+        next_frame().await
+    }
+}
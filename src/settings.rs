@@ -0,0 +1,145 @@
+// the settings file: player preferences that should stick between runs. the
+// key bindings (see keymap.rs) are stored by name so the file stays readable
+// and editable by hand:
+//
+//   [keys]
+//   thrust = "W"
+//   fire = "Enter"
+//
+//   [pad]
+//   fire = "right_bumper"
+//   debug = ""
+//
+//   [camera]
+//   zoom_speed = 0.2
+//   max_zoom = 4.0
+//
+//   [controls]
+//   scheme = "mouse_aim"
+//
+//   [audio]
+//   master = 0.5
+//
+//   [tuning]
+//   hull_drag = 0.08
+//
+//   [leaderboard]
+//   url = "https://example.com/fluidish"
+//   name = "dylan"
+//
+//   [accessibility]
+//   palette = "colorblind"
+//
+//   [visualization]
+//   colormap = "inferno"
+
+use std::collections::BTreeMap;
+use std::fs;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::accessibility::Accessibility;
+use crate::audio::AudioSettings;
+use crate::camera::CameraSettings;
+use crate::input::ControlSettings;
+use crate::scores::LeaderboardSettings;
+use crate::visualization::VisualizationSettings;
+
+pub const SETTINGS_PATH: &str = "settings.toml";
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Settings {
+    // action name -> key name
+    #[serde(default)]
+    pub keys: BTreeMap<String, String>,
+    // action name -> gamepad button name, see gamepad.rs
+    #[serde(default)]
+    pub pad: BTreeMap<String, String>,
+    #[serde(default)]
+    pub camera: CameraSettings,
+    #[serde(default)]
+    pub controls: ControlSettings,
+    #[serde(default)]
+    pub audio: AudioSettings,
+    #[serde(default)]
+    pub leaderboard: LeaderboardSettings,
+    #[serde(default)]
+    pub accessibility: Accessibility,
+    #[serde(default)]
+    pub visualization: VisualizationSettings,
+    // tuning panel value name -> value, see tuning.rs
+    #[serde(default)]
+    pub tuning: BTreeMap<String, f32>,
+}
+
+impl Settings {
+    // a missing file means all the defaults; a broken one gets reported and
+    // treated the same way
+    pub fn load(path: &str) -> Settings {
+        match fs::read_to_string(path) {
+            Ok(text) => toml::from_str(&text).unwrap_or_else(|err| {
+                warn!("couldn't read settings {}: {}", path, err);
+                Settings::default()
+            }),
+            Err(_) => Settings::default(),
+        }
+    }
+
+    pub fn save(&self, path: &str) {
+        let result = toml::to_string(self)
+            .map_err(|err| err.to_string())
+            .and_then(|text| fs::write(path, text).map_err(|err| err.to_string()));
+        if let Err(err) = result {
+            warn!("couldn't save settings {}: {}", path, err);
+        }
+    }
+}
+
+// rewrite just the `key = value` lines of one [section] of the file, in
+// place, leaving everything else (comments included) as it was. keys the
+// section didn't have go on the end of it, and a missing section goes on the
+// end of the file
+pub fn write_section(path: &str, section: &str, values: &[(&str, String)]) -> Result<(), String> {
+    let text = fs::read_to_string(path).unwrap_or_default();
+    let header = format!("[{}]", section);
+    let mut lines: Vec<String> = text.lines().map(|line| line.to_owned()).collect();
+    let start = lines.iter().position(|line| line.trim() == header);
+    let start = match start {
+        Some(start) => start,
+        None => {
+            if lines.last().map_or(false, |line| !line.trim().is_empty()) {
+                lines.push(String::new());
+            }
+            lines.push(header);
+            lines.len() - 1
+        }
+    };
+    let end = lines[start + 1..].iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |i| start + 1 + i);
+    let mut missing = vec![];
+    for (key, value) in values.iter() {
+        let existing = (start + 1..end)
+            .find(|i| lines[*i].contains('=') && lines[*i].split('=').next().unwrap().trim() == *key);
+        match existing {
+            Some(i) => {
+                // keep a comment on the end of the line
+                let comment = lines[i].find('#').map(|at| format!(" {}", &lines[i][at..])).unwrap_or_default();
+                lines[i] = format!("{} = {}{}", key, value, comment);
+            }
+            None => missing.push(format!("{} = {}", key, value)),
+        }
+    }
+    // after the section's last non-blank line
+    let mut insert_at = end;
+    while insert_at > start + 1 && lines[insert_at - 1].trim().is_empty() {
+        insert_at -= 1;
+    }
+    for (n, line) in missing.into_iter().enumerate() {
+        lines.insert(insert_at + n, line);
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    fs::write(path, text).map_err(|err| format!("couldn't write {}: {}", path, err))
+}
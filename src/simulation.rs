@@ -0,0 +1,340 @@
+// the simulation core: the ECS world plus the workload that steps it
+//
+// with the `parallel` feature shipyard runs systems whose borrows don't
+// conflict at the same time, so keep each system's borrows as narrow as they
+// can be: views instead of view-muts where it only reads, and small uniques
+// rather than one big one. drawing and input systems all borrow the Screen
+// unique (mutably to draw, read-only for input), since macroquad isn't thread
+// safe.
+
+use shipyard::{error, UniqueView, UniqueViewMut, Workload, WorkloadBuilder, World};
+
+use crate::attract::autopilot;
+use crate::camera::{begin_world_view, end_world_view, pan_camera, zoom_camera};
+use crate::clock::SimClock;
+use crate::collision::detect_collisions;
+use crate::demo::{self, record_or_play, Demo};
+use crate::diagnostics::collect_diagnostics;
+use crate::effects::{draw_effects, update_effects};
+use crate::drifters::{draw_drifters, spawn_drifter, update_drifters};
+use crate::emitters::{drain_outflows, draw_emitters, run_emitters, run_sinks};
+use crate::trails::draw_trails;
+use crate::force_fields::{apply_force_fields, draw_force_fields, place_force_fields};
+use crate::enemies::{draw_enemies, resolve_enemy_hits, scatter_wreckage, spawn_enemies, update_enemies};
+use crate::game_state::{request_transitions, update_game_state, GameState, Phase};
+use crate::governor::govern_particle_count;
+use crate::memory::collect_memory_stats;
+use crate::hud::{draw_debug_hud, draw_hud};
+use crate::input::{handle_key_presses, measure_frame_time, read_controls, recognize_gestures};
+use crate::inspector::{draw_inspector, pick_entity};
+use crate::commands::run_commands;
+use crate::confinement::confine_vorticity;
+use crate::console::{draw_console, read_console};
+use crate::events::{clear_events, start_tick};
+use crate::export::export_field;
+use crate::islands::draw_islands;
+use crate::level::{check_win, draw_checkpoints};
+use crate::lsystem::draw_decorations;
+use crate::net::{draw_remote_boats, update_remote_boats};
+use crate::neighbours::repel_particles;
+use crate::osc::read_osc;
+use crate::keymap::{draw_key_bindings, read_pad, rebind_keys, KeyBindings};
+use crate::profiling::{self, collect_profile, draw_profiler};
+use crate::projection::project_flow;
+use crate::refine::{refine_keys, resample_grid};
+use crate::score::keep_score;
+use crate::spawns::run_spawns;
+use crate::tools::{
+    apply_vortices, drag_particles, draw_tool, grab_boat, edit_placements, erase_particles, paint_velocity, paint_walls, particle_hotkeys, place_vortex,
+    run_edit_commands, run_tool_commands, switch_tool,
+};
+use crate::stats::record_stats;
+use crate::telemetry::{record_telemetry, write_telemetry};
+use crate::trajectories::{capture_trajectories, save_trajectories};
+use crate::turtles::{draw_turtles, drift_turtles};
+use crate::touch::{draw_touch_controls, read_touch};
+use crate::tuning::{draw_tuning, tune};
+use crate::visualization::{cycle_visualization, draw_field};
+use crate::wake::couple_boat;
+use crate::weather::{apply_weather, draw_weather};
+use crate::window::{resize_world, track_screen};
+use crate::weapons::{despawn_offscreen_or_expired, draw_projectiles, move_projectiles, spawn_projectiles};
+use crate::pickups::{collect_pickups, draw_pickups, spawn_pickups, update_pickups};
+use crate::particle_store::{
+    gather_particle_store, scatter_particle_store, soa_move_particles,
+    soa_update_grid_flow, soa_update_particles_vectors,
+};
+use crate::boat::{draw_boats, steer_player, update_player};
+use crate::fluid::{apply_grid_updates, swap_grid_buffers, update_grid_flow};
+use crate::particle::{age_particles, move_particle, update_particles_vectors};
+use crate::render::{draw_status, render};
+use crate::{clean_up, init_world, FrameTime};
+
+// how the particles are laid out for the hot loops
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParticleLayout {
+    // iterate the Particle components directly
+    Components,
+    // copy into a ParticleStore (one Vec<f32> per field) for the hot loops
+    // and write the results back into the components once per step
+    StructOfArrays,
+}
+
+pub struct Simulation {
+    pub world: World,
+    // the built in level being played, if it's not the scenario file's (see presets.rs)
+    preset: Option<usize>,
+}
+
+impl ParticleLayout {
+    // --soa runs the particle hot loops over flat arrays instead of the components
+    pub fn from_args() -> ParticleLayout {
+        if std::env::args().any(|arg| arg == "--soa") {
+            ParticleLayout::StructOfArrays
+        } else {
+            ParticleLayout::Components
+        }
+    }
+}
+
+// the step is split in three so the sim can also run without a window (see
+// bench.rs): input reads macroquad's context, sim only touches the world, and
+// render draws
+const INPUT: &str = "Input";
+const SIM: &str = "Sim";
+const RENDER: &str = "Render";
+const STATE: &str = "State";
+
+fn sim_workload(layout: ParticleLayout) -> WorkloadBuilder {
+    // the boat is steered, and the hotkeys' edits carried out, per tick, so a
+    // replay (see demo.rs) plays out the same at any frame rate
+    let builder = Workload::builder(SIM)
+        .with_system(start_tick)
+        .with_system(record_or_play)
+        .with_system(resize_world)
+        .with_system(resample_grid)
+        .with_system(run_edit_commands)
+        .with_system(run_tool_commands)
+        .with_system(apply_vortices)
+        .with_system(apply_force_fields)
+        .with_system(steer_player)
+        .with_system(couple_boat)
+        .with_system(spawn_projectiles)
+        .with_system(govern_particle_count)
+        .with_system(run_emitters)
+        .with_system(run_sinks)
+        .with_system(drain_outflows)
+        .with_system(age_particles)
+        .with_system(apply_weather)
+        .with_system(move_projectiles)
+        .with_system(despawn_offscreen_or_expired)
+        .with_system(spawn_enemies)
+        .with_system(spawn_pickups)
+        .with_system(run_spawns);
+    let builder = match layout {
+        ParticleLayout::Components => builder
+            .with_system(move_particle)
+            .with_system(update_grid_flow),
+        ParticleLayout::StructOfArrays => builder
+            .with_system(gather_particle_store)
+            .with_system(soa_move_particles)
+            .with_system(soa_update_grid_flow),
+    };
+    let builder = builder
+        .with_system(update_pickups)
+        .with_system(update_player)
+        .with_system(update_drifters)
+        .with_system(update_enemies)
+        .with_system(drift_turtles)
+        .with_system(update_remote_boats)
+        .with_system(update_effects)
+        .with_system(detect_collisions)
+        .with_system(resolve_enemy_hits)
+        .with_system(collect_pickups)
+        .with_system(apply_grid_updates)
+        .with_system(confine_vorticity)
+        .with_system(project_flow);
+    let builder = match layout {
+        ParticleLayout::Components => builder.with_system(update_particles_vectors),
+        ParticleLayout::StructOfArrays => builder
+            .with_system(soa_update_particles_vectors)
+            .with_system(scatter_particle_store),
+    };
+    builder
+        .with_system(repel_particles)
+        .with_system(scatter_wreckage)
+        .with_system(clean_up)
+        .with_system(check_win)
+        .with_system(capture_trajectories)
+        .with_system(record_telemetry)
+        .with_system(record_stats)
+        .with_system(collect_diagnostics)
+        .with_system(collect_memory_stats)
+        .with_system(swap_grid_buffers)
+        .with_system(keep_score)
+}
+
+impl Simulation {
+    pub fn new(layout: ParticleLayout) -> Simulation {
+        let mut world = World::new();
+        init_world(&mut world, None);
+
+        Workload::builder(INPUT)
+            .with_system(track_screen)
+            .with_system(clear_events)
+            .with_system(measure_frame_time)
+            .with_system(rebind_keys)
+            .with_system(read_pad)
+            .with_system(read_console)
+            .with_system(run_commands)
+            .with_system(export_field)
+            .with_system(save_trajectories)
+            .with_system(write_telemetry)
+            .with_system(handle_key_presses)
+            .with_system(request_transitions)
+            .with_system(cycle_visualization)
+            .with_system(read_controls)
+            .with_system(recognize_gestures)
+            .with_system(autopilot)
+            .with_system(read_touch)
+            .with_system(switch_tool)
+            .with_system(zoom_camera)
+            .with_system(pan_camera)
+            .with_system(tune)
+            .with_system(grab_boat)
+            .with_system(drag_particles)
+            .with_system(particle_hotkeys)
+            .with_system(spawn_drifter)
+            .with_system(paint_velocity)
+            .with_system(read_osc)
+            .with_system(paint_walls)
+            .with_system(erase_particles)
+            .with_system(edit_placements)
+            .with_system(place_force_fields)
+            .with_system(refine_keys)
+            .with_system(pick_entity)
+            .with_system(place_vortex)
+            .add_to_world(&world)
+            .unwrap();
+        sim_workload(layout).add_to_world(&world).unwrap();
+        Workload::builder(STATE).with_system(update_game_state).add_to_world(&world).unwrap();
+        Workload::builder(RENDER)
+            .with_system(begin_world_view)
+            .with_system(draw_field)
+            .with_system(draw_islands)
+            .with_system(draw_checkpoints)
+            .with_system(draw_decorations)
+            .with_system(draw_trails)
+            .with_system(render)
+            .with_system(draw_boats)
+            .with_system(draw_drifters)
+            .with_system(draw_enemies)
+            .with_system(draw_pickups)
+            .with_system(draw_turtles)
+            .with_system(draw_remote_boats)
+            .with_system(draw_emitters)
+            .with_system(draw_force_fields)
+            .with_system(draw_effects)
+            .with_system(draw_projectiles)
+            .with_system(end_world_view)
+            .with_system(draw_weather)
+            .with_system(draw_status)
+            .with_system(draw_hud)
+            .with_system(draw_inspector)
+            .with_system(draw_tuning)
+            .with_system(draw_debug_hud)
+            .with_system(draw_tool)
+            .with_system(draw_touch_controls)
+            .with_system(collect_profile)
+            .with_system(draw_profiler)
+            .with_system(draw_key_bindings)
+            .with_system(draw_console)
+            .add_to_world(&world)
+            .unwrap();
+
+        Simulation { world, preset: None }
+    }
+
+    // run one frame of the game loop: input, however many sim ticks the frame
+    // owes, then drawing. gives back how many ticks that was
+    pub fn step(&self) -> Result<u32, error::RunWorkload> {
+        self.step_gated(|_| true)
+    }
+
+    // a step where `before_tick` gets a say before each tick: false holds the
+    // sim where it is for the rest of the frame (see net.rs). free running
+    // (see clock.rs) that's the only thing that stops it (see pacing.rs)
+    pub fn step_gated(&self, mut before_tick: impl FnMut(&World) -> bool) -> Result<u32, error::RunWorkload> {
+        let _t = profiling::scope("whole step");
+        self.scrub_replay()?;
+        self.world.run_workload(INPUT)?;
+        self.world.run_workload(STATE)?;
+        let mut ticks = 0;
+        for _ in 0..self.ticks_due() {
+            // (the main loop takes it from here, see game_state.rs)
+            if self.phase() != Phase::Playing || !before_tick(&self.world) {
+                break;
+            }
+            self.run_tick()?;
+            ticks += 1;
+        }
+        self.world.run_workload(RENDER)?;
+        Ok(ticks)
+    }
+
+    fn run_tick(&self) -> Result<(), error::RunWorkload> {
+        demo::before_tick(&self.world);
+        self.world.run_workload(SIM)?;
+        self.world.run_workload(STATE)
+    }
+
+    // [ and ] during a replay: rewind to a keyframe if that helps, then run
+    // the sim (without drawing) up to the tick asked for
+    fn scrub_replay(&self) -> Result<(), error::RunWorkload> {
+        let target = self.world
+            .run(|demo: UniqueView<Demo>, keys: UniqueView<KeyBindings>| demo.scrub_target(&keys));
+        if let Some(target) = target {
+            demo::rewind(&self.world, target);
+            while self.world.run(|demo: UniqueView<Demo>| demo.tick()) < target {
+                self.run_tick()?;
+            }
+        }
+        Ok(())
+    }
+
+    // how many times the sim runs this frame (see clock.rs)
+    fn ticks_due(&self) -> u32 {
+        let frame_time = self.world.borrow::<UniqueView<FrameTime>>().map_or(0., |frame_time| frame_time.0);
+        self.world.borrow::<UniqueViewMut<SimClock>>().map_or(1, |mut clock| clock.advance(frame_time))
+    }
+
+    pub fn set_free_running(&self, free_running: bool) {
+        if let Ok(mut clock) = self.world.borrow::<UniqueViewMut<SimClock>>() {
+            clock.free_running = free_running;
+        }
+    }
+
+    // just the simulation part of a step: no input, no drawing, no window needed
+    pub fn step_headless(&self) -> Result<(), error::RunWorkload> {
+        let _t = profiling::scope("whole step");
+        self.world.run_workload(SIM)?;
+        self.world.run_workload(STATE)
+    }
+
+    // playing, over or restarting, as of the last workload
+    pub fn phase(&self) -> Phase {
+        self.world.borrow::<UniqueView<GameState>>().map_or(Phase::Playing, |state| state.phase)
+    }
+
+    // start over on one of the built in levels (or the scenario file's, for None)
+    pub fn load_preset(&mut self, preset: Option<usize>) {
+        self.preset = preset;
+        self.reset();
+    }
+
+    // throw everything away and start over
+    pub fn reset(&mut self) {
+        self.world.clear();
+        init_world(&mut self.world, self.preset);
+    }
+}
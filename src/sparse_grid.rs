@@ -0,0 +1,104 @@
+// grid storage that only allocates the parts of the domain that are in use
+//
+// the grid is cut into square chunks of cells, and a chunk is only allocated
+// once something writes to it. reads from an unallocated chunk all see the same
+// `empty` value, and chunks that have gone back to all-empty can be handed back
+// with free_chunks_where. cells are still addressed by the same flat row-major
+// index as a dense grid, so `grid[cell_ix]` works as before; indexing mutably
+// allocates the cell's chunk, so stick to `get` where a read is all you need.
+
+use std::ops::{Index, IndexMut};
+
+// chunks are CHUNK_SIZE x CHUNK_SIZE cells
+const CHUNK_SIZE: usize = 8;
+
+#[derive(Clone)]
+pub struct SparseGrid<T> {
+    cols: usize,
+    rows: usize,
+    chunk_cols: usize,
+    chunks: Vec<Option<Box<[T]>>>,
+    empty: T,
+}
+
+impl<T: Clone> SparseGrid<T> {
+    pub fn new(cols: usize, rows: usize, empty: T) -> SparseGrid<T> {
+        let chunk_cols = (cols + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        let chunk_rows = (rows + CHUNK_SIZE - 1) / CHUNK_SIZE;
+        SparseGrid { cols, rows, chunk_cols, chunks: vec![None; chunk_cols * chunk_rows], empty }
+    }
+
+    pub fn len(&self) -> usize {
+        self.cols * self.rows
+    }
+
+    // which chunk a cell is in, and where in the chunk
+    fn locate(&self, cell_ix: usize) -> (usize, usize) {
+        let (x, y) = (cell_ix % self.cols, cell_ix / self.cols);
+        ((y / CHUNK_SIZE) * self.chunk_cols + x / CHUNK_SIZE,
+         (y % CHUNK_SIZE) * CHUNK_SIZE + x % CHUNK_SIZE)
+    }
+
+    pub fn get(&self, cell_ix: usize) -> &T {
+        let (chunk, offset) = self.locate(cell_ix);
+        match &self.chunks[chunk] {
+            Some(cells) => &cells[offset],
+            None => &self.empty,
+        }
+    }
+
+    // allocates the cell's chunk if it isn't already
+    pub fn get_mut(&mut self, cell_ix: usize) -> &mut T {
+        let (chunk, offset) = self.locate(cell_ix);
+        let empty = &self.empty;
+        let cells = self.chunks[chunk]
+            .get_or_insert_with(|| vec![empty.clone(); CHUNK_SIZE * CHUNK_SIZE].into_boxed_slice());
+        &mut cells[offset]
+    }
+
+    pub fn is_allocated(&self, cell_ix: usize) -> bool {
+        self.chunks[self.locate(cell_ix).0].is_some()
+    }
+
+    // every cell in index order, unallocated ones included (as `empty`)
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        (0..self.len()).map(move |cell_ix| self.get(cell_ix))
+    }
+
+    // hand back every chunk whose cells all pass `is_empty`
+    pub fn free_chunks_where<F: Fn(&T) -> bool>(&mut self, is_empty: F) {
+        for chunk in self.chunks.iter_mut() {
+            if chunk.as_ref().map_or(false, |cells| cells.iter().all(|cell| is_empty(cell))) {
+                *chunk = None;
+            }
+        }
+    }
+
+    pub fn allocated_chunks(&self) -> usize {
+        self.chunks.iter().filter(|chunk| chunk.is_some()).count()
+    }
+
+    pub fn total_chunks(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // memory held by the allocated chunks and the chunk table
+    pub fn bytes(&self) -> usize {
+        self.allocated_chunks() * CHUNK_SIZE * CHUNK_SIZE * std::mem::size_of::<T>()
+            + self.chunks.capacity() * std::mem::size_of::<Option<Box<[T]>>>()
+    }
+}
+
+impl<T: Clone> Index<usize> for SparseGrid<T> {
+    type Output = T;
+
+    fn index(&self, cell_ix: usize) -> &T {
+        self.get(cell_ix)
+    }
+}
+
+impl<T: Clone> IndexMut<usize> for SparseGrid<T> {
+    fn index_mut(&mut self, cell_ix: usize) -> &mut T {
+        self.get_mut(cell_ix)
+    }
+}
@@ -0,0 +1,60 @@
+// the scenario's [[spawns]]: things that turn up at a set time and place
+// partway through the level, rather than being there from the start. the
+// enemies have their own table (see enemies.rs); this is everything else:
+//
+//   what = "particles"  a burst of count of them, flung out every which way
+//                       at up to speed
+//   what = "pickup"     one pickup, of kind "repair", "boost" or "gem" (or
+//                       any of them, if it's left out)
+//   what = "drifter"    an unsteered boat (see drifters.rs)
+//
+// they go off the tick the level's clock reaches their `at`, so a replay sees
+// them at the same moment.
+
+use macroquad::prelude::*;
+use shipyard::{EntitiesViewMut, UniqueView, UniqueViewMut, ViewMut};
+
+use crate::collision::Collider;
+use crate::drifters::Drifter;
+use crate::geometry::wrap_position;
+use crate::grid::GridGeometry;
+use crate::level::Level;
+use crate::pickups::{Pickup, PickupKind};
+use crate::pool::ParticlePool;
+use crate::profiling;
+use crate::scenario::SpawnEvent;
+use crate::{new_particle_at, Particle, Randomness};
+
+pub fn run_spawns(mut level: UniqueViewMut<Level>,
+                  grid: UniqueView<GridGeometry>,
+                  mut pool: UniqueViewMut<ParticlePool>,
+                  mut entities: EntitiesViewMut,
+                  mut particles: ViewMut<Particle>,
+                  mut items: ViewMut<Pickup>,
+                  mut drifters: ViewMut<Drifter>,
+                  mut colliders: ViewMut<Collider>,
+                  _random: UniqueViewMut<Randomness>) {
+    let _t = profiling::scope("run_spawns");
+    let now = level.seconds();
+    let (due, later): (Vec<_>, Vec<_>) = level.spawns.drain(..).partition(|spawn| spawn.at() <= now);
+    level.spawns = later;
+    for spawn in due {
+        let at = wrap_position(spawn.pos(), grid.size());
+        match spawn {
+            SpawnEvent::Particles { count, speed, .. } => {
+                pool.spawn_bulk(&mut entities, &mut particles, (0..count).map(|_| {
+                    let angle = rand::gen_range(0., std::f32::consts::PI * 2.);
+                    let speed = rand::gen_range(0., speed);
+                    new_particle_at(&grid, at.x, at.y, angle.cos() * speed, angle.sin() * speed)
+                }));
+            }
+            SpawnEvent::Pickup { kind, .. } => {
+                let kind = kind.unwrap_or_else(PickupKind::random);
+                entities.add_entity((&mut items, &mut colliders), Pickup::new(kind, at));
+            }
+            SpawnEvent::Drifter { .. } => {
+                entities.add_entity((&mut drifters, &mut colliders), Drifter::new(at.x, at.y));
+            }
+        }
+    }
+}
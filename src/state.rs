@@ -0,0 +1,284 @@
+// saving and loading the whole sim: F5 writes the particles, the grid's
+// flow and walls, the players' boats, the vortices and the scores to a state
+// file, and
+// F9 puts them all back. `--state PATH` picks the file (state.toml by
+// default), and `--load PATH` (or `--load-state PATH`) does the same and
+// starts every game from it, for coming back to the same flow (or the same
+// bug) again and again.
+//
+// the random number generator can't be read back out, so saving reseeds it
+// with a fresh seed and writes that down instead; loading reseeds with it,
+// which picks the run back up the same way each time it's loaded.
+//
+// emitters and sinks already live in the scenario file, so they aren't saved
+// here. nor is the level's clock: the score's points come back, and the
+// seconds afloat carry on being added from wherever the level's got to.
+
+use std::fs;
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{EntitiesViewMut, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View, ViewMut, World};
+
+use crate::boat::PlayerId;
+use crate::demo::path_after;
+use crate::grid::GridGeometry;
+use crate::keymap::{Action, KeyBindings};
+use crate::pool::ParticlePool;
+use crate::score::Score;
+use crate::sparse_grid::SparseGrid;
+use crate::tools::{Vortex, Vortices};
+use crate::{new_particle_at, Boat, CellType, Cells, FluidCell, NextCells, Particle, ParticleKind, Real};
+
+const DEFAULT_STATE_PATH: &str = "state.toml";
+
+#[derive(Serialize, Deserialize)]
+struct SavedParticle {
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+    size: f32,
+    #[serde(default)]
+    kind: ParticleKind,
+    #[serde(default)]
+    age: f32,
+}
+
+// only the cells with something in them get saved
+#[derive(Serialize, Deserialize)]
+struct SavedCell {
+    ix: usize,
+    solid: bool,
+    // inflows and outflows (files from before those just have `solid`)
+    #[serde(default)]
+    kind: Option<CellType>,
+    vx: f32,
+    vy: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedBoat {
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+    heading: f32,
+    health: f32,
+}
+
+impl SavedBoat {
+    fn of(boat: &Boat) -> SavedBoat {
+        SavedBoat { x: boat.loc.x as f32, y: boat.loc.y as f32, vx: boat.vel.x, vy: boat.vel.y,
+                    heading: boat.heading(), health: boat.health }
+    }
+
+    fn put_back(&self, boat: &mut Boat) {
+        boat.loc.x = self.x as Real;
+        boat.loc.y = self.y as Real;
+        boat.vel = Vec2::new(self.vx, self.vy);
+        let turn = self.heading - boat.heading();
+        boat.turn(turn);
+        boat.health = self.health;
+    }
+}
+
+// a player's boat and score other than the first's (with --two-player)
+#[derive(Serialize, Deserialize)]
+struct SavedPlayer {
+    player: u8,
+    points: u32,
+    // (none if it had sunk)
+    #[serde(default)]
+    boat: Option<SavedBoat>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedVortex {
+    x: f32,
+    y: f32,
+    strength: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedState {
+    seed: u64,
+    // the grid it was saved from; a file from a different sized grid is refused
+    cols: usize,
+    rows: usize,
+    // the first player's (none if it had sunk, leaving the second to carry on)
+    #[serde(default)]
+    boat: Option<SavedBoat>,
+    #[serde(default)]
+    vortices: Vec<SavedVortex>,
+    #[serde(default)]
+    cells: Vec<SavedCell>,
+    #[serde(default)]
+    particles: Vec<SavedParticle>,
+    // the first player's
+    #[serde(default)]
+    score: u32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    others: Vec<SavedPlayer>,
+}
+
+impl SavedState {
+    // `seed` is what the random numbers get reseeded with on loading
+    pub fn capture(world: &World, seed: u64) -> SavedState {
+        world.run(|particles: ViewMut<Particle>,
+                   map: UniqueView<Cells>,
+                   grid: UniqueView<GridGeometry>,
+                   vortices: UniqueView<Vortices>,
+                   scores: View<Score>,
+                   boats: View<Boat>,
+                   ids: View<PlayerId>| {
+            let first = (&scores, &ids).iter().with_id().find(|(_, (_, id))| id.is_first());
+            SavedState {
+                seed,
+                cols: grid.cols,
+                rows: grid.rows,
+                boat: first.and_then(|(entity, _)| boats.get(entity).ok()).map(SavedBoat::of),
+                vortices: vortices.0.iter()
+                    .map(|vortex| SavedVortex { x: vortex.center().x, y: vortex.center().y, strength: vortex.strength() })
+                    .collect(),
+                cells: (0..grid.len())
+                    .filter(|cell_ix| map.all_cells.is_allocated(*cell_ix))
+                    .map(|cell_ix| (cell_ix, &map.all_cells[cell_ix]))
+                    .filter(|(_, cell)| cell.kind != CellType::Water || cell.flow_v.length() > 0.)
+                    .map(|(ix, cell)| SavedCell { ix, solid: cell.is_solid(), kind: Some(cell.kind),
+                                                  vx: cell.flow_v.x, vy: cell.flow_v.y })
+                    .collect(),
+                particles: particles.iter()
+                    .map(|p| SavedParticle { x: p.position.x as f32, y: p.position.y as f32,
+                                             vx: p.velocity.x, vy: p.velocity.y, size: p.size,
+                                             kind: p.kind, age: p.age })
+                    .collect(),
+                score: first.map_or(0, |(_, (score, _))| score.points),
+                others: (&scores, &ids).iter().with_id()
+                    .filter(|(_, (_, id))| !id.is_first())
+                    .map(|(entity, (score, id))| SavedPlayer { player: id.0, points: score.points,
+                                                             boat: boats.get(entity).ok().map(SavedBoat::of) })
+                    .collect(),
+            }
+        })
+    }
+
+    pub fn restore(&self, world: &World) -> Result<(), String> {
+        world.run(|mut particles: ViewMut<Particle>,
+                   mut entities: EntitiesViewMut,
+                   mut pool: UniqueViewMut<ParticlePool>,
+                   mut map: UniqueViewMut<Cells>,
+                   mut next: UniqueViewMut<NextCells>,
+                   grid: UniqueView<GridGeometry>,
+                   mut vortices: UniqueViewMut<Vortices>,
+                   mut scores: ViewMut<Score>,
+                   mut boats: ViewMut<Boat>,
+                   ids: View<PlayerId>| {
+            if (self.cols, self.rows) != (grid.cols, grid.rows) {
+                return Err(format!("it's from a {}x{} grid, this one's {}x{}", self.cols, self.rows, grid.cols, grid.rows));
+            }
+            rand::srand(self.seed);
+
+            let all: Vec<EntityId> = particles.iter().with_id().map(|(id, _)| id).collect();
+            pool.despawn_bulk(&mut particles, &all);
+            pool.spawn_bulk(&mut entities, &mut particles, self.particles.iter().map(|saved| {
+                let mut particle = new_particle_at(&grid, saved.x, saved.y, saved.vx, saved.vy);
+                particle.size = saved.size;
+                particle.age = saved.age;
+                particle.of_kind(saved.kind)
+            }));
+
+            let mut cells = SparseGrid::new(grid.cols, grid.rows, FluidCell::empty());
+            for saved in self.cells.iter().filter(|saved| saved.ix < grid.len()) {
+                let cell = &mut cells[saved.ix];
+                cell.kind = saved.kind.unwrap_or(if saved.solid { CellType::Solid } else { CellType::Water });
+                cell.flow_v = Vec2::new(saved.vx, saved.vy);
+                cell.quiet_frames = 0;
+            }
+            next.0.all_cells = cells.clone();
+            map.all_cells = cells;
+
+            vortices.0 = self.vortices.iter()
+                .map(|saved| Vortex::new(Vec2::new(saved.x, saved.y), saved.strength))
+                .collect();
+
+            // (a boat that's sunk since stays sunk)
+            for (entity, (score, id)) in (&mut scores, &ids).iter().with_id() {
+                let (points, saved) = if id.is_first() {
+                    (self.score, self.boat.as_ref())
+                } else {
+                    match self.others.iter().find(|other| other.player == id.0) {
+                        Some(other) => (other.points, other.boat.as_ref()),
+                        None => continue,
+                    }
+                };
+                score.points = points;
+                if let (Some(saved), Ok(boat)) = (saved, (&mut boats).get(entity)) {
+                    saved.put_back(boat);
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+// where F5 and F9 save and load
+pub struct StateFile {
+    path: String,
+}
+
+impl StateFile {
+    pub fn from_args() -> StateFile {
+        let path = path_after("--load")
+            .or_else(|| path_after("--load-state"))
+            .or_else(|| path_after("--state"))
+            .unwrap_or_else(|| DEFAULT_STATE_PATH.to_string());
+        StateFile { path }
+    }
+
+    // with --load, every game starts from the file (called once the world's
+    // set up for it)
+    pub fn load_at_start(&self, world: &World) {
+        if path_after("--load").is_some() || path_after("--load-state").is_some() {
+            self.load(world);
+        }
+    }
+
+    pub fn save(&self, world: &World) {
+        let seed = rand::rand() as u64;
+        rand::srand(seed);
+        let state = SavedState::capture(world, seed);
+        let result = toml::to_string(&state)
+            .map_err(|err| err.to_string())
+            .and_then(|text| fs::write(&self.path, text).map_err(|err| err.to_string()));
+        match result {
+            Ok(()) => info!("saved the sim to {}", self.path),
+            Err(err) => warn!("couldn't save the sim to {}: {}", self.path, err),
+        }
+    }
+
+    pub fn load(&self, world: &World) {
+        let result = fs::read_to_string(&self.path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| toml::from_str::<SavedState>(&text).map_err(|err| err.to_string()))
+            .and_then(|state| state.restore(world));
+        match result {
+            Ok(()) => info!("loaded the sim from {}", self.path),
+            Err(err) => warn!("couldn't load the sim from {}: {}", self.path, err),
+        }
+    }
+
+    // called from the main loop, outside the workloads, since saving and
+    // loading both need the whole world
+    pub fn handle_keys(&self, world: &World) {
+        let (save, load) = world.run(|keys: UniqueView<KeyBindings>| {
+            (keys.pressed(Action::SaveState), keys.pressed(Action::LoadState))
+        });
+        if save {
+            self.save(world);
+        }
+        if load {
+            self.load(world);
+        }
+    }
+}
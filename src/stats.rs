@@ -0,0 +1,174 @@
+// the post-game screen. RunStats adds up the run as it goes (how far the
+// first player's boat went, its top speed, a trail of where it's been, and how
+// hard the water ran where), and when the game's over RunSummary::take copies out what the
+// screen needs before the world's reset. the start screen then shows it down
+// the right hand side: a little map of the run's average flow, a block at a
+// time, with the boat's trail over it, and under that the score, time survived,
+// distance, particles collected (sunk, see emitters.rs), enemies destroyed
+// and peak speed.
+
+use macroquad::prelude::*;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View, World};
+
+use crate::accessibility::Accessibility;
+use crate::boat::PlayerId;
+use crate::geometry::wrapped_dist;
+use crate::grid::GridGeometry;
+use crate::level::Level;
+use crate::profiling;
+use crate::score::{self, Score};
+use crate::{Boat, Cells};
+
+// how often the trail and the flow are sampled
+const SAMPLE_TICKS: u32 = 30;
+// the trail's points get thinned out to half whenever there are this many
+const MAX_TRAIL: usize = 2000;
+// the flow map's blocks
+const MAP_COLS: usize = 16;
+const MAP_ROWS: usize = 9;
+// how wide the map is drawn, on screen
+const MAP_WIDTH: f32 = 200.;
+// the flow speed that's the top of the map's colours
+const MAP_MAX_SPEED: f32 = 3.;
+
+#[derive(Component, Default)]
+pub struct RunStats {
+    ticks: u32,
+    distance: f32,
+    peak_speed: f32,
+    last: Option<Vec2>,
+    trail: Vec<Vec2>,
+    // flow speed summed over the samples, per block
+    flow: Vec<f32>,
+    samples: u32,
+    // sunk by the player's shots (see enemies.rs)
+    pub enemies_destroyed: u32,
+}
+
+pub fn record_stats(mut stats: UniqueViewMut<RunStats>,
+                    boats: View<Boat>,
+                    ids: View<PlayerId>,
+                    map: UniqueView<Cells>,
+                    grid: UniqueView<GridGeometry>) {
+    let _t = profiling::scope("record_stats");
+    // (until it sinks, if the second player carries on)
+    let player = (&boats, &ids).iter().find(|(_, id)| id.is_first()).map(|(boat, _)| boat);
+    let at = player.map(|player| Vec2::new(player.loc.x as f32, player.loc.y as f32));
+    if let (Some(last), Some(at)) = (stats.last, at) {
+        // the short way, across the edge if it wrapped
+        stats.distance += wrapped_dist(last, at, grid.size());
+    }
+    stats.last = at;
+    if let Some(player) = player {
+        stats.peak_speed = stats.peak_speed.max(player.vel.length());
+    }
+    stats.ticks += 1;
+    if stats.ticks % SAMPLE_TICKS != 0 {
+        return;
+    }
+    if let Some(at) = at {
+        stats.trail.push(at);
+    }
+    if stats.trail.len() >= MAX_TRAIL {
+        stats.trail = stats.trail.iter().step_by(2).copied().collect();
+    }
+    if stats.flow.is_empty() {
+        stats.flow = vec![0.; MAP_COLS * MAP_ROWS];
+    }
+    let mut sums = vec![0.; MAP_COLS * MAP_ROWS];
+    let mut counts = vec![0u32; MAP_COLS * MAP_ROWS];
+    for cell_ix in 0..grid.len() {
+        let center = grid.center(cell_ix);
+        let col = ((center.x / grid.width * MAP_COLS as f32) as usize).min(MAP_COLS - 1);
+        let row = ((center.y / grid.height * MAP_ROWS as f32) as usize).min(MAP_ROWS - 1);
+        sums[row * MAP_COLS + col] += map.all_cells[cell_ix].flow_v.length();
+        counts[row * MAP_COLS + col] += 1;
+    }
+    for (block, (sum, count)) in sums.iter().zip(counts.iter()).enumerate() {
+        if *count > 0 {
+            stats.flow[block] += sum / *count as f32;
+        }
+    }
+    stats.samples += 1;
+}
+
+pub struct RunSummary {
+    score: u32,
+    seconds: f32,
+    distance: f32,
+    collected: usize,
+    enemies_destroyed: u32,
+    peak_speed: f32,
+    // the trail as fractions (0 to 1) of the way across and down
+    trail: Vec<Vec2>,
+    // average flow speed per block
+    flow: Vec<f32>,
+    access: Accessibility,
+}
+
+impl RunSummary {
+    // called once the game's over, before the world's reset
+    pub fn take(world: &World) -> RunSummary {
+        world.run(|stats: UniqueView<RunStats>,
+                   scores: View<Score>,
+                   level: UniqueView<Level>,
+                   grid: UniqueView<GridGeometry>,
+                   access: UniqueView<Accessibility>| {
+            let samples = stats.samples.max(1) as f32;
+            RunSummary {
+                score: score::best(scores.iter()),
+                seconds: level.seconds(),
+                distance: stats.distance,
+                collected: level.sunk,
+                enemies_destroyed: stats.enemies_destroyed,
+                peak_speed: stats.peak_speed,
+                trail: stats.trail.iter().map(|p| Vec2::new(p.x / grid.width, p.y / grid.height)).collect(),
+                flow: stats.flow.iter().map(|sum| sum / samples).collect(),
+                access: *access,
+            }
+        })
+    }
+
+    // screen space, on the start screen: the map in the top right, the
+    // numbers under it
+    pub fn draw(&self) {
+        let lines = [
+            format!("score             {}", self.score),
+            format!("time survived     {:.1}s", self.seconds),
+            format!("distance          {:.0}", self.distance),
+            format!("particles sunk    {}", self.collected),
+            format!("enemies destroyed {}", self.enemies_destroyed),
+            format!("peak speed        {:.2}", self.peak_speed),
+        ];
+        let height = MAP_WIDTH * screen_height() / screen_width();
+        let (x, y) = (screen_width() - MAP_WIDTH - 16., 16.);
+        for (i, line) in lines.iter().enumerate() {
+            let line_y = y + height + 40. + i as f32 * 18.;
+            self.access.text_backing(x, line_y, measure_text(line, None, 16, 1.).width, 16.);
+            draw_text(line, x, line_y, 16., if self.access.high_contrast { WHITE } else { LIGHTGRAY });
+        }
+
+        let (block_w, block_h) = (MAP_WIDTH / MAP_COLS as f32, height / MAP_ROWS as f32);
+        for (block, speed) in self.flow.iter().enumerate() {
+            let color = Color { a: 0.7, ..self.access.ramp(speed / MAP_MAX_SPEED, 0., 0.4) };
+            let (col, row) = (block % MAP_COLS, block / MAP_COLS);
+            draw_rectangle(x + col as f32 * block_w, y + row as f32 * block_h, block_w, block_h, color);
+        }
+        draw_rectangle_lines(x, y, MAP_WIDTH, height, 1., GRAY);
+        let to_map = |p: Vec2| Vec2::new(x + p.x * MAP_WIDTH, y + p.y * height);
+        for pair in self.trail.windows(2) {
+            // where it wrapped round the edge there's no line to draw
+            let step = pair[1] - pair[0];
+            if step.x.abs() > 0.5 || step.y.abs() > 0.5 {
+                continue;
+            }
+            let (from, to) = (to_map(pair[0]), to_map(pair[1]));
+            draw_line(from.x, from.y, to.x, to.y, 1.5, WHITE);
+        }
+        if let Some(end) = self.trail.last() {
+            let end = to_map(*end);
+            draw_circle(end.x, end.y, 3., RED);
+        }
+        draw_text("your run", x, y + height + 16., 16., GRAY);
+    }
+}
@@ -0,0 +1,126 @@
+// run reports, for comparing parameter sweeps from a script: `--telemetry
+// PATH` writes a json report when the game exits (esc):
+//
+//   duration_seconds     wall clock, start to exit
+//   sim_seconds          ticks run, in sim time
+//   score                see score.rs
+//   samples              every SAMPLE_TICKS: the tick, the particle count and
+//                        the solver residual (the mean |divergence| over the
+//                        water cells, how far the flow is from incompressible)
+//   systems              each system's average milliseconds a call over the
+//                        whole run, and how many calls
+//
+// `--bench` takes it too, and writes its results table as json (see bench.rs).
+
+use std::fs;
+
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+use serde::Serialize;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View};
+
+use crate::clock::TICK;
+use crate::demo::path_after;
+use crate::grid::GridGeometry;
+use crate::keymap::{Action, KeyBindings};
+use crate::profiling::Profiler;
+use crate::score::{self, Score};
+use crate::{Cells, Particle};
+
+const SAMPLE_TICKS: usize = 60;
+
+#[derive(Serialize)]
+struct Sample {
+    tick: usize,
+    particles: usize,
+    residual: f32,
+}
+
+#[derive(Serialize)]
+struct SystemReport {
+    name: &'static str,
+    average_ms: f64,
+    calls: u64,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    duration_seconds: f64,
+    sim_seconds: f32,
+    score: u32,
+    samples: &'a [Sample],
+    systems: Vec<SystemReport>,
+}
+
+#[derive(Component)]
+pub struct Telemetry {
+    path: Option<String>,
+    started_at: f64,
+    ticks: usize,
+    samples: Vec<Sample>,
+}
+
+impl Telemetry {
+    pub fn from_args() -> Telemetry {
+        Telemetry { path: path_after("--telemetry"), started_at: date::now(), ticks: 0, samples: vec![] }
+    }
+}
+
+// mean |divergence| over the cells that aren't solid
+pub fn residual(map: &Cells, grid: &GridGeometry) -> f32 {
+    let cells = &map.all_cells;
+    let (mut total, mut count) = (0., 0);
+    for cell_ix in 0..grid.len() {
+        if cells[cell_ix].is_solid() {
+            continue;
+        }
+        let [left, right, up, down] = grid.neighbours(cell_ix);
+        let divergence = (cells[right].flow_v.x - cells[left].flow_v.x) / (2. * grid.cell_width)
+            + (cells[down].flow_v.y - cells[up].flow_v.y) / (2. * grid.cell_height);
+        total += divergence.abs();
+        count += 1;
+    }
+    if count == 0 { 0. } else { total / count as f32 }
+}
+
+// every sim tick
+pub fn record_telemetry(mut telemetry: UniqueViewMut<Telemetry>,
+                        map: UniqueView<Cells>,
+                        grid: UniqueView<GridGeometry>,
+                        particles: View<Particle>) {
+    if telemetry.path.is_none() {
+        return;
+    }
+    if telemetry.ticks % SAMPLE_TICKS == 0 {
+        let sample = Sample { tick: telemetry.ticks, particles: particles.iter().count(), residual: residual(&map, &grid) };
+        telemetry.samples.push(sample);
+    }
+    telemetry.ticks += 1;
+}
+
+// runs before handle_key_presses, which ends the game on esc
+pub fn write_telemetry(telemetry: UniqueView<Telemetry>,
+                       scores: View<Score>,
+                       profiler: UniqueView<Profiler>,
+                       keys: UniqueView<KeyBindings>) {
+    let path = match telemetry.path.as_ref() {
+        Some(path) if keys.pressed(Action::Exit) => path,
+        _ => return,
+    };
+    let report = Report {
+        duration_seconds: date::now() - telemetry.started_at,
+        sim_seconds: telemetry.ticks as f32 * TICK,
+        score: score::best(scores.iter()),
+        samples: &telemetry.samples,
+        systems: profiler.systems.iter()
+            .map(|timing| SystemReport { name: timing.name, average_ms: timing.run_average_ms(), calls: timing.calls })
+            .collect(),
+    };
+    let result = serde_json::to_string_pretty(&report)
+        .map_err(|err| err.to_string())
+        .and_then(|text| fs::write(path, text).map_err(|err| err.to_string()));
+    match result {
+        Ok(()) => info!("wrote the run's telemetry to {}", path),
+        Err(err) => warn!("couldn't write telemetry {}: {}", path, err),
+    }
+}
@@ -0,0 +1,157 @@
+// generated levels: `[terrain]` in a scenario file (or `--generate SEED`,
+// which makes a whole level from nothing) lays islands out from noise, so
+// every seed is a different map:
+//
+//   [terrain]
+//   seed = 7
+//   sea_level = 0.58      # higher is less land
+//   checkpoints = 4       # for a race; 0 for none
+//   enemy_spawns = 0      # spread round the map, 10 seconds apart
+//
+// the noise is a height for every point: above the sea level is land (solid
+// cells), and below it the water gets deeper away from the shore. shallow
+// water drags on the flow (see FluidCell::depth). the boat starts in the
+// deepest water, and the checkpoints and enemy spawns are spread as far apart
+// as they'll go from there. anything the file places itself (spawn,
+// checkpoints, enemies) is kept instead. the noise wraps round like the world
+// does, so the islands carry on across the edges.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::grid::GridGeometry;
+use crate::scenario::{EnemySpawn, Scenario, WinCondition};
+
+// noise cells across the world for the biggest features, and how many
+// octaves of finer detail go on top
+const BASE_FREQUENCY: u32 = 3;
+const OCTAVES: u32 = 4;
+// how far below the sea level counts as full depth
+const SHELF: f32 = 0.12;
+// only water at least this deep gets checkpoints and spawns
+const OPEN_WATER: f32 = 0.6;
+const ENEMY_SPAWN_INTERVAL: f32 = 10.;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TerrainDef {
+    pub seed: u32,
+    #[serde(default = "sea_level")]
+    pub sea_level: f32,
+    #[serde(default)]
+    pub checkpoints: usize,
+    #[serde(default)]
+    pub enemy_spawns: usize,
+}
+
+fn sea_level() -> f32 {
+    0.58
+}
+
+// 0 to 1, the same every time for the same numbers
+fn hash(seed: u32, x: u32, y: u32) -> f32 {
+    let mut h = seed.wrapping_mul(0x9e37_79b9) ^ x.wrapping_mul(0x85eb_ca6b) ^ y.wrapping_mul(0xc2b2_ae35);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    h as f32 / u32::MAX as f32
+}
+
+// smoothly interpolated random values on a `period` square lattice, wrapping
+fn value_noise(seed: u32, u: f32, v: f32, period: u32) -> f32 {
+    let (x, y) = (u * period as f32, v * period as f32);
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let (sx, sy) = (tx * tx * (3. - 2. * tx), ty * ty * (3. - 2. * ty));
+    let corner = |dx: u32, dy: u32| {
+        let cx = (x0 as i64 + dx as i64).rem_euclid(period as i64) as u32;
+        let cy = (y0 as i64 + dy as i64).rem_euclid(period as i64) as u32;
+        hash(seed, cx, cy)
+    };
+    let top = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * sx;
+    let bottom = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * sx;
+    top + (bottom - top) * sy
+}
+
+impl TerrainDef {
+    // `--generate SEED`: a race round four checkpoints
+    pub fn from_args() -> Option<TerrainDef> {
+        let seed = crate::demo::path_after("--generate")?.parse().ok()?;
+        Some(TerrainDef { seed, sea_level: sea_level(), checkpoints: 4, enemy_spawns: 0 })
+    }
+
+    // 0 to 1 at a point given as a fraction (0 to 1) of the way across and down
+    pub fn height(&self, u: f32, v: f32) -> f32 {
+        let (mut total, mut amplitude, mut weight) = (0., 1., 0.);
+        for octave in 0..OCTAVES {
+            let period = BASE_FREQUENCY << octave;
+            total += value_noise(self.seed.wrapping_add(octave), u, v, period) * amplitude;
+            weight += amplitude;
+            amplitude *= 0.5;
+        }
+        total / weight
+    }
+
+    // how deep the water is at a cell (0 to 1), or None if it's land
+    pub fn depth(&self, grid: &GridGeometry, cell_ix: usize) -> Option<f32> {
+        let center = grid.center(cell_ix);
+        let height = self.height(center.x / grid.width, center.y / grid.height);
+        if height > self.sea_level {
+            None
+        } else {
+            Some(((self.sea_level - height) / SHELF).min(1.))
+        }
+    }
+
+    // fill in what the scenario doesn't place itself
+    pub fn place(&self, scenario: &mut Scenario, grid: &GridGeometry) {
+        let open: Vec<Vec2> = (0..grid.len())
+            .filter(|cell_ix| self.depth(grid, *cell_ix).map_or(false, |depth| depth >= OPEN_WATER))
+            .map(|cell_ix| grid.center(cell_ix))
+            .collect();
+        if open.is_empty() {
+            warn!("terrain seed {} is all land, nowhere to start", self.seed);
+            return;
+        }
+        let middle = Vec2::new(grid.width / 2., grid.height / 2.);
+        let spawn = match scenario.spawn {
+            Some([x, y]) => Vec2::new(x, y),
+            None => {
+                // the open water nearest the middle
+                let nearest = open.iter().copied()
+                    .min_by(|a, b| a.distance(middle).partial_cmp(&b.distance(middle)).unwrap())
+                    .unwrap();
+                scenario.spawn = Some([nearest.x, nearest.y]);
+                nearest
+            }
+        };
+        // each new point as far as it can be from all the ones so far
+        let mut taken = vec![spawn];
+        let mut spread = |count: usize| -> Vec<Vec2> {
+            let mut points = vec![];
+            for _ in 0..count {
+                let furthest = open.iter().copied().max_by(|a, b| {
+                    let gap = |p: &Vec2| taken.iter().map(|t| t.distance(*p)).fold(f32::MAX, f32::min);
+                    gap(a).partial_cmp(&gap(b)).unwrap()
+                });
+                if let Some(point) = furthest {
+                    taken.push(point);
+                    points.push(point);
+                }
+            }
+            points
+        };
+        if scenario.checkpoints.is_empty() {
+            scenario.checkpoints = spread(self.checkpoints).iter().map(|p| [p.x, p.y]).collect();
+            if !scenario.checkpoints.is_empty() && scenario.win.is_none() {
+                scenario.win = Some(WinCondition::default());
+            }
+        }
+        if scenario.enemies.is_empty() {
+            scenario.enemies = spread(self.enemy_spawns).iter().enumerate()
+                .map(|(i, p)| EnemySpawn { at: ENEMY_SPAWN_INTERVAL * (i + 1) as f32, x: p.x, y: p.y, count: 1 })
+                .collect();
+        }
+    }
+}
@@ -0,0 +1,802 @@
+// what the mouse does. T cycles through the tools:
+//   boat   - grab the boat with the left button to drag it around, and let go
+//            while it's moving to fling it
+//   spray  - left-drag sprays particles from the cursor along the drag direction
+//   brush  - left-drag paints the mouse's motion into the cells under a round
+//            brush, stirring the fluid. ctrl+scroll sizes the brush
+//   wall   - left-drag paints solid cells under the brush, shift-left-drag
+//            erases them. alt-left-drag paints inflow cells instead, pushing
+//            water in along the drag, and alt-shift-left-drag outflow cells,
+//            which let it out (see fluid.rs's CellType). ctrl+scroll sizes
+//            this brush too
+//   erase  - left-drag deletes the particles under the brush
+//   edit   - left-drag places a particle emitter aimed along the drag,
+//            shift-click places a sink, right-click deletes either, and
+//            ctrl+scroll over one widens or narrows the emitter's spread or
+//            the sink's radius. these are saved to the scenario file as they
+//            change
+//   inspect - click on the boat, a particle or a cell to see its fields (see
+//            inspector.rs)
+// S sprays a burst of particles out from the cursor, = doubles the number of
+// particles, - halves it, 0 and 9 add and take away a batch, and C clears
+// them all.
+// the keyboard boat controls work whichever tool is picked, and so does
+// right-click, which drops a vortex into the flow under the cursor. ctrl+scroll
+// (outside the brush and edit tools) sets how hard the vortex swirls, and which way
+// (negative is clockwise). plain scrolling zooms and middle-drag pans (see
+// camera.rs), and all the tools work in world coordinates, wherever the camera
+// is. while the camera's panning (or the mouse is over a panel) the tools
+// don't see the left button.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, EntitiesViewMut, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::boat::PlayerId;
+use crate::camera::Camera;
+use crate::collision::BOAT_RADIUS;
+use crate::config::SimConfig;
+use crate::emitters::{Emitter, Sink, DEFAULT_SINK_RADIUS, MAX_EMITTER_SPREAD, MAX_SINK_RADIUS, MIN_SINK_RADIUS};
+use crate::governor::MAX_PARTICLES;
+use crate::geometry::{wrapped_delta, wrapped_dist};
+use crate::input::ControlScheme;
+use crate::grid::GridGeometry;
+use crate::keymap::{Action, KeyBindings};
+use crate::pool::ParticlePool;
+use crate::profiling;
+use crate::level::Level;
+use crate::scenario::Scenario;
+use crate::window::ScreenInfo;
+use crate::{lerp, new_particle, new_particle_at, Boat, CellType, Cells, FrameTime, Particle, Randomness, Real, Screen};
+
+// most particles the spray puts out per second
+const MAX_SPRAY_RATE: f32 = 600.;
+// how far either side of the drag direction the spray fans out, in radians
+const SPRAY_SPREAD: f32 = 0.5;
+// spray speed per pixel of drag, and the most it'll go
+const SPRAY_SPEED_PER_PIXEL: f32 = 0.05;
+const MIN_SPRAY_SPEED: f32 = 0.3;
+const MAX_SPRAY_SPEED: f32 = 4.;
+
+// vortex radius in pixels, and how the swirl strength is set and dies away
+const VORTEX_RADIUS: f32 = 60.;
+const SWIRL_STEP: f32 = 0.1;
+pub const MAX_SWIRL: f32 = 2.;
+const VORTEX_DECAY: f32 = 0.95;
+const VORTEX_MIN_STRENGTH: f32 = 0.01;
+
+// brush radius limits and wheel step, in pixels
+const MIN_BRUSH_RADIUS: f32 = 10.;
+const MAX_BRUSH_RADIUS: f32 = 150.;
+const BRUSH_RADIUS_STEP: f32 = 5.;
+// how much of a frame's mouse motion goes into the flow, and how quickly the
+// cells under the brush take it on
+const BRUSH_VELOCITY_SCALE: f32 = 0.2;
+const BRUSH_BLEND: f32 = 0.5;
+
+// emitter speed per pixel of aiming drag, and the most it'll go
+const EMITTER_SPEED_PER_PIXEL: f32 = 0.03;
+const MAX_EMITTER_SPEED: f32 = 4.;
+// how close a right-click has to be to an emitter or sink to delete it
+const PICK_RADIUS: f32 = 12.;
+// what a ctrl+scroll over an emitter or a sink changes it by (radians, pixels)
+const SPREAD_STEP: f32 = 0.05;
+const SINK_RADIUS_STEP: f32 = 5.;
+
+// how close to the boat a click has to be to grab it
+const BOAT_GRAB_RADIUS: f32 = BOAT_RADIUS * 2.;
+// how much of each frame's mouse motion goes into the fling (the rest is the
+// last few frames'), and the fastest the boat can be thrown
+const FLING_SMOOTHING: f32 = 0.5;
+const MAX_FLING_SPEED: f32 = 8.;
+
+// particles in an S burst, and how far and fast they scatter
+const BURST_SIZE: usize = 500;
+const BURST_RADIUS: f32 = 20.;
+const BURST_SPEED: f32 = 1.;
+// the flow an inflow cell's painted with, and how far the mouse has to move
+// in a frame to re-aim it
+const INFLOW_SPEED: f32 = 2.;
+const INFLOW_AIM_MOTION: f32 = 1.;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Tool {
+    Boat,
+    Spray,
+    Brush,
+    Wall,
+    Erase,
+    Edit,
+    Inspect,
+}
+
+impl Tool {
+    fn next(self) -> Tool {
+        match self {
+            Tool::Boat => Tool::Spray,
+            Tool::Spray => Tool::Brush,
+            Tool::Brush => Tool::Wall,
+            Tool::Wall => Tool::Erase,
+            Tool::Erase => Tool::Edit,
+            Tool::Edit => Tool::Inspect,
+            Tool::Inspect => Tool::Boat,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Tool::Boat => "boat",
+            Tool::Spray => "spray",
+            Tool::Brush => "brush",
+            Tool::Wall => "wall",
+            Tool::Erase => "erase",
+            Tool::Edit => "edit",
+            Tool::Inspect => "inspect",
+        }
+    }
+
+    // the tools with a brush, whose size the scroll wheel sets
+    fn has_brush(self) -> bool {
+        self == Tool::Brush || self == Tool::Wall || self == Tool::Erase
+    }
+}
+
+#[derive(Component)]
+pub struct ToolMode {
+    pub tool: Tool,
+    // strength (and with its sign, direction) of the next right-click vortex
+    pub swirl: f32,
+    pub brush_radius: f32,
+    // where the mouse was last frame, for the brush's motion
+    last_mouse: Vec2,
+    // which way an inflow's painted, the last way the mouse was dragged
+    inflow_aim: Vec2,
+    // where the emitter being aimed will go
+    pending_emitter: Option<Vec2>,
+}
+
+impl Default for ToolMode {
+    fn default() -> ToolMode {
+        ToolMode { tool: Tool::Boat, swirl: 0.5, brush_radius: 40., last_mouse: Vec2::new(0., 0.),
+                   inflow_aim: Vec2::new(1., 0.), pending_emitter: None }
+    }
+}
+
+pub struct Vortex {
+    center: Vec2,
+    strength: f32,
+}
+
+impl Vortex {
+    pub fn new(center: Vec2, strength: f32) -> Vortex {
+        Vortex { center, strength }
+    }
+
+    pub fn center(&self) -> Vec2 {
+        self.center
+    }
+
+    pub fn strength(&self) -> f32 {
+        self.strength
+    }
+}
+
+// the vortices still spinning down
+#[derive(Component, Default)]
+pub struct Vortices(pub Vec<Vortex>);
+
+// the tools', the hotkeys' and the right-click's edits, waiting for the next
+// sim tick. they're carried out there (see run_tool_commands and
+// run_edit_commands) rather than straight away, so a replay (see demo.rs)
+// can record them and play them back on the same tick
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolCommand {
+    Burst { x: f32, y: f32 },
+    Double,
+    AddBatch,
+    Halve,
+    RemoveBatch,
+    Clear,
+    Vortex { x: f32, y: f32, strength: f32 },
+    // exactly this many particles, from the tuning panel's slider
+    SetCount { count: usize },
+    // the grid at another resolution (see refine.rs)
+    Resample { cols: usize, rows: usize },
+    // the world at the window's new size (see window.rs)
+    Resize { width: f32, height: f32 },
+    // a frame's worth of the spray, fanned out around `direction`
+    Spray { x: f32, y: f32, direction: f32, speed: f32, count: usize },
+    // the brush's motion this frame
+    Stir { x: f32, y: f32, dx: f32, dy: f32, radius: f32 },
+    // the wall tool, with which way an inflow's aimed
+    Paint { x: f32, y: f32, radius: f32, kind: CellType, aim_x: f32, aim_y: f32 },
+    Erase { x: f32, y: f32, radius: f32 },
+    // the edit tool's: whatever's under (x, y) for the last two
+    PlaceEmitter { x: f32, y: f32, vx: f32, vy: f32 },
+    PlaceSink { x: f32, y: f32 },
+    Unplace { x: f32, y: f32 },
+    Adjust { x: f32, y: f32, step: f32 },
+    // the boat tool's: a player's boat held at (x, y), or let go at (vx, vy)
+    HoldBoat { player: u8, x: f32, y: f32 },
+    FlingBoat { player: u8, vx: f32, vy: f32 },
+}
+
+#[derive(Component, Default)]
+pub struct ToolCommands(pub Vec<ToolCommand>);
+
+// follows the mouse around while dragging (lagging behind it a little, which
+// is where the spray's direction and speed come from), and holds the boat
+// while the boat tool has it
+#[derive(Component, Default)]
+pub struct ParticleDragger {
+    pub point_x: f32,
+    pub point_y: f32,
+    // particles the spray is allowed to put out, topped up each frame
+    pub spray_budget: f32,
+    // while a boat's held: whose, and where it is relative to the mouse
+    boat_grab: Option<(u8, Vec2)>,
+    // the mouse's recent motion, which the boat takes with it when let go
+    fling: Vec2,
+    last_mouse: Vec2,
+}
+
+// the left button, unless it's panning the camera or on a panel
+fn left_down(camera: &Camera) -> bool {
+    is_mouse_button_down(MouseButton::Left) && camera.tools_have_mouse()
+}
+
+fn left_pressed(camera: &Camera) -> bool {
+    is_mouse_button_pressed(MouseButton::Left) && camera.tools_have_mouse()
+}
+
+pub fn switch_tool(mut tool_mode: UniqueViewMut<ToolMode>,
+                   keys: UniqueView<KeyBindings>,
+                   _screen: UniqueView<Screen>) {
+    if keys.pressed(Action::NextTool) {
+        tool_mode.tool = tool_mode.tool.next();
+    }
+    let (_, wheel) = mouse_wheel();
+    let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+    if wheel != 0. && ctrl {
+        if tool_mode.tool.has_brush() {
+            tool_mode.brush_radius = (tool_mode.brush_radius + BRUSH_RADIUS_STEP * wheel.signum())
+                .max(MIN_BRUSH_RADIUS).min(MAX_BRUSH_RADIUS);
+        } else if tool_mode.tool != Tool::Edit {
+            tool_mode.swirl = (tool_mode.swirl + SWIRL_STEP * wheel.signum()).max(-MAX_SWIRL).min(MAX_SWIRL);
+        }
+    }
+}
+
+// the edit tool: place, aim and delete emitters and sinks (see
+// run_edit_commands for where it happens)
+pub fn edit_placements(mut tool_mode: UniqueViewMut<ToolMode>,
+                       mut commands: UniqueViewMut<ToolCommands>,
+                       camera: UniqueView<Camera>,
+                       _screen: UniqueView<Screen>) {
+    if tool_mode.tool != Tool::Edit {
+        tool_mode.pending_emitter = None;
+        return;
+    }
+    let mouse = camera.mouse_world();
+    let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+
+    if left_pressed(&camera) {
+        if shift {
+            commands.0.push(ToolCommand::PlaceSink { x: mouse.x, y: mouse.y });
+        } else {
+            tool_mode.pending_emitter = Some(mouse);
+        }
+    }
+    if is_mouse_button_released(MouseButton::Left) {
+        if let Some(pos) = tool_mode.pending_emitter.take() {
+            let drag = mouse - pos;
+            // a click without much of a drag just points it right
+            let velocity = if drag.length() < 2. {
+                Vec2::new(1., 0.)
+            } else {
+                drag.normalize() * (drag.length() * EMITTER_SPEED_PER_PIXEL).min(MAX_EMITTER_SPEED)
+            };
+            commands.0.push(ToolCommand::PlaceEmitter { x: pos.x, y: pos.y, vx: velocity.x, vy: velocity.y });
+        }
+    }
+    let (_, wheel) = mouse_wheel();
+    let ctrl = is_key_down(KeyCode::LeftControl) || is_key_down(KeyCode::RightControl);
+    if wheel != 0. && ctrl {
+        commands.0.push(ToolCommand::Adjust { x: mouse.x, y: mouse.y, step: wheel.signum() });
+    }
+    if is_mouse_button_pressed(MouseButton::Right) {
+        commands.0.push(ToolCommand::Unplace { x: mouse.x, y: mouse.y });
+    }
+}
+
+// the wall tool: paint (or with shift, erase) solid cells under the brush
+// (see paint_cells)
+pub fn paint_walls(tool_mode: UniqueView<ToolMode>,
+                   mut commands: UniqueViewMut<ToolCommands>,
+                   camera: UniqueView<Camera>,
+                   _screen: UniqueView<Screen>) {
+    if tool_mode.tool != Tool::Wall || !left_down(&camera) {
+        return;
+    }
+    let mouse = camera.mouse_world();
+    let shift = is_key_down(KeyCode::LeftShift) || is_key_down(KeyCode::RightShift);
+    let alt = is_key_down(KeyCode::LeftAlt) || is_key_down(KeyCode::RightAlt);
+    let kind = match (alt, shift) {
+        (false, false) => CellType::Solid,
+        (false, true) => CellType::Water,
+        (true, false) => CellType::Inflow,
+        (true, true) => CellType::Outflow,
+    };
+    commands.0.push(ToolCommand::Paint { x: mouse.x, y: mouse.y, radius: tool_mode.brush_radius, kind,
+                                         aim_x: tool_mode.inflow_aim.x, aim_y: tool_mode.inflow_aim.y });
+}
+
+// the cells under the wall tool's brush made `kind`. walls get the flow
+// zeroed, and erased cells start out still
+fn paint_cells(map: &mut Cells, grid: &GridGeometry, at: Vec2, radius: f32, kind: CellType, aim: Vec2) {
+    let mut cells = grid.cells_within(at, radius);
+    // a small brush might not reach any cell's center, but always gets the one it's over
+    if at.x >= 0. && at.x < grid.width && at.y >= 0. && at.y < grid.height {
+        cells.push(grid.index_at(at.x, at.y));
+    }
+    for cell_ix in cells {
+        let cell = &mut map.all_cells[cell_ix];
+        if kind == CellType::Inflow {
+            // repainting an inflow re-aims it
+            cell.kind = kind;
+            cell.flow_v = aim * INFLOW_SPEED;
+            cell.quiet_frames = 0;
+        } else if cell.kind != kind {
+            cell.kind = kind;
+            cell.flow_v = Vec2::new(0., 0.);
+            cell.quiet_frames = 0;
+        }
+    }
+}
+
+// the erase tool: delete every particle under the brush (see erase_under)
+pub fn erase_particles(tool_mode: UniqueView<ToolMode>,
+                       mut commands: UniqueViewMut<ToolCommands>,
+                       camera: UniqueView<Camera>,
+                       _screen: UniqueView<Screen>) {
+    if tool_mode.tool != Tool::Erase || !left_down(&camera) {
+        return;
+    }
+    let mouse = camera.mouse_world();
+    commands.0.push(ToolCommand::Erase { x: mouse.x, y: mouse.y, radius: tool_mode.brush_radius });
+}
+
+// only the particles in the cells the brush touches get a distance check
+fn erase_under(pool: &mut ParticlePool, particles: &mut ViewMut<Particle>, grid: &GridGeometry, at: Vec2, radius: f32) {
+    let _t = profiling::scope("erase_particles");
+    // a cell can have particles inside the brush without its center being
+    // inside it, so look as far out as the cells' corners can reach
+    let reach = radius + Vec2::new(grid.cell_width, grid.cell_height).length() / 2.;
+    let mut touched = vec![false; grid.len()];
+    for cell_ix in grid.cells_within(at, reach) {
+        touched[cell_ix] = true;
+    }
+    let erased: Vec<EntityId> = particles.iter().with_id()
+        .filter(|(_, particle)| {
+            touched[particle.cell_ix]
+                && wrapped_dist(Vec2::new(particle.position.x as f32, particle.position.y as f32), at, grid.size())
+                    < radius
+        })
+        .map(|(id, _)| id)
+        .collect();
+    pool.despawn_bulk(particles, &erased);
+}
+
+// the brush: blend the mouse's motion into the cells under it, with less of
+// it towards the edge of the brush (see stir). like the vortices, this writes
+// straight into last step's grid.
+pub fn paint_velocity(mut tool_mode: UniqueViewMut<ToolMode>,
+                      mut commands: UniqueViewMut<ToolCommands>,
+                      camera: UniqueView<Camera>,
+                      _screen: UniqueView<Screen>) {
+    let _t = profiling::scope("paint_velocity");
+    let mouse = camera.mouse_world();
+    let motion = mouse - tool_mode.last_mouse;
+    tool_mode.last_mouse = mouse;
+    if motion.length() > INFLOW_AIM_MOTION {
+        tool_mode.inflow_aim = motion.normalize();
+    }
+    if tool_mode.tool != Tool::Brush || !left_down(&camera) {
+        return;
+    }
+    commands.0.push(ToolCommand::Stir { x: mouse.x, y: mouse.y, dx: motion.x, dy: motion.y,
+                                        radius: tool_mode.brush_radius });
+}
+
+// blend `motion` (world units this frame) into the cells within `radius` of `center`
+pub fn stir(map: &mut Cells, grid: &GridGeometry, center: Vec2, motion: Vec2, radius: f32) {
+    for cell_ix in grid.cells_within(center, radius) {
+        let falloff = 1. - wrapped_dist(grid.center(cell_ix), center, grid.size()) / radius;
+        let cell = &mut map.all_cells[cell_ix];
+        let target = motion * BRUSH_VELOCITY_SCALE;
+        cell.flow_v += (target - cell.flow_v) * BRUSH_BLEND * falloff;
+        cell.quiet_frames = 0;
+    }
+}
+
+pub fn place_vortex(mut commands: UniqueViewMut<ToolCommands>,
+                    tool_mode: UniqueView<ToolMode>,
+                    scheme: UniqueView<ControlScheme>,
+                    camera: UniqueView<Camera>,
+                    _screen: UniqueView<Screen>) {
+    // right-click deletes in the edit tool, and fires in mouse-aim
+    let taken = tool_mode.tool == Tool::Edit || scheme.has_mouse(tool_mode.tool);
+    if !taken && is_mouse_button_pressed(MouseButton::Right) {
+        let at = camera.mouse_world();
+        commands.0.push(ToolCommand::Vortex { x: at.x, y: at.y, strength: tool_mode.swirl });
+    }
+}
+
+// spin the cells around each vortex, strongest in the middle and fading out
+// to the edge, then let the vortices die down a little, once a tick. this
+// writes straight into last step's grid (it runs before the sim starts
+// reading it), waking the cells it touches.
+pub fn apply_vortices(mut vortices: UniqueViewMut<Vortices>,
+                      mut map: UniqueViewMut<Cells>,
+                      grid: UniqueView<GridGeometry>) {
+    let _t = profiling::scope("apply_vortices");
+    for vortex in vortices.0.iter() {
+        for cell_ix in grid.cells_within(vortex.center, VORTEX_RADIUS) {
+            let offset = wrapped_delta(vortex.center, grid.center(cell_ix), grid.size());
+            let distance = offset.length();
+            if distance == 0. {
+                continue;
+            }
+            let tangent = Vec2::new(-offset.y, offset.x) / distance;
+            let cell = &mut map.all_cells[cell_ix];
+            cell.flow_v += tangent * vortex.strength * (1. - distance / VORTEX_RADIUS);
+            cell.quiet_frames = 0;
+        }
+    }
+    vortices.0.retain(|vortex| vortex.strength.abs() > VORTEX_MIN_STRENGTH);
+    for vortex in vortices.0.iter_mut() {
+        vortex.strength *= VORTEX_DECAY;
+    }
+}
+
+pub fn drag_particles(mut dragger: UniqueViewMut<ParticleDragger>,
+                      tool_mode: UniqueView<ToolMode>,
+                      frame_time: UniqueView<FrameTime>,
+                      mut commands: UniqueViewMut<ToolCommands>,
+                      camera: UniqueView<Camera>,
+                      _screen: UniqueView<Screen>) {
+    let _t = profiling::scope("drag_particles");
+    let mouse = camera.mouse_world();
+    let (mouse_x, mouse_y) = (mouse.x, mouse.y);
+    if !left_down(&camera) {
+        dragger.point_x = mouse_x;
+        dragger.point_y = mouse_y;
+        dragger.spray_budget = 0.;
+        return;
+    }
+    dragger.point_x = lerp(dragger.point_x, mouse_x, 0.03);
+    dragger.point_y = lerp(dragger.point_y, mouse_y, 0.03);
+    let drag = Vec2::new(mouse_x - dragger.point_x, mouse_y - dragger.point_y);
+
+    match tool_mode.tool {
+        Tool::Spray => {
+            let budget = dragger.spray_budget + MAX_SPRAY_RATE * frame_time.0;
+            let count = budget.floor();
+            dragger.spray_budget = (budget - count).min(1.);
+            let direction = drag.y.atan2(drag.x);
+            let speed = (drag.length() * SPRAY_SPEED_PER_PIXEL).max(MIN_SPRAY_SPEED).min(MAX_SPRAY_SPEED);
+            if count >= 1. {
+                commands.0.push(ToolCommand::Spray { x: mouse_x, y: mouse_y, direction, speed, count: count as usize });
+            }
+        }
+        // the other tools have systems of their own
+        Tool::Boat | Tool::Brush | Tool::Wall | Tool::Erase | Tool::Edit | Tool::Inspect => {}
+    }
+}
+
+// the boat tool: pick a player's boat up, carry it, throw it. (not in the
+// mouse-aim control scheme, where the mouse is steering the boat instead)
+pub fn grab_boat(mut dragger: UniqueViewMut<ParticleDragger>,
+                 tool_mode: UniqueView<ToolMode>,
+                 scheme: UniqueView<ControlScheme>,
+                 mut commands: UniqueViewMut<ToolCommands>,
+                 boats: View<Boat>,
+                 ids: View<PlayerId>,
+                 camera: UniqueView<Camera>,
+                 _screen: UniqueView<Screen>) {
+    if tool_mode.tool != Tool::Boat || scheme.has_mouse(tool_mode.tool) {
+        dragger.boat_grab = None;
+        return;
+    }
+    let mouse = camera.mouse_world();
+    let mouse_motion = mouse - dragger.last_mouse;
+    dragger.last_mouse = mouse;
+    if left_pressed(&camera) {
+        let under = (&boats, &ids).iter()
+            .map(|(boat, id)| (id.0, Vec2::new(boat.loc.x as f32, boat.loc.y as f32)))
+            .find(|(_, boat)| (mouse - *boat).length() < BOAT_GRAB_RADIUS);
+        if let Some((player, boat)) = under {
+            dragger.boat_grab = Some((player, boat - mouse));
+            dragger.fling = Vec2::new(0., 0.);
+        }
+    }
+    let (player, offset) = match dragger.boat_grab {
+        Some(grab) => grab,
+        None => return,
+    };
+    // sunk while it was held
+    if !ids.iter().any(|id| id.0 == player) {
+        dragger.boat_grab = None;
+        return;
+    }
+    if left_down(&camera) {
+        let pos = mouse + offset;
+        commands.0.push(ToolCommand::HoldBoat { player, x: pos.x, y: pos.y });
+        let fling = dragger.fling;
+        dragger.fling = fling + (mouse_motion - fling) * FLING_SMOOTHING;
+    } else {
+        let speed = dragger.fling.length();
+        let fling = if speed > MAX_FLING_SPEED { dragger.fling * (MAX_FLING_SPEED / speed) } else { dragger.fling };
+        commands.0.push(ToolCommand::FlingBoat { player, vx: fling.x, vy: fling.y });
+        dragger.boat_grab = None;
+    }
+}
+
+// the particle count hotkeys. the particles all come from (and go back to)
+// the pool, so adding and taking away over and over reuses the same entities
+pub fn particle_hotkeys(mut commands: UniqueViewMut<ToolCommands>,
+                        keys: UniqueView<KeyBindings>,
+                        camera: UniqueView<Camera>,
+                        _screen: UniqueView<Screen>) {
+    if keys.pressed(Action::SpawnBurst) {
+        let mouse = camera.mouse_world();
+        commands.0.push(ToolCommand::Burst { x: mouse.x, y: mouse.y });
+    }
+    let keyed = [
+        (Action::DoubleParticles, ToolCommand::Double),
+        (Action::AddParticles, ToolCommand::AddBatch),
+        (Action::ClearParticles, ToolCommand::Clear),
+        (Action::HalveParticles, ToolCommand::Halve),
+        (Action::RemoveParticles, ToolCommand::RemoveBatch),
+    ];
+    for (action, command) in keyed.iter() {
+        if keys.pressed(*action) {
+            commands.0.push(*command);
+        }
+    }
+}
+
+// first in the tick after the replay's had its say: carries out the edits
+// queued since the last one
+pub fn run_tool_commands(mut commands: UniqueViewMut<ToolCommands>,
+                         mut vortices: UniqueViewMut<Vortices>,
+                         mut pool: UniqueViewMut<ParticlePool>,
+                         mut entities: EntitiesViewMut,
+                         mut particles: ViewMut<Particle>,
+                         config: UniqueView<SimConfig>,
+                         grid: UniqueView<GridGeometry>,
+                         _random: UniqueViewMut<Randomness>) {
+    let _t = profiling::scope("run_tool_commands");
+    for command in commands.0.drain(..) {
+        let count = particles.iter().count();
+        let keep = match command {
+            ToolCommand::Burst { x, y } => {
+                let burst = BURST_SIZE.min(MAX_PARTICLES.saturating_sub(count));
+                pool.spawn_bulk(&mut entities, &mut particles, (0..burst).map(|_| {
+                    let angle = rand::gen_range(0., std::f32::consts::PI * 2.);
+                    let (x, y) = (x + angle.cos() * rand::gen_range(0., BURST_RADIUS),
+                                  y + angle.sin() * rand::gen_range(0., BURST_RADIUS));
+                    let speed = rand::gen_range(0., BURST_SPEED);
+                    new_particle_at(&grid, x.max(0.).min(grid.width - 1.), y.max(0.).min(grid.height - 1.),
+                                    angle.cos() * speed, angle.sin() * speed)
+                }));
+                None
+            }
+            ToolCommand::Double => {
+                let more = count.max(1).min(MAX_PARTICLES.saturating_sub(count));
+                pool.spawn_bulk(&mut entities, &mut particles, (0..more).map(|_| new_particle(&grid)));
+                None
+            }
+            ToolCommand::AddBatch => {
+                let more = config.particle_batch.min(MAX_PARTICLES.saturating_sub(count));
+                pool.spawn_bulk(&mut entities, &mut particles, (0..more).map(|_| new_particle(&grid)));
+                None
+            }
+            ToolCommand::Clear => Some(0),
+            ToolCommand::Halve => Some(count / 2),
+            ToolCommand::RemoveBatch => Some(count.saturating_sub(config.particle_batch)),
+            ToolCommand::SetCount { count: wanted } => {
+                let wanted = wanted.min(MAX_PARTICLES);
+                pool.spawn_bulk(&mut entities, &mut particles,
+                                (0..wanted.saturating_sub(count)).map(|_| new_particle(&grid)));
+                Some(wanted)
+            }
+            ToolCommand::Vortex { x, y, strength } => {
+                vortices.0.push(Vortex { center: Vec2::new(x, y), strength });
+                None
+            }
+            ToolCommand::Spray { x, y, direction, speed, count } => {
+                let (x, y) = (x.max(0.).min(grid.width - 1.), y.max(0.).min(grid.height - 1.));
+                pool.spawn_bulk(&mut entities, &mut particles, (0..count).map(|_| {
+                    let angle = direction + rand::gen_range(-SPRAY_SPREAD, SPRAY_SPREAD);
+                    new_particle_at(&grid, x, y, angle.cos() * speed, angle.sin() * speed)
+                }));
+                None
+            }
+            ToolCommand::Erase { x, y, radius } => {
+                erase_under(&mut pool, &mut particles, &grid, Vec2::new(x, y), radius);
+                None
+            }
+            // resample_grid, resize_world and run_edit_commands have already
+            // taken these out
+            ToolCommand::Resample { .. } | ToolCommand::Resize { .. } | ToolCommand::Stir { .. }
+            | ToolCommand::Paint { .. } | ToolCommand::PlaceEmitter { .. } | ToolCommand::PlaceSink { .. }
+            | ToolCommand::Unplace { .. } | ToolCommand::Adjust { .. } | ToolCommand::HoldBoat { .. }
+            | ToolCommand::FlingBoat { .. } => None,
+        };
+        if let Some(keep) = keep {
+            let cull: Vec<EntityId> = particles.iter().with_id()
+                .map(|(id, _)| id)
+                .skip(keep)
+                .collect();
+            pool.despawn_bulk(&mut particles, &cull);
+        }
+    }
+}
+
+// in the tick before run_tool_commands: the brush, wall, edit and boat tools'
+// edits, on the cells, the placements and the boats. the placements are saved
+// to the scenario file as they change
+pub fn run_edit_commands(mut commands: UniqueViewMut<ToolCommands>,
+                         mut map: UniqueViewMut<Cells>,
+                         grid: UniqueView<GridGeometry>,
+                         mut entities: EntitiesViewMut,
+                         mut emitters: ViewMut<Emitter>,
+                         mut sinks: ViewMut<Sink>,
+                         mut boats: ViewMut<Boat>,
+                         ids: View<PlayerId>,
+                         level: UniqueView<Level>) {
+    let _t = profiling::scope("run_edit_commands");
+    let size = grid.size();
+    let mut changed = false;
+    commands.0.retain(|command| {
+        match *command {
+            ToolCommand::Stir { x, y, dx, dy, radius } => {
+                stir(&mut map, &grid, Vec2::new(x, y), Vec2::new(dx, dy), radius);
+            }
+            ToolCommand::Paint { x, y, radius, kind, aim_x, aim_y } => {
+                paint_cells(&mut map, &grid, Vec2::new(x, y), radius, kind, Vec2::new(aim_x, aim_y));
+            }
+            ToolCommand::PlaceEmitter { x, y, vx, vy } => {
+                entities.add_entity(&mut emitters, Emitter::new(Vec2::new(x, y), Vec2::new(vx, vy)));
+                changed = true;
+            }
+            ToolCommand::PlaceSink { x, y } => {
+                entities.add_entity(&mut sinks, Sink { pos: Vec2::new(x, y), radius: DEFAULT_SINK_RADIUS });
+                changed = true;
+            }
+            // an emitter's spread, or failing that a sink's radius
+            ToolCommand::Adjust { x, y, step } => {
+                let at = Vec2::new(x, y);
+                if let Some(emitter) = (&mut emitters).iter().find(|emitter| wrapped_dist(emitter.pos, at, size) < PICK_RADIUS) {
+                    emitter.spread = (emitter.spread + SPREAD_STEP * step).max(0.).min(MAX_EMITTER_SPREAD);
+                    changed = true;
+                } else if let Some(sink) = (&mut sinks).iter().find(|sink| wrapped_dist(sink.pos, at, size) < sink.radius) {
+                    sink.radius = (sink.radius + SINK_RADIUS_STEP * step).max(MIN_SINK_RADIUS).min(MAX_SINK_RADIUS);
+                    changed = true;
+                }
+            }
+            ToolCommand::Unplace { x, y } => {
+                let at = Vec2::new(x, y);
+                let emitter = (&emitters).iter().with_id().find(|(_, emitter)| wrapped_dist(emitter.pos, at, size) < PICK_RADIUS);
+                let sink = (&sinks).iter().with_id().find(|(_, sink)| wrapped_dist(sink.pos, at, size) < PICK_RADIUS);
+                if let Some((id, _)) = emitter {
+                    emitters.remove(id);
+                    entities.delete_unchecked(id);
+                    changed = true;
+                } else if let Some((id, _)) = sink {
+                    sinks.remove(id);
+                    entities.delete_unchecked(id);
+                    changed = true;
+                }
+            }
+            ToolCommand::HoldBoat { player, x, y } => {
+                if let Some((boat, _)) = (&mut boats, &ids).iter().find(|(_, id)| id.0 == player) {
+                    boat.loc.x = x as Real;
+                    boat.loc.y = y as Real;
+                    // held still in the hand, the sim would otherwise carry on moving it
+                    boat.vel = Vec2::new(0., 0.);
+                }
+            }
+            ToolCommand::FlingBoat { player, vx, vy } => {
+                if let Some((boat, _)) = (&mut boats, &ids).iter().find(|(_, id)| id.0 == player) {
+                    boat.vel = Vec2::new(vx, vy);
+                }
+            }
+            _ => return true,
+        }
+        false
+    });
+
+    if let (true, Some(path)) = (changed, &level.save_path) {
+        Scenario::save_placements(path, (&emitters).iter(), (&sinks).iter());
+    }
+}
+
+// the cursor for each tool, centred on (x, y). macroquad's own is hidden
+// while playing, so this is the only pointer there is
+fn draw_cursor(tool: Tool, x: f32, y: f32, swirl: f32) {
+    match tool {
+        // an open hand, roughly: a ring to grab the boat with
+        Tool::Boat => draw_circle_lines(x, y, 6., 1., WHITE),
+        // a crosshair with the spray fanning out to the right
+        Tool::Spray => {
+            draw_line(x - 4., y, x + 4., y, 1., WHITE);
+            draw_line(x, y - 4., x, y + 4., 1., WHITE);
+            for i in -1..=1 {
+                let angle = i as f32 * SPRAY_SPREAD;
+                draw_circle(x + 8. * angle.cos(), y + 8. * angle.sin(), 1., SKYBLUE);
+            }
+        }
+        Tool::Brush => {
+            draw_line(x - 4., y, x + 4., y, 1., WHITE);
+            draw_line(x, y - 4., x, y + 4., 1., WHITE);
+        }
+        Tool::Wall => draw_rectangle_lines(x - 4., y - 4., 8., 8., 1., WHITE),
+        Tool::Erase => {
+            draw_line(x - 4., y - 4., x + 4., y + 4., 1., WHITE);
+            draw_line(x - 4., y + 4., x + 4., y - 4., 1., WHITE);
+        }
+        // a plus with a dot, like a fresh emitter
+        Tool::Edit => {
+            draw_line(x - 5., y, x + 5., y, 1., WHITE);
+            draw_line(x, y - 5., x, y + 5., 1., WHITE);
+            draw_circle(x, y, 2., GREEN);
+        }
+        // a magnifying glass
+        Tool::Inspect => {
+            draw_circle_lines(x, y, 5., 1., WHITE);
+            draw_line(x + 3.5, y + 3.5, x + 8., y + 8., 2., WHITE);
+        }
+    }
+    // outside the brush tools right-click drops a vortex: show which way it'll
+    // spin with a little arc and arrowhead off to the side
+    if !tool.has_brush() && tool != Tool::Edit && swirl != 0. {
+        let (cx, cy, r) = (x + 12., y - 12., 4.);
+        let segments = 6;
+        for i in 0..segments {
+            let a0 = i as f32 / segments as f32 * 4.5;
+            let a1 = (i + 1) as f32 / segments as f32 * 4.5;
+            let (a0, a1) = if swirl > 0. { (a0, a1) } else { (-a0, -a1) };
+            draw_line(cx + r * a0.cos(), cy + r * a0.sin(), cx + r * a1.cos(), cy + r * a1.sin(), 1., ORANGE);
+        }
+        let end = if swirl > 0. { 4.5f32 } else { -4.5 };
+        draw_circle(cx + r * end.cos(), cy + r * end.sin(), 1.5, ORANGE);
+    }
+}
+
+// the current tool in the bottom right, and the cursor. this is drawn in
+// screen pixels, after the camera's done, so anything in the world (the
+// brush, the emitter being aimed) is scaled by the zoom here
+pub fn draw_tool(_screen: UniqueViewMut<Screen>,
+                 screen: UniqueView<ScreenInfo>,
+                 tool_mode: UniqueView<ToolMode>,
+                 camera: UniqueView<Camera>) {
+    let (mouse_x, mouse_y) = mouse_position();
+    draw_cursor(tool_mode.tool, mouse_x, mouse_y, tool_mode.swirl);
+    if let Some(pos) = tool_mode.pending_emitter {
+        let pos = camera.world_to_screen(pos);
+        draw_line(pos.x, pos.y, mouse_x, mouse_y, 1., GREEN);
+    }
+    let label = if tool_mode.tool.has_brush() {
+        draw_circle_lines(mouse_x, mouse_y, tool_mode.brush_radius * camera.pixels_per_unit(), 1., GRAY);
+        format!("tool (T): {}, radius (ctrl+wheel) {}", tool_mode.tool.name(), tool_mode.brush_radius)
+    } else if tool_mode.tool == Tool::Edit {
+        "tool (T): edit, spread / sink radius (ctrl+wheel over one)".to_owned()
+    } else {
+        format!("tool (T): {}, swirl (ctrl+wheel) {:+.1}", tool_mode.tool.name(), tool_mode.swirl)
+    };
+    let width = measure_text(&label, None, 16, 1.).width;
+    draw_text(&label, screen.width - width - 8., screen.height - 8., 16., WHITE);
+}
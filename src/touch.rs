@@ -0,0 +1,134 @@
+// touch controls, for phones and the web build. they switch on by themselves
+// the first time a touch comes in:
+//   - a touch on the left half of the screen is a virtual joystick, centred
+//     where it went down: sideways turns, pushing up thrusts
+//   - a quick tap on the right half fires
+//   - dragging a finger anywhere else stirs the fluid under it (so the
+//     joystick thumb and a stirring finger can be down at once)
+// (macroquad also turns touches into mouse clicks by default, which would set
+// the mouse tools off as well, so that gets turned off along with it)
+
+use std::collections::HashMap;
+
+use macroquad::miniquad::date;
+use macroquad::prelude::*;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::boat::PlayerId;
+use crate::camera::Camera;
+use crate::input::BoatControls;
+use crate::profiling;
+use crate::tools::{ToolCommand, ToolCommands};
+use crate::window::ScreenInfo;
+use crate::Screen;
+
+// how far (in pixels) the joystick goes before it's at full deflection
+const JOYSTICK_RADIUS: f32 = 50.;
+// a tap is a touch shorter and stiller than this
+const TAP_SECONDS: f64 = 0.25;
+const TAP_SLOP: f32 = 10.;
+const STIR_RADIUS: f32 = 50.;
+
+struct TouchStart {
+    position: Vec2,
+    time: f64,
+}
+
+#[derive(Component, Default)]
+pub struct TouchControls {
+    pub active: bool,
+    starts: HashMap<u64, TouchStart>,
+    // the joystick's touch, and where it went down
+    joystick: Option<(u64, Vec2)>,
+    joystick_at: Vec2,
+    // where each stirring finger was last frame (in the world)
+    drags: HashMap<u64, Vec2>,
+}
+
+// runs after read_controls, and takes over the first player's boat from the
+// keyboard/gamepad while a finger is on the joystick
+pub fn read_touch(mut touch: UniqueViewMut<TouchControls>,
+                  mut controls: ViewMut<BoatControls>,
+                  ids: View<PlayerId>,
+                  mut commands: UniqueViewMut<ToolCommands>,
+                  camera: UniqueView<Camera>,
+                  _screen: UniqueView<Screen>,
+                  screen: UniqueView<ScreenInfo>) {
+    let _t = profiling::scope("read_touch");
+    let touches = touches();
+    if touches.is_empty() && !touch.active {
+        return;
+    }
+    if !touch.active {
+        touch.active = true;
+        simulate_mouse_with_touch(false);
+    }
+
+    let now = date::now();
+    let mut fire = false;
+    for t in touches.iter() {
+        match t.phase {
+            TouchPhase::Started => {
+                touch.starts.insert(t.id, TouchStart { position: t.position, time: now });
+                if touch.joystick.is_none() && t.position.x < screen.width / 2. {
+                    touch.joystick = Some((t.id, t.position));
+                }
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                if let Some(start) = touch.starts.remove(&t.id) {
+                    let tap = now - start.time < TAP_SECONDS && (t.position - start.position).length() < TAP_SLOP;
+                    if tap && start.position.x >= screen.width / 2. {
+                        fire = true;
+                    }
+                }
+                if touch.joystick.map_or(false, |(id, _)| id == t.id) {
+                    touch.joystick = None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut stick = None;
+    if let Some((id, origin)) = touch.joystick {
+        if let Some(t) = touches.iter().find(|t| t.id == id) {
+            let offset = (t.position - origin) / JOYSTICK_RADIUS;
+            stick = Some((offset.x.max(-1.).min(1.), (-offset.y).max(0.).min(1.)));
+            touch.joystick_at = t.position;
+        }
+    }
+    if let Some((controls, _)) = (&mut controls, &ids).iter().find(|(_, id)| id.is_first()) {
+        controls.fire = controls.fire || fire;
+        if let Some((turn, thrust)) = stick {
+            controls.turn = turn;
+            controls.thrust = thrust;
+        }
+    }
+
+    // any other finger that's moved further than a tap stirs where it goes
+    let joystick = touch.joystick.map(|(id, _)| id);
+    let mut drags = HashMap::new();
+    for t in touches.iter() {
+        if Some(t.id) == joystick || t.phase == TouchPhase::Ended || t.phase == TouchPhase::Cancelled {
+            continue;
+        }
+        let moved = touch.starts.get(&t.id).map_or(false, |start| (t.position - start.position).length() >= TAP_SLOP);
+        if !moved {
+            continue;
+        }
+        let at = camera.screen_to_world(t.position);
+        if let Some(last) = touch.drags.get(&t.id) {
+            let motion = at - *last;
+            commands.0.push(ToolCommand::Stir { x: at.x, y: at.y, dx: motion.x, dy: motion.y, radius: STIR_RADIUS });
+        }
+        drags.insert(t.id, at);
+    }
+    touch.drags = drags;
+}
+
+pub fn draw_touch_controls(_screen: UniqueViewMut<Screen>, touch: UniqueView<TouchControls>) {
+    if let Some((_, origin)) = touch.joystick {
+        draw_circle_lines(origin.x, origin.y, JOYSTICK_RADIUS, 1., GRAY);
+        draw_circle(touch.joystick_at.x, touch.joystick_at.y, 10., Color::new(1., 1., 1., 0.4));
+    }
+}
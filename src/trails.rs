@@ -0,0 +1,162 @@
+// trail mode (N, see visualization.rs): rather than being drawn fresh each
+// frame, the particles are drawn into a render target the size of the world
+// that's only faded a little each frame, never cleared. so each one leaves a
+// long smooth trail behind it that fades out over a second or two, and the
+// flow's shape builds up on the screen the way it does in the classic flow
+// pictures.
+//
+// GL calls have to happen on the main thread (see gpu.rs), so the drawing into
+// the target is done from the main loop, by TrailRenderer::update before the
+// frame's step. it leaves the target's texture in the TrailCanvas unique, and
+// draw_trails puts it on the screen under the boat, added on top of what's
+// there so the faded black doesn't hide the field view underneath.
+
+use macroquad::miniquad::{BlendFactor, BlendState, Equation, PipelineParams};
+use macroquad::prelude::*;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View, World};
+
+use crate::accessibility::Accessibility;
+use crate::clock::SimClock;
+use crate::grid::GridGeometry;
+use crate::visualization::Visualization;
+use crate::{Particle, Screen};
+
+// how much of what's on the canvas fades away each frame
+const FADE: f32 = 0.04;
+// the trails are drawn this much wider than the particles' usual lines
+const TRAIL_WIDTH: f32 = 2.;
+
+// macroquad's own shaders: it's only the blending that's different
+const VERTEX_SHADER: &str = r#"#version 100
+attribute vec3 position;
+attribute vec2 texcoord;
+attribute vec4 color0;
+
+varying lowp vec2 uv;
+varying lowp vec4 color;
+
+uniform mat4 Model;
+uniform mat4 Projection;
+
+void main() {
+    gl_Position = Projection * Model * vec4(position, 1);
+    color = color0 / 255.0;
+    uv = texcoord;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"#version 100
+varying lowp vec4 color;
+varying lowp vec2 uv;
+
+uniform sampler2D Texture;
+
+void main() {
+    gl_FragColor = color * texture2D(Texture, uv);
+}
+"#;
+
+// what the renderer's drawn, for draw_trails
+#[derive(Component, Default)]
+pub struct TrailCanvas {
+    texture: Option<Texture2D>,
+    // draws a texture added onto what's under it
+    additive: Option<Material>,
+}
+
+pub struct TrailRenderer {
+    // the canvas, and the world size it was made for
+    target: Option<(RenderTarget, (u32, u32))>,
+    additive: Material,
+    // whether it was drawn into last frame (if not, it's stale and gets cleared)
+    was_on: bool,
+}
+
+impl TrailRenderer {
+    pub fn new() -> TrailRenderer {
+        let additive = load_material(
+            VERTEX_SHADER,
+            FRAGMENT_SHADER,
+            MaterialParams {
+                pipeline_params: PipelineParams {
+                    color_blend: Some(BlendState::new(Equation::Add, BlendFactor::One, BlendFactor::One)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        TrailRenderer { target: None, additive, was_on: false }
+    }
+
+    // fade the canvas a little and draw this frame's stretch of each
+    // particle's trail onto it
+    pub fn update(&mut self, world: &World) {
+        let on = world.borrow::<UniqueView<Visualization>>().map_or(false, |vis| vis.trails);
+        let mut canvas = match world.borrow::<UniqueViewMut<TrailCanvas>>() {
+            Ok(canvas) => canvas,
+            Err(_) => return,
+        };
+        if !on {
+            canvas.texture = None;
+            self.was_on = false;
+            return;
+        }
+        let grid = world.borrow::<UniqueView<GridGeometry>>().unwrap();
+        let size = (grid.width.round().max(1.) as u32, grid.height.round().max(1.) as u32);
+        let target = match self.target {
+            Some((target, made_for)) if made_for == size => target,
+            _ => {
+                let target = render_target(size.0, size.1);
+                target.texture.set_filter(FilterMode::Linear);
+                self.target = Some((target, size));
+                self.was_on = false;
+                target
+            }
+        };
+
+        set_camera(&Camera2D {
+            render_target: Some(target),
+            ..Camera2D::from_display_rect(Rect::new(0., 0., grid.width, grid.height))
+        });
+        if self.was_on {
+            draw_rectangle(0., 0., grid.width, grid.height, Color::new(0., 0., 0., FADE));
+        } else {
+            clear_background(BLACK);
+        }
+        let particles = world.borrow::<View<Particle>>().unwrap();
+        let access = world.borrow::<UniqueView<Accessibility>>().unwrap();
+        let alpha = world.borrow::<UniqueView<SimClock>>().map_or(1., |clock| clock.alpha());
+        let width = access.particle_width * TRAIL_WIDTH;
+        for particle in particles.iter() {
+            // from where it was drawn last frame, near enough
+            let to = particle.drawn_position(alpha);
+            let from = to - particle.velocity;
+            draw_line(from.x, from.y, to.x, to.y, width, particle.color(&access));
+        }
+        set_default_camera();
+
+        self.was_on = true;
+        canvas.texture = Some(target.texture);
+        canvas.additive = Some(self.additive);
+    }
+}
+
+// world space, before the boat's drawn
+pub fn draw_trails(_screen: UniqueViewMut<Screen>,
+                   canvas: UniqueView<TrailCanvas>,
+                   vis: UniqueView<Visualization>,
+                   grid: UniqueView<GridGeometry>) {
+    let (texture, additive) = match (canvas.texture, canvas.additive) {
+        (Some(texture), Some(additive)) if vis.trails => (texture, additive),
+        _ => return,
+    };
+    gl_use_material(additive);
+    // (a render target comes out upside down)
+    draw_texture_ex(texture, 0., 0., WHITE, DrawTextureParams {
+        dest_size: Some(vec2(grid.width, grid.height)),
+        flip_y: true,
+        ..Default::default()
+    });
+    gl_use_default_material();
+}
@@ -0,0 +1,122 @@
+// particle trajectories, for measuring how the transfer schemes spread and
+// mix things: `--trajectories PATH` tags a handful of particles (--track N of
+// them, 64 by default, spread through the lot) on the first tick and writes
+// down where each one is and how fast it's going every sim tick after that.
+// it's all saved when the game ends on esc, as CSV, or as JSON if PATH ends .json:
+//
+//   tick,particle,x,y,vx,vy           one row per tagged particle per tick
+//   {"tick_seconds": .., "particles": [{"particle": 0, "samples": [[tick, x, y, vx, vy], ..]}, ..]}
+//
+// a tagged particle that gets removed (sunk, culled, erased) just stops
+// having samples.
+
+use std::fmt::Write as _;
+use std::fs;
+
+use macroquad::prelude::*;
+use serde::Serialize;
+use shipyard::{Component, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View};
+
+use crate::clock::TICK;
+use crate::console::Console;
+use crate::demo::path_after;
+use crate::keymap::{Action, KeyBindings};
+use crate::profiling;
+use crate::Particle;
+
+const DEFAULT_TRACKED: usize = 64;
+
+#[derive(Serialize)]
+struct Track {
+    particle: usize,
+    // tick, x, y, vx, vy
+    samples: Vec<[f32; 5]>,
+}
+
+#[derive(Serialize)]
+struct TrajectoryFile<'a> {
+    tick_seconds: f32,
+    particles: &'a [Track],
+}
+
+#[derive(Component)]
+pub struct Trajectories {
+    // where to save, if there's a capture going at all
+    path: Option<String>,
+    wanted: usize,
+    // the tagged particles, in the same order as `tracks`; empty until the first tick
+    tagged: Vec<EntityId>,
+    tracks: Vec<Track>,
+    tick: usize,
+}
+
+impl Trajectories {
+    pub fn from_args() -> Trajectories {
+        let wanted = path_after("--track").and_then(|n| n.parse().ok()).unwrap_or(DEFAULT_TRACKED);
+        Trajectories { path: path_after("--trajectories"), wanted, tagged: vec![], tracks: vec![], tick: 0 }
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("tick,particle,x,y,vx,vy\n");
+        for track in self.tracks.iter() {
+            for [tick, x, y, vx, vy] in track.samples.iter() {
+                writeln!(csv, "{},{},{},{},{},{}", tick, track.particle, x, y, vx, vy).unwrap();
+            }
+        }
+        csv
+    }
+
+    fn save(&self, path: &str) -> Result<(), String> {
+        let text = if path.ends_with(".json") {
+            let file = TrajectoryFile { tick_seconds: TICK, particles: &self.tracks };
+            serde_json::to_string(&file).map_err(|err| err.to_string())?
+        } else {
+            self.to_csv()
+        };
+        fs::write(path, text).map_err(|err| format!("couldn't write {}: {}", path, err))
+    }
+}
+
+// every sim tick, after the particles have moved
+pub fn capture_trajectories(mut trajectories: UniqueViewMut<Trajectories>, particles: View<Particle>) {
+    let _t = profiling::scope("capture_trajectories");
+    let trajectories = &mut *trajectories;
+    if trajectories.path.is_none() {
+        return;
+    }
+    if trajectories.tick == 0 {
+        let every = (particles.iter().count() / trajectories.wanted.max(1)).max(1);
+        trajectories.tagged = particles.iter().with_id()
+            .step_by(every)
+            .take(trajectories.wanted)
+            .map(|(id, _)| id)
+            .collect();
+        trajectories.tracks = (0..trajectories.tagged.len())
+            .map(|particle| Track { particle, samples: vec![] })
+            .collect();
+    }
+    let tick = trajectories.tick as f32;
+    for (id, track) in trajectories.tagged.iter().zip(trajectories.tracks.iter_mut()) {
+        if let Ok(p) = particles.get(*id) {
+            track.samples.push([tick, p.position.x as f32, p.position.y as f32, p.velocity.x, p.velocity.y]);
+        }
+    }
+    trajectories.tick += 1;
+}
+
+// runs before handle_key_presses, which ends the game on esc
+pub fn save_trajectories(trajectories: UniqueView<Trajectories>,
+                         mut console: UniqueViewMut<Console>,
+                         keys: UniqueView<KeyBindings>) {
+    let path = match trajectories.path.as_ref() {
+        Some(path) if keys.pressed(Action::Exit) => path,
+        _ => return,
+    };
+    match trajectories.save(path) {
+        Ok(()) => info!("saved {} trajectories to {}", trajectories.tracks.len(), path),
+        Err(err) => {
+            warn!("{}", err);
+            console.print(err);
+        }
+    }
+}
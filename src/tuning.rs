@@ -0,0 +1,367 @@
+// the tuning panel (F2): live sliders for the handful of coupling numbers that
+// decide what the sim feels like, with a button to put them all back and one
+// to save them as the defaults, in the [tuning] section of settings.toml
+// (rewritten in place, so any comments in the file stay put).
+//
+//   particle <- cell   how hard a particle is pulled toward its cell's flow
+//   cell <- particles  how fast a cell's flow takes on its particles' velocity
+//   thrust blend       how quickly thrust swings the boat's velocity round
+//   hull drag          how hard the flow drags the drifting boats along
+//   trail length       how long the particles' velocity tails are drawn
+//   hull turn          how quickly a boat's hull is drawn swinging round to
+//                      where it's going (see Boat::settle_heading)
+//   viscosity          how much a cell's flow evens out with its neighbours'
+//                      each step (see apply_grid_updates)
+//   vorticity          how hard the swirls in the flow are spun back up (see
+//                      confinement.rs)
+//   repulsion          how hard particles too near each other push apart (see
+//                      neighbours.rs)
+//   thrust power       the speed the engine adds on top of the boat's own
+//
+// under those are the sim's own sizes, from the SimConfig (see config.rs):
+// the particle count, which changes as soon as the slider's let go of (the
+// same way the tools' edits do, so a replay has it too), and the grid's cols
+// and rows, which take from the next game. and under those, buttons for the
+// views: the debug view, and stepping through the field views, particle
+// styles and colormaps, the same as the keys do (see visualization.rs).
+//
+// the last two also have keys, panel or not: Y / U for less and more
+// viscosity, H / J for less and more vorticity.
+//
+// the values live in the Tuning unique, which the systems using them read
+// every step. while the mouse is over the panel the tools leave it alone.
+
+use macroquad::prelude::*;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View};
+
+use crate::camera::Camera;
+use crate::config::SimConfig;
+use crate::keymap::{Action, KeyBindings};
+use crate::settings::{write_section, Settings, SETTINGS_PATH};
+use crate::tools::{ToolCommand, ToolCommands};
+use crate::visualization::Visualization;
+use crate::{GameMode, GameModeInfo, Particle, Screen};
+
+#[derive(Component, Clone, Copy)]
+pub struct Tuning {
+    pub cell_to_particle: f32,
+    pub particle_to_cell: f32,
+    pub thrust_blend: f32,
+    pub hull_drag: f32,
+    pub trail_length: f32,
+    pub hull_turn: f32,
+    pub viscosity: f32,
+    pub vorticity: f32,
+    pub repulsion: f32,
+    pub thrust_power: f32,
+}
+
+impl Default for Tuning {
+    fn default() -> Tuning {
+        Tuning { cell_to_particle: 0.03, particle_to_cell: 0.1, thrust_blend: 0.1, hull_drag: 0.05, trail_length: 8.,
+                 hull_turn: 0.15, viscosity: 0., vorticity: 0., repulsion: 0., thrust_power: 0.1 }
+    }
+}
+
+// label, range
+const SLIDERS: [(&str, f32, f32); 10] = [
+    ("particle <- cell", 0., 0.2),
+    ("cell <- particles", 0., 1.),
+    ("thrust blend", 0., 0.5),
+    ("hull drag", 0., 0.3),
+    ("trail length", 0., 32.),
+    ("hull turn", 0.01, 1.),
+    ("viscosity", 0., 1.),
+    ("vorticity", 0., 0.5),
+    ("repulsion", 0., 0.5),
+    ("thrust power", 0., 0.5),
+];
+
+// what the console's `set` calls them, indexed like SLIDERS
+pub const NAMES: [&str; 10] = ["cell_to_particle", "particle_to_cell", "thrust_blend", "hull_drag", "trail_length", "hull_turn",
+                              "viscosity", "vorticity", "repulsion", "thrust_power"];
+
+// the SimConfig's, under those: label, range
+const SIM_SLIDERS: [(&str, f32, f32); 3] = [
+    ("particles", 0., 20_000.),
+    ("grid cols", 4., 160.),
+    ("grid rows", 4., 96.),
+];
+
+// the view buttons, under those
+const TOGGLES: [&str; 4] = ["debug view", "field", "particle style", "colormap"];
+
+const VISCOSITY: usize = 6;
+const VORTICITY: usize = 7;
+// a key press moves a value this much of its slider's range
+const KEY_STEP: f32 = 0.05;
+
+// how long the save button says "saved" after a save (and a key's change
+// shows), in seconds
+const SAVED_SECONDS: f64 = 2.;
+
+impl Tuning {
+    // the defaults, with whatever the settings file has saved over them
+    pub fn from_settings(settings: &Settings) -> Tuning {
+        let mut tuning = Tuning::default();
+        for (name, value) in settings.tuning.iter() {
+            if !tuning.set(name, *value) {
+                warn!("no tuning value called {} (in {})", name, SETTINGS_PATH);
+            }
+        }
+        tuning
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let values: Vec<(&str, String)> = NAMES.iter().enumerate()
+            .map(|(i, name)| (*name, format!("{}", self.value(i))))
+            .collect();
+        write_section(SETTINGS_PATH, "tuning", &values)
+    }
+
+    // set a value by name, false if there's no such value
+    pub fn set(&mut self, name: &str, value: f32) -> bool {
+        match NAMES.iter().position(|n| *n == name) {
+            Some(i) => {
+                *self.value_mut(i) = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn value(&self, i: usize) -> f32 {
+        match i {
+            0 => self.cell_to_particle,
+            1 => self.particle_to_cell,
+            2 => self.thrust_blend,
+            3 => self.hull_drag,
+            4 => self.trail_length,
+            5 => self.hull_turn,
+            6 => self.viscosity,
+            7 => self.vorticity,
+            8 => self.repulsion,
+            _ => self.thrust_power,
+        }
+    }
+
+    // the value slider `i` moves
+    fn value_mut(&mut self, i: usize) -> &mut f32 {
+        match i {
+            0 => &mut self.cell_to_particle,
+            1 => &mut self.particle_to_cell,
+            2 => &mut self.thrust_blend,
+            3 => &mut self.hull_drag,
+            4 => &mut self.trail_length,
+            5 => &mut self.hull_turn,
+            6 => &mut self.viscosity,
+            7 => &mut self.vorticity,
+            8 => &mut self.repulsion,
+            _ => &mut self.thrust_power,
+        }
+    }
+
+    // a key's worth up (or down, for negative `steps`) its slider's range
+    fn nudge(&mut self, i: usize, steps: f32) {
+        let (_, min, max) = SLIDERS[i];
+        let value = self.value_mut(i);
+        *value = (*value + steps * KEY_STEP * (max - min)).max(min).min(max);
+    }
+}
+
+#[derive(Component, Default)]
+pub struct TuningPanel {
+    open: bool,
+    // the slider being dragged
+    dragging: Option<usize>,
+    // the particle count the slider's been dragged to, until it's let go
+    particles: Option<f32>,
+    // when the values were last saved, for the button
+    saved_at: Option<f64>,
+    // the value a key last changed, and when, for showing with the panel shut
+    nudged: Option<(usize, f64)>,
+}
+
+const PANEL_LEFT: f32 = 8.;
+const PANEL_TOP: f32 = 200.;
+const PANEL_WIDTH: f32 = 260.;
+const ROW_HEIGHT: f32 = 24.;
+// where the slider tracks start, and how long they are
+const TRACK_LEFT: f32 = PANEL_LEFT + 120.;
+const TRACK_WIDTH: f32 = 90.;
+// the tuning's sliders and then the config's, one track each
+const TRACKS: usize = SLIDERS.len() + SIM_SLIDERS.len();
+// the title, the tracks, the view buttons and the reset and save row
+const ROWS: usize = TRACKS + TOGGLES.len() + 2;
+
+fn panel_rect() -> Rect {
+    Rect::new(PANEL_LEFT, PANEL_TOP, PANEL_WIDTH, ROW_HEIGHT * ROWS as f32)
+}
+
+// the slider tracks (a row tall, so they're easy to hit)
+fn track_rect(i: usize) -> Rect {
+    Rect::new(TRACK_LEFT, PANEL_TOP + ROW_HEIGHT * (i + 1) as f32, TRACK_WIDTH, ROW_HEIGHT)
+}
+
+// the view buttons, where the tracks would be
+fn toggle_rect(i: usize) -> Rect {
+    Rect { y: track_rect(TRACKS + i).y + 3., h: ROW_HEIGHT - 6., w: TRACK_WIDTH + 30., ..track_rect(0) }
+}
+
+fn reset_rect() -> Rect {
+    Rect::new(PANEL_LEFT + 8., PANEL_TOP + ROW_HEIGHT * (ROWS - 1) as f32 + 2., 60., ROW_HEIGHT - 6.)
+}
+
+fn save_rect() -> Rect {
+    Rect { x: PANEL_LEFT + 76., ..reset_rect() }
+}
+
+// the config slider `i`'s value (counting from the first of them), and
+// `particles` is how many there are now
+fn sim_value(i: usize, config: &SimConfig, panel: &TuningPanel, particles: usize) -> f32 {
+    match i {
+        0 => panel.particles.unwrap_or(particles as f32),
+        1 => config.cols as f32,
+        _ => config.rows as f32,
+    }
+}
+
+// runs before the tools, so it can claim the mouse first
+pub fn tune(mut tuning: UniqueViewMut<Tuning>,
+            mut panel: UniqueViewMut<TuningPanel>,
+            mut camera: UniqueViewMut<Camera>,
+            mut config: UniqueViewMut<SimConfig>,
+            mut vis: UniqueViewMut<Visualization>,
+            mut game_mode: UniqueViewMut<GameModeInfo>,
+            mut commands: UniqueViewMut<ToolCommands>,
+            keys: UniqueView<KeyBindings>,
+            _screen: UniqueView<Screen>) {
+    if keys.pressed(Action::Tuning) {
+        panel.open = !panel.open;
+    }
+    let nudges = [
+        (Action::LessViscosity, VISCOSITY, -1.),
+        (Action::MoreViscosity, VISCOSITY, 1.),
+        (Action::LessVorticity, VORTICITY, -1.),
+        (Action::MoreVorticity, VORTICITY, 1.),
+    ];
+    for (action, i, steps) in nudges.iter() {
+        if keys.pressed(*action) {
+            tuning.nudge(*i, *steps);
+            panel.nudged = Some((*i, get_time()));
+        }
+    }
+    let (mouse_x, mouse_y) = mouse_position();
+    let mouse = Vec2::new(mouse_x, mouse_y);
+    if !panel.open {
+        panel.dragging = None;
+        panel.particles = None;
+        camera.ui_has_mouse = false;
+        return;
+    }
+
+    if is_mouse_button_pressed(MouseButton::Left) {
+        panel.dragging = (0..TRACKS).find(|i| track_rect(*i).contains(mouse));
+        match (0..TOGGLES.len()).find(|i| toggle_rect(*i).contains(mouse)) {
+            Some(0) if game_mode.game_mode == GameMode::Debug => game_mode.game_mode = GameMode::Default,
+            Some(0) => game_mode.game_mode = GameMode::Debug,
+            Some(1) => vis.next_field(),
+            Some(2) => vis.next_particles(),
+            Some(3) => vis.next_colormap(),
+            _ => {}
+        }
+        if reset_rect().contains(mouse) {
+            *tuning = Tuning::default();
+        }
+        if save_rect().contains(mouse) {
+            match tuning.save() {
+                Ok(()) => panel.saved_at = Some(get_time()),
+                Err(err) => warn!("{}", err),
+            }
+        }
+    }
+    if !is_mouse_button_down(MouseButton::Left) {
+        // the particles are only added or taken away once the slider's let
+        // go, rather than on every frame of the drag
+        if let Some(count) = panel.particles.take() {
+            commands.0.push(ToolCommand::SetCount { count: count as usize });
+            config.particles = count as usize;
+            config.edited = true;
+        }
+        panel.dragging = None;
+    }
+    if let Some(i) = panel.dragging {
+        let along = ((mouse.x - TRACK_LEFT) / TRACK_WIDTH).max(0.).min(1.);
+        if i < SLIDERS.len() {
+            let (_, min, max) = SLIDERS[i];
+            *tuning.value_mut(i) = min + along * (max - min);
+        } else {
+            let (_, min, max) = SIM_SLIDERS[i - SLIDERS.len()];
+            let value = (min + along * (max - min)).round();
+            match i - SLIDERS.len() {
+                0 => panel.particles = Some(value),
+                1 => config.cols = value as usize,
+                _ => config.rows = value as usize,
+            }
+            config.edited = true;
+        }
+    }
+    camera.ui_has_mouse = panel.dragging.is_some() || panel_rect().contains(mouse);
+}
+
+pub fn draw_tuning(_screen: UniqueViewMut<Screen>,
+                   tuning: UniqueView<Tuning>,
+                   panel: UniqueView<TuningPanel>,
+                   config: UniqueView<SimConfig>,
+                   vis: UniqueView<Visualization>,
+                   game_mode: UniqueView<GameModeInfo>,
+                   particles: View<Particle>) {
+    if !panel.open {
+        // just what a key's changed, for a moment
+        if let Some((i, at)) = panel.nudged {
+            if get_time() - at < SAVED_SECONDS {
+                let label = format!("{} {:.3}", SLIDERS[i].0, tuning.value(i));
+                draw_text(&label, PANEL_LEFT + 8., PANEL_TOP, 18., WHITE);
+            }
+        }
+        return;
+    }
+    let rect = panel_rect();
+    draw_rectangle(rect.x, rect.y, rect.w, rect.h, Color::new(0., 0., 0., 0.8));
+    draw_rectangle_lines(rect.x, rect.y, rect.w, rect.h, 1., GRAY);
+    draw_text("tuning (F2)", PANEL_LEFT + 8., PANEL_TOP + ROW_HEIGHT - 6., 18., WHITE);
+    let count = particles.iter().count();
+    for i in 0..TRACKS {
+        let (label, min, max, value, text) = if i < SLIDERS.len() {
+            let (label, min, max) = SLIDERS[i];
+            (label, min, max, tuning.value(i), format!("{:.3}", tuning.value(i)))
+        } else {
+            let (label, min, max) = SIM_SLIDERS[i - SLIDERS.len()];
+            let value = sim_value(i - SLIDERS.len(), &config, &panel, count);
+            (label, min, max, value, format!("{}", value))
+        };
+        let track = track_rect(i);
+        let middle = track.y + track.h / 2.;
+        let knob = track.x + ((value - min) / (max - min)).max(0.).min(1.) * track.w;
+        let color = if panel.dragging == Some(i) { YELLOW } else { WHITE };
+        draw_text(label, PANEL_LEFT + 8., middle + 5., 16., WHITE);
+        draw_line(track.x, middle, track.x + track.w, middle, 1., GRAY);
+        draw_rectangle(knob - 3., middle - 7., 6., 14., color);
+        draw_text(&text, track.x + track.w + 6., middle + 5., 16., color);
+    }
+    let debug = if game_mode.game_mode == GameMode::Debug { "on" } else { "off" };
+    let states = [debug, vis.field.name(), vis.particles.name(), vis.colormap.name()];
+    for (i, (label, state)) in TOGGLES.iter().zip(states.iter()).enumerate() {
+        let button = toggle_rect(i);
+        draw_text(label, PANEL_LEFT + 8., button.y + button.h - 5., 16., WHITE);
+        draw_rectangle_lines(button.x, button.y, button.w, button.h, 1., GRAY);
+        draw_text(state, button.x + 6., button.y + button.h - 5., 16., WHITE);
+    }
+    let reset = reset_rect();
+    draw_rectangle_lines(reset.x, reset.y, reset.w, reset.h, 1., GRAY);
+    draw_text("reset", reset.x + 12., reset.y + reset.h - 5., 16., WHITE);
+    let save = save_rect();
+    let saved = panel.saved_at.map_or(false, |at| get_time() - at < SAVED_SECONDS);
+    draw_rectangle_lines(save.x, save.y, save.w, save.h, 1., GRAY);
+    draw_text(if saved { "saved" } else { "save" }, save.x + 12., save.y + save.h - 5., 16., WHITE);
+}
@@ -0,0 +1,238 @@
+// turtle graphics: a pen with a position and a heading, drawing straight
+// away with macroquad as it moves. every boat draws its hull with one (see
+// boat.rs), and turtles.rs lets them loose on their own. turtle_path.rs
+// records paths to play back through one.
+
+use macroquad::prelude::*;
+use shipyard::Component;
+
+use crate::{Point2, Real};
+
+// how finely the turtle's arcs are cut into straight lines
+const ARC_SEGMENT_DEGREES: f32 = 10.;
+
+// how the turtle's pen draws a line. the lengths are in pixels, and the
+// pattern carries on round corners rather than starting again at each one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LineStyle {
+    Solid,
+    Dashed { dash: f32, gap: f32 },
+    // a dot (as wide as the line) every `spacing`
+    Dotted { spacing: f32 },
+}
+
+#[derive(Component, Clone)]
+pub struct Turtle {
+    pub loc: Point2,
+    pub direction: f32,
+    pub pen_down: bool,
+    pub color: Color,
+    pub line_width: f32,
+    line_style: LineStyle,
+    // how far along the dash pattern the pen is
+    pattern_at: f32,
+    // saved by push, for pop to go back to
+    stack: Vec<TurtleState>,
+    // the corners so far, between begin_fill and end_fill
+    fill: Option<Vec<Vec2>>,
+}
+
+#[derive(Clone)]
+struct TurtleState {
+    loc: Point2,
+    direction: f32,
+    pen_down: bool,
+    color: Color,
+    line_width: f32,
+    line_style: LineStyle,
+}
+
+// creates a new turtle at x,y, pen is up
+pub fn new_turtle() -> Turtle {
+    Turtle { loc: Point2 {x: 0., y: 0.}, direction: 0., pen_down: false, line_width: 1., color: WHITE,
+             line_style: LineStyle::Solid, pattern_at: 0., stack: vec![], fill: None}
+}
+
+// draws a simple (not self-crossing) polygon, concave or not, by clipping
+// off one ear (a corner whose triangle has no other corners in it) at a time
+fn fill_polygon(corners: &[Vec2], color: Color) {
+    let cross = |a: Vec2, b: Vec2, c: Vec2| (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x);
+    let mut left: Vec<Vec2> = corners.to_vec();
+    // the closing corner often lands back on the first
+    if left.len() > 1 && left[0].distance(*left.last().unwrap()) < 1e-3 {
+        left.pop();
+    }
+    // which way round it goes, so we know which corners point out
+    let winding: f32 = (0..left.len())
+        .map(|i| { let (a, b) = (left[i], left[(i + 1) % left.len()]); a.x * b.y - b.x * a.y })
+        .sum();
+    while left.len() > 3 {
+        let n = left.len();
+        let ear = (0..n).find(|&i| {
+            let (a, b, c) = (left[(i + n - 1) % n], left[i], left[(i + 1) % n]);
+            cross(a, b, c) * winding > 0.
+                && !left.iter().any(|&p| {
+                    p != a && p != b && p != c
+                        && cross(a, b, p) * winding >= 0. && cross(b, c, p) * winding >= 0. && cross(c, a, p) * winding >= 0.
+                })
+        });
+        // none left that are clean (it crosses itself, or has repeated
+        // corners): fan out the rest rather than loop for ever
+        let i = match ear {
+            Some(i) => i,
+            None => break,
+        };
+        draw_triangle(left[(i + n - 1) % n], left[i], left[(i + 1) % n], color);
+        left.remove(i);
+    }
+    for i in 1..left.len().saturating_sub(1) {
+        draw_triangle(left[0], left[i], left[i + 1], color);
+    }
+}
+
+pub fn rad_to_deg(rads: f32) -> f32{
+    rads * std::f32::consts::PI / 180.
+}
+
+impl Turtle {
+    pub fn forward(&mut self, amount: f32) {
+        let (old_x, old_y) = (self.loc.x as f32, self.loc.y as f32);
+        let (new_x, new_y) = (old_x + amount * self.direction.cos(), old_y + amount * self.direction.sin());
+        self.loc.x = new_x as Real;
+        self.loc.y = new_y as Real;
+        if self.pen_down { 
+            self.draw_styled(Vec2::new(old_x, old_y), Vec2::new(new_x, new_y));
+            if let Some(corners) = self.fill.as_mut() {
+                corners.push(Vec2::new(new_x, new_y));
+            }
+        }
+    }
+    // text at the turtle, running the way it's facing, in its colour. `size`
+    // is the font size in pixels. the turtle stays where it is
+    pub fn write(&self, text: &str, size: f32) {
+        let params = TextParams {
+            font_size: size.round() as u16,
+            color: self.color,
+            rotation: self.direction,
+            ..Default::default()
+        };
+        draw_text_ex(text, self.loc.x as f32, self.loc.y as f32, params);
+    }
+    // a line in the current style, carrying on the pattern from the last one
+    fn draw_styled(&mut self, from: Vec2, to: Vec2) {
+        let length = from.distance(to);
+        let (period, on) = match self.line_style {
+            LineStyle::Solid => {
+                draw_line(from.x, from.y, to.x, to.y, self.line_width, self.color);
+                return;
+            }
+            LineStyle::Dashed { dash, gap } => (dash + gap, dash),
+            LineStyle::Dotted { spacing } => (spacing, 0.),
+        };
+        if length <= 0. || period <= 0. {
+            return;
+        }
+        let along = |d: f32| from + (to - from) * (d / length);
+        // walk the line a pattern at a time, from where the last one left off
+        let mut d = -self.pattern_at;
+        while d < length {
+            if on > 0. {
+                let (start, end) = (d.max(0.), (d + on).min(length));
+                if end > start {
+                    let (a, b) = (along(start), along(end));
+                    draw_line(a.x, a.y, b.x, b.y, self.line_width, self.color);
+                }
+            } else if d >= 0. {
+                let dot = along(d);
+                draw_circle(dot.x, dot.y, self.line_width / 2., self.color);
+            }
+            d += period;
+        }
+        self.pattern_at = (self.pattern_at + length) % period;
+    }
+    pub fn set_line_style(&mut self, style: LineStyle) {
+        self.line_style = style;
+        self.pattern_at = 0.;
+    }
+    // start a filled shape where the turtle is. everywhere it goes with the
+    // pen down until end_fill is a corner
+    pub fn begin_fill(&mut self) {
+        self.fill = Some(vec![Vec2::new(self.loc.x as f32, self.loc.y as f32)]);
+    }
+    // fill the shape since begin_fill with the current colour, closing it
+    // back to the start
+    pub fn end_fill(&mut self) {
+        if let Some(corners) = self.fill.take() {
+            fill_polygon(&corners, self.color);
+        }
+    }
+    pub fn turn_right(&mut self, degrees: f32) {
+        self.direction += rad_to_deg(degrees);
+    }
+    pub fn turn_left(&mut self, degrees: f32) {
+        self.direction -= rad_to_deg(degrees);
+    }
+    // follow a circle of `radius` round to the right for `degrees`, drawn as
+    // short straight segments (if the pen's down)
+    pub fn arc_right(&mut self, radius: f32, degrees: f32) {
+        let segments = (degrees.abs() / ARC_SEGMENT_DEGREES).ceil().max(1.) as usize;
+        let step = degrees / segments as f32;
+        let chord = 2. * radius * (rad_to_deg(step) / 2.).sin().abs();
+        for _ in 0..segments {
+            // half a turn either side of each segment keeps it on the circle
+            self.turn_right(step / 2.);
+            self.forward(chord);
+            self.turn_right(step / 2.);
+        }
+    }
+    pub fn arc_left(&mut self, radius: f32, degrees: f32) {
+        self.arc_right(radius, -degrees);
+    }
+    // a whole circle, ending up back where it started, facing the same way.
+    // the centre is `radius` off to the right
+    pub fn circle(&mut self, radius: f32) {
+        self.arc_right(radius, 360.);
+    }
+    pub fn pen_down(&mut self) {
+        self.pen_down = true;
+    }
+    pub fn pen_up(&mut self) {
+        self.pen_down = false;
+    }
+    pub fn set_color(&mut self, new_color: Color){
+        self.color = new_color;
+    }
+    pub fn set_line_width(&mut self, new_width: f32) {
+        self.line_width = new_width;
+    }
+    pub fn position(&self) -> Vec2 {
+        Vec2::new(self.loc.x as f32, self.loc.y as f32)
+    }
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.loc.x = x as Real;
+        self.loc.y = y as Real;
+    }
+    // remember where the turtle is, which way it's facing and how it's
+    // drawing, to come back to with pop (for branching drawings)
+    pub fn push(&mut self) {
+        self.stack.push(TurtleState {
+            loc: self.loc,
+            direction: self.direction,
+            pen_down: self.pen_down,
+            color: self.color,
+            line_width: self.line_width,
+            line_style: self.line_style,
+        });
+    }
+    // back to the last push. popping more than was pushed does nothing
+    pub fn pop(&mut self) {
+        if let Some(state) = self.stack.pop() {
+            self.loc = state.loc;
+            self.direction = state.direction;
+            self.pen_down = state.pen_down;
+            self.color = state.color;
+            self.line_width = state.line_width;
+            self.line_style = state.line_style;
+        }
+    }
+}
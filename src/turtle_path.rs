@@ -0,0 +1,144 @@
+// shapes defined once and stamped wherever they're wanted. a TurtlePath
+// records turtle commands instead of drawing them:
+//
+//   let flag = TurtlePath::new("flag").pen_down().forward(10.).turn_right(90.).forward(4.);
+//
+// and draw replays them with a fresh turtle that starts at a position and
+// heading, with every distance (and line width) scaled, so one path can be a
+// hull, a marker or an icon at any size and angle, every frame. every boat's
+// hull is one (see boat.rs), as is the drifting turtles' arrowhead.
+//
+// move_to in a path is relative to where the replay started, turned with it:
+// x along the heading it started with, y off to the right of it.
+
+use macroquad::prelude::*;
+
+use crate::{new_turtle, LineStyle, Turtle};
+
+#[derive(Clone, Copy, Debug)]
+pub enum TurtleCommand {
+    MoveTo(f32, f32),
+    Forward(f32),
+    TurnRight(f32),
+    TurnLeft(f32),
+    ArcRight(f32, f32),
+    ArcLeft(f32, f32),
+    PenUp,
+    PenDown,
+    SetColor(Color),
+    SetLineWidth(f32),
+    SetLineStyle(LineStyle),
+    BeginFill,
+    EndFill,
+    Push,
+    Pop,
+}
+
+#[derive(Clone)]
+pub struct TurtlePath {
+    pub name: &'static str,
+    pub commands: Vec<TurtleCommand>,
+}
+
+impl TurtlePath {
+    pub fn new(name: &'static str) -> TurtlePath {
+        TurtlePath { name, commands: vec![] }
+    }
+
+    fn with(mut self, command: TurtleCommand) -> TurtlePath {
+        self.commands.push(command);
+        self
+    }
+
+    pub fn move_to(self, x: f32, y: f32) -> TurtlePath {
+        self.with(TurtleCommand::MoveTo(x, y))
+    }
+    pub fn forward(self, amount: f32) -> TurtlePath {
+        self.with(TurtleCommand::Forward(amount))
+    }
+    pub fn turn_right(self, degrees: f32) -> TurtlePath {
+        self.with(TurtleCommand::TurnRight(degrees))
+    }
+    pub fn turn_left(self, degrees: f32) -> TurtlePath {
+        self.with(TurtleCommand::TurnLeft(degrees))
+    }
+    pub fn arc_right(self, radius: f32, degrees: f32) -> TurtlePath {
+        self.with(TurtleCommand::ArcRight(radius, degrees))
+    }
+    pub fn arc_left(self, radius: f32, degrees: f32) -> TurtlePath {
+        self.with(TurtleCommand::ArcLeft(radius, degrees))
+    }
+    pub fn pen_up(self) -> TurtlePath {
+        self.with(TurtleCommand::PenUp)
+    }
+    pub fn pen_down(self) -> TurtlePath {
+        self.with(TurtleCommand::PenDown)
+    }
+    pub fn set_color(self, color: Color) -> TurtlePath {
+        self.with(TurtleCommand::SetColor(color))
+    }
+    pub fn set_line_width(self, width: f32) -> TurtlePath {
+        self.with(TurtleCommand::SetLineWidth(width))
+    }
+    pub fn set_line_style(self, style: LineStyle) -> TurtlePath {
+        self.with(TurtleCommand::SetLineStyle(style))
+    }
+    pub fn begin_fill(self) -> TurtlePath {
+        self.with(TurtleCommand::BeginFill)
+    }
+    pub fn end_fill(self) -> TurtlePath {
+        self.with(TurtleCommand::EndFill)
+    }
+    pub fn push(self) -> TurtlePath {
+        self.with(TurtleCommand::Push)
+    }
+    pub fn pop(self) -> TurtlePath {
+        self.with(TurtleCommand::Pop)
+    }
+
+    // replay onto `turtle` as it stands, distances times `scale`
+    pub fn replay(&self, turtle: &mut Turtle, scale: f32) {
+        let origin = turtle.position();
+        let (ahead, right) = (Vec2::new(turtle.direction.cos(), turtle.direction.sin()),
+                              Vec2::new(-turtle.direction.sin(), turtle.direction.cos()));
+        for command in self.commands.iter() {
+            match *command {
+                TurtleCommand::MoveTo(x, y) => {
+                    let to = origin + (ahead * x + right * y) * scale;
+                    turtle.move_to(to.x, to.y);
+                }
+                TurtleCommand::Forward(amount) => turtle.forward(amount * scale),
+                TurtleCommand::TurnRight(degrees) => turtle.turn_right(degrees),
+                TurtleCommand::TurnLeft(degrees) => turtle.turn_left(degrees),
+                TurtleCommand::ArcRight(radius, degrees) => turtle.arc_right(radius * scale, degrees),
+                TurtleCommand::ArcLeft(radius, degrees) => turtle.arc_left(radius * scale, degrees),
+                TurtleCommand::PenUp => turtle.pen_up(),
+                TurtleCommand::PenDown => turtle.pen_down(),
+                TurtleCommand::SetColor(color) => turtle.set_color(color),
+                TurtleCommand::SetLineWidth(width) => turtle.set_line_width(width * scale),
+                TurtleCommand::SetLineStyle(style) => turtle.set_line_style(scaled_style(style, scale)),
+                TurtleCommand::BeginFill => turtle.begin_fill(),
+                TurtleCommand::EndFill => turtle.end_fill(),
+                TurtleCommand::Push => turtle.push(),
+                TurtleCommand::Pop => turtle.pop(),
+            }
+        }
+    }
+
+    // stamp the shape with a fresh turtle (pen up, white, width 1) at `at`,
+    // facing `heading` radians
+    pub fn draw(&self, at: Vec2, scale: f32, heading: f32) {
+        let mut turtle = new_turtle();
+        turtle.move_to(at.x, at.y);
+        turtle.direction = heading;
+        self.replay(&mut turtle, scale);
+    }
+}
+
+fn scaled_style(style: LineStyle, scale: f32) -> LineStyle {
+    match style {
+        LineStyle::Solid => LineStyle::Solid,
+        LineStyle::Dashed { dash, gap } => LineStyle::Dashed { dash: dash * scale, gap: gap * scale },
+        LineStyle::Dotted { spacing } => LineStyle::Dotted { spacing: spacing * scale },
+    }
+}
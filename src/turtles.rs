@@ -0,0 +1,120 @@
+// drawing turtles that live on their own as entities, not just the one inside
+// each boat. `--turtles N` lets N of them loose at random spots: the flow
+// carries them along, they face the way it takes them, and each leaves a
+// fading streak behind it, so the water draws its own pictures.
+//
+// a Turtle is an ordinary component, so other systems can give turtles other
+// jobs; the Streak is only for the ones that draw a trail.
+
+use std::collections::VecDeque;
+
+use macroquad::color;
+use macroquad::prelude::*;
+use shipyard::{Component, EntitiesViewMut, IntoIter, UniqueView, UniqueViewMut, View, ViewMut, World};
+
+use crate::demo::path_after;
+use crate::geometry::wrap_coord;
+use crate::grid::GridGeometry;
+use crate::profiling;
+use crate::turtle_path::TurtlePath;
+use crate::{new_turtle, Cells, Real, Screen, Turtle};
+
+// how many points of its path each turtle remembers
+const STREAK_LENGTH: usize = 240;
+// how far a turtle goes a step, per unit of flow
+const DRIFT_SPEED: f32 = 1.;
+// a jump further than this between two points is the turtle wrapping round
+// the edge, not a line to draw
+const WRAP_JUMP: f32 = 50.;
+
+#[derive(Component, Default)]
+pub struct Streak {
+    // oldest first
+    points: VecDeque<Vec2>,
+}
+
+// from init_world
+pub fn spawn_turtles(world: &World) {
+    let count = match path_after("--turtles").and_then(|n| n.parse::<usize>().ok()) {
+        Some(count) => count,
+        None => return,
+    };
+    world.run(|grid: UniqueView<GridGeometry>,
+               mut entities: EntitiesViewMut,
+               mut turtles: ViewMut<Turtle>,
+               mut streaks: ViewMut<Streak>| {
+        for i in 0..count {
+            let mut turtle = new_turtle();
+            turtle.move_to(rand::gen_range(0., grid.width), rand::gen_range(0., grid.height));
+            // spread round the colour wheel, so the streaks can be told apart
+            turtle.set_color(color::hsl_to_rgb(i as f32 / count as f32, 0.8, 0.6));
+            turtle.set_line_width(2.);
+            turtle.pen_down();
+            entities.add_entity((&mut turtles, &mut streaks), (turtle, Streak::default()));
+        }
+    });
+}
+
+pub fn drift_turtles(mut turtles: ViewMut<Turtle>,
+                     mut streaks: ViewMut<Streak>,
+                     map: UniqueView<Cells>,
+                     grid: UniqueView<GridGeometry>) {
+    let _t = profiling::scope("drift_turtles");
+    for (turtle, streak) in (&mut turtles, &mut streaks).iter() {
+        let flow = map.sample_flow(&grid, turtle.loc.x as f32, turtle.loc.y as f32);
+        if flow.length() > 0. {
+            turtle.direction = flow.y.atan2(flow.x);
+        }
+        // moved with the pen up: the sim doesn't draw, the streak does that
+        let pen_down = turtle.pen_down;
+        turtle.pen_up();
+        turtle.forward(flow.length() * DRIFT_SPEED);
+        turtle.pen_down = pen_down;
+        turtle.loc.x = wrap_coord(turtle.loc.x, grid.width as Real);
+        turtle.loc.y = wrap_coord(turtle.loc.y, grid.height as Real);
+        if streak.points.len() == STREAK_LENGTH {
+            streak.points.pop_front();
+        }
+        streak.points.push_back(Vec2::new(turtle.loc.x as f32, turtle.loc.y as f32));
+    }
+}
+
+// a little filled arrowhead pointing forward, for the head of each streak
+fn head() -> TurtlePath {
+    TurtlePath::new("turtle head")
+        .forward(4.)
+        .pen_down()
+        .begin_fill()
+        .turn_right(150.)
+        .forward(8.)
+        .turn_right(120.)
+        .forward(8.)
+        .turn_right(120.)
+        .forward(8.)
+        .end_fill()
+}
+
+// world space. the streak fades out toward its tail
+pub fn draw_turtles(_screen: UniqueViewMut<Screen>, turtles: View<Turtle>, streaks: View<Streak>) {
+    let head = head();
+    for (turtle, streak) in (&turtles, &streaks).iter() {
+        if !turtle.pen_down {
+            continue;
+        }
+        let points = &streak.points;
+        for i in 1..points.len() {
+            let (from, to) = (points[i - 1], points[i]);
+            if from.distance(to) > WRAP_JUMP {
+                continue;
+            }
+            let mut color = turtle.color;
+            color.a *= i as f32 / points.len() as f32;
+            draw_line(from.x, from.y, to.x, to.y, turtle.line_width, color);
+        }
+        let mut stamp = new_turtle();
+        stamp.set_color(turtle.color);
+        stamp.move_to(turtle.loc.x as f32, turtle.loc.y as f32);
+        stamp.direction = turtle.direction;
+        head.replay(&mut stamp, 1.);
+    }
+}
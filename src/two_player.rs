@@ -0,0 +1,38 @@
+// local two player (--two-player): a second boat on the same keyboard, W to
+// thrust, A and E to turn and Q to fire, while the arrows steer the usual
+// one. it's a player's boat like the first (see boat.rs), with its own
+// PlayerId, controls, charge, boost and score alongside it, so it goes
+// through all the same systems: it bounces off the land and gets rammed,
+// fires, picks up pickups (its gems going to its own score), sinks once its
+// health's gone, and is in a replay (see demo.rs). the game's over once both
+// boats have sunk, and the better of the two scores is the one that goes on
+// the tables.
+
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueView, World};
+
+use crate::boat::{spawn_player, PlayerId};
+use crate::geometry::wrap_position;
+use crate::grid::GridGeometry;
+use crate::new_boat;
+
+// how far below the first boat the second one starts
+const START_OFFSET: f32 = 40.;
+
+#[derive(Component, Default)]
+pub struct TwoPlayer(pub bool);
+
+impl TwoPlayer {
+    pub fn from_args() -> TwoPlayer {
+        TwoPlayer(std::env::args().any(|arg| arg == "--two-player"))
+    }
+}
+
+// in init_world, after the first player's boat
+pub fn spawn_second_player(world: &World, spawn: Vec2) {
+    let size = world.run(|grid: UniqueView<GridGeometry>| grid.size());
+    let at = wrap_position(spawn + Vec2::new(0., START_OFFSET), size);
+    let mut boat = new_boat(at.x, at.y, 0., 0.);
+    boat.set_color(GREEN);
+    spawn_player(world, PlayerId(2), boat);
+}
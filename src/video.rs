@@ -0,0 +1,231 @@
+// video capture: F7 starts and stops streaming the frames to an ffmpeg child
+// process, which encodes them into PREFIX-N.mp4 (`--video PREFIX` to change
+// the "video" part, and to start recording straight away). ffmpeg has to be
+// on the path; without it F7 just says so.
+//
+//   --video-fps N        frames per second of the video (30 by default). the
+//                        game's frames are dropped or repeated to keep the
+//                        video running at the same speed as the game
+//   --video-size WxH     scale the video to this size (the window's, by default)
+//
+//   --video-frames DIR   write the frames out as DIR/frame-00000.png,
+//                        DIR/frame-00001.png... instead (no ffmpeg needed),
+//                        for turning into a gif or a clip with whatever else
+//
+// F12 saves a screenshot of the next frame as screenshot-N.png, the first N
+// that isn't taken. anything in the world can ask for one too, through the
+// Capture unique (the console's `screenshot` does).
+//
+// esc finishes the video off properly before exiting. like the frame pacing
+// this lives in the main loop, since it has to grab each frame after it's
+// drawn. there's no spawning processes or writing files in the browser, so
+// no video or screenshots there.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use macroquad::prelude::*;
+use shipyard::{Component, UniqueView, UniqueViewMut, World};
+
+use crate::demo::path_after;
+use crate::keymap::{Action, KeyBindings};
+
+const DEFAULT_PREFIX: &str = "video";
+const DEFAULT_FPS: f32 = 30.;
+const SCREENSHOT_PREFIX: &str = "screenshot";
+
+// what's been asked for from inside the world, picked up once the frame's drawn
+#[derive(Component, Default)]
+pub struct Capture {
+    pub screenshot: bool,
+}
+
+struct Encoder {
+    ffmpeg: Child,
+    path: String,
+    // the size of the frames going in, which can't change partway
+    width: u16,
+    height: u16,
+}
+
+pub struct VideoRecorder {
+    prefix: String,
+    fps: f32,
+    size: Option<(u32, u32)>,
+    // videos so far, for numbering the files
+    count: usize,
+    recording: bool,
+    // started on the first frame after F7, once the frame size is known
+    encoder: Option<Encoder>,
+    // --video-frames: where the frames go instead of to ffmpeg, and how many
+    // have so far
+    frames_dir: Option<String>,
+    frames: usize,
+    // game time not yet covered by frames of the video
+    owed: f32,
+}
+
+impl VideoRecorder {
+    pub fn from_args() -> VideoRecorder {
+        let size = path_after("--video-size").and_then(|size| {
+            let mut parts = size.split('x').map(|n| n.parse::<u32>().ok());
+            Some((parts.next()??, parts.next()??))
+        });
+        VideoRecorder {
+            prefix: path_after("--video").unwrap_or_else(|| DEFAULT_PREFIX.to_owned()),
+            fps: path_after("--video-fps").and_then(|n| n.parse().ok()).unwrap_or(DEFAULT_FPS),
+            size,
+            count: 0,
+            recording: std::env::args().any(|arg| arg == "--video"),
+            encoder: None,
+            frames_dir: path_after("--video-frames"),
+            frames: 0,
+            owed: 0.,
+        }
+    }
+
+    pub fn handle_keys(&mut self, world: &World) {
+        let (toggle, exit) = world.run(|keys: UniqueView<KeyBindings>, mut capture: UniqueViewMut<Capture>| {
+            if keys.pressed(Action::Screenshot) {
+                capture.screenshot = true;
+            }
+            (keys.pressed(Action::RecordVideo), keys.pressed(Action::Exit))
+        });
+        if toggle {
+            if self.recording {
+                self.stop();
+            } else {
+                self.recording = true;
+            }
+        }
+        // the sim's about to exit, the video has to be finished first
+        if exit {
+            self.stop();
+        }
+    }
+
+    fn start(&mut self, width: u16, height: u16) -> Result<Encoder, String> {
+        let path = format!("{}-{}.mp4", self.prefix, self.count);
+        self.count += 1;
+        // macroquad's frames come bottom row first
+        let mut filters = String::from("vflip");
+        if let Some((w, h)) = self.size {
+            filters.push_str(&format!(",scale={}:{}", w, h));
+        }
+        let ffmpeg = Command::new("ffmpeg")
+            .args(&["-y", "-loglevel", "error", "-f", "rawvideo", "-pixel_format", "rgba"])
+            .args(&["-video_size", &format!("{}x{}", width, height), "-framerate", &self.fps.to_string()])
+            .args(&["-i", "-", "-vf", &filters, "-pix_fmt", "yuv420p", &path])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|err| format!("couldn't start ffmpeg: {}", err))?;
+        info!("recording video to {}", path);
+        Ok(Encoder { ffmpeg, path, width, height })
+    }
+
+    pub fn stop(&mut self) {
+        self.recording = false;
+        self.owed = 0.;
+        if let Some(mut encoder) = self.encoder.take() {
+            // closing its input is what tells ffmpeg the video's over
+            drop(encoder.ffmpeg.stdin.take());
+            match encoder.ffmpeg.wait() {
+                Ok(status) if status.success() => info!("saved video {}", encoder.path),
+                Ok(status) => warn!("ffmpeg failed on {}: {}", encoder.path, status),
+                Err(err) => warn!("ffmpeg failed on {}: {}", encoder.path, err),
+            }
+        }
+    }
+
+    // call once the frame's drawn, before next_frame
+    pub fn capture(&mut self, world: &World) {
+        let screenshot = world.borrow::<UniqueViewMut<Capture>>()
+            .map_or(false, |mut capture| std::mem::take(&mut capture.screenshot));
+        if screenshot {
+            save_screenshot(&get_screen_data());
+        }
+        if !self.recording {
+            return;
+        }
+        let frame = get_screen_data();
+        if let Some(dir) = self.frames_dir.clone() {
+            self.write_frames(&frame, &dir);
+            return;
+        }
+        if self.encoder.is_none() {
+            match self.start(frame.width, frame.height) {
+                Ok(encoder) => self.encoder = Some(encoder),
+                Err(err) => {
+                    warn!("{}", err);
+                    self.recording = false;
+                    return;
+                }
+            }
+        }
+        let encoder = self.encoder.as_mut().unwrap();
+        if (frame.width, frame.height) != (encoder.width, encoder.height) {
+            // the window changed size, carry on in a new file
+            self.stop();
+            self.recording = true;
+            return;
+        }
+        // as many video frames as the game time this frame took covers
+        self.owed += get_frame_time();
+        let mut result = Ok(());
+        while self.owed >= 1. / self.fps && result.is_ok() {
+            self.owed -= 1. / self.fps;
+            result = encoder.ffmpeg.stdin.as_mut().unwrap().write_all(&frame.bytes);
+        }
+        if let Err(err) = result {
+            warn!("lost ffmpeg partway through {}: {}", encoder.path, err);
+            self.stop();
+        }
+    }
+
+    // --video-frames: as many numbered pngs as the game time this frame took
+    // covers, like the video
+    fn write_frames(&mut self, frame: &Image, dir: &str) {
+        if cfg!(target_arch = "wasm32") {
+            warn!("no recording frames in the browser");
+            self.recording = false;
+            return;
+        }
+        if self.frames == 0 {
+            if let Err(err) = fs::create_dir_all(dir) {
+                warn!("couldn't make {}: {}", dir, err);
+                self.recording = false;
+                return;
+            }
+            info!("recording frames to {}", dir);
+        }
+        self.owed += get_frame_time();
+        while self.owed >= 1. / self.fps {
+            self.owed -= 1. / self.fps;
+            frame.export_png(&format!("{}/frame-{:05}.png", dir, self.frames));
+            self.frames += 1;
+        }
+    }
+
+    // a dot in the top right while recording
+    pub fn draw_indicator(&self) {
+        if self.recording {
+            draw_circle(screen_width() - 12., 12., 5., RED);
+        }
+    }
+}
+
+// (export_png turns macroquad's bottom row first frames the right way up)
+fn save_screenshot(frame: &Image) {
+    if cfg!(target_arch = "wasm32") {
+        warn!("no screenshots in the browser");
+        return;
+    }
+    let path = (0..)
+        .map(|n| format!("{}-{}.png", SCREENSHOT_PREFIX, n))
+        .find(|path| !Path::new(path).exists())
+        .unwrap();
+    frame.export_png(&path);
+    info!("saved {}", path);
+}
@@ -0,0 +1,392 @@
+// what gets drawn, picked from the keyboard: V steps through the ways of
+// showing the flow field underneath everything, and B through the ways of
+// drawing the particles. the names of the new modes show at the top of the
+// screen for a moment after a switch. (D's debug view, the grid lines and
+// per-cell arrows, is separate and still works on top of any of these.)
+//
+// N switches the particles between drawing them where they are this frame
+// and trail mode, where they're left to fade out slowly instead, drawing the
+// long smooth paths of a classic flow picture (see trails.rs).
+//
+// M steps through the colormaps the heatmaps (speed, and particle density)
+// and streamlines are coloured with, and the settings file picks the one it
+// starts on:
+//
+//   [visualization]
+//   colormap = "inferno"    # or "palette" (the default), "ice", "grey"
+//
+// "palette" is the accessibility palette's (see accessibility.rs), which osc
+// and the music visualizer turn and brighten; the others stay put.
+//
+// the streamlines are traced through the flow with rk4 on the interpolated
+// field (see Cells::sample_flow), so they curve smoothly through the cells
+// and wrap round the edges like the water does. besides the lattice of short
+// ones there's a ring of longer ones around the cursor, for following where
+// the water from one spot ends up.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, View};
+
+use crate::accessibility::{along_stops, Accessibility};
+use crate::batch::MeshBatch;
+use crate::camera::Camera;
+use crate::geometry::wrap_position;
+use crate::grid::GridGeometry;
+use crate::keymap::{Action, KeyBindings};
+use crate::{Cells, Particle, Screen};
+
+// how long the mode names stay up after a switch, in seconds
+const LABEL_SECONDS: f64 = 2.;
+// flow speed (pixels per step) that shows as the hottest heatmap colour
+const HEATMAP_MAX_SPEED: f32 = 4.;
+// divergence and vorticity that show fully saturated
+const DIVERGENCE_SCALE: f32 = 0.1;
+const VORTICITY_SCALE: f32 = 0.1;
+// particles per cell that show as fully occupied
+const OCCUPANCY_SCALE: f32 = 16.;
+// streamlines start every this many cells, and run this many steps
+const STREAMLINE_SPACING: usize = 4;
+const STREAMLINE_STEPS: usize = 12;
+// the ones from the cursor: how many, how far out they start, how long they run
+const PROBE_LINES: usize = 8;
+const PROBE_RADIUS: f32 = 6.;
+const PROBE_STEPS: usize = 80;
+// flow slower than this is too still to say which way a streamline goes
+const MIN_STREAMLINE_SPEED: f32 = 0.01;
+
+// a few stops along inferno, black through purple and orange to pale yellow
+const INFERNO: [(f32, f32, f32); 5] = [
+    (0.00, 0.00, 0.02),
+    (0.34, 0.06, 0.43),
+    (0.73, 0.21, 0.33),
+    (0.98, 0.55, 0.04),
+    (0.99, 1.00, 0.64),
+];
+// deep blue through cyan to white
+const ICE: [(f32, f32, f32); 4] = [
+    (0.02, 0.05, 0.25),
+    (0.05, 0.35, 0.70),
+    (0.40, 0.85, 0.95),
+    (1.00, 1.00, 1.00),
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FieldView {
+    None,
+    Heatmap,
+    Streamlines,
+    Divergence,
+    Vorticity,
+    Occupancy,
+}
+
+impl FieldView {
+    fn next(self) -> FieldView {
+        match self {
+            FieldView::None => FieldView::Heatmap,
+            FieldView::Heatmap => FieldView::Streamlines,
+            FieldView::Streamlines => FieldView::Divergence,
+            FieldView::Divergence => FieldView::Vorticity,
+            FieldView::Vorticity => FieldView::Occupancy,
+            FieldView::Occupancy => FieldView::None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            FieldView::None => "no field",
+            FieldView::Heatmap => "speed heatmap",
+            FieldView::Streamlines => "streamlines",
+            FieldView::Divergence => "divergence",
+            FieldView::Vorticity => "vorticity",
+            FieldView::Occupancy => "occupancy",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Colormap {
+    Palette,
+    Inferno,
+    Ice,
+    Grey,
+}
+
+impl Colormap {
+    fn next(self) -> Colormap {
+        match self {
+            Colormap::Palette => Colormap::Inferno,
+            Colormap::Inferno => Colormap::Ice,
+            Colormap::Ice => Colormap::Grey,
+            Colormap::Grey => Colormap::Palette,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Colormap::Palette => "palette colours",
+            Colormap::Inferno => "inferno",
+            Colormap::Ice => "ice",
+            Colormap::Grey => "greyscale",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(default)]
+pub struct VisualizationSettings {
+    pub colormap: Colormap,
+}
+
+impl Default for VisualizationSettings {
+    fn default() -> VisualizationSettings {
+        VisualizationSettings { colormap: Colormap::Palette }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ParticleStyle {
+    // each particle with its velocity tail, crowded cells as blobs
+    Trails,
+    // each particle as a dot, crowded cells as blobs
+    Dots,
+    // every occupied cell as a blob
+    Blobs,
+    Hidden,
+}
+
+impl ParticleStyle {
+    fn next(self) -> ParticleStyle {
+        match self {
+            ParticleStyle::Trails => ParticleStyle::Dots,
+            ParticleStyle::Dots => ParticleStyle::Blobs,
+            ParticleStyle::Blobs => ParticleStyle::Hidden,
+            ParticleStyle::Hidden => ParticleStyle::Trails,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ParticleStyle::Trails => "particle trails",
+            ParticleStyle::Dots => "particle dots",
+            ParticleStyle::Blobs => "particle blobs",
+            ParticleStyle::Hidden => "particles hidden",
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct Visualization {
+    pub field: FieldView,
+    pub particles: ParticleStyle,
+    pub colormap: Colormap,
+    // particles drawn as fading trails (see trails.rs)
+    pub trails: bool,
+    // 0 to 1, how far round the colour wheel the heatmap's turned (see osc.rs)
+    pub hue: f32,
+    // 0 to 1, how light the heatmap's colours are (see visualizer.rs)
+    pub brightness: f32,
+    // when the mode last changed, for the label
+    changed_at: Option<f64>,
+}
+
+impl Visualization {
+    pub fn new(settings: &VisualizationSettings) -> Visualization {
+        Visualization { field: FieldView::None, particles: ParticleStyle::Trails, colormap: settings.colormap,
+                        trails: false, hue: 0., brightness: 0.5, changed_at: None }
+    }
+
+    // the keys' switches, which the tuning panel's buttons make too
+    pub fn next_field(&mut self) {
+        self.field = self.field.next();
+        self.changed_at = Some(get_time());
+    }
+
+    pub fn next_particles(&mut self) {
+        self.particles = self.particles.next();
+        self.changed_at = Some(get_time());
+    }
+
+    pub fn next_colormap(&mut self) {
+        self.colormap = self.colormap.next();
+        self.changed_at = Some(get_time());
+    }
+
+    pub fn toggle_trails(&mut self) {
+        self.trails = !self.trails;
+        self.changed_at = Some(get_time());
+    }
+
+    // the current modes, for a little while after they change
+    pub fn label(&self) -> Option<String> {
+        match self.changed_at {
+            Some(at) if get_time() - at < LABEL_SECONDS => {
+                let particles = if self.trails { "fading trails" } else { self.particles.name() };
+                Some(format!("{} / {} / {}", self.field.name(), particles, self.colormap.name()))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn cycle_visualization(mut vis: UniqueViewMut<Visualization>,
+                           keys: UniqueView<KeyBindings>,
+                           _screen: UniqueView<Screen>) {
+    if keys.pressed(Action::FieldView) {
+        vis.next_field();
+    }
+    if keys.pressed(Action::ParticleStyle) {
+        vis.next_particles();
+    }
+    if keys.pressed(Action::Colormap) {
+        vis.next_colormap();
+    }
+    if keys.pressed(Action::Trails) {
+        vis.toggle_trails();
+    }
+}
+
+// `t` is 0 (still, or empty) to 1 (fast, or crowded), along the colormap.
+// the palette's goes blue through to red, unless the hue's been turned (or
+// the colorblind palette's on, see accessibility.rs)
+fn heat_color(t: f32, alpha: f32, vis: &Visualization, access: &Accessibility) -> Color {
+    let mut color = match vis.colormap {
+        Colormap::Palette => access.ramp(t, vis.hue, vis.brightness),
+        Colormap::Inferno => along_stops(&INFERNO, t),
+        Colormap::Ice => along_stops(&ICE, t),
+        Colormap::Grey => {
+            let t = t.max(0.).min(1.);
+            Color::new(t, t, t, 1.)
+        }
+    };
+    color.a = alpha;
+    color
+}
+
+// red (or orange) for positive, blue for negative, fading out towards zero
+fn signed_color(value: f32, scale: f32, access: &Accessibility) -> Color {
+    let strength = (value.abs() / scale).min(1.);
+    let (positive, negative) = access.signed_colors();
+    let color = if value > 0. { positive } else { negative };
+    Color { a: strength * 0.6, ..color }
+}
+
+// one rk4 step along the flow's direction from `at`, `step` long whatever the
+// speed, or None once the flow's too still to follow or it runs into a wall
+fn streamline_step(map: &Cells, grid: &GridGeometry, at: Vec2, step: f32) -> Option<Vec2> {
+    let direction = |p: Vec2| {
+        let flow = map.sample_flow(grid, p.x, p.y);
+        let speed = flow.length();
+        if speed < MIN_STREAMLINE_SPEED { None } else { Some(flow / speed) }
+    };
+    let k1 = direction(at)?;
+    let k2 = direction(at + k1 * step / 2.)?;
+    let k3 = direction(at + k2 * step / 2.)?;
+    let k4 = direction(at + k3 * step)?;
+    let next = at + (k1 + k2 * 2. + k3 * 2. + k4) * step / 6.;
+    let wrapped = wrap_position(next, grid.size());
+    if map.all_cells[grid.index_at(wrapped.x, wrapped.y)].is_solid() {
+        return None;
+    }
+    Some(next)
+}
+
+// a streamline from `from`, `steps` half cells long, brighter towards the head
+// so you can tell which way it goes
+fn trace_streamline(batch: &mut MeshBatch, map: &Cells, grid: &GridGeometry, from: Vec2, steps: usize,
+                    vis: &Visualization, access: &Accessibility) {
+    let step = grid.cell_width.min(grid.cell_height) / 2.;
+    let mut at = from;
+    for i in 0..steps {
+        let next = match streamline_step(map, grid, at, step) {
+            Some(next) => next,
+            None => break,
+        };
+        let speed = map.sample_flow(grid, at.x, at.y).length();
+        let color = heat_color(speed / HEATMAP_MAX_SPEED, (i + 1) as f32 / steps as f32, vis, access);
+        batch.line(at.x, at.y, next.x, next.y, 1., color);
+        // over an edge, carry on from the other side
+        at = wrap_position(next, grid.size());
+    }
+}
+
+// world space, under the particles and the boat
+pub fn draw_field(_screen: UniqueViewMut<Screen>,
+                  vis: UniqueView<Visualization>,
+                  map: UniqueView<Cells>,
+                  grid: UniqueView<GridGeometry>,
+                  particles: View<Particle>,
+                  access: UniqueView<Accessibility>,
+                  camera: UniqueView<Camera>) {
+    if vis.field == FieldView::None {
+        return;
+    }
+    let cells = &map.all_cells;
+    let mut batch = MeshBatch::new();
+    let fill = |batch: &mut MeshBatch, cell_ix: usize, color: Color| {
+        let center = grid.center(cell_ix);
+        batch.rect(center.x - grid.cell_width / 2., center.y - grid.cell_height / 2.,
+                   grid.cell_width, grid.cell_height, color);
+    };
+    match vis.field {
+        FieldView::None => {}
+        FieldView::Heatmap => {
+            for cell_ix in 0..grid.len() {
+                let speed = cells[cell_ix].flow_v.length();
+                if speed > 0. {
+                    fill(&mut batch, cell_ix, heat_color(speed / HEATMAP_MAX_SPEED, 0.5, &vis, &access));
+                }
+            }
+        }
+        FieldView::Divergence | FieldView::Vorticity => {
+            for cell_ix in 0..grid.len() {
+                if cells[cell_ix].is_solid() {
+                    continue;
+                }
+                let [left, right, up, down] = grid.neighbours(cell_ix);
+                let (l, r, u, d) = (cells[left].flow_v, cells[right].flow_v, cells[up].flow_v, cells[down].flow_v);
+                let (dx, dy) = (2. * grid.cell_width, 2. * grid.cell_height);
+                let color = if vis.field == FieldView::Divergence {
+                    signed_color((r.x - l.x) / dx + (d.y - u.y) / dy, DIVERGENCE_SCALE, &access)
+                } else {
+                    signed_color((r.y - l.y) / dx - (d.x - u.x) / dy, VORTICITY_SCALE, &access)
+                };
+                fill(&mut batch, cell_ix, color);
+            }
+        }
+        FieldView::Occupancy => {
+            let mut counts = vec![0u32; grid.len()];
+            for particle in particles.iter() {
+                counts[particle.cell_ix] += 1;
+            }
+            for (cell_ix, count) in counts.iter().enumerate() {
+                if *count > 0 {
+                    fill(&mut batch, cell_ix, heat_color(*count as f32 / OCCUPANCY_SCALE, 0.5, &vis, &access));
+                }
+            }
+        }
+        FieldView::Streamlines => {
+            // a lattice of short ones, from the middles of the cells
+            for row in (0..grid.rows).step_by(STREAMLINE_SPACING) {
+                for col in (0..grid.cols).step_by(STREAMLINE_SPACING) {
+                    let cell_ix = row * grid.cols + col;
+                    if !cells[cell_ix].is_solid() {
+                        trace_streamline(&mut batch, &map, &grid, grid.center(cell_ix), STREAMLINE_STEPS, &vis, &access);
+                    }
+                }
+            }
+            // and long ones from around the cursor
+            let mouse = camera.mouse_world();
+            if mouse.x >= 0. && mouse.x < grid.width && mouse.y >= 0. && mouse.y < grid.height {
+                for i in 0..PROBE_LINES {
+                    let angle = i as f32 / PROBE_LINES as f32 * std::f32::consts::PI * 2.;
+                    let from = mouse + Vec2::new(angle.cos(), angle.sin()) * PROBE_RADIUS;
+                    trace_streamline(&mut batch, &map, &grid, from, PROBE_STEPS, &vis, &access);
+                }
+            }
+        }
+    }
+    batch.draw();
+}
@@ -0,0 +1,188 @@
+// the sim as a music visualizer: `--visualize SONG.wav` plays the song (on a
+// loop) and, every frame, takes an FFT of the bit that's playing and splits
+// it into three bands:
+//
+//   bass    stirs the fluid up all over, like the OSC /turbulence
+//   mids    turn the heatmap colours round the colour wheel
+//   highs   brighten the heatmap
+//
+// each band is measured against the loudest it's been lately, so quiet songs
+// move things as much as loud ones. it switches the heatmap on to begin with
+// (V still changes it). the song has to be an uncompressed 16 bit WAV.
+//
+// like the sound effects this lives in the main loop, since loading the song
+// has to be awaited.
+
+use std::f32::consts::PI;
+use std::fs;
+
+use macroquad::audio::{load_sound_from_bytes, play_sound, PlaySoundParams};
+use macroquad::prelude::*;
+use shipyard::{UniqueView, UniqueViewMut, World};
+
+use crate::demo::path_after;
+use crate::grid::GridGeometry;
+use crate::osc::stir_randomly;
+use crate::visualization::{FieldView, Visualization};
+use crate::Cells;
+
+// samples per FFT, a power of two
+const WINDOW: usize = 1024;
+// where the bands split, in Hz
+const BASS_TOP: f32 = 250.;
+const MIDS_TOP: f32 = 2000.;
+// how quickly the bands follow the music (0 to 1 a frame), rising and falling
+const ATTACK: f32 = 0.6;
+const RELEASE: f32 = 0.15;
+// how much of the loudest-lately is forgotten a frame
+const PEAK_DECAY: f32 = 0.995;
+// hue turned a second at full mids
+const HUE_SPEED: f32 = 0.2;
+
+// the samples (mixed down to mono) and rate of a 16 bit PCM wav
+fn read_wav(bytes: &[u8]) -> Result<(Vec<f32>, u32), String> {
+    if bytes.get(..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WAVE") {
+        return Err("not a wav file".to_owned());
+    }
+    let (mut channels, mut rate, mut bits) = (0usize, 0u32, 0u16);
+    let mut at = 12;
+    while at + 8 <= bytes.len() {
+        let id = &bytes[at..at + 4];
+        let size = u32::from_le_bytes([bytes[at + 4], bytes[at + 5], bytes[at + 6], bytes[at + 7]]) as usize;
+        let body = bytes.get(at + 8..at + 8 + size).unwrap_or(&bytes[at + 8..]);
+        if id == b"fmt " && body.len() >= 16 {
+            if u16::from_le_bytes([body[0], body[1]]) != 1 {
+                return Err("only uncompressed wavs work".to_owned());
+            }
+            channels = u16::from_le_bytes([body[2], body[3]]) as usize;
+            rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]);
+            bits = u16::from_le_bytes([body[14], body[15]]);
+        } else if id == b"data" {
+            if bits != 16 || channels == 0 {
+                return Err("only 16 bit wavs work".to_owned());
+            }
+            let samples = body.chunks_exact(2 * channels)
+                .map(|frame| {
+                    frame.chunks_exact(2)
+                        .map(|s| i16::from_le_bytes([s[0], s[1]]) as f32 / 32768.)
+                        .sum::<f32>() / channels as f32
+                })
+                .collect();
+            return Ok((samples, rate));
+        }
+        // chunks are padded to an even size
+        at += 8 + size + (size & 1);
+    }
+    Err("no sound in the file".to_owned())
+}
+
+// in place, radix 2. `re` and `im` are the same power of two long
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2. * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (w_re, w_im) = ((angle * k as f32).cos(), (angle * k as f32).sin());
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * w_re - im[b] * w_im;
+                let t_im = re[b] * w_im + im[b] * w_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+pub struct MusicVisualizer {
+    samples: Vec<f32>,
+    rate: u32,
+    started_at: f64,
+    // bass, mids, highs: smoothed, and the loudest each has been lately
+    bands: [f32; 3],
+    peaks: [f32; 3],
+}
+
+impl MusicVisualizer {
+    // None without --visualize, or if the song won't load
+    pub async fn from_args(world: &World) -> Option<MusicVisualizer> {
+        let path = path_after("--visualize")?;
+        let loaded = fs::read(&path).map_err(|err| err.to_string())
+            .and_then(|bytes| read_wav(&bytes).map(|song| (bytes, song)));
+        let (bytes, (samples, rate)) = match loaded {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                warn!("couldn't play {}: {}", path, err);
+                return None;
+            }
+        };
+        let sound = match load_sound_from_bytes(&bytes).await {
+            Ok(sound) => sound,
+            Err(err) => {
+                warn!("couldn't play {}: {:?}", path, err);
+                return None;
+            }
+        };
+        play_sound(sound, PlaySoundParams { looped: true, volume: 1. });
+        world.run(|mut vis: UniqueViewMut<Visualization>| vis.field = FieldView::Heatmap);
+        Some(MusicVisualizer { samples, rate, started_at: get_time(), bands: [0.; 3], peaks: [1e-6; 3] })
+    }
+
+    // the energy in each band of the window that's playing now
+    fn measure(&self) -> [f32; 3] {
+        let playing = ((get_time() - self.started_at) * self.rate as f64) as usize;
+        let mut re: Vec<f32> = (0..WINDOW)
+            .map(|i| {
+                // a hann window, so the edges don't smear into every band
+                let hann = 0.5 - 0.5 * (2. * PI * i as f32 / WINDOW as f32).cos();
+                self.samples[(playing + i) % self.samples.len()] * hann
+            })
+            .collect();
+        let mut im = vec![0.; WINDOW];
+        fft(&mut re, &mut im);
+        let mut energy = [0.; 3];
+        for bin in 1..WINDOW / 2 {
+            let hz = bin as f32 * self.rate as f32 / WINDOW as f32;
+            let band = if hz < BASS_TOP { 0 } else if hz < MIDS_TOP { 1 } else { 2 };
+            energy[band] += (re[bin] * re[bin] + im[bin] * im[bin]).sqrt();
+        }
+        energy
+    }
+
+    // once a frame, after the step
+    pub fn update(&mut self, world: &World) {
+        if self.samples.is_empty() {
+            return;
+        }
+        let energy = self.measure();
+        for band in 0..3 {
+            self.peaks[band] = (self.peaks[band] * PEAK_DECAY).max(energy[band]);
+            let level = energy[band] / self.peaks[band];
+            let follow = if level > self.bands[band] { ATTACK } else { RELEASE };
+            self.bands[band] += (level - self.bands[band]) * follow;
+        }
+        let [bass, mids, highs] = self.bands;
+        world.run(|mut map: UniqueViewMut<Cells>, grid: UniqueView<GridGeometry>, mut vis: UniqueViewMut<Visualization>| {
+            stir_randomly(&mut map, &grid, bass * bass);
+            vis.hue = (vis.hue + mids * HUE_SPEED * get_frame_time()).rem_euclid(1.);
+            vis.brightness = 0.3 + 0.4 * highs;
+        });
+    }
+}
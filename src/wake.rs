@@ -0,0 +1,109 @@
+// the players' boats and the water, both ways round. the flow under the hull
+// drags the boat along with it (a lighter pull than a drifter's, see
+// drifters.rs, so the thrust still tells), and while the boat's under thrust
+// its wake goes back in: the cells behind the stern are pushed the opposite
+// way to the bow, and a few particles are let go there, moving that way, to
+// carry it on into the rest of the flow (see fluid.rs). so running the boat
+// hard through a current churns it up, and particles can be steered with it.
+// the engine smokes as well: smoke particles (see particle.rs) that drift off
+// on the flow without pushing it, and burn out.
+//
+// the puffs drawn off the stern (see boat.rs's steer) are just for show.
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntitiesViewMut, IntoIter, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::clock::TICK;
+use crate::geometry::{wrap_position, wrapped_dist};
+use crate::grid::GridGeometry;
+use crate::input::BoatControls;
+use crate::pool::ParticlePool;
+use crate::profiling;
+use crate::tuning::Tuning;
+use crate::{new_particle_at, Boat, Cells, Particle, ParticleKind, Randomness};
+
+// how much of the tuning's hull drag the player's boat gets
+const PLAYER_DRAG_SHARE: f32 = 0.2;
+// how far behind the boat's middle the wake starts, and how wide it is
+const WAKE_OFFSET: f32 = 10.;
+const WAKE_RADIUS: f32 = 15.;
+// how hard a full thrust pushes the water behind, per tick
+const WAKE_PUSH: f32 = 0.1;
+// particles let go a second at full thrust, and how fast they leave
+const WAKE_PARTICLES: f32 = 12.;
+const WAKE_PARTICLE_SPEED: f32 = 1.;
+// smoke particles a second at full thrust
+const SMOKE_PARTICLES: f32 = 8.;
+
+// what's owed of a player's boat's next wake and smoke particles
+#[derive(Component, Default)]
+pub struct Wake {
+    budget: f32,
+    smoke_budget: f32,
+}
+
+pub fn couple_boat(mut boats: ViewMut<Boat>,
+                   mut wakes: ViewMut<Wake>,
+                   mut map: UniqueViewMut<Cells>,
+                   mut pool: UniqueViewMut<ParticlePool>,
+                   mut entities: EntitiesViewMut,
+                   mut particles: ViewMut<Particle>,
+                   controls: View<BoatControls>,
+                   grid: UniqueView<GridGeometry>,
+                   tuning: UniqueView<Tuning>,
+                   _random: UniqueViewMut<Randomness>) {
+    let _t = profiling::scope("couple_boat");
+    for (player, wake, controls) in (&mut boats, &mut wakes, &controls).iter() {
+        couple(player, wake, controls, &mut map, &mut pool, &mut entities, &mut particles, &grid, &tuning);
+    }
+}
+
+// one boat's drag and wake
+fn couple(player: &mut Boat,
+          wake: &mut Wake,
+          controls: &BoatControls,
+          map: &mut Cells,
+          pool: &mut ParticlePool,
+          entities: &mut EntitiesViewMut,
+          particles: &mut ViewMut<Particle>,
+          grid: &GridGeometry,
+          tuning: &Tuning) {
+    let at = Vec2::new(player.loc.x as f32, player.loc.y as f32);
+    let flow = map.sample_flow(grid, at.x, at.y);
+    let vel = player.vel;
+    player.vel = vel + (flow - vel) * tuning.hull_drag * PLAYER_DRAG_SHARE;
+
+    if controls.thrust <= 0. {
+        wake.budget = 0.;
+        wake.smoke_budget = 0.;
+        return;
+    }
+    let heading = Vec2::new(player.heading().cos(), player.heading().sin());
+    let stern = at - heading * WAKE_OFFSET;
+    let stern = wrap_position(stern, grid.size());
+    for cell_ix in grid.cells_within(stern, WAKE_RADIUS) {
+        let cell = &mut map.all_cells[cell_ix];
+        if cell.is_solid() {
+            continue;
+        }
+        let falloff = 1. - wrapped_dist(grid.center(cell_ix), stern, grid.size()) / WAKE_RADIUS;
+        cell.flow_v -= heading * WAKE_PUSH * controls.thrust * falloff;
+        cell.quiet_frames = 0;
+    }
+
+    if map.all_cells[grid.index_at(stern.x, stern.y)].is_solid() {
+        return;
+    }
+    let budget = wake.budget + WAKE_PARTICLES * controls.thrust * TICK;
+    let count = budget.floor();
+    wake.budget = budget - count;
+    let smoke_budget = wake.smoke_budget + SMOKE_PARTICLES * controls.thrust * TICK;
+    let smoke = smoke_budget.floor();
+    wake.smoke_budget = smoke_budget - smoke;
+    let behind = (-heading).y.atan2((-heading).x);
+    pool.spawn_bulk(entities, particles, (0..(count + smoke) as usize).map(|i| {
+        let angle = behind + rand::gen_range(-0.3, 0.3);
+        let particle = new_particle_at(grid, stern.x, stern.y, angle.cos() * WAKE_PARTICLE_SPEED, angle.sin() * WAKE_PARTICLE_SPEED);
+        if i < count as usize { particle } else { particle.of_kind(ParticleKind::Smoke) }
+    }));
+}
@@ -0,0 +1,182 @@
+// the boats' guns. holding fire charges a shot (there's a bar beside the
+// boat while it does), and letting go fires it off the bow: the longer the charge,
+// the faster and bigger the shot, and the harder it shoves the water it goes
+// through. shots run out after a couple of seconds, or when they hit a wall,
+// an enemy (see enemies.rs) or leave the world.
+
+use macroquad::prelude::*;
+use shipyard::{Component, EntitiesViewMut, EntityId, IntoIter, IntoWithId, UniqueView, UniqueViewMut, View, ViewMut};
+
+use crate::clock::{SimClock, TICK};
+use crate::collision::{Collider, BOAT_RADIUS};
+use crate::events::{Event, Events};
+use crate::geometry::wrapped_dist;
+use crate::grid::GridGeometry;
+use crate::boat::PlayerId;
+use crate::input::BoatControls;
+use crate::profiling;
+use crate::{Boat, Cells, Screen};
+
+// seconds to a full charge
+const CHARGE_SECONDS: f32 = 1.5;
+// a tap still fires something
+const MIN_CHARGE: f32 = 0.15;
+// (empty, full) charge
+const SHOT_SPEED: (f32, f32) = (3., 9.);
+const SHOT_RADIUS: (f32, f32) = (2., 6.);
+const SHOT_IMPULSE: (f32, f32) = (0.3, 2.);
+// of an enemy's health (see enemies.rs)
+const SHOT_DAMAGE: (f32, f32) = (0.25, 1.);
+// steps a shot lasts
+const SHOT_LIFETIME: u32 = 120;
+// how far round a shot (in shot radii) it pushes the water
+const WAKE_RADII: f32 = 3.;
+
+fn scaled(range: (f32, f32), charge: f32) -> f32 {
+    range.0 + (range.1 - range.0) * charge
+}
+
+// (the shots don't wrap, they're gone once they're off the edge)
+fn in_world(grid: &GridGeometry, pos: Vec2) -> bool {
+    pos.x >= 0. && pos.x < grid.width && pos.y >= 0. && pos.y < grid.height
+}
+
+// a player's, alongside their boat
+#[derive(Component, Default)]
+pub struct ShotCharge {
+    // 0 to 1, while fire's held
+    pub charge: f32,
+    charging: bool,
+}
+
+#[derive(Component)]
+pub struct Projectile {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub radius: f32,
+    // how hard it pushes the flow it goes through
+    pub impulse: f32,
+    // how much of an enemy's health it takes
+    pub damage: f32,
+    // which player fired it, for the points if it sinks something
+    pub owner: u8,
+    age: u32,
+    // hit a wall this step, for despawn_offscreen_or_expired
+    spent: bool,
+}
+
+// builds each boat's charge while its fire's held, and fires a shot on release
+pub fn spawn_projectiles(mut shots: ViewMut<ShotCharge>,
+                   controls: View<BoatControls>,
+                   boats: View<Boat>,
+                   ids: View<PlayerId>,
+                   mut entities: EntitiesViewMut,
+                   mut projectiles: ViewMut<Projectile>,
+                   mut colliders: ViewMut<Collider>,
+                   mut events: UniqueViewMut<Events>) {
+    let _t = profiling::scope("spawn_projectiles");
+    for (shot, controls, boat, id) in (&mut shots, &controls, &boats, &ids).iter() {
+        if controls.fire {
+            shot.charge = if shot.charging { (shot.charge + TICK / CHARGE_SECONDS).min(1.) } else { 0. };
+            shot.charging = true;
+            continue;
+        }
+        if !shot.charging {
+            continue;
+        }
+        shot.charging = false;
+        let charge = shot.charge.max(MIN_CHARGE);
+        shot.charge = 0.;
+
+        let heading = Vec2::new(boat.heading().cos(), boat.heading().sin());
+        let radius = scaled(SHOT_RADIUS, charge);
+        // out past the bow, so it doesn't start inside the boat
+        let pos = Vec2::new(boat.loc.x as f32, boat.loc.y as f32) + heading * (BOAT_RADIUS + radius);
+        let vel = boat.vel + heading * scaled(SHOT_SPEED, charge);
+        entities.add_entity((&mut projectiles, &mut colliders),
+                            (Projectile { pos, vel, radius, impulse: scaled(SHOT_IMPULSE, charge),
+                                          damage: scaled(SHOT_DAMAGE, charge), owner: id.0, age: 0, spent: false },
+                             Collider { center: pos, radius }));
+        events.send(Event::Fired { charge });
+    }
+}
+
+// moves the shots, pushing the water along in front of them. like the
+// vortices, this writes straight into last step's grid, so it runs before the
+// rest of the sim reads it
+pub fn move_projectiles(mut projectiles: ViewMut<Projectile>,
+                        mut colliders: ViewMut<Collider>,
+                        mut map: UniqueViewMut<Cells>,
+                        mut events: UniqueViewMut<Events>,
+                        grid: UniqueView<GridGeometry>) {
+    let _t = profiling::scope("move_projectiles");
+    for (id, shot) in (&mut projectiles).iter().with_id() {
+        shot.pos += shot.vel;
+        shot.age += 1;
+        // (on its way out, see despawn_offscreen_or_expired)
+        if shot.age > SHOT_LIFETIME || !in_world(&grid, shot.pos) {
+            continue;
+        }
+        if map.all_cells[grid.index_at(shot.pos.x, shot.pos.y)].is_solid() {
+            events.send(Event::Impact { strength: shot.impulse / SHOT_IMPULSE.1 });
+            shot.spent = true;
+            continue;
+        }
+        let reach = shot.radius * WAKE_RADII;
+        let push = shot.vel / shot.vel.length().max(0.01) * shot.impulse;
+        for cell_ix in grid.cells_within(shot.pos, reach) {
+            let falloff = 1. - wrapped_dist(grid.center(cell_ix), shot.pos, grid.size()) / reach;
+            let cell = &mut map.all_cells[cell_ix];
+            if !cell.is_solid() {
+                cell.flow_v += push * falloff;
+                cell.quiet_frames = 0;
+            }
+        }
+        if let Ok(collider) = (&mut colliders).get(id) {
+            collider.center = shot.pos;
+        }
+    }
+}
+
+// after move_projectiles: the shots that have left the world, run out, or hit
+// a wall
+pub fn despawn_offscreen_or_expired(mut projectiles: ViewMut<Projectile>,
+                                    mut colliders: ViewMut<Collider>,
+                                    mut entities: EntitiesViewMut,
+                                    grid: UniqueView<GridGeometry>) {
+    let _t = profiling::scope("despawn_offscreen_or_expired");
+    let spent: Vec<EntityId> = projectiles.iter().with_id()
+        .filter(|(_, shot)| shot.spent || shot.age > SHOT_LIFETIME || !in_world(&grid, shot.pos))
+        .map(|(id, _)| id)
+        .collect();
+    for id in spent {
+        projectiles.remove(id);
+        colliders.remove(id);
+        entities.delete_unchecked(id);
+    }
+}
+
+// world space: the shots, and the charge bar beside each boat that's charging
+pub fn draw_projectiles(_screen: UniqueViewMut<Screen>,
+                        projectiles: View<Projectile>,
+                        shots: View<ShotCharge>,
+                        boats: View<Boat>,
+                        clock: UniqueView<SimClock>) {
+    // part way along their last step, like the boats (see clock.rs)
+    let alpha = clock.alpha();
+    for projectile in projectiles.iter() {
+        let at = projectile.pos - projectile.vel * (1. - alpha);
+        draw_circle(at.x, at.y, projectile.radius, ORANGE);
+    }
+    for (shot, boat) in (&shots, &boats).iter() {
+        if shot.charge <= 0. {
+            continue;
+        }
+        let at = boat.drawn_loc(alpha);
+        let (x, y) = (at.x + BOAT_RADIUS + 4., at.y - BOAT_RADIUS);
+        let height = BOAT_RADIUS * 2.;
+        draw_rectangle_lines(x, y, 4., height, 1., GRAY);
+        let color = if shot.charge >= 1. { RED } else { ORANGE };
+        draw_rectangle(x, y + height * (1. - shot.charge), 4., height * shot.charge, color);
+    }
+}
@@ -0,0 +1,289 @@
+// the weather: things that happen to the water on a schedule, from the
+// scenario file, timed from the start of the level.
+//
+//   [[currents]]       a current across the whole map: at (seconds in),
+//                      duration, vx, vy (the flow it pulls toward), and ramp
+//                      (seconds to build up and die away, 5 by default)
+//   [[storms]]         at, duration, ramp as for currents, and intensity (0
+//                      to 1, 1 by default). a storm churns the water up,
+//                      blows gusts across it from a shifting direction,
+//                      darkens everything, and makes the boats harder to
+//                      handle (see steer)
+//   [seasons]          a slow turn of the year, if it's there: year (seconds
+//                      for all four seasons, 480 by default), start (how far
+//                      through the year to begin, 0 to 1, 0 being spring) and
+//                      melt ([vx, vy], the spring meltwater's current)
+//
+// the seasons blend into each other. spring is thin fast water with the melt
+// running and the emitters going strong; winter is thick and sluggish, the
+// flow smeared out and dying away, with hardly anything emitted (see SEASONS).
+//
+// a few seconds before anything starts, a warning comes up saying what's on
+// its way, and a storm says so while it lasts. the season's name sits in the
+// bottom corner.
+
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+use shipyard::{Component, UniqueView, UniqueViewMut};
+
+use crate::accessibility::Accessibility;
+use crate::grid::GridGeometry;
+use crate::level::Level;
+use crate::osc::stir_randomly;
+use crate::profiling;
+use crate::scenario::Scenario;
+use crate::window::ScreenInfo;
+use crate::{Cells, Randomness, Screen};
+
+// how much of the way to a current's flow the water goes a tick, at full strength
+const CURRENT_PULL: f32 = 0.02;
+// how long before something starts the warning comes up
+const WARNING_SECONDS: f32 = 5.;
+// at full intensity: how much turbulence, how hard the wind pulls (like a
+// current's), how fast it blows, how dark it gets, and how much of the
+// boats' steering and thrust is lost
+const STORM_TURBULENCE: f32 = 0.6;
+const STORM_WIND_PULL: f32 = 0.01;
+const STORM_WIND_SPEED: f32 = 3.;
+const STORM_SHADE: f32 = 0.45;
+const STORM_CONTROL_LOSS: f32 = 0.6;
+// how hard the spring melt pulls, at its height (like a current's)
+const MELT_PULL: f32 = 0.01;
+
+#[derive(Clone, Copy)]
+struct Season {
+    name: &'static str,
+    // how much of the way to its neighbours' average a cell's flow goes a tick
+    viscosity: f32,
+    // how much of its flow a cell loses a tick
+    damping: f32,
+    // times the emitters' rates
+    spawn_rate: f32,
+    // how much of the melt current there is
+    melt: f32,
+}
+
+// each at its height; in between, they blend
+const SEASONS: [Season; 4] = [
+    Season { name: "spring", viscosity: 0., damping: 0., spawn_rate: 1.5, melt: 1. },
+    Season { name: "summer", viscosity: 0.01, damping: 0., spawn_rate: 1., melt: 0. },
+    Season { name: "autumn", viscosity: 0.03, damping: 0.002, spawn_rate: 0.8, melt: 0. },
+    Season { name: "winter", viscosity: 0.08, damping: 0.01, spawn_rate: 0.4, melt: 0. },
+];
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CurrentDef {
+    pub at: f32,
+    pub duration: f32,
+    pub vx: f32,
+    pub vy: f32,
+    #[serde(default = "ramp")]
+    pub ramp: f32,
+}
+
+fn ramp() -> f32 {
+    5.
+}
+
+fn one() -> f32 {
+    1.
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StormDef {
+    pub at: f32,
+    pub duration: f32,
+    #[serde(default = "ramp")]
+    pub ramp: f32,
+    #[serde(default = "one")]
+    pub intensity: f32,
+}
+
+// 0 to 1: building up over the ramp, full for the rest, then dying away over
+// another ramp after the duration
+fn envelope(at: f32, duration: f32, ramp: f32, seconds: f32) -> f32 {
+    let since = seconds - at;
+    let ramp = ramp.max(0.01);
+    if since < 0. || since > duration + ramp {
+        0.
+    } else {
+        (since / ramp).min(1.).min((duration + ramp - since) / ramp)
+    }
+}
+
+impl CurrentDef {
+    fn strength(&self, seconds: f32) -> f32 {
+        envelope(self.at, self.duration, self.ramp, seconds)
+    }
+
+    // "east", "north west", ... (y is down the screen)
+    fn heading(&self) -> &'static str {
+        const NAMES: [&str; 8] = ["east", "south east", "south", "south west", "west", "north west", "north", "north east"];
+        let octant = (self.vy.atan2(self.vx) / (std::f32::consts::PI / 4.)).round() as i32;
+        NAMES[octant.rem_euclid(8) as usize]
+    }
+}
+
+impl StormDef {
+    fn strength(&self, seconds: f32) -> f32 {
+        envelope(self.at, self.duration, self.ramp, seconds) * self.intensity.max(0.).min(1.)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct SeasonsDef {
+    pub year: f32,
+    pub start: f32,
+    pub melt: [f32; 2],
+}
+
+impl Default for SeasonsDef {
+    fn default() -> SeasonsDef {
+        SeasonsDef { year: 480., start: 0., melt: [1., 0.5] }
+    }
+}
+
+impl SeasonsDef {
+    // the season now, blended between the two it's between
+    fn at(&self, seconds: f32) -> Season {
+        let through = (seconds / self.year.max(1.) + self.start).rem_euclid(1.) * SEASONS.len() as f32;
+        let (from, to) = (SEASONS[through as usize % 4], SEASONS[(through as usize + 1) % 4]);
+        let t = through.fract();
+        let t = t * t * (3. - 2. * t);
+        let blend = |a: f32, b: f32| a + (b - a) * t;
+        Season {
+            name: SEASONS[through.round() as usize % 4].name,
+            viscosity: blend(from.viscosity, to.viscosity),
+            damping: blend(from.damping, to.damping),
+            spawn_rate: blend(from.spawn_rate, to.spawn_rate),
+            melt: blend(from.melt, to.melt),
+        }
+    }
+}
+
+#[derive(Component, Default)]
+pub struct Weather {
+    currents: Vec<CurrentDef>,
+    storms: Vec<StormDef>,
+    seasons: Option<SeasonsDef>,
+}
+
+impl Weather {
+    pub fn new(scenario: &Scenario) -> Weather {
+        Weather { currents: scenario.currents.clone(), storms: scenario.storms.clone(), seasons: scenario.seasons.clone() }
+    }
+
+    // times the emitters' rates (see run_emitters)
+    pub fn spawn_rate(&self, seconds: f32) -> f32 {
+        self.seasons.as_ref().map_or(1., |seasons| seasons.at(seconds).spawn_rate)
+    }
+
+    // how stormy it is, 0 to 1 (the strongest storm, if they overlap)
+    pub fn storminess(&self, seconds: f32) -> f32 {
+        self.storms.iter().map(|storm| storm.strength(seconds)).fold(0., f32::max)
+    }
+
+    // how much of their steering and thrust the boats have, 0 to 1
+    pub fn control(&self, seconds: f32) -> f32 {
+        1. - STORM_CONTROL_LOSS * self.storminess(seconds)
+    }
+}
+
+// the storm wind at a moment: its direction wanders and it comes in gusts.
+// worked out from the time alone, so replays and netplay see the same wind
+fn wind(seconds: f32) -> Vec2 {
+    let angle = (seconds * 0.13).sin() * 2.5 + (seconds * 0.05).cos();
+    let gust = 0.6 + 0.25 * (seconds * 1.7).sin() + 0.15 * (seconds * 4.1).sin();
+    Vec2::new(angle.cos(), angle.sin()) * gust * STORM_WIND_SPEED
+}
+
+pub fn apply_weather(weather: UniqueView<Weather>,
+                     level: UniqueView<Level>,
+                     mut map: UniqueViewMut<Cells>,
+                     grid: UniqueView<GridGeometry>,
+                     _random: UniqueViewMut<Randomness>) {
+    let _t = profiling::scope("apply_weather");
+    let seconds = level.seconds();
+    let mut pulls: Vec<(Vec2, f32)> = weather.currents.iter()
+        .map(|current| (Vec2::new(current.vx, current.vy), CURRENT_PULL * current.strength(seconds)))
+        .collect();
+    let storminess = weather.storminess(seconds);
+    if storminess > 0. {
+        pulls.push((wind(seconds), STORM_WIND_PULL * storminess));
+        stir_randomly(&mut map, &grid, STORM_TURBULENCE * storminess);
+    }
+    let season = weather.seasons.as_ref().map(|seasons| (seasons.at(seconds), seasons.melt));
+    if let Some((season, [vx, vy])) = season {
+        pulls.push((Vec2::new(vx, vy), MELT_PULL * season.melt));
+    }
+    for (target, pull) in pulls.into_iter().filter(|(_, pull)| *pull > 0.) {
+        for cell_ix in 0..grid.len() {
+            let cell = &mut map.all_cells[cell_ix];
+            if !cell.is_solid() {
+                cell.flow_v += (target - cell.flow_v) * pull;
+            }
+        }
+    }
+    if let Some((season, _)) = season {
+        // thick water: each cell's flow drawn toward its neighbours', and all
+        // of it running down
+        let flows: Vec<Vec2> = map.all_cells.iter().map(|cell| cell.flow_v).collect();
+        for cell_ix in 0..grid.len() {
+            if map.all_cells[cell_ix].is_solid() {
+                continue;
+            }
+            let water: Vec<Vec2> = grid.neighbours(cell_ix).iter()
+                .filter(|n| !map.all_cells[**n].is_solid())
+                .map(|n| flows[*n])
+                .collect();
+            let cell = &mut map.all_cells[cell_ix];
+            if !water.is_empty() {
+                let average = water.iter().fold(Vec2::new(0., 0.), |sum, flow| sum + *flow) / water.len() as f32;
+                cell.flow_v += (average - cell.flow_v) * season.viscosity;
+            }
+            cell.flow_v *= 1. - season.damping;
+        }
+    }
+}
+
+// screen space, before the status line: the storm's shade over the world
+// (unless flashing's turned down), and the warnings
+pub fn draw_weather(_screen: UniqueViewMut<Screen>,
+                    screen: UniqueView<ScreenInfo>,
+                    weather: UniqueView<Weather>,
+                    level: UniqueView<Level>,
+                    access: UniqueView<Accessibility>) {
+    let seconds = level.seconds();
+    let storminess = weather.storminess(seconds);
+    if storminess > 0. && !access.reduce_flashing {
+        draw_rectangle(0., 0., screen.width, screen.height, Color::new(0.02, 0.02, 0.08, STORM_SHADE * storminess));
+    }
+    let mut lines = vec![];
+    for current in weather.currents.iter() {
+        let until = current.at - seconds;
+        if until > 0. && until <= WARNING_SECONDS {
+            lines.push(format!("current heading {} in {:.0}s", current.heading(), until.ceil()));
+        }
+    }
+    for storm in weather.storms.iter() {
+        let until = storm.at - seconds;
+        if until > 0. && until <= WARNING_SECONDS {
+            lines.push(format!("storm coming in {:.0}s", until.ceil()));
+        }
+    }
+    if storminess > 0.5 {
+        lines.push("storm! hard to steer".to_owned());
+    }
+    for (i, text) in lines.iter().enumerate() {
+        let width = measure_text(text, None, 22, 1.).width;
+        let (x, y) = (screen.width / 2. - width / 2., 80. + i as f32 * 24.);
+        access.text_backing(x, y, width, 22.);
+        draw_text(text, x, y, 22., if access.high_contrast { YELLOW } else { ORANGE });
+    }
+    if let Some(seasons) = weather.seasons.as_ref() {
+        let name = seasons.at(seconds).name;
+        access.text_backing(8., screen.height - 8., measure_text(name, None, 20, 1.).width, 20.);
+        draw_text(name, 8., screen.height - 8., 20., if access.high_contrast { WHITE } else { LIGHTGRAY });
+    }
+}
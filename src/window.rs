@@ -0,0 +1,99 @@
+// the window: how big it is, read once a frame into ScreenInfo so every
+// system sees the same size, and the world following it when it changes. the
+// window can be any size (resized, `--fullscreen`, or a browser canvas), and
+// whenever it's a new one the world is made that size too, at the same
+// resolution: each cell stretches with it (see Cells::resample, the same as
+// refine.rs's), and the particles, the boats, the pickups and the colliders
+// are moved to the same place on it. whatever isn't (enemies, drifters) wraps
+// back on by itself.
+//
+// the change goes through the ToolCommands like refine.rs's, so a replay
+// plays back at the size it was recorded at (letterboxed if the window's a
+// different shape, see camera.rs), and both ends of a netplay game agree on
+// it. ScreenInfo's put back to nothing on a reset (see init_world), so the
+// next frame fits the new world to the window again.
+
+use macroquad::prelude::*;
+use shipyard::{Component, IntoIter, UniqueView, UniqueViewMut, ViewMut};
+
+use crate::boat::Boat;
+use crate::camera::Camera;
+use crate::collision::Collider;
+use crate::grid::GridGeometry;
+use crate::pickups::Pickup;
+use crate::profiling;
+use crate::refine::regrid;
+use crate::tools::{ToolCommand, ToolCommands};
+use crate::{Cells, NextCells, Particle, Real, Screen};
+
+// the window's size in pixels, as of the start of this frame
+#[derive(Component, Default, Clone, Copy, PartialEq, Debug)]
+pub struct ScreenInfo {
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ScreenInfo {
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.width, self.height)
+    }
+}
+
+// first in the input
+pub fn track_screen(mut info: UniqueViewMut<ScreenInfo>,
+                    mut camera: UniqueViewMut<Camera>,
+                    mut commands: UniqueViewMut<ToolCommands>,
+                    _screen: UniqueView<Screen>) {
+    let now = ScreenInfo { width: screen_width(), height: screen_height() };
+    // (minimised, or a canvas that's not laid out yet)
+    if now == *info || now.width < 1. || now.height < 1. {
+        return;
+    }
+    *info = now;
+    camera.window = now.size();
+    commands.0.push(ToolCommand::Resize { width: now.width, height: now.height });
+}
+
+// in the sim, before resample_grid: the world at the window's size
+pub fn resize_world(mut commands: UniqueViewMut<ToolCommands>,
+                    mut grid: UniqueViewMut<GridGeometry>,
+                    mut map: UniqueViewMut<Cells>,
+                    mut next: UniqueViewMut<NextCells>,
+                    mut camera: UniqueViewMut<Camera>,
+                    mut particles: ViewMut<Particle>,
+                    mut boats: ViewMut<Boat>,
+                    mut pickups: ViewMut<Pickup>,
+                    mut colliders: ViewMut<Collider>) {
+    let _t = profiling::scope("resize_world");
+    let mut asked = None;
+    commands.0.retain(|command| match command {
+        ToolCommand::Resize { width, height } => {
+            asked = Some(Vec2::new(*width, *height));
+            false
+        }
+        _ => true,
+    });
+    let size = match asked {
+        Some(size) if size != grid.size() => size,
+        _ => return,
+    };
+
+    let scale = size / grid.size();
+    for particle in (&mut particles).iter() {
+        particle.position.x *= scale.x as Real;
+        particle.position.y *= scale.y as Real;
+    }
+    for boat in (&mut boats).iter() {
+        boat.loc.x *= scale.x as Real;
+        boat.loc.y *= scale.y as Real;
+    }
+    for pickup in (&mut pickups).iter() {
+        pickup.pos *= scale;
+    }
+    for collider in (&mut colliders).iter() {
+        collider.center *= scale;
+    }
+    let resized = GridGeometry::new(size.x, size.y, grid.cols, grid.rows);
+    regrid(&mut grid, &mut map, &mut next, &mut particles, resized);
+    camera.resize_world(size);
+}
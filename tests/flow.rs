@@ -0,0 +1,123 @@
+// the particles' flow going into the cells and back: what update_grid_flow
+// adds up (FlowAccumulator) and what apply_grid_updates does with it
+// (FluidCell::apply_flow_update). the cells take on the particles' momentum,
+// so with nothing else going on it's conserved, as far as the lerp's blend
+// lets it through.
+
+use grid_world::fluid::{FlowAccumulator, FluidCell};
+use grid_world::grid::GridGeometry;
+use grid_world::{new_cells, new_particle_at, ParticleKind};
+use macroquad::prelude::*;
+
+const CELLS: usize = 16;
+const TOLERANCE: f32 = 1e-4;
+
+fn velocities(count: usize, seed: u32) -> Vec<Vec2> {
+    let mut state = seed;
+    let mut next = move || {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (state >> 8) as f32 / (1 << 23) as f32 - 1.
+    };
+    (0..count).map(|_| Vec2::new(next() * 3., next() * 3.)).collect()
+}
+
+fn close(a: Vec2, b: Vec2) -> bool {
+    (a - b).length() <= TOLERANCE * (1. + a.length().max(b.length()))
+}
+
+#[test]
+fn the_accumulator_sums_each_cells_particles() {
+    let vels = velocities(1000, 1);
+    let acc = vels.iter().enumerate()
+        .fold(FlowAccumulator::new(CELLS), |acc, (i, v)| acc.add_at(i % CELLS, *v, 1));
+    for cell_ix in 0..CELLS {
+        let mine: Vec<&Vec2> = vels.iter().enumerate().filter(|(i, _)| i % CELLS == cell_ix).map(|(_, v)| v).collect();
+        assert_eq!(acc.counts[cell_ix] as usize, mine.len());
+        let sum = mine.iter().fold(Vec2::new(0., 0.), |sum, v| sum + **v);
+        assert!(close(acc.flow_sums[cell_ix], sum), "cell {}: {:?} vs {:?}", cell_ix, acc.flow_sums[cell_ix], sum);
+    }
+}
+
+#[test]
+fn merging_is_the_same_as_adding_them_all_to_one() {
+    let vels = velocities(1000, 2);
+    let add = |acc: FlowAccumulator, (i, v): (usize, &Vec2)| acc.add_at(i * 7 % CELLS, *v, 1);
+    let whole = vels.iter().enumerate().fold(FlowAccumulator::new(CELLS), add);
+    let (first, second) = vels.split_at(400);
+    let merged = first.iter().enumerate().fold(FlowAccumulator::new(CELLS), add)
+        .merge(second.iter().enumerate().map(|(i, v)| (i + 400, v)).fold(FlowAccumulator::new(CELLS), add));
+    assert_eq!(whole.counts, merged.counts);
+    for cell_ix in 0..CELLS {
+        assert!(close(whole.flow_sums[cell_ix], merged.flow_sums[cell_ix]));
+    }
+}
+
+#[test]
+fn heavier_particles_count_for_more() {
+    let grid = GridGeometry::new(100., 100., 4, 4);
+    let water = new_particle_at(&grid, 10., 10., 1., 0.);
+    let debris = new_particle_at(&grid, 10., 10., -1., 0.).of_kind(ParticleKind::Debris);
+    let smoke = new_particle_at(&grid, 10., 10., 0., 5.).of_kind(ParticleKind::Smoke);
+    let acc = FlowAccumulator::new(grid.len()).add(&water).add(&debris).add(&smoke);
+    let cell_ix = water.get_cell_index();
+    assert_eq!(acc.counts[cell_ix], ParticleKind::Water.weight() + ParticleKind::Debris.weight());
+    // smoke weighs nothing, so it doesn't push the water about
+    let expected = Vec2::new(1., 0.) * ParticleKind::Water.weight() as f32
+        + Vec2::new(-1., 0.) * ParticleKind::Debris.weight() as f32;
+    assert!(close(acc.flow_sums[cell_ix], expected));
+}
+
+#[test]
+fn a_full_blend_takes_on_the_particles_momentum() {
+    let grid = GridGeometry::new(100., 100., 4, 4);
+    let mut cells = new_cells(&grid);
+    for cell_ix in 0..grid.len() {
+        cells.all_cells[cell_ix].flow_v = Vec2::new(0., 0.);
+    }
+    let vels = velocities(500, 3);
+    let acc = vels.iter().enumerate().fold(FlowAccumulator::new(grid.len()), |acc, (i, v)| acc.add_at(i % grid.len(), *v, 1));
+    cells.add_flow_updates(&acc);
+    let mut momentum_after = Vec2::new(0., 0.);
+    for cell_ix in 0..grid.len() {
+        let cell = &mut cells.all_cells[cell_ix];
+        let count = cell.particle_count;
+        cell.apply_flow_update(1.);
+        // the cell's flow is its particles' mean velocity: the same momentum
+        momentum_after += cell.flow_v * count as f32;
+    }
+    let momentum_before = vels.iter().fold(Vec2::new(0., 0.), |sum, v| sum + *v);
+    assert!(close(momentum_before, momentum_after), "{:?} vs {:?}", momentum_before, momentum_after);
+}
+
+#[test]
+fn a_partial_blend_is_a_lerp_toward_the_mean() {
+    let mut cell = FluidCell::empty();
+    cell.flow_v = Vec2::new(2., -1.);
+    let vels = velocities(50, 4);
+    let sum = vels.iter().fold(Vec2::new(0., 0.), |sum, v| sum + *v);
+    cell.update_flow(sum, vels.len() as u32);
+    let blend = 0.25;
+    cell.apply_flow_update(blend);
+    let mean = sum / vels.len() as f32;
+    let expected = Vec2::new(2., -1.) + (mean - Vec2::new(2., -1.)) * blend;
+    assert!(close(cell.flow_v, expected), "{:?} vs {:?}", cell.flow_v, expected);
+    // and the updates are used up
+    assert_eq!(cell.particle_count, 0);
+    assert_eq!(cell.flow_updates, Vec2::new(0., 0.));
+}
+
+#[test]
+fn no_particles_leaves_the_flow_alone() {
+    let mut cell = FluidCell::empty();
+    cell.flow_v = Vec2::new(0.5, 0.25);
+    cell.apply_flow_update(1.);
+    assert_eq!(cell.flow_v, Vec2::new(0.5, 0.25));
+}
+
+#[test]
+fn a_particle_fully_pulled_takes_on_the_flow() {
+    let grid = GridGeometry::new(100., 100., 4, 4);
+    let mut particle = new_particle_at(&grid, 50., 50., 3., -2.);
+    particle.update_velocity_from_flow(Vec2::new(0.5, 0.5), 1.);
+    assert!(close(particle.velocity, Vec2::new(0.5, 0.5)));
+}
@@ -0,0 +1,129 @@
+// a seeded run of the core particle/cell loop, checked against a snapshot in
+// tests/golden/. it's the same systems the sim's tick runs for the fluid (see
+// simulation.rs), minus everything game-side (boat, enemies, terrain), on a
+// world built here rather than by init_world so it doesn't need a window, the
+// settings or a scenario file. any change to the physics shows up as the
+// numbers moving; if that's on purpose, rewrite the snapshot with
+//
+//   UPDATE_GOLDEN=1 cargo test --test golden
+//
+// and commit it along with the change. a missing snapshot fails too, rather
+// than quietly writing one that's then checked against itself.
+//
+// the snapshot's for the default features (parallel, which sums the flow in
+// fixed chunks so it comes out the same every run, see
+// FlowAccumulator::par_sum). f64 integrates differently and simd is a
+// different path, so the test doesn't build with either.
+#![cfg(all(feature = "parallel", not(feature = "f64"), not(feature = "simd")))]
+
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use grid_world::fluid::{apply_grid_updates, swap_grid_buffers, update_grid_flow};
+use grid_world::grid::GridGeometry;
+use grid_world::particle::{move_particle, update_particles_vectors};
+use grid_world::tuning::Tuning;
+use grid_world::{new_cells, new_particle, NextCells, Particle};
+use macroquad::prelude::*;
+use shipyard::{EntitiesViewMut, IntoIter, UniqueView, View, ViewMut, World};
+
+const SEED: u64 = 1037;
+const STEPS: usize = 1000;
+const PARTICLES: usize = 500;
+// how many particles' positions go in the snapshot as well as the sums
+const SAMPLED: usize = 8;
+// relative, for the float sums to survive a different compiler's rounding
+const TOLERANCE: f64 = 1e-3;
+
+fn build_world() -> World {
+    rand::srand(SEED);
+    let mut world = World::new();
+    let grid = GridGeometry::new(800., 600., 40, 30);
+    let cells = new_cells(&grid);
+    world.run(|mut entities: EntitiesViewMut, mut particles: ViewMut<Particle>| {
+        for _ in 0..PARTICLES {
+            entities.add_entity(&mut particles, new_particle(&grid));
+        }
+    });
+    world.add_unique(NextCells(cells.clone())).unwrap();
+    world.add_unique(cells).unwrap();
+    world.add_unique(grid).unwrap();
+    world.add_unique(Tuning::default()).unwrap();
+    world
+}
+
+fn step(world: &World) {
+    world.run(move_particle);
+    world.run(update_grid_flow);
+    world.run(apply_grid_updates);
+    world.run(update_particles_vectors);
+    world.run(swap_grid_buffers);
+}
+
+// one `name value` a line
+fn snapshot(world: &World) -> String {
+    world.run(|particles: View<Particle>, map: UniqueView<grid_world::Cells>| {
+        let mut out = String::new();
+        let (mut px, mut py, mut vx, mut vy) = (0f64, 0f64, 0f64, 0f64);
+        for particle in particles.iter() {
+            px += particle.position.x as f64;
+            py += particle.position.y as f64;
+            vx += particle.velocity.x as f64;
+            vy += particle.velocity.y as f64;
+        }
+        let (mut fx, mut fy) = (0f64, 0f64);
+        for cell in map.all_cells.iter() {
+            fx += cell.flow_v.x as f64;
+            fy += cell.flow_v.y as f64;
+        }
+        writeln!(out, "count {}", particles.iter().count()).unwrap();
+        for (name, value) in [("position_x", px), ("position_y", py), ("velocity_x", vx), ("velocity_y", vy),
+                              ("flow_x", fx), ("flow_y", fy)].iter() {
+            writeln!(out, "{} {}", name, value).unwrap();
+        }
+        for (i, particle) in particles.iter().take(SAMPLED).enumerate() {
+            writeln!(out, "particle_{}_x {}", i, particle.position.x).unwrap();
+            writeln!(out, "particle_{}_y {}", i, particle.position.y).unwrap();
+        }
+        out
+    })
+}
+
+fn parse(snapshot: &str) -> Vec<(String, f64)> {
+    snapshot.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            Some((parts.next()?.to_string(), parts.next()?.parse().ok()?))
+        })
+        .collect()
+}
+
+#[test]
+fn a_thousand_steps_match_the_snapshot() {
+    let world = build_world();
+    for _ in 0..STEPS {
+        step(&world);
+    }
+    let got = snapshot(&world);
+
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/sim_1000.txt");
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, &got).unwrap();
+        eprintln!("wrote {}", path.display());
+        return;
+    }
+    let expected = match std::fs::read_to_string(&path) {
+        Ok(expected) => expected,
+        Err(err) => panic!("can't read {} ({}); run with UPDATE_GOLDEN=1 to write it", path.display(), err),
+    };
+    let (got, expected) = (parse(&got), parse(&expected));
+    assert_eq!(got.len(), expected.len(), "the snapshot has different lines; UPDATE_GOLDEN=1 to rewrite it");
+    for ((name, value), (expected_name, expected_value)) in got.iter().zip(expected.iter()) {
+        assert_eq!(name, expected_name);
+        let scale = 1f64.max(expected_value.abs());
+        assert!((value - expected_value).abs() <= TOLERANCE * scale,
+                "{} is {}, the snapshot has {} (UPDATE_GOLDEN=1 if that's on purpose)",
+                name, value, expected_value);
+    }
+}
@@ -0,0 +1,123 @@
+// the grid's lookups: which cell a position falls in (index_at, and contains
+// agreeing with it), right up to the edges and past them, and the wrapping
+// sums from geometry.rs.
+
+use grid_world::geometry::{wrap_coord, wrap_position, wrapped_delta, wrapped_dist};
+use grid_world::grid::GridGeometry;
+use macroquad::prelude::*;
+
+const WIDTH: f32 = 800.;
+const HEIGHT: f32 = 600.;
+const COLS: usize = 40;
+const ROWS: usize = 30;
+
+fn grid() -> GridGeometry {
+    GridGeometry::new(WIDTH, HEIGHT, COLS, ROWS)
+}
+
+// a few thousand points spread over (and a little past) the world, the same
+// every run
+fn points() -> Vec<Vec2> {
+    let mut state: u32 = 1;
+    let mut next = move || {
+        state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        (state >> 8) as f32 / (1 << 24) as f32
+    };
+    (0..5000).map(|_| Vec2::new(next() * WIDTH * 1.2 - WIDTH * 0.1, next() * HEIGHT * 1.2 - HEIGHT * 0.1)).collect()
+}
+
+#[test]
+fn corners_are_the_corner_cells() {
+    let grid = grid();
+    assert_eq!(grid.index_at(0., 0.), 0);
+    assert_eq!(grid.index_at(WIDTH - 0.001, 0.), COLS - 1);
+    assert_eq!(grid.index_at(0., HEIGHT - 0.001), (ROWS - 1) * COLS);
+    assert_eq!(grid.index_at(WIDTH - 0.001, HEIGHT - 0.001), COLS * ROWS - 1);
+}
+
+#[test]
+fn cell_edges_belong_to_the_cell_after() {
+    let grid = grid();
+    let (w, h) = (grid.cell_width, grid.cell_height);
+    assert_eq!(grid.index_at(w, 0.), 1);
+    assert_eq!(grid.index_at(w - 0.001, 0.), 0);
+    assert_eq!(grid.index_at(0., h), COLS);
+    assert_eq!(grid.index_at(0., h - 0.001), 0);
+}
+
+#[test]
+fn out_of_range_is_clamped_to_the_edge_cells() {
+    let grid = grid();
+    assert_eq!(grid.index_at(-5., -5.), 0);
+    assert_eq!(grid.index_at(WIDTH, HEIGHT), COLS * ROWS - 1);
+    assert_eq!(grid.index_at(WIDTH * 3., 0.), COLS - 1);
+    assert_eq!(grid.index_at(0., -HEIGHT), 0);
+    assert_eq!(grid.index_at(std::f32::NAN, std::f32::NAN), 0);
+    assert_eq!(grid.index_at(std::f32::INFINITY, std::f32::NEG_INFINITY), COLS - 1);
+}
+
+#[test]
+fn every_index_is_in_range_and_agrees_with_contains() {
+    let grid = grid();
+    for at in points() {
+        let cell_ix = grid.index_at(at.x, at.y);
+        assert!(cell_ix < grid.len(), "{:?} gave {}", at, cell_ix);
+        assert!(grid.contains(cell_ix, at.x, at.y), "{:?} isn't in its own cell {}", at, cell_ix);
+    }
+}
+
+#[test]
+fn each_centre_is_in_its_own_cell() {
+    let grid = grid();
+    for cell_ix in 0..grid.len() {
+        let center = grid.center(cell_ix);
+        assert_eq!(grid.index_at(center.x, center.y), cell_ix);
+    }
+}
+
+#[test]
+fn neighbours_wrap_round_the_edges() {
+    let grid = grid();
+    // left, right, up, down
+    assert_eq!(grid.neighbours(0), [COLS - 1, 1, (ROWS - 1) * COLS, COLS]);
+    let last = grid.len() - 1;
+    assert_eq!(grid.neighbours(last), [last - 1, last + 1 - COLS, last - COLS, COLS - 1]);
+}
+
+#[test]
+fn wrapped_positions_land_on_the_world() {
+    let size = Vec2::new(WIDTH, HEIGHT);
+    for at in points() {
+        let wrapped = wrap_position(at * 5. - size * 2., size);
+        assert!(wrapped.x >= 0. && wrapped.x < WIDTH, "{:?}", wrapped);
+        assert!(wrapped.y >= 0. && wrapped.y < HEIGHT, "{:?}", wrapped);
+        let x = wrap_coord(at.x * 5. - WIDTH * 2., WIDTH);
+        assert!(x >= 0. && x < WIDTH, "{}", x);
+    }
+    // a hair under nothing mustn't round up to the far edge
+    let wrapped = wrap_position(Vec2::new(-1e-7, -1e-7), size);
+    assert!(wrapped.x < WIDTH && wrapped.y < HEIGHT);
+}
+
+#[test]
+fn wrapping_a_position_keeps_it_in_place() {
+    let size = Vec2::new(WIDTH, HEIGHT);
+    for at in points() {
+        let wrapped = wrap_position(at, size);
+        assert!(wrapped_dist(at, wrapped, size) < 1e-3, "{:?} moved to {:?}", at, wrapped);
+    }
+}
+
+#[test]
+fn wrapped_deltas_go_the_short_way() {
+    let size = Vec2::new(WIDTH, HEIGHT);
+    let delta = wrapped_delta(Vec2::new(10., 10.), Vec2::new(WIDTH - 10., HEIGHT - 10.), size);
+    assert!((delta - Vec2::new(-20., -20.)).length() < 1e-3, "{:?}", delta);
+    for (a, b) in points().iter().zip(points().iter().rev()) {
+        let delta = wrapped_delta(*a, *b, size);
+        assert!(delta.x.abs() <= WIDTH / 2. + 1e-3 && delta.y.abs() <= HEIGHT / 2. + 1e-3, "{:?}", delta);
+        // and it's the same either way round, backwards
+        assert!((delta + wrapped_delta(*b, *a, size)).length() < 1e-3);
+        assert!((wrapped_dist(*a, *b, size) - delta.length()).abs() < 1e-3);
+    }
+}